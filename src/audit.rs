@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::json;
+use crate::sha256::{hex, sha256};
+
+// what the first entry points back to
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+// one line per thing we published, for when somebody asks why the bot said that. every line carries the
+// hash of the one before it, so lines can't be edited or dropped without verify noticing
+pub struct AuditLog {
+  path: PathBuf,
+  last_hash: String,
+}
+
+pub struct Entry<'a> {
+  pub text: &'a str,
+  // the filters the text passed
+  pub filters: &'a [String],
+  // MarkovChain::fingerprint of whatever generated it
+  pub model: &'a str,
+  // who/what/where, e.g. the operator and the endpoint
+  pub context: &'a [(String, String)],
+}
+
+impl AuditLog {
+  pub fn open(path: &Path) -> io::Result<AuditLog> {
+    let last_hash = match fs::read_to_string(path) {
+      Ok(contents) => match contents.lines().last() {
+        Some(line) => split(line).map( |(_, hash)| hash.to_string() ).ok_or_else( || invalid(format!("{} doesn't end in an audit entry", path.display())) )?,
+        None => GENESIS.to_string(),
+      },
+      Err(error) if error.kind() == io::ErrorKind::NotFound => GENESIS.to_string(),
+      Err(error) => return Err(error),
+    };
+
+    return Ok(AuditLog { path: path.to_path_buf(), last_hash });
+  }
+
+  pub fn record(&mut self, entry: &Entry) -> io::Result<()> {
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).map( |d| d.as_secs() ).unwrap_or(0);
+    let filters: Vec<String> = entry.filters.iter().map( |f| json::string(f) ).collect();
+    let context: Vec<String> = entry.context.iter().map( |(k, v)| format!("{}:{}", json::string(k), json::string(v)) ).collect();
+
+    let body = format!(
+      "{{\"time\":{},\"text\":{},\"filters\":[{}],\"model\":{},\"context\":{{{}}},\"previous\":\"{}\"}}",
+      time, json::string(entry.text), filters.join(","), json::string(entry.model), context.join(","), self.last_hash
+    );
+    let hash = hex(&sha256(body.as_bytes()));
+
+    let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+    writeln!(file, "{},\"hash\":\"{}\"}}", &body[..body.len() - 1], hash)?;
+    file.sync_data()?;
+
+    self.last_hash = hash;
+    return Ok(());
+  }
+}
+
+// a line is the hashed body with the hash tacked on as its last field
+fn split(line: &str) -> Option<(String, &str)> {
+  let (body, hash) = line.rsplit_once(",\"hash\":\"")?;
+  return Some((format!("{}}}", body), hash.strip_suffix("\"}")?));
+}
+
+fn invalid(message: String) -> io::Error {
+  return io::Error::new(io::ErrorKind::InvalidData, message);
+}
+
+// an entry read back, for looking through what was posted
+pub struct Record {
+  pub time: u64,
+  pub text: String,
+  pub context: BTreeMap<String, String>,
+}
+
+// every entry, oldest first. a log that doesn't exist yet has nothing in it. this doesn't verify anything
+pub fn read(path: &Path) -> io::Result<Vec<Record>> {
+  let contents = match fs::read_to_string(path) {
+    Ok(contents) => contents,
+    Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+    Err(error) => return Err(error),
+  };
+
+  let mut records = Vec::new();
+  for (i, line) in contents.lines().enumerate().filter( |(_, line)| !line.trim().is_empty() ) {
+    let entry = json::parse(line).map_err( |error| invalid(format!("line {}: {}", i + 1, error)) )?;
+    let context = match entry.get("context") {
+      Some(json::Value::Object(fields)) => fields.iter().filter_map( |(k, v)| Some((k.clone(), v.as_str()?.to_string())) ).collect(),
+      _ => BTreeMap::new(),
+    };
+    records.push(Record {
+      time: entry.get("time").and_then(json::Value::as_u64).unwrap_or(0),
+      text: entry.get("text").and_then(json::Value::as_str).unwrap_or_default().to_string(),
+      context,
+    });
+  }
+  return Ok(records);
+}
+
+// checks every hash and link in the chain, returns how many entries there are
+pub fn verify(path: &Path) -> io::Result<usize> {
+  let mut previous = GENESIS.to_string();
+  let mut entries = 0;
+
+  for (i, line) in fs::read_to_string(path)?.lines().enumerate() {
+    let (body, hash) = split(line).ok_or_else( || invalid(format!("line {}: not an audit entry", i + 1)) )?;
+    if hex(&sha256(body.as_bytes())) != hash {
+      return Err(invalid(format!("line {}: hash doesn't match, the entry was changed", i + 1)));
+    }
+    if !body.ends_with(&format!(",\"previous\":\"{}\"}}", previous)) {
+      return Err(invalid(format!("line {}: doesn't follow the line before it, something was removed or reordered", i + 1)));
+    }
+    previous = hash.to_string();
+    entries += 1;
+  }
+
+  return Ok(entries);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn chains_entries_and_catches_tampering() {
+    let path = std::env::temp_dir().join(format!("erowidcoin-audit-{}.jsonl", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let context = vec!(("operator".to_string(), "imogen".to_string()));
+
+    for text in ["Buy the dip.", "Hodl the moon."] {
+      let mut log = AuditLog::open(&path).unwrap();
+      log.record(&Entry { text, filters: &["length".to_string()], model: "abc", context: &context }).unwrap();
+    }
+    assert_eq!(verify(&path).unwrap(), 2);
+    let records = read(&path).unwrap();
+    assert_eq!(records[1].text, "Hodl the moon.");
+    assert_eq!(records[1].context["operator"], "imogen");
+
+    let contents = fs::read_to_string(&path).unwrap();
+    fs::write(&path, contents.replace("dip", "top")).unwrap();
+    assert!(verify(&path).unwrap_err().to_string().starts_with("line 1: hash doesn't match"));
+
+    fs::write(&path, contents.lines().nth(1).unwrap()).unwrap();
+    assert!(verify(&path).unwrap_err().to_string().starts_with("line 1: doesn't follow"));
+
+    fs::remove_file(&path).unwrap();
+  }
+}
@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::sha256::{hex, sha256};
+use crate::toml::Value;
+
+// signed requests older (or newer) than this are rejected, so a captured one can't be replayed later
+const MAX_CLOCK_SKEW: u64 = 5 * 60;
+
+// who's allowed to use the api: static keys sent as `Authorization: Bearer <key>`, or requests signed with a
+// shared secret (see signature). both can be configured at once
+#[derive(Clone, Debug, Default)]
+pub struct Auth {
+  pub api_keys: Vec<String>,
+  pub hmac_secret: Option<String>,
+}
+
+impl Auth {
+  pub fn is_configured(&self) -> bool {
+    return !self.api_keys.is_empty() || self.hmac_secret.is_some();
+  }
+
+  // from the [server] table of the config file
+  pub fn from_table(table: &BTreeMap<String, Value>) -> Result<Auth, String> {
+    let mut auth = Auth::default();
+
+    if let Some(keys) = table.get("api_keys") {
+      let keys = keys.as_array().ok_or("api_keys should be a list of strings")?;
+      for key in keys {
+        auth.api_keys.push(key.as_str().ok_or("api_keys should be a list of strings")?.to_string());
+      }
+    }
+    if let Some(secret) = table.get("hmac_secret") {
+      auth.hmac_secret = Some(secret.as_str().ok_or("hmac_secret should be a string")?.to_string());
+    }
+
+    return Ok(auth);
+  }
+
+  // headers are (lowercased name, value), like server::Request has them
+  pub fn allows(&self, method: &str, path: &str, headers: &[(String, String)], body: &[u8]) -> bool {
+    let header = |name: &str| headers.iter().find( |(n, _)| n == name ).map( |(_, v)| v.as_str() );
+
+    if let Some(key) = header("authorization").and_then( |value| value.strip_prefix("Bearer ") ) {
+      if self.api_keys.iter().any( |expected| constant_time_eq(expected.as_bytes(), key.as_bytes()) ) {
+        return true;
+      }
+    }
+
+    if let (Some(secret), Some(timestamp), Some(signature)) = (&self.hmac_secret, header("x-timestamp"), header("x-signature")) {
+      let fresh = match (timestamp.parse::<u64>(), SystemTime::now().duration_since(UNIX_EPOCH)) {
+        (Ok(timestamp), Ok(now)) => now.as_secs().abs_diff(timestamp) <= MAX_CLOCK_SKEW,
+        _ => false,
+      };
+      let expected = self::signature(secret, method, path, timestamp, body);
+      return fresh && constant_time_eq(expected.as_bytes(), signature.to_lowercase().as_bytes());
+    }
+
+    return false;
+  }
+}
+
+// hex encoded HMAC-SHA256 over "METHOD\npath\ntimestamp\n" followed by the body, sent as X-Signature along with
+// X-Timestamp (unix seconds)
+pub fn signature(secret: &str, method: &str, path: &str, timestamp: &str, body: &[u8]) -> String {
+  let mut message = format!("{}\n{}\n{}\n", method, path, timestamp).into_bytes();
+  message.extend_from_slice(body);
+
+  return hex(&hmac_sha256(secret.as_bytes(), &message));
+}
+
+// so the time it takes to reject a key doesn't give away how much of it was right
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+  if a.len() != b.len() {
+    return false;
+  }
+  return a.iter().zip(b).fold(0, |difference, (x, y)| difference | (x ^ y)) == 0;
+}
+
+// RFC 2104
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+  let mut block = [0u8; 64];
+  if key.len() > 64 {
+    block[..32].copy_from_slice(&sha256(key));
+  } else {
+    block[..key.len()].copy_from_slice(key);
+  }
+
+  let mut inner: Vec<u8> = block.iter().map( |b| b ^ 0x36 ).collect();
+  inner.extend_from_slice(message);
+  let mut outer: Vec<u8> = block.iter().map( |b| b ^ 0x5c ).collect();
+  outer.extend_from_slice(&sha256(&inner));
+
+  return sha256(&outer);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn matches_the_rfc_vector() {
+    // RFC 4231 test case 2
+    assert_eq!(hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")), "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+  }
+
+  #[test]
+  fn accepts_keys_and_signatures() {
+    let auth = Auth { api_keys: vec!("hunter2".to_string()), hmac_secret: Some("shh".to_string()) };
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs().to_string();
+    let headers = |pairs: &[(&str, &str)]| pairs.iter().map( |(n, v)| (n.to_string(), v.to_string()) ).collect::<Vec<(String, String)>>();
+
+    assert!(auth.allows("GET", "/generate", &headers(&[("authorization", "Bearer hunter2")]), b""));
+    assert!(!auth.allows("GET", "/generate", &headers(&[("authorization", "Bearer hunter3")]), b""));
+    assert!(!auth.allows("GET", "/generate", &[], b""));
+
+    let signed = signature("shh", "POST", "/trigger", &now, b"{}");
+    assert!(auth.allows("POST", "/trigger", &headers(&[("x-timestamp", &now), ("x-signature", &signed)]), b"{}"));
+    // same signature, different body
+    assert!(!auth.allows("POST", "/trigger", &headers(&[("x-timestamp", &now), ("x-signature", &signed)]), b"{\"a\":1}"));
+    let stale = signature("shh", "POST", "/trigger", "1000", b"{}");
+    assert!(!auth.allows("POST", "/trigger", &headers(&[("x-timestamp", "1000"), ("x-signature", &stale)]), b"{}"));
+  }
+}
@@ -0,0 +1,111 @@
+use std::collections::{HashMap, HashSet};
+use crate::filter::GaveUp;
+use crate::markov_chain::{ErowidCoinError, MarkovChain};
+use crate::queue::QueuedPost;
+use crate::sensitive::Sensitivity;
+
+const DAY: u64 = 24 * 60 * 60;
+
+// tries per slot before we give up on finding something new enough
+const ATTEMPTS_PER_SLOT: usize = 100;
+
+pub struct Plan {
+  pub days: u64,
+  pub per_day: u64,
+  // unix seconds, the first slot goes one interval after this (or after the last thing already queued)
+  pub start: u64,
+  // bigram jaccard similarity above which a candidate counts as a rerun of something already queued
+  pub max_similarity: f64,
+  // which posts get queued as sensitive
+  pub sensitivity: Sensitivity,
+}
+
+// fills a queue ahead of time so posting can keep going while the machine that trains is off. candidates that
+// repeat (or nearly repeat) anything already queued are thrown away
+pub fn backfill(mchain: &mut MarkovChain, existing: &[QueuedPost], plan: &Plan) -> Result<Vec<QueuedPost>, ErowidCoinError> {
+  let interval = DAY / plan.per_day.max(1);
+  let last = existing.iter().filter_map( |post| post.scheduled_at ).max().unwrap_or(0).max(plan.start);
+
+  let mut seen: HashSet<String> = existing.iter().map( |post| post.text.clone() ).collect();
+  let mut seen_bigrams: Vec<HashSet<(String, String)>> = existing.iter().map( |post| bigrams(&post.text) ).collect();
+  let mut posts = Vec::new();
+  let model = mchain.fingerprint();
+
+  for slot in 1..=(plan.days * plan.per_day) {
+    let mut rejections: HashMap<String, usize> = HashMap::new();
+
+    let text = loop {
+      if rejections.values().sum::<usize>() >= ATTEMPTS_PER_SLOT {
+        return Err(ErowidCoinError::GaveUp(GaveUp::new(ATTEMPTS_PER_SLOT, rejections)));
+      }
+
+      let candidate = mchain.generate_tweet()?;
+      if seen.contains(&candidate) {
+        *rejections.entry("duplicate".to_string()).or_insert(0) += 1;
+        mchain.record_rejection("duplicate");
+        continue;
+      }
+      let candidate_bigrams = bigrams(&candidate);
+      if seen_bigrams.iter().any( |other| similarity(&candidate_bigrams, other) > plan.max_similarity ) {
+        *rejections.entry("diversity".to_string()).or_insert(0) += 1;
+        mchain.record_rejection("diversity");
+        continue;
+      }
+
+      seen_bigrams.push(candidate_bigrams);
+      break candidate;
+    };
+
+    seen.insert(text.clone());
+    let sensitive = plan.sensitivity.is_sensitive(&text, mchain.locale());
+    posts.push(QueuedPost::new(text, Some(last + slot * interval)).with_model(model.clone()).with_sensitive(sensitive));
+  }
+
+  return Ok(posts);
+}
+
+fn bigrams(text: &str) -> HashSet<(String, String)> {
+  let words: Vec<String> = text.split_whitespace().map( |word| word.to_lowercase() ).collect();
+  return words.windows(2).map( |pair| (pair[0].clone(), pair[1].clone()) ).collect();
+}
+
+fn similarity(a: &HashSet<(String, String)>, b: &HashSet<(String, String)>) -> f64 {
+  if a.is_empty() || b.is_empty() {
+    return 0.0;
+  }
+  return a.intersection(b).count() as f64 / a.union(b).count() as f64;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::path::Path;
+
+  #[test]
+  fn schedules_distinct_posts_after_the_existing_queue() {
+    let mut mchain = MarkovChain::new().with_seed(7);
+    mchain.create_tweets(Path::new("./seed"), 0).unwrap();
+
+    let existing = vec!(QueuedPost::new("Already queued.".to_string(), Some(10 * DAY)));
+    let plan = Plan { days: 2, per_day: 3, start: 0, max_similarity: 0.5, sensitivity: Sensitivity::Always };
+    let posts = backfill(&mut mchain, &existing, &plan).unwrap();
+
+    assert_eq!(posts.len(), 6);
+    assert_eq!(posts[0].scheduled_at, Some(10 * DAY + DAY / 3));
+    assert_eq!(posts[5].scheduled_at, Some(12 * DAY));
+    let unique: HashSet<&String> = posts.iter().map( |post| &post.text ).collect();
+    assert_eq!(unique.len(), 6);
+    assert!(posts.iter().all( |post| post.sensitive ));
+  }
+
+  #[test]
+  fn gives_up_when_everything_is_a_rerun() {
+    let mut mchain = MarkovChain::new();
+    mchain.create_tweets(Path::new("./txt"), 0).unwrap();
+
+    // everything counts as too similar to the first post
+    let plan = Plan { days: 1, per_day: 2, start: 0, max_similarity: -1.0, sensitivity: Sensitivity::Never };
+    let error = backfill(&mut mchain, &[], &plan).unwrap_err();
+    assert!(error.to_string().starts_with("gave up after 100 attempts, most candidates failed the"));
+  }
+}
@@ -0,0 +1,124 @@
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::BTreeMap;
+use crate::{http, json};
+use crate::daemon::format_time;
+use crate::publish::{self, Format, Publisher};
+use crate::toml::Value;
+
+const DEFAULT_SERVICE: &str = "https://bsky.social";
+
+// posts to bluesky over atproto: log in for a session, then create an app.bsky.feed.post record in the account's
+// repo. a post every few hours doesn't need a session kept fresh, every post logs in again. use an app password
+// (settings, privacy and security, app passwords), not the account's
+pub struct BlueskyPublisher {
+  // whoever hosts the account, bsky.social unless it's self-hosted
+  service: String,
+  // "erowidcoin.bsky.social", or the account's did
+  handle: String,
+  app_password: String,
+}
+
+// what a session gives back that posting needs
+struct Session {
+  access_jwt: String,
+  did: String,
+}
+
+impl BlueskyPublisher {
+  pub fn new(service: &str, handle: &str, app_password: &str) -> BlueskyPublisher {
+    return BlueskyPublisher { service: service.trim_end_matches('/').to_string(), handle: handle.to_string(), app_password: app_password.to_string() };
+  }
+
+  // handle and app_password from EROWIDCOIN_BLUESKY_HANDLE and EROWIDCOIN_BLUESKY_APP_PASSWORD, or the table. the
+  // service is optional
+  pub fn from_table(table: &BTreeMap<String, Value>) -> Result<BlueskyPublisher, String> {
+    let handle = publish::setting("bluesky", table, "handle", "EROWIDCOIN_BLUESKY_HANDLE")?;
+    let app_password = publish::setting("bluesky", table, "app_password", "EROWIDCOIN_BLUESKY_APP_PASSWORD")?;
+    let service = match table.get("service") {
+      Some(service) => service.as_str().ok_or("service should be a string")?.to_string(),
+      None => DEFAULT_SERVICE.to_string(),
+    };
+    return Ok(BlueskyPublisher::new(&service, &handle, &app_password));
+  }
+
+  // one xrpc procedure, its response when it worked
+  fn call(&self, method: &str, headers: &[String], body: &str) -> io::Result<json::Value> {
+    let (status, body) = http::post_json(&format!("{}/xrpc/{}", self.service, method), headers, body)?;
+    let response = json::parse(&body).ok();
+    if status != "200" {
+      let message = response.as_ref().and_then( |response| response.get("message").or(response.get("error"))?.as_str() ).unwrap_or(body.trim());
+      return Err(http::failed("bluesky", &status, message));
+    }
+    return response.ok_or_else( || io::Error::other(format!("bluesky said 200 but sent back something that isn't json: {}", body)) );
+  }
+
+  fn session(&self) -> io::Result<Session> {
+    let body = format!("{{\"identifier\":{},\"password\":{}}}", json::string(&self.handle), json::string(&self.app_password));
+    let response = self.call("com.atproto.server.createSession", &[], &body)?;
+    let field = |name: &str| response.get(name).and_then( |value| value.as_str() ).map(String::from)
+      .ok_or_else( || io::Error::other(format!("bluesky's session didn't come with a {}", name)) );
+    return Ok(Session { access_jwt: field("accessJwt")?, did: field("did")? });
+  }
+}
+
+// the createRecord body for a post made at `now` (unix seconds)
+fn post_record(did: &str, text: &str, now: u64) -> String {
+  // "2021-11-20 06:00:00 UTC" -> "2021-11-20T06:00:00.000Z"
+  let created_at = format!("{}.000Z", format_time(now)[..19].replace(' ', "T"));
+  return format!(
+    "{{\"repo\":{},\"collection\":\"app.bsky.feed.post\",\"record\":{{\"$type\":\"app.bsky.feed.post\",\"text\":{},\"createdAt\":{}}}}}",
+    json::string(did), json::string(text), json::string(&created_at)
+  );
+}
+
+// at://<did>/app.bsky.feed.post/<rkey> -> the post on bsky.app
+fn post_link(uri: &str) -> Option<String> {
+  let (did, rkey) = uri.strip_prefix("at://")?.split_once("/app.bsky.feed.post/")?;
+  return Some(format!("https://bsky.app/profile/{}/post/{}", did, rkey));
+}
+
+// so the password doesn't end up in a log by accident
+impl std::fmt::Debug for BlueskyPublisher {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    return write!(f, "BlueskyPublisher({})", self.handle);
+  }
+}
+
+impl Publisher for BlueskyPublisher {
+  fn name(&self) -> String {
+    return "bluesky".to_string();
+  }
+
+  // the link is the post's. there's no idempotency key to pass along, a record key would do it but posts want
+  // theirs made from the time
+  fn publish(&self, text: &str) -> io::Result<Option<String>> {
+    let session = self.session()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map( |d| d.as_secs() ).unwrap_or(0);
+    let response = self.call("com.atproto.repo.createRecord", &[format!("Authorization: Bearer {}", session.access_jwt)], &post_record(&session.did, text, now))?;
+    let uri = response.get("uri").and_then( |uri| uri.as_str() ).unwrap_or_default();
+    return Ok(post_link(uri));
+  }
+
+  fn format(&self) -> Format {
+    return Format::platform("bluesky").unwrap();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn builds_posts_and_their_links() {
+    let record = json::parse(&post_record("did:plc:abc", "Hodl \"the\" moon.", 1637388000)).unwrap();
+    assert_eq!(record.get("repo").and_then( |repo| repo.as_str() ), Some("did:plc:abc"));
+    let post = record.get("record").unwrap();
+    assert_eq!(post.get("text").and_then( |text| text.as_str() ), Some("Hodl \"the\" moon."));
+    assert_eq!(post.get("createdAt").and_then( |at| at.as_str() ), Some("2021-11-20T06:00:00.000Z"));
+
+    assert_eq!(post_link("at://did:plc:abc/app.bsky.feed.post/3k2yihcrp6f2c").unwrap(), "https://bsky.app/profile/did:plc:abc/post/3k2yihcrp6f2c");
+    assert_eq!(post_link("https://example.com"), None);
+    assert_eq!(BlueskyPublisher::new("https://bsky.social/", "a", "b").service, "https://bsky.social");
+  }
+}
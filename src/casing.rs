@@ -0,0 +1,127 @@
+use std::io;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use crate::model::{Reader, Writer};
+
+// how the words of a case folded graph were written, so generation can put it back. only the middles of sentences
+// count: "The" at the start of one says nothing about "the", "Bitcoin" halfway through one says it's a name
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Casing {
+  // folded word -> how it was written, and how often. only while training, it isn't saved
+  seen: HashMap<String, HashMap<String, usize>>,
+  // folded word -> the way it was written most, for the words where that isn't the folded one. what generation reads
+  preferred: HashMap<String, String>,
+}
+
+impl Casing {
+  pub fn observe(&mut self, folded: &str, written: &str) {
+    let forms = match self.seen.get_mut(folded) {
+      Some(forms) => forms,
+      None => self.seen.entry(folded.to_string()).or_default(),
+    };
+    match forms.get_mut(written) {
+      Some(count) => *count += 1,
+      None => { forms.insert(written.to_string(), 1); },
+    }
+  }
+
+  pub fn merge(&mut self, other: Casing) {
+    for (folded, forms) in other.seen {
+      let mine = self.seen.entry(folded).or_default();
+      for (written, count) in forms {
+        *mine.entry(written).or_insert(0) += count;
+      }
+    }
+  }
+
+  // works out the preferred ways from everything seen. ties go to the folded word, then to whichever sorts first. a
+  // loaded model has nothing seen and keeps the ones it came with
+  pub fn finish(&mut self) {
+    if self.seen.is_empty() {
+      return;
+    }
+    self.preferred = self.seen.iter().filter_map( |(folded, forms)| {
+      let (written, _) = forms.iter().max_by( |a, b| a.1.cmp(b.1).then((a.0 == folded).cmp(&(b.0 == folded))).then(b.0.cmp(a.0)) )?;
+      return (written != folded).then( || (folded.clone(), written.clone()) );
+    }).collect();
+  }
+
+  // the word the way it's mostly written, with a capital if it starts a sentence
+  pub fn restore<'a>(&'a self, word: &'a str, starts_sentence: bool) -> Cow<'a, str> {
+    let word = self.preferred.get(word).map_or(word, |written| written.as_str());
+    return match starts_sentence {
+      true => capitalize(word),
+      false => Cow::Borrowed(word),
+    };
+  }
+
+  // folded word -> written, sorted so the same graph always comes out the same
+  pub fn preferred(&self) -> Vec<(&str, &str)> {
+    let mut preferred: Vec<(&str, &str)> = self.preferred.iter().map( |(folded, written)| (folded.as_str(), written.as_str()) ).collect();
+    preferred.sort();
+    return preferred;
+  }
+
+  // model version 5 on
+  pub fn write(&self, out: &mut Writer) {
+    let preferred = self.preferred();
+    out.u32(preferred.len() as u32);
+    for (folded, written) in preferred {
+      out.string(folded);
+      out.string(written);
+    }
+  }
+
+  pub fn read(reader: &mut Reader) -> io::Result<Casing> {
+    let mut casing = Casing::default();
+    for _ in 0..reader.u32()? {
+      let folded = reader.string()?;
+      casing.prefer(folded, reader.string()?);
+    }
+    return Ok(casing);
+  }
+
+  // the way to write a folded word, from wherever it was worked out before
+  pub fn prefer(&mut self, folded: String, written: String) {
+    self.preferred.insert(folded, written);
+  }
+}
+
+// the first letter uppercase, after any quotes or brackets in front of it
+pub fn capitalize(word: &str) -> Cow<'_, str> {
+  let Some((at, letter)) = word.char_indices().find( |(_, c)| c.is_alphanumeric() ) else {
+    return Cow::Borrowed(word);
+  };
+  if !letter.is_lowercase() {
+    return Cow::Borrowed(word);
+  }
+  return Cow::Owned(format!("{}{}{}", &word[..at], letter.to_uppercase(), &word[at + letter.len_utf8()..]));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn keeps_the_way_words_were_mostly_written() {
+    let mut casing = Casing::default();
+    for (folded, written) in [("bitcoin", "Bitcoin"), ("bitcoin", "Bitcoin"), ("bitcoin", "bitcoin"), ("nft", "NFT"), ("nft", "nft"), ("moon", "moon")] {
+      casing.observe(folded, written);
+    }
+    let mut other = Casing::default();
+    other.observe("bitcoin", "BITCOIN");
+    casing.merge(other);
+    casing.finish();
+
+    assert_eq!(casing.restore("bitcoin", false), "Bitcoin");
+    // a tie stays lowercase
+    assert_eq!(casing.restore("nft", false), "nft");
+    assert_eq!(casing.restore("moon", true), "Moon");
+    assert_eq!(capitalize("“acid"), "“Acid");
+    assert_eq!(capitalize("42"), "42");
+
+    let mut out = Writer::default();
+    casing.write(&mut out);
+    assert_eq!(Casing::read(&mut Reader::new(&out.bytes)).unwrap().preferred, casing.preferred);
+  }
+}
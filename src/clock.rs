@@ -0,0 +1,63 @@
+use std::cell::{Cell, RefCell};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// where the daemon gets the time from, in unix seconds. the real one sleeps, the fake one just moves its hands,
+// which is what lets dry runs and tests go through weeks of schedule instantly
+pub trait Clock {
+  fn now(&self) -> u64;
+  // returns no earlier than `at`, but possibly later: a suspended laptop wakes up whenever it wakes up
+  fn sleep_until(&self, at: u64);
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+  fn now(&self) -> u64 {
+    return SystemTime::now().duration_since(UNIX_EPOCH).map( |d| d.as_secs() ).unwrap_or(0);
+  }
+
+  fn sleep_until(&self, at: u64) {
+    // sleeping can come up short, so keep going until it's actually time
+    loop {
+      let now = self.now();
+      if now >= at {
+        return;
+      }
+      thread::sleep(Duration::from_secs(at - now));
+    }
+  }
+}
+
+pub struct FakeClock {
+  now: Cell<u64>,
+  // (at, seconds): the first sleep that gets past `at` oversleeps by `seconds`
+  suspensions: RefCell<Vec<(u64, u64)>>,
+}
+
+impl FakeClock {
+  pub fn new(start: u64) -> FakeClock {
+    return FakeClock { now: Cell::new(start), suspensions: RefCell::new(Vec::new()) };
+  }
+
+  // pretend the machine was asleep for a while, for testing what happens to missed posts
+  pub fn with_suspension(self, at: u64, seconds: u64) -> FakeClock {
+    self.suspensions.borrow_mut().push((at, seconds));
+    return self;
+  }
+}
+
+impl Clock for FakeClock {
+  fn now(&self) -> u64 {
+    return self.now.get();
+  }
+
+  fn sleep_until(&self, at: u64) {
+    let mut at = at.max(self.now.get());
+    let mut suspensions = self.suspensions.borrow_mut();
+    if let Some(i) = suspensions.iter().position( |(start, _)| *start <= at ) {
+      at += suspensions.remove(i).1;
+    }
+    self.now.set(at);
+  }
+}
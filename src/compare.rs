@@ -0,0 +1,100 @@
+use std::error::Error;
+use std::collections::HashSet;
+use std::path::Path;
+use crate::markov_chain::{self, MarkovChain};
+use crate::quality::QualityReport;
+use crate::json;
+
+// everything we want to eyeball when deciding between two models
+pub struct ModelReport {
+  pub name: String,
+  pub perplexity: f64,
+  pub quality: QualityReport,
+  pub novelty: f64,
+}
+
+impl ModelReport {
+  // trains on the model directory, samples a batch, and scores it against the holdout text
+  pub fn new(mchain: &mut MarkovChain, model: &Path, holdout: &Path, samples: i32) -> Result<ModelReport, Box<dyn Error>> {
+    let tweets = mchain.create_tweets(model, samples)?;
+    let training = markov_chain::read_documents(model)?;
+
+    return Ok(ModelReport {
+      name: model.display().to_string(),
+      perplexity: mchain.perplexity(holdout)?,
+      quality: QualityReport::new(&tweets),
+      novelty: novelty(&tweets, &training),
+    });
+  }
+}
+
+fn trigrams(text: &str) -> Vec<Vec<&str>> {
+  let words: Vec<&str> = text.split_whitespace().collect();
+  return words.windows(3).map( |w| w.to_vec() ).collect();
+}
+
+// average share of each tweet's trigrams that never show up in the training text.
+// bigrams are useless here since a first order chain can only ever produce bigrams it has seen
+pub fn novelty(tweets: &[String], training: &[String]) -> f64 {
+  let seen: HashSet<Vec<&str>> = training.iter().flat_map( |doc| trigrams(doc) ).collect();
+
+  let mut total = 0.0;
+  let mut scored = 0;
+  for tweet in tweets {
+    let grams = trigrams(tweet);
+    if grams.is_empty() {
+      continue;
+    }
+    let novel = grams.iter().filter( |g| !seen.contains(*g) ).count();
+    total += novel as f64 / grams.len() as f64;
+    scored += 1;
+  }
+
+  if scored == 0 {
+    return 0.0;
+  }
+  return total / scored as f64;
+}
+
+pub fn render_markdown(reports: &[ModelReport]) -> String {
+  let mut out = String::from("| model | perplexity | distinct-1 | distinct-2 | self-BLEU | novelty |\n");
+  out.push_str("|---|---|---|---|---|---|\n");
+
+  for report in reports {
+    out.push_str(&format!(
+      "| {} | {:.2} | {:.4} | {:.4} | {:.4} | {:.4} |\n",
+      report.name, report.perplexity, report.quality.distinct_1, report.quality.distinct_2, report.quality.self_bleu, report.novelty
+    ));
+  }
+  return out;
+}
+
+pub fn render_json(reports: &[ModelReport]) -> String {
+  let entries: Vec<String> = reports.iter().map( |report| format!(
+    "{{\"model\":{},\"samples\":{},\"perplexity\":{},\"distinct_1\":{},\"distinct_2\":{},\"self_bleu\":{},\"novelty\":{}}}",
+    json::string(&report.name),
+    report.quality.samples,
+    json::number(report.perplexity),
+    json::number(report.quality.distinct_1),
+    json::number(report.quality.distinct_2),
+    json::number(report.quality.self_bleu),
+    json::number(report.novelty),
+  )).collect();
+
+  return format!("[{}]", entries.join(","));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn novelty_counts_unseen_trigrams() {
+    let training = vec!["I bought the dip and the dip bought me.".to_string()];
+    let tweets = ["I bought the dip and".to_string(), "the dip bought the dip".to_string()];
+
+    // first tweet is copied verbatim, second has one new trigram ("dip bought the") out of three
+    assert_eq!(novelty(&tweets[..1], &training), 0.0);
+    assert!((novelty(&tweets[1..], &training) - 1.0 / 3.0).abs() < 1e-9);
+  }
+}
@@ -0,0 +1,368 @@
+use std::path::Path;
+use std::collections::BTreeMap;
+use crate::bluesky::BlueskyPublisher;
+use crate::daemon::{self, Cron};
+use crate::mastodon::MastodonPublisher;
+use crate::publish::{Format, APIS, TARGET_KEYS};
+use crate::toml::Value;
+use crate::twitter::Credentials;
+
+type Table = BTreeMap<String, Value>;
+
+const SERVER_KEYS: &[&str] = &[
+  "api_keys", "hmac_secret", "queue", "audit_log", "pause_file", "requests_per_minute", "global_requests_per_minute", "max_concurrent",
+  "reply_cooldown", "replies_per_hour", "reply_allow", "reply_deny",
+];
+const CROSSPOST_KEYS: &[&str] = &["canonical", "targets"];
+
+// erowidcoin.toml, or whatever --config points at:
+//
+//   [chain]
+//   text = "./txt"
+//   order = 2
+//   max_chars = 280
+//   banned = ["rugpull", "ponzi"]
+//
+//   [schedule]
+//   cron = "0 */6 * * *"
+//   quiet_hours = "22-7"
+//
+//   [server]            see SERVER_KEYS
+//   [target.<name>]     see publish::from_table
+//   [crosspost]         see publish::from_crosspost
+//
+// [chain] and [schedule] are flags: a key is its flag with underscores (max_chars is --max-chars), a list is the
+// flag's commas and `true` is a switch. text is the text directory. a flag on the command line beats the same key
+// here. secrets are read from the environment before the file, see publish::setting
+pub const CONFIG_FILE: &str = "erowidcoin.toml";
+
+pub const CHAIN_KEYS: &[&str] = &[
+  "text", "model", "stats", "seed", "rng", "order", "doc_cap", "doc_separator", "min_documents", "min_count", "temperature", "top_k", "locale", "abbreviations", "corpus", "include",
+  "exclude", "poetry", "fold_case", "syllables", "scrub", "numbers", "max_chars", "min_words", "max_words", "long", "banned", "blocklist", "profanity", "ban_in_training", "max_attempts", "retry", "max_overlap", "history",
+  "history_similarity", "dead_end", "tokenizer", "joiner", "timings", "about", "taxonomy", "plugins", "sensitive",
+];
+pub const SCHEDULE_KEYS: &[&str] = &[
+  "interval", "cron", "jitter", "quiet_hours", "themes", "catch_up", "retries", "backoff", "style", "profiles", "anniversary", "milestones",
+  "followers_file",
+];
+
+// everything wrong with a config file, not just the first thing, so it can all be fixed in one go. serve runs this
+// too, a typo'd key shouldn't silently fall back to a default
+pub fn check(config: &Table) -> Vec<String> {
+  let mut problems = Vec::new();
+  unknown_keys("the top level", config, &["chain", "schedule", "server", "target", "crosspost"], &mut problems);
+
+  for (name, keys) in [("chain", CHAIN_KEYS), ("schedule", SCHEDULE_KEYS)] {
+    match config.get(name) {
+      Some(Value::Table(table)) => {
+        unknown_keys(&format!("[{}]", name), table, keys, &mut problems);
+        for (key, value) in table {
+          if flag_value(value).is_none() {
+            problems.push(format!("{}.{} should be a string, number, boolean or a list of those, not a {}", name, key, value.type_name()));
+          }
+        }
+        match name {
+          "chain" => chain_paths(table, &mut problems),
+          _ => schedule_syntax(table, &mut problems),
+        }
+      },
+      Some(other) => problems.push(format!("[{}] should be a table, not a {}", name, other.type_name())),
+      None => {},
+    }
+  }
+
+  let mut has_credentials = false;
+  match config.get("server") {
+    Some(Value::Table(server)) => {
+      unknown_keys("[server]", server, SERVER_KEYS, &mut problems);
+
+      match server.get("api_keys") {
+        Some(Value::Array(keys)) => {
+          if keys.iter().any( |key| key.as_str().is_none_or( |key| key.trim().is_empty() ) ) {
+            problems.push("server.api_keys should only contain non-empty strings".to_string());
+          }
+          has_credentials |= !keys.is_empty();
+        },
+        Some(other) => problems.push(format!("server.api_keys should be a list of strings, not a {}", other.type_name())),
+        None => {},
+      }
+      match server.get("hmac_secret") {
+        Some(Value::String(secret)) if secret.len() < 16 => problems.push("server.hmac_secret is too short to be a secret, use at least 16 characters".to_string()),
+        Some(Value::String(_)) => has_credentials = true,
+        Some(other) => problems.push(format!("server.hmac_secret should be a string, not a {}", other.type_name())),
+        None => {},
+      }
+
+      for key in ["queue", "audit_log", "pause_file"] {
+        writable_path(&format!("server.{}", key), server.get(key), &mut problems);
+      }
+      for key in ["requests_per_minute", "global_requests_per_minute", "max_concurrent", "replies_per_hour"] {
+        if let Some(value) = server.get(key) {
+          if !value.as_integer().is_some_and( |n| n > 0 && n <= u32::MAX as i64 ) {
+            problems.push(format!("server.{} should be a positive integer", key));
+          }
+        }
+      }
+      if let Some(cooldown) = server.get("reply_cooldown") {
+        match cooldown.as_str().map(daemon::parse_interval) {
+          Some(Ok(_)) => {},
+          Some(Err(error)) => problems.push(format!("server.reply_cooldown: {}", error)),
+          None => problems.push("server.reply_cooldown should be an interval like \"10m\"".to_string()),
+        }
+      }
+      for key in ["reply_allow", "reply_deny"] {
+        if server.get(key).is_some_and( |users| users.as_array().is_none_or( |users| users.iter().any( |user| user.as_str().is_none() ) ) ) {
+          problems.push(format!("server.{} should be a list of handles", key));
+        }
+      }
+    },
+    Some(other) => problems.push(format!("[server] should be a table, not a {}", other.type_name())),
+    None => {},
+  }
+
+  match config.get("target") {
+    Some(Value::Table(targets)) => {
+      for (name, target) in targets {
+        let Some(target) = target.as_table() else {
+          problems.push(format!("[target.{}] should be a table", name));
+          continue;
+        };
+        unknown_keys(&format!("[target.{}]", name), target, TARGET_KEYS, &mut problems);
+        writable_path(&format!("target.{}.file", name), target.get("file"), &mut problems);
+        match target.get("api") {
+          Some(Value::String(api)) if APIS.contains(&api.as_str()) => {},
+          Some(Value::String(api)) => problems.push(format!("target.{}.api is {:?}, expected one of {}", name, api, APIS.join(", "))),
+          Some(other) => problems.push(format!("target.{}.api should be a string, not a {}", name, other.type_name())),
+          None => {},
+        }
+        if target.contains_key("file") && target.contains_key("api") {
+          problems.push(format!("[target.{}] can have a file or an api, not both", name));
+        }
+        if target.contains_key("plugin") && (target.contains_key("file") || target.contains_key("api")) {
+          problems.push(format!("[target.{}] is a plugin, it can't have a file or an api too", name));
+        }
+        let mut strings = true;
+        for key in ["consumer_key", "consumer_secret", "access_token", "access_secret", "instance", "handle", "app_password", "service", "plugin"] {
+          if target.get(key).is_some_and( |value| value.as_str().is_none() ) {
+            problems.push(format!("target.{}.{} should be a string", name, key));
+            strings = false;
+          }
+        }
+        // the same lookup the publisher does, so keys that are in neither the table nor the environment show up here
+        // and not on the first post
+        let credentials = match target.get("api").and_then( |api| api.as_str() ) {
+          _ if !strings || target.contains_key("file") || target.contains_key("plugin") => Ok(()),
+          Some("twitter") => Credentials::from_table(target).map(drop),
+          Some("mastodon") => MastodonPublisher::from_table(target).map(drop),
+          Some("bluesky") => BlueskyPublisher::from_table(target).map(drop),
+          _ => Ok(()),
+        };
+        if let Err(error) = credentials {
+          problems.push(format!("target.{}: {}", name, error));
+        }
+        match target.get("platform") {
+          Some(Value::String(platform)) => if let Err(error) = Format::platform(platform) {
+            problems.push(format!("target.{}.platform: {}", name, error));
+          },
+          Some(other) => problems.push(format!("target.{}.platform should be a string, not a {}", name, other.type_name())),
+          None => {},
+        }
+        if target.get("max_length").is_some_and( |value| value.as_integer().is_none_or( |n| n <= 0 ) ) {
+          problems.push(format!("target.{}.max_length should be a positive integer", name));
+        }
+        if target.get("enabled").is_some_and( |value| value.as_bool().is_none() ) {
+          problems.push(format!("target.{}.enabled should be true or false", name));
+        }
+      }
+      // POST /trigger is the only thing that publishes, and it doesn't exist without credentials
+      if !targets.is_empty() && !has_credentials {
+        problems.push("targets are configured but [server] has no api_keys or hmac_secret, so nothing can trigger a post".to_string());
+      }
+    },
+    Some(other) => problems.push(format!("[target] should be a table, not a {}", other.type_name())),
+    None => {},
+  }
+
+  match config.get("crosspost") {
+    Some(Value::Table(crosspost)) => {
+      unknown_keys("[crosspost]", crosspost, CROSSPOST_KEYS, &mut problems);
+      let targets = config.get("target").and_then( |targets| targets.as_table() );
+      let known = |name: &str| targets.is_some_and( |targets| targets.contains_key(name) );
+
+      let names: Vec<&str> = match crosspost.get("targets").and_then( |t| t.as_array() ) {
+        Some(names) if names.iter().all( |name| name.as_str().is_some() ) => names.iter().filter_map( |name| name.as_str() ).collect(),
+        _ => {
+          problems.push("crosspost.targets should be a list of target names".to_string());
+          Vec::new()
+        },
+      };
+      for name in names.iter().filter( |name| !known(name) ) {
+        problems.push(format!("crosspost.targets mentions {}, but there's no [target.{}]", name, name));
+      }
+      match crosspost.get("canonical").and_then( |c| c.as_str() ) {
+        Some(canonical) if !names.contains(&canonical) => problems.push(format!("crosspost.canonical is {}, which isn't in crosspost.targets", canonical)),
+        Some(_) => {},
+        None => problems.push("crosspost.canonical should be the name of a target".to_string()),
+      }
+    },
+    Some(other) => problems.push(format!("[crosspost] should be a table, not a {}", other.type_name())),
+    None => {},
+  }
+
+  return problems;
+}
+
+// [chain] and [schedule] as (flag, value), the way they'd have come in on the command line. false switches are left
+// out
+pub fn flags(config: &Table) -> Vec<(String, String)> {
+  let tables = ["chain", "schedule"].into_iter().filter_map( |name| config.get(name)?.as_table() );
+  return tables.flat_map( |table| table.iter() )
+    .filter( |(_, value)| **value != Value::Boolean(false) )
+    .filter_map( |(key, value)| Some((key.replace('_', "-"), flag_value(value)?)) )
+    .collect();
+}
+
+fn flag_value(value: &Value) -> Option<String> {
+  return match value {
+    Value::String(s) => Some(s.clone()),
+    Value::Integer(n) => Some(n.to_string()),
+    Value::Float(f) => Some(f.to_string()),
+    Value::Boolean(_) => Some(String::new()),
+    Value::Array(values) => values.iter().map( |value| match value {
+      Value::Array(_) | Value::Table(_) | Value::Boolean(_) => None,
+      value => flag_value(value),
+    }).collect::<Option<Vec<String>>>().map( |values| values.join(",") ),
+    Value::Table(_) => None,
+  };
+}
+
+// what training reads has to be there already. the model doesn't, it's written after training if it isn't
+fn chain_paths(chain: &Table, problems: &mut Vec<String>) {
+  let missing = |key: &str, path: &str| format!("chain.{} is {}, which isn't there", key, path);
+  for (key, value) in chain {
+    let Some(paths) = flag_value(value) else {
+      continue;
+    };
+    match key.as_str() {
+      "text" | "stats" if !Path::new(&paths).exists() => problems.push(missing(key, &paths)),
+      // a directory each, maybe with a weight, like --corpus
+      "corpus" => for source in paths.split(',').map(str::trim) {
+        let dir = source.rsplit_once(':').filter( |(_, weight)| weight.parse::<f64>().is_ok() ).map_or(source, |(dir, _)| dir);
+        if !Path::new(dir).is_dir() {
+          problems.push(missing(key, dir));
+        }
+      },
+      "model" => writable_path("chain.model", Some(value), problems),
+      _ => {},
+    }
+  }
+}
+
+// [schedule] through the daemon's own parsers, a cron it can't read would otherwise only come up once it starts
+fn schedule_syntax(schedule: &Table, problems: &mut Vec<String>) {
+  if schedule.contains_key("interval") && schedule.contains_key("cron") {
+    problems.push("schedule.interval and schedule.cron both say when to post, pick one".to_string());
+  }
+  for (key, value) in schedule {
+    let Some(spec) = flag_value(value) else {
+      continue;
+    };
+    let parsed = match key.as_str() {
+      "interval" | "jitter" | "backoff" => daemon::parse_interval(&spec).map(drop),
+      "cron" => spec.parse::<Cron>().map(drop),
+      "quiet_hours" => daemon::parse_quiet_hours(&spec).map(drop),
+      "themes" => daemon::parse_themes(&spec).map(drop),
+      _ => Ok(()),
+    };
+    if let Err(error) = parsed {
+      problems.push(format!("schedule.{}: {}", key, error));
+    }
+  }
+}
+
+fn unknown_keys(context: &str, table: &Table, allowed: &[&str], problems: &mut Vec<String>) {
+  for key in table.keys() {
+    if !allowed.contains(&key.as_str()) {
+      problems.push(format!("unknown key `{}` in {} (expected one of: {})", key, context, allowed.join(", ")));
+    }
+  }
+}
+
+// a file we'll be writing to: it doesn't have to exist yet, but the directory it goes in does
+fn writable_path(key: &str, value: Option<&Value>, problems: &mut Vec<String>) {
+  let path = match value {
+    Some(Value::String(path)) => Path::new(path),
+    Some(other) => return problems.push(format!("{} should be a string, not a {}", key, other.type_name())),
+    None => return,
+  };
+
+  let parent = path.parent().filter( |parent| !parent.as_os_str().is_empty() ).unwrap_or(Path::new("."));
+  if !parent.is_dir() {
+    problems.push(format!("{} is {}, but {} isn't a directory", key, path.display(), parent.display()));
+  } else if path.is_dir() {
+    problems.push(format!("{} is {}, which is a directory", key, path.display()));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::toml;
+
+  #[test]
+  fn reports_every_problem() {
+    let config = toml::parse(r#"
+      [server]
+      api_key = "typo"
+      hmac_secret = "short"
+      max_concurrent = 0
+      queue = "/definitely/not/here/queue.jsonl"
+
+      [target.test]
+      file = "canary.jsonl"
+      platform = "myspace"
+    "#).unwrap();
+
+    assert_eq!(check(&config), vec!(
+      "unknown key `api_key` in [server] (expected one of: api_keys, hmac_secret, queue, audit_log, pause_file, requests_per_minute, global_requests_per_minute, max_concurrent, reply_cooldown, replies_per_hour, reply_allow, reply_deny)",
+      "server.hmac_secret is too short to be a secret, use at least 16 characters",
+      "server.queue is /definitely/not/here/queue.jsonl, but /definitely/not/here isn't a directory",
+      "server.max_concurrent should be a positive integer",
+      "target.test.platform: unknown platform \"myspace\", expected twitter, mastodon, bluesky or discord",
+      "targets are configured but [server] has no api_keys or hmac_secret, so nothing can trigger a post",
+    ));
+
+    let config = toml::parse("[server]\napi_keys = [\"k\"]\n\n[target.test]\nfile = \"canary.jsonl\"").unwrap();
+    assert!(check(&config).is_empty());
+
+    let config = toml::parse("[chain]\nmax_chars = 280\nbanned = [\"rug\", \"ponzi\"]\npoetry = true\nlong = false\nmax_char = 1\n\n[schedule]\ncron = [[1]]").unwrap();
+    assert_eq!(check(&config), vec!(
+      "unknown key `max_char` in [chain] (expected one of: ".to_string() + &CHAIN_KEYS.join(", ") + ")",
+      "schedule.cron should be a string, number, boolean or a list of those, not a array".to_string(),
+    ));
+    assert_eq!(flags(&config)[..3], [
+      ("banned".to_string(), "rug,ponzi".to_string()), ("max-char".to_string(), "1".to_string()), ("max-chars".to_string(), "280".to_string()),
+    ]);
+    assert!(flags(&config).contains(&("poetry".to_string(), String::new())));
+
+    let config = toml::parse("[server]\napi_keys = [\"k\"]\n\n[crosspost]\ncanonical = \"blog\"\ntargets = [\"test\", \"discord\"]\n\n[target.test]\nenabled = \"no\"").unwrap();
+    assert_eq!(check(&config), vec!(
+      "target.test.enabled should be true or false",
+      "crosspost.targets mentions discord, but there's no [target.discord]",
+      "crosspost.canonical is blog, which isn't in crosspost.targets",
+    ));
+
+    let config = toml::parse("[chain]\ncorpus = [\"/nonexistent/dir\", \"txt:0.5\"]\nmodel = \"/nonexistent/model.bin\"\n\n[schedule]\ncron = \"not a cron at all\"\nquiet_hours = \"22-22\"\n\n[server]\napi_keys = [\"k\"]\n\n[target.production]\napi = \"twitter\"").unwrap();
+    let problems = check(&config);
+    assert_eq!(problems[..4], [
+      "chain.corpus is /nonexistent/dir, which isn't there",
+      "chain.model is /nonexistent/model.bin, but /nonexistent isn't a directory",
+      "schedule.cron: could not parse cron schedule \"not a cron at all\": \"all\" should be 0 to 7",
+      "schedule.quiet_hours: could not parse quiet hours \"22-22\", expected something like 22-7",
+    ]);
+    // unless they're in the environment
+    if std::env::var("EROWIDCOIN_TWITTER_CONSUMER_KEY").is_err() {
+      assert_eq!(problems[4], "target.production: twitter needs consumer_key in the config or EROWIDCOIN_TWITTER_CONSUMER_KEY in the environment");
+    }
+  }
+}
@@ -0,0 +1,760 @@
+use std::env;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::time::Duration;
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use std::cmp::Reverse;
+use crate::audit::{self, AuditLog, Entry};
+use crate::clock::Clock;
+use crate::markov_chain::{MarkovChain, Sampling};
+use crate::milestones::Milestones;
+use crate::queue::{Queue, QueuedPost};
+use crate::server::Settings;
+use crate::profile::Mix;
+use crate::publish;
+
+const DAY: u64 = 24 * 60 * 60;
+
+// "90s", "30m", "6h", "1d"
+pub fn parse_interval(spec: &str) -> Result<Duration, String> {
+  let spec = spec.trim();
+  let split = spec.find( |c: char| !c.is_ascii_digit() ).unwrap_or(spec.len());
+  let (amount, unit) = spec.split_at(split);
+
+  let amount: u64 = amount.parse().map_err( |_| format!("could not parse interval {:?}, expected something like 6h", spec) )?;
+  let seconds = match unit {
+    "s" => 1,
+    "m" => 60,
+    "h" => 60 * 60,
+    "d" => 24 * 60 * 60,
+    _ => return Err(format!("unknown interval unit {:?} in {:?}, expected s, m, h or d", unit, spec)),
+  };
+  if amount == 0 {
+    return Err("the interval has to be longer than 0".to_string());
+  }
+  return Ok(Duration::from_secs(amount * seconds));
+}
+
+// "22-7", hours in UTC. wraps around midnight if the end comes first
+pub fn parse_quiet_hours(spec: &str) -> Result<(u64, u64), String> {
+  return hours(spec).ok_or_else( || format!("could not parse quiet hours {:?}, expected something like 22-7", spec) );
+}
+
+fn hours(spec: &str) -> Option<(u64, u64)> {
+  let (start, end) = spec.trim().split_once('-')?;
+  let (start, end): (u64, u64) = (start.trim().parse().ok()?, end.trim().parse().ok()?);
+  if start > 23 || end > 23 || start == end {
+    return None;
+  }
+  return Some((start, end));
+}
+
+// whether `at` is from start up to (not including) end, the hours being like parse_quiet_hours's
+fn within((start, end): (u64, u64), at: u64) -> bool {
+  let hour = at % DAY / 3600;
+  return if start < end { start <= hour && hour < end } else { hour >= start || hour < end };
+}
+
+// a part of the day whose posts come out their own way: leaning towards some words, or weirder (or tamer) than the
+// chain's usual temperature. "6-11=coffee+gm+premarket" for mornings, "2-5=temperature:1.6" for the small hours,
+// "20-23=moon+temperature:1.2" for both. hours are UTC like the quiet hours
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+  pub hours: (u64, u64),
+  pub boost: Vec<String>,
+  pub temperature: Option<f64>,
+}
+
+impl FromStr for Theme {
+  type Err = String;
+
+  fn from_str(spec: &str) -> Result<Theme, String> {
+    let (hours_spec, parts) = spec.split_once('=').ok_or_else( || format!("expected hours=words, got {:?}", spec) )?;
+    let hours = hours(hours_spec).ok_or_else( || format!("could not parse the hours of theme {:?}, expected something like 6-11", spec) )?;
+    let mut theme = Theme { hours, boost: Vec::new(), temperature: None };
+    for part in parts.split('+').map(str::trim).filter( |part| !part.is_empty() ) {
+      match part.strip_prefix("temperature:") {
+        Some(temperature) => {
+          let temperature: f64 = temperature.trim().parse().map_err( |_| format!("could not parse the temperature in theme {:?}", spec) )?;
+          if !temperature.is_finite() {
+            return Err(format!("the temperature in theme {:?} has to be a number", spec));
+          }
+          theme.temperature = Some(temperature);
+        },
+        None => theme.boost.push(part.to_string()),
+      }
+    }
+    if theme.boost.is_empty() && theme.temperature.is_none() {
+      return Err(format!("theme {:?} doesn't change anything, give it some words or a temperature", spec));
+    }
+    return Ok(theme);
+  }
+}
+
+// --themes: one or more of them, comma separated. in hours that more than one covers the first one wins
+pub fn parse_themes(spec: &str) -> Result<Vec<Theme>, String> {
+  return spec.split(',').map(str::trim).filter( |theme| !theme.is_empty() ).map(str::parse).collect();
+}
+
+// "0 */6 * * *": minute, hour, day of the month, month and day of the week (0 or 7 is sunday), in UTC. each field
+// is *, a number, a range (1-5), any of those with a step (*/15, 8-18/2), or a list of them (0,30). like cron, a day
+// that's restricted both ways only has to match one of them
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cron {
+  // bit n set means n matches
+  minutes: u64,
+  hours: u64,
+  days: u64,
+  months: u64,
+  weekdays: u64,
+  any_day: bool,
+  any_weekday: bool,
+}
+
+// a cron schedule that never comes up within this many days never will
+const CRON_SEARCH_DAYS: u64 = 8 * 366;
+
+impl Cron {
+  // the first time after `after` that matches, to the minute
+  pub fn next_after(&self, after: u64) -> Option<u64> {
+    let mut at = after - after % 60 + 60;
+    let give_up = at + CRON_SEARCH_DAYS * DAY;
+    while at < give_up {
+      let (_, month, day) = civil((at / DAY) as i64);
+      let weekday = (at / DAY + 4) % 7;
+      let day_matches = match (self.any_day, self.any_weekday) {
+        (false, false) => bit(self.days, day) || bit(self.weekdays, weekday),
+        _ => bit(self.days, day) && bit(self.weekdays, weekday),
+      };
+      if !bit(self.months, month) || !day_matches {
+        at += DAY - at % DAY;
+      } else if !bit(self.hours, at % DAY / 3600) {
+        at += 3600 - at % 3600;
+      } else if !bit(self.minutes, at % 3600 / 60) {
+        at += 60;
+      } else {
+        return Some(at);
+      }
+    }
+    return None;
+  }
+}
+
+fn bit(set: u64, n: u64) -> bool {
+  return set & 1 << n != 0;
+}
+
+// one field, as a set of bits from min to max
+fn cron_field(spec: &str, min: u64, max: u64) -> Result<u64, String> {
+  let mut set = 0;
+  for part in spec.split(',') {
+    let (range, step) = match part.split_once('/') {
+      Some((range, step)) => (range, step.parse::<u64>().ok().filter( |&step| step > 0 ).ok_or_else( || format!("bad step in {:?}", part) )?),
+      None => (part, 1),
+    };
+    let number = |n: &str| n.parse::<u64>().ok().filter( |n| (min..=max).contains(n) ).ok_or_else( || format!("{:?} should be {} to {}", n, min, max) );
+    let (start, end) = match range.split_once('-') {
+      _ if range == "*" => (min, max),
+      Some((start, end)) => (number(start)?, number(end)?),
+      // a step on a single number runs to the end, like cron's
+      None if step > 1 => (number(range)?, max),
+      None => (number(range)?, number(range)?),
+    };
+    if start > end {
+      return Err(format!("{:?} runs backwards", part));
+    }
+    for n in (start..=end).step_by(step as usize) {
+      set |= 1 << n;
+    }
+  }
+  return Ok(set);
+}
+
+impl FromStr for Cron {
+  type Err = String;
+
+  fn from_str(spec: &str) -> Result<Cron, String> {
+    let fields: Vec<&str> = spec.split_whitespace().collect();
+    let [minutes, hours, days, months, weekdays] = fields[..] else {
+      return Err(format!("could not parse cron schedule {:?}, expected 5 fields like \"0 */6 * * *\"", spec));
+    };
+    let error = |error: String| format!("could not parse cron schedule {:?}: {}", spec, error);
+    let mut weekday_set = cron_field(weekdays, 0, 7).map_err(error)?;
+    // 7 is sunday too
+    if bit(weekday_set, 7) {
+      weekday_set |= 1;
+    }
+    let cron = Cron {
+      minutes: cron_field(minutes, 0, 59).map_err(error)?,
+      hours: cron_field(hours, 0, 23).map_err(error)?,
+      days: cron_field(days, 1, 31).map_err(error)?,
+      months: cron_field(months, 1, 12).map_err(error)?,
+      weekdays: weekday_set,
+      any_day: days == "*",
+      any_weekday: weekdays == "*",
+    };
+    if cron.next_after(0).is_none() {
+      return Err(format!("cron schedule {:?} never comes up", spec));
+    }
+    return Ok(cron);
+  }
+}
+
+// what to do about slots that went by while we weren't around (suspended, stopped, or in quiet hours)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CatchUp {
+  // post once and carry on with the schedule
+  Skip,
+  // post everything that was missed, back to back
+  All,
+}
+
+impl FromStr for CatchUp {
+  type Err = String;
+
+  fn from_str(value: &str) -> Result<CatchUp, String> {
+    return match value {
+      "skip" => Ok(CatchUp::Skip),
+      "all" => Ok(CatchUp::All),
+      other => Err(format!("unknown catch-up policy {:?}, expected skip or all", other)),
+    };
+  }
+}
+
+pub struct Schedule {
+  pub interval: Duration,
+  // slots go by this instead of the interval when there is one
+  pub cron: Option<Cron>,
+  // each post goes out up to this much before or after its slot, so they don't land on the dot
+  pub jitter: Duration,
+  // (start, end) hours in UTC, nothing gets posted in between
+  pub quiet_hours: Option<(u64, u64)>,
+  pub catch_up: CatchUp,
+  // a post that fails for a reason that might go away (see publish::is_transient) is tried this many more times,
+  // waiting backoff before the first retry and twice as long before each one after that
+  pub retries: usize,
+  pub backoff: Duration,
+  // how posts come out depending on when they go out, see Theme
+  pub themes: Vec<Theme>,
+}
+
+impl Schedule {
+  pub fn every(interval: Duration) -> Schedule {
+    return Schedule {
+      interval, cron: None, jitter: Duration::ZERO, quiet_hours: None, catch_up: CatchUp::Skip, retries: 3, backoff: Duration::from_secs(60),
+      themes: Vec::new(),
+    };
+  }
+
+  pub fn cron(cron: Cron) -> Schedule {
+    return Schedule { cron: Some(cron), ..Schedule::every(Duration::ZERO) };
+  }
+
+  // the slot after this one
+  fn next_slot(&self, slot: u64) -> u64 {
+    return match &self.cron {
+      // parsing made sure it comes up
+      Some(cron) => cron.next_after(slot).unwrap(),
+      None => slot + self.interval.as_secs(),
+    };
+  }
+
+  fn jittered(&self, slot: u64, rng: &mut impl Rng) -> u64 {
+    let jitter = self.jitter.as_secs();
+    return (slot + rng.gen_range(0..=2 * jitter)).saturating_sub(jitter);
+  }
+
+  fn is_quiet(&self, at: u64) -> bool {
+    return self.quiet_hours.is_some_and( |hours| within(hours, at) );
+  }
+
+  fn theme(&self, at: u64) -> Option<&Theme> {
+    return self.themes.iter().find( |theme| within(theme.hours, at) );
+  }
+
+  // the next time quiet hours end
+  fn quiet_until(&self, at: u64) -> u64 {
+    let end = self.quiet_hours.map( |(_, end)| end ).unwrap_or(0);
+    let same_day = at - at % DAY + end * 3600;
+    return if same_day > at { same_day } else { same_day + DAY };
+  }
+}
+
+pub struct Daemon<'a> {
+  pub mchain: &'a mut MarkovChain,
+  pub settings: &'a Settings,
+  pub schedule: Schedule,
+  // a profile is picked for every post
+  pub profiles: Mix,
+  // when set, crossing a follower milestone gets a post of its own
+  pub milestones: Option<Milestones>,
+  // the chance, each post, of reposting something from a year ago instead. needs the audit log, that's our history
+  pub anniversary: Option<f64>,
+  // a FakeClock for dry runs, so every wait is skipped and just added to the clock
+  pub clock: &'a dyn Clock,
+  // for the jitter, separate from the chain's so a --seed reproduces the whole schedule
+  pub rng: ChaCha8Rng,
+  // nothing is published or written
+  pub dry_run: bool,
+}
+
+impl Daemon<'_> {
+  // posts once per interval, forever unless there's a limit on the number of posts. what happens each time goes to `log`
+  pub fn run(&mut self, iterations: Option<usize>, log: &mut impl Write) -> io::Result<()> {
+    let mut slot = self.clock.now();
+    let mut posted = 0;
+
+    while iterations.is_none_or( |limit| posted < limit ) {
+      slot = self.schedule.next_slot(slot);
+      let mut at = self.schedule.jittered(slot, &mut self.rng);
+      let now = loop {
+        self.clock.sleep_until(at);
+        let now = self.clock.now();
+        if !self.schedule.is_quiet(now) {
+          break now;
+        }
+        at = self.schedule.quiet_until(now);
+      };
+
+      let mut missed = 0;
+      while self.schedule.next_slot(slot) <= now {
+        slot = self.schedule.next_slot(slot);
+        missed += 1;
+      }
+      let posts = match self.schedule.catch_up {
+        CatchUp::Skip => 1,
+        CatchUp::All => 1 + missed,
+      };
+      if missed > 0 {
+        let policy = if self.schedule.catch_up == CatchUp::All { "posting them now" } else { "skipping them" };
+        writeln!(log, "[{}] missed {} slot(s), {}", format_time(now), missed, policy)?;
+      }
+
+      for _ in 0..posts {
+        if iterations.is_some_and( |limit| posted >= limit ) {
+          break;
+        }
+        self.tick(now, log)?;
+        posted += 1;
+      }
+    }
+    return Ok(());
+  }
+
+  // one scheduled post. problems with a single post get logged, the schedule keeps going. true if it went out (or
+  // would have, in a dry run)
+  fn tick(&mut self, now: u64, log: &mut impl Write) -> io::Result<bool> {
+    // checked every time, pausing shouldn't need a restart to take effect
+    if self.settings.kill_switch.is_paused() {
+      let reason = self.settings.kill_switch.reason().unwrap_or_default();
+      writeln!(log, "[{}] paused, skipping ({})", format_time(now), reason)?;
+      return Ok(false);
+    }
+
+    let profile = self.profiles.pick(&mut self.rng).clone();
+    self.mchain.set_styles(profile.styles.clone());
+
+    // a milestone beats everything else. a follower count we can't read shouldn't stop the regular post
+    let milestone = match self.milestones.as_mut().map( |milestones| milestones.poll() ) {
+      Some(Ok(milestone)) => milestone,
+      Some(Err(error)) => {
+        writeln!(log, "[{}] couldn't check the follower count: {}", format_time(now), error)?;
+        None
+      },
+      None => None,
+    };
+    let special = match (milestone, self.anniversary) {
+      (Some(milestone), _) => self.milestone_post(milestone).map( |post| ("milestone", post) ),
+      (None, Some(chance)) if self.rng.gen_bool(chance) => self.anniversary_post(now)?.map( |post| ("anniversary", post) ),
+      _ => None,
+    };
+
+    // what kind of post this was, for the log and the audit entry
+    let (kind, labelled) = match &special {
+      Some((kind, _)) => (kind.to_string(), true),
+      None => (profile.name.clone(), !profile.is_plain()),
+    };
+    let generated = match special {
+      Some((_, tweet)) => tweet,
+      None => {
+        // the theme is only for this post, the chain goes back to how it was after
+        let sampling = self.mchain.sampling();
+        if let Some(theme) = self.schedule.theme(now) {
+          self.mchain.set_sampling(Sampling { temperature: theme.temperature.unwrap_or(sampling.temperature), ..sampling });
+          self.mchain.set_boost(theme.boost.clone());
+        }
+        let generated = if profile.haiku { self.mchain.generate_haiku() } else { self.mchain.generate_tweet() };
+        self.mchain.set_sampling(sampling);
+        self.mchain.set_boost(Vec::new());
+        match generated {
+          Ok(tweet) => tweet,
+          Err(error) => {
+            writeln!(log, "[{}] couldn't generate a tweet: {}", format_time(now), error)?;
+            return Ok(false);
+          },
+        }
+      },
+    };
+    // with a queue this is only the generating half, `queue post` publishes (and adapts) whatever ends up there. a
+    // publishing outage doesn't hold up generation that way, and either side can be restarted on its own
+    let (tweet, destination) = match &self.settings.queue {
+      Some(queue) => (generated, format!("queue {}", queue.display())),
+      None => (self.settings.publisher.format().adapt(&generated), self.settings.publisher.name()),
+    };
+
+    // a queued post's audit entry is written when it's posted, see Queue::post_due
+    let audited = self.settings.audit_log.as_ref().filter( |_| self.settings.queue.is_none() );
+    if self.dry_run {
+      if audited.is_some() {
+        writeln!(log, "[{}] would write an audit entry", format_time(now))?;
+      }
+      let styled = if labelled { format!(" ({})", kind) } else { String::new() };
+      writeln!(log, "[{}] would post to {}{}: {}", format_time(now), destination, styled, tweet)?;
+      return Ok(true);
+    }
+
+    if let Some(path) = audited {
+      let context = vec!(
+        ("operator".to_string(), env::var("USER").unwrap_or_default()),
+        ("endpoint".to_string(), "daemon".to_string()),
+        ("destination".to_string(), destination.clone()),
+        ("kind".to_string(), kind),
+      );
+      let filters = self.mchain.filter_names();
+      AuditLog::open(path)?.record(&Entry { text: &tweet, filters: &filters, model: &self.mchain.fingerprint(), context: &context })?;
+    }
+
+    let sensitive = self.settings.sensitivity.is_sensitive(&tweet, self.mchain.locale());
+    let Some(queue) = &self.settings.queue else {
+      return self.publish(&tweet, sensitive, log);
+    };
+    let sent = Queue::new(queue).append(&[QueuedPost::new(tweet, None).with_model(self.mchain.fingerprint()).with_sensitive(sensitive)]);
+    match &sent {
+      Ok(()) => writeln!(log, "[{}] posted to {}", format_time(now), destination)?,
+      Err(error) => writeln!(log, "[{}] posting to {} failed: {}", format_time(now), destination, error)?,
+    }
+    return Ok(sent.is_ok());
+  }
+
+  // straight to the publisher, retrying transient failures with the schedule's backoff. every attempt has the same
+  // key, so one whose response got lost doesn't go out twice
+  fn publish(&mut self, tweet: &str, sensitive: bool, log: &mut impl Write) -> io::Result<bool> {
+    let publisher = &self.settings.publisher;
+    let key = format!("{:016x}", self.rng.gen::<u64>());
+    let mut wait = self.schedule.backoff;
+    let mut retries = self.schedule.retries;
+    loop {
+      let now = self.clock.now();
+      match publisher.publish_flagged(tweet, Some(&key), sensitive) {
+        Ok(_) => {
+          writeln!(log, "[{}] posted to {}", format_time(now), publisher.name())?;
+          return Ok(true);
+        },
+        Err(error) if retries > 0 && publish::is_transient(&error) => {
+          writeln!(log, "[{}] posting to {} failed, retrying in {}s: {}", format_time(now), publisher.name(), wait.as_secs(), error)?;
+          self.clock.sleep_until(now + wait.as_secs());
+          wait *= 2;
+          retries -= 1;
+        },
+        Err(error) => {
+          writeln!(log, "[{}] posting to {} failed: {}", format_time(now), publisher.name(), error)?;
+          return Ok(false);
+        },
+      }
+    }
+  }
+
+  // one post right now, the way a scheduled one would go out. the schedule and quiet hours don't come into it
+  pub fn post_now(&mut self, log: &mut impl Write) -> io::Result<bool> {
+    let now = self.clock.now();
+    return self.tick(now, log);
+  }
+}
+
+impl Daemon<'_> {
+  // the chain's take on the occasion, under a header saying what it is
+  fn milestone_post(&mut self, milestone: u64) -> Option<String> {
+    let tweet = self.mchain.generate_tweet().ok()?;
+    let post = format!("🎉 {} followers 🎉\n\n{}", milestone, tweet);
+    return if self.mchain.passes_filters(&post) { Some(post) } else { None };
+  }
+
+  // "one year ago today", quoting whatever did best on this day last year with something new from the chain
+  // underneath. None if there's nothing to look back on, or the result doesn't get past the filters
+  fn anniversary_post(&mut self, now: u64) -> io::Result<Option<String>> {
+    let Some(path) = &self.settings.audit_log else {
+      return Ok(None);
+    };
+    let today = format_time(now);
+    let year: i64 = today[..4].parse().unwrap_or(0);
+    let a_year_ago = format!("{:04}{}", year - 1, &today[4..10]);
+
+    // nothing records engagement yet, there's no platform to ask. until something does the first post of the day
+    // wins. reposts don't get reposted
+    let records = audit::read(path)?;
+    let best = records.iter()
+      .filter( |record| format_time(record.time).starts_with(&a_year_ago) )
+      .filter( |record| record.context.get("kind").is_none_or( |kind| kind != "anniversary" ) )
+      .min_by_key( |record| Reverse(record.context.get("engagement").and_then( |e| e.parse::<u64>().ok() ).unwrap_or(0)) );
+    let Some(best) = best else {
+      return Ok(None);
+    };
+
+    let Ok(framing) = self.mchain.generate_tweet() else {
+      return Ok(None);
+    };
+    let post = format!("One year ago today:\n“{}”\n\n{}", best.text, framing);
+    return Ok(if self.mchain.passes_filters(&post) { Some(post) } else { None });
+  }
+}
+
+// unix seconds as "2021-11-20 06:00:00 UTC"
+pub fn format_time(unix: u64) -> String {
+  let (year, month, day) = civil((unix / 86400) as i64);
+  let seconds = unix % 86400;
+  return format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC", year, month, day, seconds / 3600, seconds / 60 % 60, seconds % 60);
+}
+
+// days since 1970 to (year, month, day). Howard Hinnant's days_from_civil run backwards
+fn civil(days: i64) -> (i64, u64, u64) {
+  let z = days + 719468;
+  let era = z.div_euclid(146097);
+  let day_of_era = z.rem_euclid(146097);
+  let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let mp = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+  return (year, month as u64, day as u64);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+  use std::path::Path;
+  use rand::SeedableRng;
+  use crate::clock::FakeClock;
+
+  #[test]
+  fn parses_intervals_and_times() {
+    assert_eq!(parse_interval("6h"), Ok(Duration::from_secs(6 * 60 * 60)));
+    assert_eq!(parse_interval("90s"), Ok(Duration::from_secs(90)));
+    assert!(parse_interval("6 hours").is_err());
+    assert!(parse_interval("0m").is_err());
+    assert_eq!(parse_quiet_hours("22-7"), Ok((22, 7)));
+    assert!(parse_quiet_hours("7-7").is_err());
+
+    assert_eq!(format_time(0), "1970-01-01 00:00:00 UTC");
+    assert_eq!(format_time(1637388000), "2021-11-20 06:00:00 UTC");
+    assert_eq!(format_time(951782400), "2000-02-29 00:00:00 UTC");
+  }
+
+  // 2021-11-20 00:00:00 UTC
+  const START: u64 = 1637366400;
+
+  #[test]
+  fn parses_cron_schedules() {
+    let next = |spec: &str, after: u64| format_time(spec.parse::<Cron>().unwrap().next_after(after).unwrap());
+    // START is a saturday
+    assert_eq!(next("0 */6 * * *", START), "2021-11-20 06:00:00 UTC");
+    assert_eq!(next("0 */6 * * *", START - 1), "2021-11-20 00:00:00 UTC");
+    assert_eq!(next("30 9 * * 1-5", START), "2021-11-22 09:30:00 UTC");
+    assert_eq!(next("0 12 29 2 *", START), "2024-02-29 12:00:00 UTC");
+    // restricted both ways, either one will do
+    assert_eq!(next("0 0 1 * 0", START), "2021-11-21 00:00:00 UTC");
+    assert_eq!(next("0,30 8-18/5 * * 7", START), "2021-11-21 08:00:00 UTC");
+    assert!("0 */6 * *".parse::<Cron>().is_err());
+    assert!("60 * * * *".parse::<Cron>().is_err());
+    assert!("0 0 31 2 *".parse::<Cron>().is_err());
+  }
+
+  // the first `failures` posts fail with `error`
+  struct Flaky {
+    failures: std::sync::Mutex<usize>,
+    error: fn() -> io::Error,
+    keys: std::sync::Mutex<Vec<String>>,
+  }
+
+  impl crate::publish::Publisher for Flaky {
+    fn name(&self) -> String {
+      return "flaky".to_string();
+    }
+
+    fn publish(&self, text: &str) -> io::Result<Option<String>> {
+      return self.publish_with_key(text, "");
+    }
+
+    fn publish_with_key(&self, _text: &str, key: &str) -> io::Result<Option<String>> {
+      self.keys.lock().unwrap().push(key.to_string());
+      let mut failures = self.failures.lock().unwrap();
+      if *failures > 0 {
+        *failures -= 1;
+        return Err((self.error)());
+      }
+      return Ok(None);
+    }
+  }
+
+  #[test]
+  fn retries_transient_failures_with_backoff() {
+    let mut mchain = MarkovChain::new().with_seed(1);
+    mchain.train_documents(["Wen lambo."]);
+    let post = |failures, error: fn() -> io::Error, mchain: &mut MarkovChain| {
+      let flaky = std::sync::Arc::new(Flaky { failures: std::sync::Mutex::new(failures), error, keys: std::sync::Mutex::new(Vec::new()) });
+      let settings = Settings { publisher: flaky.clone(), ..Settings::default() };
+      let clock = FakeClock::new(START);
+      let mut daemon = Daemon {
+        mchain, settings: &settings, schedule: Schedule::every(Duration::from_secs(60)), profiles: Mix::default(), milestones: None,
+        anniversary: None, clock: &clock, rng: ChaCha8Rng::seed_from_u64(1), dry_run: false,
+      };
+      let mut log = Vec::new();
+      let posted = daemon.post_now(&mut log).unwrap();
+      let keys = flaky.keys.lock().unwrap().clone();
+      return (posted, String::from_utf8(log).unwrap(), keys);
+    };
+
+    let (posted, log, keys) = post(2, || io::Error::other(crate::publish::Transient("503".to_string())), &mut mchain);
+    assert!(posted, "{}", log);
+    assert!(log.contains("[2021-11-20 00:00:00 UTC] posting to flaky failed, retrying in 60s: 503"), "{}", log);
+    assert!(log.contains("[2021-11-20 00:03:00 UTC] posted to flaky"), "{}", log);
+    assert!(keys.len() == 3 && keys.iter().all( |key| *key == keys[0] ), "{:?}", keys);
+
+    let (posted, log, _) = post(5, || io::Error::from(io::ErrorKind::TimedOut), &mut mchain);
+    assert!(!posted);
+    assert_eq!(log.matches("retrying").count(), 3, "{}", log);
+
+    let (posted, log, keys) = post(1, || io::Error::other("403 duplicate"), &mut mchain);
+    assert!(!posted && keys.len() == 1);
+    assert!(log.contains("posting to flaky failed: 403 duplicate"), "{}", log);
+  }
+
+  fn dry_run(schedule: Schedule, clock: &FakeClock, posts: usize) -> Vec<String> {
+    let mut mchain = MarkovChain::new().with_seed(1);
+    mchain.create_tweets(Path::new("./txt"), 0).unwrap();
+    let settings = Settings::default();
+    let mut daemon = Daemon { mchain: &mut mchain, settings: &settings, schedule, profiles: Mix::default(), milestones: None, anniversary: None, clock, rng: ChaCha8Rng::seed_from_u64(1), dry_run: true };
+
+    let mut log = Vec::new();
+    daemon.run(Some(posts), &mut log).unwrap();
+    return String::from_utf8(log).unwrap().lines().map( |line| line.to_string() ).collect();
+  }
+
+  #[test]
+  fn fast_forwards_through_weeks_of_schedule() {
+    let schedule = || Schedule { jitter: Duration::from_secs(30 * 60), quiet_hours: Some((22, 7)), ..Schedule::every(Duration::from_secs(6 * 60 * 60)) };
+    let log = dry_run(schedule(), &FakeClock::new(START), 4 * 7 * 3);
+    assert_eq!(log, dry_run(schedule(), &FakeClock::new(START), 4 * 7 * 3));
+
+    let posts: Vec<&String> = log.iter().filter( |line| line.contains("would post to stdout: ") ).collect();
+    assert_eq!(posts.len(), 84);
+    // the midnight slot waits out the quiet hours, and the 6am one that came due meanwhile is skipped
+    assert!(posts[0].starts_with("[2021-11-20 07:00:00 UTC]"));
+    assert!(posts[83].starts_with("[2021-12-17 1"));
+    for post in &posts {
+      let hour: u64 = post[12..14].parse().unwrap();
+      assert!((7..22).contains(&hour), "posted during quiet hours: {}", post);
+    }
+    assert!(posts.iter().any( |post| !post.contains(":00:00 UTC]") ));
+  }
+
+  #[test]
+  fn themes_change_posts_by_the_hour() {
+    let themes = parse_themes("6-11=coffee+gm, 2-5=temperature:1.6, 22-1=moon+temperature:0.5").unwrap();
+    assert_eq!(themes[0], Theme { hours: (6, 11), boost: vec!("coffee".to_string(), "gm".to_string()), temperature: None });
+    assert_eq!((themes[2].hours, themes[2].temperature), ((22, 1), Some(0.5)));
+    assert!("6-11=".parse::<Theme>().is_err());
+    assert!("morning=coffee".parse::<Theme>().is_err());
+    assert!("6-11=temperature:hot".parse::<Theme>().is_err());
+
+    let mut mchain = MarkovChain::new().with_seed(1);
+    let words = ["coffee", "lambo", "moon", "rugs", "sats", "bags", "dips", "pumps", "wagmi", "frens"];
+    mchain.train_documents(words.iter().map( |word| format!("Morning means {}.", word) ));
+    let settings = Settings::default();
+    let clock = FakeClock::new(START);
+    let schedule = Schedule { themes: parse_themes("6-12=coffee").unwrap(), ..Schedule::every(Duration::from_secs(60 * 60)) };
+    let mut daemon = Daemon {
+      mchain: &mut mchain, settings: &settings, schedule, profiles: Mix::default(), milestones: None, anniversary: None, clock: &clock,
+      rng: ChaCha8Rng::seed_from_u64(1), dry_run: true,
+    };
+    let mut log = Vec::new();
+    daemon.run(Some(24 * 4), &mut log).unwrap();
+
+    // (themed, not) posts that had coffee in them
+    let mut coffee = (0, 0);
+    for post in String::from_utf8(log).unwrap().lines().filter( |line| line.contains("would post") && line.contains("coffee") ) {
+      let hour: u64 = post[12..14].parse().unwrap();
+      if (6..12).contains(&hour) { coffee.0 += 1 } else { coffee.1 += 1 }
+    }
+    // 24 themed posts and 72 others, a tenth of which would have had it without the theme
+    assert!(coffee.0 > 12 && coffee.1 < 20, "{:?}", coffee);
+    assert_eq!(mchain.sampling(), Sampling::default());
+  }
+
+  #[test]
+  fn catches_up_after_a_suspension() {
+    let hourly = |catch_up| Schedule { catch_up, ..Schedule::every(Duration::from_secs(60 * 60)) };
+    let posted_at = |log: &[String]| log.iter().filter( |line| line.contains("would post") ).map( |line| line[12..17].to_string() ).collect::<Vec<String>>();
+
+    // asleep from 2am to 7am
+    let log = dry_run(hourly(CatchUp::Skip), &FakeClock::new(START).with_suspension(START + 2 * 3600, 5 * 3600), 3);
+    assert!(log.contains(&"[2021-11-20 07:00:00 UTC] missed 5 slot(s), skipping them".to_string()));
+    assert_eq!(posted_at(&log), vec!("01:00", "07:00", "08:00"));
+
+    let log = dry_run(hourly(CatchUp::All), &FakeClock::new(START).with_suspension(START + 2 * 3600, 5 * 3600), 8);
+    assert_eq!(posted_at(&log), vec!("01:00", "07:00", "07:00", "07:00", "07:00", "07:00", "07:00", "08:00"));
+  }
+
+  #[test]
+  fn reposts_what_went_out_a_year_ago() {
+    let path = std::env::temp_dir().join(format!("erowidcoin-anniversary-{}.jsonl", std::process::id()));
+    let a_year_ago = START - 365 * DAY;
+    let lines = [
+      format!("{{\"time\":{},\"text\":\"Number go up.\",\"context\":{{\"engagement\":\"3\"}}}}", a_year_ago + 60),
+      format!("{{\"time\":{},\"text\":\"Wagmi.\",\"context\":{{\"engagement\":\"40\"}}}}", a_year_ago + 120),
+      format!("{{\"time\":{},\"text\":\"Too old.\",\"context\":{{}}}}", a_year_ago - DAY),
+    ];
+    fs::write(&path, lines.join("\n")).unwrap();
+
+    let mut mchain = MarkovChain::new().with_seed(1);
+    mchain.create_tweets(Path::new("./txt"), 0).unwrap();
+    let settings = Settings { audit_log: Some(path.clone()), ..Settings::default() };
+    let clock = FakeClock::new(START);
+    let mut daemon = Daemon {
+      mchain: &mut mchain, settings: &settings, schedule: Schedule::every(Duration::from_secs(6 * 60 * 60)), profiles: Mix::default(),
+      milestones: None, anniversary: Some(1.0), clock: &clock, rng: ChaCha8Rng::seed_from_u64(1), dry_run: true,
+    };
+
+    let mut log = Vec::new();
+    daemon.run(Some(1), &mut log).unwrap();
+    fs::remove_file(&path).unwrap();
+    let log = String::from_utf8(log).unwrap();
+    assert!(log.contains("would post to stdout (anniversary): One year ago today:\n“Wagmi.”\n\nThe syntactic component"), "{}", log);
+  }
+
+  #[test]
+  fn celebrates_crossing_a_milestone() {
+    let path = std::env::temp_dir().join(format!("erowidcoin-daemon-followers-{}", std::process::id()));
+    let mut mchain = MarkovChain::new().with_seed(1);
+    mchain.create_tweets(Path::new("./txt"), 0).unwrap();
+    let settings = Settings::default();
+    let clock = FakeClock::new(START);
+    let mut daemon = Daemon {
+      mchain: &mut mchain, settings: &settings, schedule: Schedule::every(Duration::from_secs(60)), profiles: Mix::default(),
+      milestones: Some(Milestones::new(&path, vec!(100))), anniversary: None, clock: &clock, rng: ChaCha8Rng::seed_from_u64(1), dry_run: true,
+    };
+
+    let mut log = Vec::new();
+    for count in ["98", "103", "104"] {
+      fs::write(&path, count).unwrap();
+      daemon.tick(START, &mut log).unwrap();
+    }
+    fs::remove_file(&path).unwrap();
+
+    let log = String::from_utf8(log).unwrap();
+    assert_eq!(log.matches("would post to stdout (milestone): 🎉 100 followers 🎉").count(), 1, "{}", log);
+    assert_eq!(log.lines().filter( |line| line.contains("would post to stdout: ") ).count(), 2);
+  }
+}
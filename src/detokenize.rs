@@ -0,0 +1,104 @@
+use std::io;
+
+// how words go back together into text. training splits on whitespace, so most corpora keep their punctuation stuck
+// to the words and a space between each is right. corpora that were tokenized before we got them ("it 's", "( like
+// this )") need the spaces taken back out. pluggable, so a mode that splits text up some other way can put it back
+// together its own way
+pub trait Joiner: Send + Sync {
+  // adds `word` to the end of text. text is everything so far, it's empty or ends in a line break at the start of
+  // a line
+  fn push(&self, text: &mut String, word: &str);
+}
+
+// the other way round, text into the words training sees, for corpora that whitespace doesn't split right (see
+// tokenize.rs, or a tokenizer plugin in plugin.rs). a line break is a token of its own
+pub trait Tokenizer: Send + Sync {
+  fn tokenize(&self, text: &str) -> io::Result<Vec<String>>;
+  // whatever a generated tweet needs once it's joined up, for tokens that can come out without their other half
+  fn tidy(&self, _text: &mut String) {}
+}
+
+fn at_line_start(text: &str) -> bool {
+  return text.is_empty() || text.ends_with('\n');
+}
+
+// a space between every two words, the way it always was
+pub struct Spaces;
+
+impl Joiner for Spaces {
+  fn push(&self, text: &mut String, word: &str) {
+    if !at_line_start(text) {
+      text.push(' ');
+    }
+    text.push_str(word);
+  }
+}
+
+// spaces except where punctuation, contractions, quotes and emoji want none: "it 's ( sort of ) 🚀 🚀 ." goes back
+// to "it's (sort of) 🚀🚀."
+pub struct Detokenizer;
+
+// these stick to the word before them
+const CLOSING: &[char] = &[',', '.', ';', ':', '!', '?', '%', ')', ']', '}', '”', '’', '…', '»'];
+// and these to the word after
+const OPENING: &[char] = &['(', '[', '{', '“', '‘', '¿', '¡', '$', '«'];
+
+impl Joiner for Detokenizer {
+  fn push(&self, text: &mut String, word: &str) {
+    if !at_line_start(text) && space_before(text, word) {
+      text.push(' ');
+    }
+    text.push_str(word);
+  }
+}
+
+fn space_before(text: &str, word: &str) -> bool {
+  let last = text.rsplit([' ', '\n']).next().unwrap_or(text);
+  // an even number of straight quotes so far means the next one opens a quote, odd means it closes one
+  let inside_quote = text.matches('"').count() % 2 == 1;
+
+  if word.chars().all( |c| CLOSING.contains(&c) ) || is_contraction(word) {
+    return false;
+  }
+  if word == "\"" {
+    return !inside_quote;
+  }
+  if last.chars().all( |c| OPENING.contains(&c) ) || (last == "\"" && inside_quote) {
+    return false;
+  }
+  return !(is_emoji(last) && is_emoji(word));
+}
+
+// 's, n't, 're and the like, with either apostrophe
+fn is_contraction(word: &str) -> bool {
+  let word = word.to_lowercase().replace('’', "'");
+  return matches!(word.as_str(), "'s" | "n't" | "'re" | "'ll" | "'m" | "'ve" | "'d");
+}
+
+// every char is a pictograph, or glues them together (zero width joiner, variation selector, skin tone)
+fn is_emoji(word: &str) -> bool {
+  return !word.is_empty() && word.chars().all( |c| matches!(c as u32,
+    0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B00..=0x2BFF | 0x200D | 0xFE0F
+  ));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn join(joiner: &dyn Joiner, text: &str) -> String {
+    let mut out = String::new();
+    for word in text.split(' ') {
+      joiner.push(&mut out, word);
+    }
+    return out;
+  }
+
+  #[test]
+  fn detokenizing_takes_the_extra_spaces_out() {
+    assert_eq!(join(&Detokenizer, "it 's ( sort of ) 🚀 🚀 ."), "it's (sort of) 🚀🚀.");
+    assert_eq!(join(&Detokenizer, "she said \" don't \" , then paid $ 5 ."), "she said \"don't\", then paid $5.");
+    assert_eq!(join(&Detokenizer, "Hodl the moon. Buy the dip."), "Hodl the moon. Buy the dip.");
+    assert_eq!(join(&Spaces, "it 's"), "it 's");
+  }
+}
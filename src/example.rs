@@ -0,0 +1,80 @@
+use std::{fs, io};
+use std::path::{Path, PathBuf};
+use crate::config::CONFIG_FILE;
+use crate::manifest::MANIFEST_FILE;
+
+// a corpus to start from, built into the binary. selftest trains on it too
+pub const CORPUS: [(&str, &str); 2] = [
+  ("bitcoin.txt", include_str!("../seed/bitcoin.txt")),
+  ("dmt.txt", include_str!("../seed/dmt.txt")),
+];
+
+const MANIFEST: &str = "description = \"the bitcoin whitepaper and an erowid experience report\"
+
+[[file]]
+path = \"bitcoin.txt\"
+tags = [\"crypto\", \"bitcoin\"]
+source = \"https://bitcoin.org/bitcoin.pdf\"
+
+[[file]]
+path = \"dmt.txt\"
+tags = [\"erowid\", \"dmt\"]
+";
+
+const CONFIG: &str = "# every key is the flag of the same name, see `erowidcoin help <command>`. flags on the command line win
+
+[chain]
+text = \"./txt\"
+order = 2
+max_chars = 280
+banned = [\"rugpull\"]
+
+[schedule]
+interval = \"6h\"
+quiet_hours = \"22-7\"
+
+# where posts go, see the README. without a target they're printed
+# [target.production]
+# api = \"mastodon\"
+";
+
+// the corpus under dir/txt with a manifest, and a config next to it. nothing gets overwritten, if any of it is
+// already there that's an error before anything's written. gives back what it wrote
+pub fn write(dir: &Path) -> io::Result<Vec<PathBuf>> {
+  let txt = dir.join("txt");
+  let mut files: Vec<(PathBuf, &str)> = CORPUS.iter().map( |(name, text)| (txt.join(name), *text) ).collect();
+  files.push((txt.join(MANIFEST_FILE), MANIFEST));
+  files.push((dir.join(CONFIG_FILE), CONFIG));
+
+  if let Some((path, _)) = files.iter().find( |(path, _)| path.exists() ) {
+    return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} is already there, pick an empty directory", path.display())));
+  }
+  fs::create_dir_all(&txt)?;
+  for (path, contents) in &files {
+    fs::write(path, contents)?;
+  }
+  return Ok(files.into_iter().map( |(path, _)| path ).collect());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{config, toml};
+  use crate::markov_chain::MarkovChain;
+
+  #[test]
+  fn writes_a_corpus_that_trains() {
+    let dir = std::env::temp_dir().join(format!("erowidcoin-example-{}", std::process::id()));
+    let written = write(&dir).unwrap();
+    assert_eq!(written.len(), 4);
+    assert_eq!(config::check(&toml::parse(CONFIG).unwrap()), Vec::<String>::new());
+
+    let mut mchain = MarkovChain::new().with_order(2).unwrap().with_seed(1);
+    let tweets = mchain.create_tweets(&dir.join("txt"), 2).unwrap();
+    assert_eq!(tweets.len(), 2);
+    assert!(mchain.provenance().files.iter().any( |file| file.tags.contains(&"dmt".to_string()) ));
+
+    assert_eq!(write(&dir).unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,362 @@
+use std::fmt;
+use std::error::Error;
+use std::collections::{HashMap, HashSet};
+use regex::Regex;
+use crate::sanitize;
+use crate::syllables;
+
+// a check every candidate tweet has to pass, otherwise we throw it away and generate another one
+pub trait Filter: Send + Sync {
+  // shows up in the diagnostics when we give up
+  fn name(&self) -> &str;
+  fn allows(&self, tweet: &str) -> bool;
+}
+
+// counted in chars rather than bytes, twitter doesn't care how many bytes an emoji is
+pub struct MaxLength(pub usize);
+
+impl Filter for MaxLength {
+  fn name(&self) -> &str {
+    return "length";
+  }
+
+  fn allows(&self, tweet: &str) -> bool {
+    return tweet.chars().count() <= self.0;
+  }
+}
+
+pub struct MaxWords(pub usize);
+
+impl Filter for MaxWords {
+  fn name(&self) -> &str {
+    return "word count";
+  }
+
+  fn allows(&self, tweet: &str) -> bool {
+    return tweet.split_whitespace().count() <= self.0;
+  }
+}
+
+// no more "Bitcoin." on its own, when an entry word ends its own sentence
+pub struct MinWords(pub usize);
+
+impl Filter for MinWords {
+  fn name(&self) -> &str {
+    return "min word count";
+  }
+
+  fn allows(&self, tweet: &str) -> bool {
+    return tweet.split_whitespace().count() >= self.0;
+  }
+}
+
+// rejects anything with one of these words in it, or a match for one of the patterns. both sides are compared as
+// skeletons, so zero width spaces or a cyrillic а in the middle of a word don't get it past. patterns see the
+// whole tweet's skeleton, which is lowercase
+#[derive(Clone, Debug)]
+pub struct BannedWords {
+  words: Vec<String>,
+  patterns: Vec<Regex>,
+}
+
+// the built in list for --profanity: slurs first, then the swearing. whole words only, so "scunthorpe" is fine
+const PROFANITY: &[&str] = &[
+  "nigger", "niggers", "nigga", "niggas", "faggot", "faggots", "fag", "fags", "retard", "retards", "retarded", "tranny",
+  "trannies", "kike", "kikes", "spic", "spics", "chink", "chinks", "wetback", "wetbacks", "gook", "gooks", "dyke",
+  "dykes", "coon", "coons", "raghead", "ragheads",
+  "fuck", "fucks", "fucked", "fucking", "fucker", "motherfucker", "shit", "shits", "shitty", "bullshit", "cunt",
+  "cunts", "bitch", "bitches", "bastard", "bastards", "asshole", "assholes", "dick", "dicks", "cock", "cocks", "pussy",
+  "twat", "twats", "wanker", "wankers", "whore", "whores", "slut", "sluts",
+];
+
+impl BannedWords {
+  pub fn new(words: &[String]) -> BannedWords {
+    return BannedWords { words: words.iter().map( |word| sanitize::skeleton(word.trim()) ).filter( |word| !word.is_empty() ).collect(), patterns: Vec::new() };
+  }
+
+  pub fn profanity() -> BannedWords {
+    return BannedWords::new(&PROFANITY.iter().map( |word| word.to_string() ).collect::<Vec<String>>());
+  }
+
+  // a blocklist file: a word a line, or a regex between slashes. blank lines and lines starting with # don't count
+  //
+  //   # doxxing
+  //   /\b\d{3}[ .-]\d{3}[ .-]\d{4}\b/
+  //   rugpull
+  pub fn parse(text: &str) -> Result<BannedWords, regex::Error> {
+    let mut banned = BannedWords::new(&[]);
+    for line in text.lines().map(str::trim).filter( |line| !line.is_empty() && !line.starts_with('#') ) {
+      match line.strip_prefix('/').and_then( |line| line.strip_suffix('/') ) {
+        Some(pattern) => banned.patterns.push(Regex::new(pattern)?),
+        None => banned.words.push(sanitize::skeleton(line)),
+      }
+    }
+    return Ok(banned);
+  }
+
+  pub fn extend(&mut self, other: BannedWords) {
+    self.words.extend(other.words);
+    self.patterns.extend(other.patterns);
+  }
+
+  pub fn is_empty(&self) -> bool {
+    return self.words.is_empty() && self.patterns.is_empty();
+  }
+}
+
+impl Filter for BannedWords {
+  fn name(&self) -> &str {
+    return "banned words";
+  }
+
+  fn allows(&self, tweet: &str) -> bool {
+    let banned_word = tweet.split_whitespace()
+      .map( |word| sanitize::skeleton(word.trim_matches( |c: char| !c.is_alphanumeric() )) )
+      .any( |word| self.words.contains(&word) );
+    return !banned_word && (self.patterns.is_empty() || !self.patterns.iter().any( |pattern| pattern.is_match(&sanitize::skeleton(tweet)) ));
+  }
+}
+
+// poetry mode: as many lines as there are counts, each with exactly that many syllables (as far as
+// syllables::count can tell)
+pub struct Syllables(pub Vec<usize>);
+
+impl Filter for Syllables {
+  fn name(&self) -> &str {
+    return "syllables";
+  }
+
+  fn allows(&self, tweet: &str) -> bool {
+    let lines: Vec<usize> = tweet.lines().filter( |line| !line.trim().is_empty() ).map(syllables::line).collect();
+    return lines == self.0;
+  }
+}
+
+// the first letters of the words (or of the lines) spell out a word, lowercase letters
+pub struct Acrostic {
+  pub letters: Vec<char>,
+  pub by_line: bool,
+}
+
+impl Acrostic {
+  pub fn new(word: &str, by_line: bool) -> Acrostic {
+    return Acrostic { letters: word.chars().filter_map( |c| initial(&c.to_string()) ).collect(), by_line };
+  }
+}
+
+impl Filter for Acrostic {
+  fn name(&self) -> &str {
+    return "acrostic";
+  }
+
+  fn allows(&self, tweet: &str) -> bool {
+    let initials: Vec<Option<char>> = match self.by_line {
+      true => tweet.lines().map(initial).collect(),
+      false => tweet.split_whitespace().map(initial).collect(),
+    };
+    return initials.len() == self.letters.len() && initials.iter().zip(&self.letters).all( |(initial, letter)| *initial == Some(*letter) );
+  }
+}
+
+// rejects anything that repeats more than `max` words in a row from the training text, which is a boring tweet at
+// best and someone's trip report quoted back at them at worst. the chain records every run of max + 1 words while
+// it trains, as hashes so it's 8 bytes a word instead of the text all over again. words are hashed lowercase and
+// without their punctuation, and tokens that are nothing but punctuation don't count, so neither a style nor the
+// way a tokenizer split the corpus up gets a copied run past it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Verbatim {
+  pub max: usize,
+  pub seen: HashSet<u64>,
+  // false for the hashes of models from before version 6, which are of the words exactly as they were written
+  pub normalized: bool,
+}
+
+impl Verbatim {
+  pub fn new(max: usize) -> Verbatim {
+    return Verbatim { max, seen: HashSet::new(), normalized: true };
+  }
+
+  // the same max and hashing, nothing recorded yet
+  pub fn empty(&self) -> Verbatim {
+    return Verbatim { max: self.max, seen: HashSet::new(), normalized: self.normalized };
+  }
+
+  // a document's words, in order
+  pub fn record<'a>(&mut self, words: impl Iterator<Item = &'a str>) {
+    let words = self.words(words);
+    for window in words.windows(self.max + 1) {
+      self.seen.insert(self.hash(window));
+    }
+  }
+
+  fn words<'a>(&self, words: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    return match self.normalized {
+      true => words.filter( |word| word.chars().any(char::is_alphanumeric) ).collect(),
+      false => words.collect(),
+    };
+  }
+
+  fn hash(&self, words: &[&str]) -> u64 {
+    return match self.normalized {
+      true => fnv(words.iter().map( |word| word.chars().filter( |c| c.is_alphanumeric() ).flat_map(char::to_lowercase) )),
+      false => fnv(words.iter().map( |word| word.chars() )),
+    };
+  }
+}
+
+impl Filter for Verbatim {
+  fn name(&self) -> &str {
+    return "verbatim";
+  }
+
+  fn allows(&self, tweet: &str) -> bool {
+    let words = self.words(tweet.split_whitespace());
+    return !words.windows(self.max + 1).any( |window| self.seen.contains(&self.hash(window)) );
+  }
+}
+
+// fnv-1a, with a byte between words that utf-8 never has. it has to come out the same in every build, the hashes
+// get saved with the model
+fn fnv(words: impl Iterator<Item = impl Iterator<Item = char>>) -> u64 {
+  let mut hash: u64 = 0xcbf29ce484222325;
+  let mut buffer = [0; 4];
+  for word in words {
+    for c in word {
+      for &byte in c.encode_utf8(&mut buffer).as_bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+      }
+    }
+    hash ^= 0xff;
+    hash = hash.wrapping_mul(0x100000001b3);
+  }
+  return hash;
+}
+
+// a word's first letter, lowercase, past any quotes or brackets in front of it
+pub fn initial(word: &str) -> Option<char> {
+  return word.chars().find( |c| c.is_alphanumeric() ).and_then( |c| c.to_lowercase().next() );
+}
+
+// running totals since the chain was made, for stats and /metrics. if the queue runs dry this says whether the
+// filters are throwing away nearly everything, and which one
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FilterStats {
+  // everything generated, kept or not
+  pub candidates: usize,
+  // filter name (or whatever else turned it down, like backfill's duplicate check) -> rejections
+  pub rejections: HashMap<String, usize>,
+}
+
+impl FilterStats {
+  pub fn reject(&mut self, reason: &str) {
+    *self.rejections.entry(reason.to_string()).or_insert(0) += 1;
+  }
+
+  // most common first, same as GaveUp
+  pub fn sorted(&self) -> Vec<(String, usize)> {
+    let mut rejections: Vec<(String, usize)> = self.rejections.clone().into_iter().collect();
+    rejections.sort_by( |a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)) );
+    return rejections;
+  }
+}
+
+// what every rejected candidate failed on, so we can say why we gave up instead of just spinning forever
+pub struct GaveUp {
+  pub attempts: usize,
+  // filter name -> rejections, most common first
+  pub rejections: Vec<(String, usize)>,
+}
+
+impl GaveUp {
+  pub fn new(attempts: usize, rejections: HashMap<String, usize>) -> GaveUp {
+    let mut rejections: Vec<(String, usize)> = rejections.into_iter().collect();
+    rejections.sort_by( |a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)) );
+
+    return GaveUp { attempts, rejections };
+  }
+}
+
+impl fmt::Display for GaveUp {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "gave up after {} attempts", self.attempts)?;
+
+    if let Some((name, count)) = self.rejections.first() {
+      write!(f, ", most candidates failed the {} filter ({} times)", name, count)?;
+    }
+    for (name, count) in self.rejections.iter().skip(1) {
+      write!(f, ", {} ({})", name, count)?;
+    }
+    return Ok(());
+  }
+}
+
+impl fmt::Debug for GaveUp {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    return fmt::Display::fmt(self, f);
+  }
+}
+
+impl Error for GaveUp {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn gave_up_lists_the_worst_filter_first() {
+    let rejections = HashMap::from([("length".to_string(), 3), ("banned words".to_string(), 7)]);
+    let error = GaveUp::new(10, rejections);
+
+    assert_eq!(error.to_string(), "gave up after 10 attempts, most candidates failed the banned words filter (7 times), length (3)");
+  }
+
+  #[test]
+  fn banned_words_see_through_lookalikes() {
+    let filter = BannedWords::new(&["Scam".to_string()]);
+    assert!(!filter.allows("Total scam."));
+    assert!(!filter.allows("Total ѕсаm."));
+    assert!(!filter.allows("Total s\u{200B}c\u{200D}am!"));
+    assert!(filter.allows("Scampi is a store of value."));
+
+    let mut filter = BannedWords::parse("# doxxing\n/\\b\\d{3}[ .-]\\d{4}\\b/\n\nRugpull\n").unwrap();
+    filter.extend(BannedWords::profanity());
+    assert!(!filter.allows("Call 555-0134 for tabs."));
+    assert!(!filter.allows("Another rugpull, lol."));
+    assert!(!filter.allows("What the fuck."));
+    assert!(filter.allows("Number go up 1000x."));
+    assert!(BannedWords::parse("/(/").is_err());
+  }
+
+  #[test]
+  fn acrostics_spell_by_word_or_line() {
+    assert!(Acrostic::new("HODL", false).allows("Have \"other\" dreams, lol."));
+    assert!(!Acrostic::new("HODL", false).allows("Have other dreams, lol and more."));
+    assert!(Acrostic::new("gm", true).allows("Good morning.\nMoon soon."));
+  }
+
+  #[test]
+  fn verbatim_runs_longer_than_the_max_get_rejected() {
+    let mut filter = Verbatim::new(3);
+    filter.record("I took the tab and waited".split_whitespace());
+    assert!(filter.allows("I took the bus and waited."));
+    assert!(!filter.allows("Then I took the tab again."));
+    assert!(Verbatim::new(3).allows("I took the tab and waited"));
+
+    // case and punctuation don't hide a copy, on either side
+    filter.record(["Mom", "called", "me", ",", "(", "Eventually", ")", "the", ",", "walls", "melted", "."].into_iter());
+    assert!(!filter.allows("MOM CALLED ME, (EVENTUALLY) THE, WALLS MELTED."));
+    assert!(!filter.allows("then 👏 eventually 👏 the 👏 walls 👏 melted"));
+    let mut exact = Verbatim { normalized: false, ..Verbatim::new(3) };
+    exact.record("I took the tab and waited".split_whitespace());
+    assert!(!exact.allows("Then I took the tab again.") && exact.allows("Then I TOOK the tab again."));
+  }
+
+  #[test]
+  fn syllables_check_every_line() {
+    let haiku = Syllables(vec!(3, 2));
+    assert!(haiku.allows("Hodl go up\nto the\n"));
+    assert!(!haiku.allows("Hodl go up\nmoon"));
+    assert!(!haiku.allows("Hodl go up"));
+  }
+}
@@ -0,0 +1,112 @@
+use std::fmt;
+use std::collections::HashMap;
+
+// which words from a blend's corpora end up in the same tweets. a word belongs to a corpus if none of the others
+// ever used it, so "the" and "and" belong to nobody and "liquidity" is crypto's. a tweet with words from two corpora
+// bridges them, and the words that do it most are the ones the blend is actually fusing
+// a word and the corpus it's from
+pub type Sourced = (String, String);
+
+pub struct FlavorReport {
+  pub samples: usize,
+  // tweets with words from more than one corpus
+  pub bridging: usize,
+  // (word, its corpus) with (word, its corpus), how many tweets had both. most first
+  pub pairs: Vec<(Sourced, Sourced, usize)>,
+  // corpus -> how many tweets had one of its words
+  pub reach: Vec<(String, usize)>,
+}
+
+// Sourced while it's being counted, borrowing the corpus name
+type WordIn<'a> = (String, &'a str);
+
+// what a word gets looked up as: no punctuation around it, lowercase
+pub fn key(word: &str) -> String {
+  return word.trim_matches( |c: char| !c.is_alphanumeric() ).to_lowercase();
+}
+
+impl FlavorReport {
+  // source_of is the one corpus a word (as key gives it) comes from, None if it's in more than one or none
+  pub fn new<'a>(tweets: &[String], source_of: impl Fn(&str) -> Option<&'a str>, top: usize) -> FlavorReport {
+    let mut pairs: HashMap<(WordIn, WordIn), usize> = HashMap::new();
+    let mut reach: HashMap<&str, usize> = HashMap::new();
+    let mut bridging = 0;
+
+    for tweet in tweets {
+      let mut words: Vec<WordIn> = tweet.split_whitespace().map(key)
+        .filter_map( |word| source_of(&word).map( |source| (word, source) ) )
+        .collect();
+      words.sort();
+      words.dedup();
+
+      let mut sources: Vec<&str> = words.iter().map( |(_, source)| *source ).collect();
+      sources.sort();
+      sources.dedup();
+      for source in &sources {
+        *reach.entry(source).or_insert(0) += 1;
+      }
+      if sources.len() > 1 {
+        bridging += 1;
+      }
+
+      for (i, a) in words.iter().enumerate() {
+        for b in &words[i + 1..] {
+          if a.1 != b.1 {
+            // the same way round every time, whichever way the tweet had them
+            let pair = if (a.1, &a.0) < (b.1, &b.0) { (a.clone(), b.clone()) } else { (b.clone(), a.clone()) };
+            *pairs.entry(pair).or_insert(0) += 1;
+          }
+        }
+      }
+    }
+
+    let mut pairs: Vec<(Sourced, Sourced, usize)> = pairs.into_iter()
+      .map( |((a, b), count)| ((a.0, a.1.to_string()), (b.0, b.1.to_string()), count) )
+      .collect();
+    pairs.sort_by( |a, b| b.2.cmp(&a.2).then_with( || (&a.0, &a.1).cmp(&(&b.0, &b.1)) ) );
+    pairs.truncate(top);
+    let mut reach: Vec<(String, usize)> = reach.into_iter().map( |(source, count)| (source.to_string(), count) ).collect();
+    reach.sort_by( |a, b| b.1.cmp(&a.1).then_with( || a.0.cmp(&b.0) ) );
+
+    return FlavorReport { samples: tweets.len(), bridging, pairs, reach };
+  }
+}
+
+impl fmt::Display for FlavorReport {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, "samples: {}", self.samples)?;
+    writeln!(f, "bridging tweets: {} ({:.1}%)", self.bridging, 100.0 * self.bridging as f64 / self.samples.max(1) as f64)?;
+    for (source, count) in &self.reach {
+      writeln!(f, "  with {} words: {}", source, count)?;
+    }
+    write!(f, "top bridges:")?;
+    if self.pairs.is_empty() {
+      write!(f, " none")?;
+    }
+    for ((a, a_source), (b, b_source), count) in &self.pairs {
+      write!(f, "\n  {} ({}) <-> {} ({}): {}", a, a_source, b, b_source, count)?;
+    }
+    return Ok(());
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn words_from_different_corpora_bridge() {
+    let source_of = |word: &str| match word {
+      "liquidity" | "hodl" => Some("crypto"),
+      "ego" | "visuals" => Some("erowid"),
+      _ => None,
+    };
+    let tweets = ["The liquidity dissolved my ego.", "Visuals and liquidity, hodl my ego.", "The end."].map(String::from);
+    let report = FlavorReport::new(&tweets, source_of, 10);
+
+    assert_eq!((report.samples, report.bridging), (3, 2));
+    assert_eq!(report.pairs[0], (("liquidity".to_string(), "crypto".to_string()), ("ego".to_string(), "erowid".to_string()), 2));
+    assert_eq!(report.pairs.len(), 4);
+    assert_eq!(report.reach, vec!(("crypto".to_string(), 2), ("erowid".to_string(), 2)));
+  }
+}
@@ -0,0 +1,170 @@
+use std::collections::BTreeMap;
+use crate::manifest::FileEntry;
+use crate::toml::{self, Value};
+
+// frontmatter at the top of a corpus file, the way blogs and notes apps write it:
+//
+//   ---
+//   title: Third time's the charm
+//   substance: DMT
+//   dose: 50 mg
+//   date: 2004-06-12
+//   tags: [erowid, visuals]
+//   ---
+//
+// or toml between +++ lines (with the dates quoted, the toml here doesn't do bare ones). it never gets trained on,
+// so "substance: DMT" can't turn up in a tweet. of yaml there's `key: value`, [lists] and `- item` lists, which is
+// as much as frontmatter ever has
+pub type Fields = BTreeMap<String, Value>;
+
+// these mean what they do in a corpus.toml [[file]], everything else is only kept for the provenance
+const ENTRY_KEYS: &[&str] = &["tags", "weight", "license", "source"];
+
+// the frontmatter and what comes after it. a first line of --- without another one to close it is just a file that
+// starts with a rule, not frontmatter
+pub fn split(contents: &str) -> Result<(Option<Fields>, &str), String> {
+  let text = contents.strip_prefix('\u{FEFF}').unwrap_or(contents);
+  for fence in ["---", "+++"] {
+    let Some(rest) = text.strip_prefix(fence).and_then( |rest| rest.strip_prefix('\n').or(rest.strip_prefix("\r\n")) ) else {
+      continue;
+    };
+    let mut at = 0;
+    for line in rest.split_inclusive('\n') {
+      if line.trim_end() == fence {
+        let header = &rest[..at];
+        let fields = match fence {
+          "---" => yaml(header)?,
+          _ => toml::parse(header).map_err( |error| error.to_string() )?,
+        };
+        return Ok((Some(fields), &rest[at + line.len()..]));
+      }
+      at += line.len();
+    }
+  }
+  return Ok((None, contents));
+}
+
+fn yaml(header: &str) -> Result<Fields, String> {
+  let mut fields = Fields::new();
+  // the key waiting on `- item` lines
+  let mut list: Option<String> = None;
+  for (i, line) in header.lines().enumerate() {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+      continue;
+    }
+    if let (Some(key), Some(item)) = (&list, trimmed.strip_prefix("- ")) {
+      if let Some(Value::Array(items)) = fields.get_mut(key) {
+        items.push(scalar(item));
+      }
+      continue;
+    }
+    let Some((key, value)) = trimmed.split_once(':') else {
+      return Err(format!("line {}: expected `key: value`", i + 2));
+    };
+    let (key, value) = (key.trim().to_string(), value.trim());
+    list = None;
+    let value = match value.strip_prefix('[').and_then( |value| value.strip_suffix(']') ) {
+      Some(items) => Value::Array(items.split(',').map(str::trim).filter( |item| !item.is_empty() ).map(scalar).collect()),
+      None if value.is_empty() => {
+        list = Some(key.clone());
+        Value::Array(Vec::new())
+      },
+      None => scalar(value),
+    };
+    fields.insert(key, value);
+  }
+  return Ok(fields);
+}
+
+fn scalar(value: &str) -> Value {
+  for quote in ['"', '\''] {
+    if let Some(value) = value.strip_prefix(quote).and_then( |value| value.strip_suffix(quote) ) {
+      return Value::String(value.to_string());
+    }
+  }
+  if let Ok(n) = value.parse() {
+    return Value::Integer(n);
+  }
+  if let Ok(f) = value.parse() {
+    return Value::Float(f);
+  }
+  return match value {
+    "true" => Value::Boolean(true),
+    "false" => Value::Boolean(false),
+    value => Value::String(value.to_string()),
+  };
+}
+
+// what the file's entry in the provenance ends up as: the manifest's, with the frontmatter filling in the rest.
+// tags add up, and substance is one of them too so the numbers can go by it (--numbers dmt=keep). title, dose, date
+// and anything else go in the entry's metadata
+pub fn entry(path: &str, fields: &Fields, listed: Option<&FileEntry>) -> Result<FileEntry, String> {
+  let mut entry = listed.cloned().unwrap_or_else( || FileEntry::new(path) );
+  let string = |key: &str| -> Result<Option<String>, String> {
+    return match fields.get(key) {
+      Some(Value::String(s)) => Ok(Some(s.clone())),
+      Some(other) => Err(format!("{} should be a string, not a {}", key, other.type_name())),
+      None => Ok(None),
+    };
+  };
+
+  let mut tags = match fields.get("tags") {
+    Some(Value::Array(tags)) => tags.iter().map( |tag| tag.as_str().map(String::from).ok_or("tags should be a list of strings") ).collect::<Result<Vec<String>, &str>>()?,
+    Some(Value::String(tag)) => vec!(tag.clone()),
+    Some(other) => return Err(format!("tags should be a list of strings, not a {}", other.type_name())),
+    None => Vec::new(),
+  };
+  // "DMT, 5-MeO-DMT" is two
+  tags.extend(string("substance")?.iter().flat_map( |substance| substance.split(',') ).map( |substance| substance.trim().to_lowercase() ).filter( |substance| !substance.is_empty() ));
+  for tag in tags {
+    if !entry.tags.contains(&tag) {
+      entry.tags.push(tag);
+    }
+  }
+
+  if let (None, Some(value)) = (listed, fields.get("weight")) {
+    entry.weight = value.as_float().filter( |weight| *weight > 0.0 && weight.is_finite() )
+      .ok_or_else( || format!("weight should be a positive number, not {:?}", value) )?;
+  }
+  entry.license = entry.license.or(string("license")?);
+  entry.source = entry.source.or(string("source")?);
+
+  for (key, value) in fields.iter().filter( |(key, _)| !ENTRY_KEYS.contains(&key.as_str()) ) {
+    let value = match value {
+      Value::String(s) => s.clone(),
+      Value::Integer(n) => n.to_string(),
+      Value::Float(f) => f.to_string(),
+      Value::Boolean(b) => b.to_string(),
+      Value::Array(items) => items.iter().filter_map( |item| item.as_str() ).collect::<Vec<&str>>().join(", "),
+      Value::Table(_) => return Err(format!("{} should be a value, not a table", key)),
+    };
+    entry.metadata.entry(key.clone()).or_insert(value);
+  }
+  return Ok(entry);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn takes_the_frontmatter_off() {
+    let report = "---\ntitle: \"Third time: the charm\"\nsubstance: DMT\ndose: 50 mg\ntags:\n  - erowid\n  - visuals\nweight: 2\n---\nThe walls started breathing.\n";
+    let (fields, body) = split(report).unwrap();
+    let fields = fields.unwrap();
+    assert_eq!(body, "The walls started breathing.\n");
+    assert_eq!(fields["title"], Value::String("Third time: the charm".to_string()));
+
+    let entry = entry("dmt.txt", &fields, None).unwrap();
+    assert_eq!(entry.tags, vec!("erowid".to_string(), "visuals".to_string(), "dmt".to_string()));
+    assert_eq!(entry.weight, 2.0);
+    assert_eq!(entry.metadata["dose"], "50 mg");
+
+    let (fields, body) = split("+++\ntags = [\"crypto\"]\ndate = \"2021-11-20\"\n+++\nHodl.").unwrap();
+    assert_eq!(body, "Hodl.");
+    assert_eq!(fields.unwrap()["date"], Value::String("2021-11-20".to_string()));
+    assert_eq!(split("---\nNo frontmatter, just a rule.").unwrap(), (None, "---\nNo frontmatter, just a rule."));
+    assert!(split("---\nnot yaml\n---\n").is_err());
+  }
+}
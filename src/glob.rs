@@ -0,0 +1,63 @@
+use std::path::Path;
+
+// which files under a corpus directory get read. a pattern without a slash matches the file name anywhere in the
+// tree ("*.txt"), one with a slash matches the path from the top of the corpus ("reports/**/*.md")
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Patterns {
+  // empty means everything
+  pub include: Vec<String>,
+  // applied after include, so it wins
+  pub exclude: Vec<String>,
+}
+
+impl Patterns {
+  // relative is the path from the top of the corpus
+  pub fn allows(&self, relative: &Path) -> bool {
+    let path = relative.to_string_lossy().replace('\\', "/");
+    let name = relative.file_name().unwrap_or_default().to_string_lossy();
+    let hit = |pattern: &String| match pattern.contains('/') {
+      true => matches(pattern, &path),
+      false => matches(pattern, &name),
+    };
+    return (self.include.is_empty() || self.include.iter().any(hit)) && !self.exclude.iter().any(hit);
+  }
+}
+
+// * is anything but a slash, ** is anything at all (a "**/" can match nothing, so "**/*.txt" matches "a.txt"),
+// ? is one character that isn't a slash. that's all there is, no [classes]
+pub fn matches(pattern: &str, text: &str) -> bool {
+  let pattern: Vec<char> = pattern.chars().collect();
+  let text: Vec<char> = text.chars().collect();
+  return matches_from(&pattern, &text);
+}
+
+fn matches_from(pattern: &[char], text: &[char]) -> bool {
+  return match pattern {
+    [] => text.is_empty(),
+    ['*', '*', '/', rest @ ..] => matches_from(rest, text) || (!text.is_empty() && matches_from(pattern, &text[1..])),
+    ['*', '*', rest @ ..] => (0..=text.len()).any( |i| matches_from(rest, &text[i..]) ),
+    ['*', rest @ ..] => (0..=text.len()).take_while( |&i| i == 0 || text[i - 1] != '/' ).any( |i| matches_from(rest, &text[i..]) ),
+    ['?', rest @ ..] => text.first().is_some_and( |&c| c != '/' ) && matches_from(rest, &text[1..]),
+    [c, rest @ ..] => text.first() == Some(c) && matches_from(rest, &text[1..]),
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn globs_match_names_and_paths() {
+    assert!(matches("*.txt", "report.txt"));
+    assert!(!matches("*.txt", "report.txt.bak"));
+    assert!(!matches("*.txt", "old/report.txt"));
+    assert!(matches("**/*.txt", "report.txt") && matches("**/*.txt", "a/b/report.txt"));
+    assert!(matches("old/?.md", "old/a.md") && !matches("old/?.md", "old/ab.md"));
+
+    let patterns = Patterns { include: vec!("*.txt".to_string()), exclude: vec!("drafts/**".to_string()) };
+    assert!(patterns.allows(Path::new("a/b/report.txt")));
+    assert!(!patterns.allows(Path::new("drafts/report.txt")));
+    assert!(!patterns.allows(Path::new("notes.md")));
+    assert!(Patterns::default().allows(Path::new("anything")));
+  }
+}
@@ -0,0 +1,169 @@
+use std::io::{self, Write};
+use std::sync::Mutex;
+use crate::filter::Filter;
+use crate::json;
+use crate::storage::Storage;
+
+// everything the chain has come out with, so a bot that's been running for a year doesn't say the same thing twice.
+// a record per tweet, {"tweet":<text>}, and a new tweet has to be far enough (by edit distance) from all of them
+pub struct History {
+  storage: Box<dyn Storage>,
+  // normalized, see normalize
+  past: Mutex<Vec<Vec<char>>>,
+  // 1.0 only throws out exact repeats (once normalized), 0.9 anything that's 90% the same
+  pub max_similarity: f64,
+}
+
+pub const DEFAULT_MAX_SIMILARITY: f64 = 0.9;
+
+impl History {
+  pub fn open(storage: Box<dyn Storage>, max_similarity: f64) -> io::Result<History> {
+    let invalid = |error: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", storage.describe(), error));
+    let mut past = Vec::new();
+    for record in storage.load()? {
+      let value = json::parse(&record).map_err(invalid)?;
+      let tweet = value.get("tweet").and_then( |tweet| tweet.as_str() ).ok_or_else( || invalid(format!("no tweet in {}", record)) )?;
+      past.push(normalize(tweet));
+    }
+    return Ok(History { storage, past: Mutex::new(past), max_similarity });
+  }
+
+  // every tweet as a json line, like queue export, re-serialized so a hand-edited history comes out clean
+  pub fn export(&self, out: &mut impl Write) -> io::Result<usize> {
+    let records = self.storage.load()?;
+    for record in &records {
+      let tweet = json::parse(record).ok().and_then( |value| Some(value.get("tweet")?.as_str()?.to_string()) ).unwrap_or_default();
+      writeln!(out, "{{\"tweet\":{}}}", json::string(&tweet))?;
+    }
+    return Ok(records.len());
+  }
+
+  // takes a history export, anything that's already in it (once normalized) is skipped so importing twice is
+  // harmless. returns (imported, skipped)
+  pub fn import(&self, export: &str) -> io::Result<(usize, usize)> {
+    let mut tweets: Vec<String> = Vec::new();
+    let mut skipped = 0;
+    {
+      let past = self.past.lock().unwrap();
+      for (i, line) in export.lines().enumerate().filter( |(_, line)| !line.trim().is_empty() ) {
+        let invalid = |error: String| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", i + 1, error));
+        let value = json::parse(line).map_err(invalid)?;
+        let tweet = value.get("tweet").and_then( |tweet| tweet.as_str() ).ok_or_else( || invalid("missing tweet".to_string()) )?;
+        let normalized = normalize(tweet);
+        if past.contains(&normalized) || tweets.iter().any( |other| normalize(other) == normalized ) {
+          skipped += 1;
+          continue;
+        }
+        tweets.push(tweet.to_string());
+      }
+    }
+
+    // nothing gets written unless the whole export parsed
+    let records: Vec<String> = tweets.iter().map( |tweet| format!("{{\"tweet\":{}}}", json::string(tweet)) ).collect();
+    self.storage.append(&records)?;
+    self.past.lock().unwrap().extend(tweets.iter().map( |tweet| normalize(tweet) ));
+    return Ok((tweets.len(), skipped));
+  }
+
+  pub fn record(&self, tweet: &str) -> io::Result<()> {
+    self.storage.append(&[format!("{{\"tweet\":{}}}", json::string(tweet))])?;
+    self.past.lock().unwrap().push(normalize(tweet));
+    return Ok(());
+  }
+
+  pub fn len(&self) -> usize {
+    return self.past.lock().unwrap().len();
+  }
+
+  pub fn is_empty(&self) -> bool {
+    return self.len() == 0;
+  }
+
+  // how close the closest thing we've already said is, 0.0 with nothing to compare to
+  pub fn closest(&self, tweet: &str) -> f64 {
+    let tweet = normalize(tweet);
+    let past = self.past.lock().unwrap();
+    let mut closest: f64 = 0.0;
+    for old in past.iter() {
+      // they can't be any closer than their lengths let them, a lot cheaper than finding out
+      let longest = tweet.len().max(old.len()).max(1) as f64;
+      if 1.0 - tweet.len().abs_diff(old.len()) as f64 / longest <= closest {
+        continue;
+      }
+      closest = closest.max(similarity(&tweet, old));
+    }
+    return closest;
+  }
+}
+
+impl Filter for History {
+  fn name(&self) -> &str {
+    return "history";
+  }
+
+  fn allows(&self, tweet: &str) -> bool {
+    return self.closest(tweet) < self.max_similarity;
+  }
+}
+
+// lowercase letters and digits with one space between words, so "Hodl!" and "hodl" are a repeat
+fn normalize(tweet: &str) -> Vec<char> {
+  let words: Vec<String> = tweet.split_whitespace()
+    .map( |word| word.chars().filter( |c| c.is_alphanumeric() ).flat_map(char::to_lowercase).collect::<String>() )
+    .filter( |word| !word.is_empty() )
+    .collect();
+  return words.join(" ").chars().collect();
+}
+
+// 1 - levenshtein distance / the longer length, 1.0 for the same text
+fn similarity(a: &[char], b: &[char]) -> f64 {
+  let longest = a.len().max(b.len());
+  if longest == 0 {
+    return 1.0;
+  }
+  // one row of the table at a time
+  let mut row: Vec<usize> = (0..=b.len()).collect();
+  for (i, &x) in a.iter().enumerate() {
+    let mut diagonal = row[0];
+    row[0] = i + 1;
+    for (j, &y) in b.iter().enumerate() {
+      let substituted = diagonal + (x != y) as usize;
+      diagonal = row[j + 1];
+      row[j + 1] = substituted.min(row[j] + 1).min(row[j + 1] + 1);
+    }
+  }
+  return 1.0 - row[b.len()] as f64 / longest as f64;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::storage::FileStorage;
+
+  #[test]
+  fn repeats_and_near_repeats_are_remembered_across_restarts() {
+    let path = std::env::temp_dir().join(format!("erowidcoin-history-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let history = History::open(Box::new(FileStorage::new(&path)), DEFAULT_MAX_SIMILARITY).unwrap();
+    assert!(history.allows("Hodl the moon."));
+    history.record("Hodl the moon, then buy the dip.").unwrap();
+
+    let history = History::open(Box::new(FileStorage::new(&path)), DEFAULT_MAX_SIMILARITY).unwrap();
+    assert_eq!(history.len(), 1);
+    assert!(!history.allows("HODL the moon... then buy the dip!"));
+    assert!(!history.allows("Hodl the moon, then buy the dips."));
+    assert!(history.allows("Hodl the moon."));
+    assert_eq!(similarity(&normalize("kitten"), &normalize("sitting")), 1.0 - 3.0 / 7.0);
+
+    let mut export = Vec::new();
+    assert_eq!(history.export(&mut export).unwrap(), 1);
+    std::fs::remove_file(&path).unwrap();
+    let other = History::open(Box::new(crate::storage::MemoryStorage::default()), DEFAULT_MAX_SIMILARITY).unwrap();
+    let export = String::from_utf8(export).unwrap() + "{\"tweet\":\"HODL the moon, then buy the dip!\"}\n{\"tweet\":\"Wagmi.\"}\n";
+    assert_eq!(other.import(&export).unwrap(), (2, 1));
+    assert!(other.import("{\"tweet\":\"fine\"}\nnot json").is_err());
+    assert_eq!(other.len(), 2);
+    assert!(!other.allows("Wagmi!"));
+  }
+}
@@ -0,0 +1,49 @@
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use crate::publish::Transient;
+
+// a json POST, for the publishers. there's no http client (or tls) in here and it's not worth one for a request
+// every few hours, so curl does the sending. its config comes in on stdin, that keeps the Authorization header out
+// of `ps`. gives back the status code and the body
+pub fn post_json(url: &str, headers: &[String], body: &str) -> io::Result<(String, String)> {
+  let mut config = vec!(
+    format!("url = {}", quoted(url)),
+    "request = \"POST\"".to_string(),
+    "header = \"Content-Type: application/json\"".to_string(),
+  );
+  config.extend(headers.iter().map( |header| format!("header = {}", quoted(header)) ));
+  config.extend([
+    format!("data-binary = {}", quoted(body)),
+    // the status code on a line of its own after the body
+    "write-out = \"\\n%{http_code}\"".to_string(),
+    "silent".to_string(),
+    "show-error".to_string(),
+  ]);
+
+  let mut curl = Command::new("curl").args(["--config", "-"]).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()
+    .map_err( |error| io::Error::new(error.kind(), format!("couldn't run curl: {}", error)) )?;
+  curl.stdin.take().unwrap().write_all(config.join("\n").as_bytes())?;
+  let output = curl.wait_with_output()?;
+  if !output.status.success() {
+    // couldn't resolve, connect or hear back, anything curl itself gives up on is the network
+    return Err(io::Error::other(Transient(format!("curl failed: {}", String::from_utf8_lossy(&output.stderr).trim()))));
+  }
+
+  let response = String::from_utf8_lossy(&output.stdout);
+  let (body, status) = response.rsplit_once('\n').unwrap_or(("", &response));
+  return Ok((status.trim().to_string(), body.to_string()));
+}
+
+// a status that isn't the one we wanted. too many requests, or the platform's own trouble, is worth trying again
+pub fn failed(platform: &str, status: &str, message: &str) -> io::Error {
+  let error = format!("{} answered {}: {}", platform, status, message);
+  return match status {
+    "429" | "500" | "502" | "503" | "504" => io::Error::other(Transient(error)),
+    _ => io::Error::other(error),
+  };
+}
+
+// a string in curl's config syntax
+fn quoted(value: &str) -> String {
+  return format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""));
+}
@@ -0,0 +1,106 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::collections::HashMap;
+use crate::sanitize::Sanitized;
+
+pub const TOP_TOKENS: usize = 10;
+
+// a file we couldn't use, with the reason
+pub type Skipped = (PathBuf, String);
+
+// what went into the model, so the corpus can be sanity checked before the bot starts posting
+#[derive(Clone, Default)]
+pub struct IngestionReport {
+  pub documents: usize,
+  pub words: usize,
+  pub unique_tokens: usize,
+  // most frequent tokens first
+  pub top_tokens: Vec<(String, usize)>,
+  // language -> documents, see language::detect
+  pub languages: HashMap<String, usize>,
+  // tag -> documents, from the manifest, frontmatter and taxonomy
+  pub tags: HashMap<String, usize>,
+  pub skipped: Vec<Skipped>,
+  // scrub rule -> matches removed, empty unless a scrubber was set
+  pub scrubbed: HashMap<String, usize>,
+  // what sanitize::clean took out
+  pub sanitized: Sanitized,
+  // documents the tokenizer failed on, which got trained on as they were
+  pub untokenized: usize,
+  // words with_training_blocklist kept out of the graph
+  pub banned: usize,
+}
+
+impl IngestionReport {
+  // another report's counts on top of these, for ingestion that got split up. unique and top tokens are left alone,
+  // they come from the graph
+  pub fn add(&mut self, other: IngestionReport) {
+    self.documents += other.documents;
+    self.words += other.words;
+    for (language, count) in other.languages {
+      *self.languages.entry(language).or_insert(0) += count;
+    }
+    for (tag, count) in other.tags {
+      *self.tags.entry(tag).or_insert(0) += count;
+    }
+    self.skipped.extend(other.skipped);
+    for (rule, count) in other.scrubbed {
+      *self.scrubbed.entry(rule).or_insert(0) += count;
+    }
+    self.sanitized.add(other.sanitized);
+    self.untokenized += other.untokenized;
+    self.banned += other.banned;
+  }
+}
+
+impl fmt::Display for IngestionReport {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, "documents: {}", self.documents)?;
+    writeln!(f, "words: {}", self.words)?;
+    writeln!(f, "unique tokens: {}", self.unique_tokens)?;
+
+    let top: Vec<String> = self.top_tokens.iter().map( |(word, count)| format!("{} ({})", word, count) ).collect();
+    writeln!(f, "top tokens: {}", top.join(", "))?;
+
+    let mut languages: Vec<(&String, &usize)> = self.languages.iter().collect();
+    languages.sort_by( |a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)) );
+    let languages: Vec<String> = languages.iter().map( |(language, count)| format!("{} ({})", language, count) ).collect();
+    writeln!(f, "languages: {}", languages.join(", "))?;
+
+    if !self.tags.is_empty() {
+      let mut tags: Vec<(&String, &usize)> = self.tags.iter().collect();
+      tags.sort_by( |a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)) );
+      let tags: Vec<String> = tags.iter().map( |(tag, count)| format!("{} ({})", tag, count) ).collect();
+      writeln!(f, "tags: {}", tags.join(", "))?;
+    }
+
+    if !self.scrubbed.is_empty() {
+      let mut scrubbed: Vec<(&String, &usize)> = self.scrubbed.iter().collect();
+      scrubbed.sort();
+      let scrubbed: Vec<String> = scrubbed.iter().map( |(rule, count)| format!("{} ({})", rule, count) ).collect();
+      writeln!(f, "scrubbed: {}", scrubbed.join(", "))?;
+    }
+
+    if self.sanitized.bidi_controls > 0 {
+      writeln!(f, "bidi controls removed: {}", self.sanitized.bidi_controls)?;
+    }
+    if self.sanitized.invisible > 0 {
+      writeln!(f, "invisible characters removed: {}", self.sanitized.invisible)?;
+    }
+    if self.sanitized.homoglyph_words > 0 {
+      writeln!(f, "words with lookalike characters: {}", self.sanitized.homoglyph_words)?;
+    }
+    if self.untokenized > 0 {
+      writeln!(f, "documents the tokenizer failed on: {}", self.untokenized)?;
+    }
+    if self.banned > 0 {
+      writeln!(f, "banned words dropped: {}", self.banned)?;
+    }
+
+    write!(f, "skipped files: {}", self.skipped.len())?;
+    for (path, reason) in &self.skipped {
+      write!(f, "\n  {}: {}", path.display(), reason)?;
+    }
+    return Ok(());
+  }
+}
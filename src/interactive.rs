@@ -0,0 +1,76 @@
+use std::io::{self, BufRead, Write};
+use crate::markov_chain::MarkovChain;
+
+const HELP: &str = "pick a number, (r)e-roll a random word, (u)ndo, (d)one, (n)ew tweet, (q)uit";
+
+// co-writing mode: shows the top candidates for the next word and lets a human pick, so the tweets are curated
+// but still chain-driven. takes any reader/writer so it isn't glued to stdin
+pub fn run(mchain: &mut MarkovChain, input: impl BufRead, mut output: impl Write, top_k: usize) -> io::Result<Vec<String>> {
+  let mut finished = Vec::new();
+  let mut words = vec!(mchain.random_entry_word().map_err(io::Error::other)?);
+  let mut lines = input.lines();
+
+  writeln!(output, "{}", HELP)?;
+
+  loop {
+    let candidates = mchain.candidates(&words.join(" "), top_k);
+
+    writeln!(output, "\n{}", words.join(" "))?;
+    for (i, (word, probability)) in candidates.iter().enumerate() {
+      writeln!(output, "  {}) {} ({:.1}%)", i + 1, word, probability * 100.0)?;
+    }
+    if candidates.is_empty() {
+      writeln!(output, "  (dead end)")?;
+    }
+    write!(output, "> ")?;
+    output.flush()?;
+
+    let line = match lines.next() {
+      Some(line) => line?,
+      None => break,
+    };
+
+    match line.trim() {
+      "r" => match mchain.sample_next(&words.join(" ")) {
+        Some(word) => words.push(word),
+        None => writeln!(output, "nowhere to go from here")?,
+      },
+      "u" => {
+        if words.len() > 1 {
+          words.pop();
+        }
+      },
+      "d" => {
+        writeln!(output, "\n{}", words.join(" "))?;
+        finished.push(words.join(" "));
+        words = vec!(mchain.random_entry_word().map_err(io::Error::other)?);
+      },
+      "n" => words = vec!(mchain.random_entry_word().map_err(io::Error::other)?),
+      "q" => break,
+      other => match other.parse::<usize>() {
+        Ok(pick) if pick >= 1 && pick <= candidates.len() => words.push(candidates[pick - 1].0.clone()),
+        _ => writeln!(output, "{}", HELP)?,
+      },
+    }
+  }
+
+  return Ok(finished);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::path::Path;
+
+  #[test]
+  fn picks_words_from_input() {
+    let mut mchain = MarkovChain::new();
+    mchain.create_tweets(Path::new("./txt"), 0).unwrap();
+
+    // "The" only ever goes to "syntactic", then "component", then undo and pick it again
+    let input = "1\n1\nu\n1\nd\nq\n".as_bytes();
+    let finished = run(&mut mchain, input, io::sink(), 3).unwrap();
+
+    assert_eq!(finished, vec!("The syntactic component".to_string()));
+  }
+}
@@ -0,0 +1,219 @@
+// just enough json to not need serde for our flat little reports, and to read our own queue/history files back
+use std::collections::BTreeMap;
+
+pub fn string(value: &str) -> String {
+  let mut out = String::from("\"");
+  for c in value.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+      c => out.push(c),
+    }
+  }
+  out.push('"');
+  return out;
+}
+
+// json has no infinity or NaN
+pub fn number(value: f64) -> String {
+  if value.is_finite() {
+    return value.to_string();
+  }
+  return "null".to_string();
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+  Null,
+  Bool(bool),
+  Number(f64),
+  String(String),
+  Array(Vec<Value>),
+  Object(BTreeMap<String, Value>),
+}
+
+impl Value {
+  pub fn get(&self, key: &str) -> Option<&Value> {
+    return match self {
+      Value::Object(fields) => fields.get(key),
+      _ => None,
+    };
+  }
+
+  pub fn as_str(&self) -> Option<&str> {
+    return match self {
+      Value::String(s) => Some(s),
+      _ => None,
+    };
+  }
+
+  // only whole, non-negative numbers (timestamps, counts)
+  pub fn as_u64(&self) -> Option<u64> {
+    return match self {
+      Value::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as u64),
+      _ => None,
+    };
+  }
+
+  pub fn as_array(&self) -> Option<&Vec<Value>> {
+    return match self {
+      Value::Array(a) => Some(a),
+      _ => None,
+    };
+  }
+}
+
+pub fn parse(text: &str) -> Result<Value, String> {
+  let mut parser = Parser { chars: text.char_indices().peekable(), text };
+  let value = parser.value()?;
+  parser.whitespace();
+  if let Some((i, _)) = parser.chars.peek() {
+    return Err(format!("unexpected trailing characters at {}", i));
+  }
+  return Ok(value);
+}
+
+struct Parser<'a> {
+  chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+  text: &'a str,
+}
+
+impl Parser<'_> {
+  fn whitespace(&mut self) {
+    while self.chars.peek().is_some_and( |(_, c)| c.is_whitespace() ) {
+      self.chars.next();
+    }
+  }
+
+  fn expect(&mut self, expected: char) -> Result<(), String> {
+    return match self.chars.next() {
+      Some((_, c)) if c == expected => Ok(()),
+      Some((i, c)) => Err(format!("expected `{}` at {}, got `{}`", expected, i, c)),
+      None => Err(format!("expected `{}`, got the end", expected)),
+    };
+  }
+
+  fn value(&mut self) -> Result<Value, String> {
+    self.whitespace();
+    let (start, c) = *self.chars.peek().ok_or("unexpected end")?;
+
+    return match c {
+      '{' => {
+        self.chars.next();
+        let mut fields = BTreeMap::new();
+        self.whitespace();
+        if self.chars.peek().is_some_and( |(_, c)| *c == '}' ) {
+          self.chars.next();
+          return Ok(Value::Object(fields));
+        }
+        loop {
+          self.whitespace();
+          let key = self.string()?;
+          self.whitespace();
+          self.expect(':')?;
+          fields.insert(key, self.value()?);
+          self.whitespace();
+          match self.chars.next() {
+            Some((_, ',')) => continue,
+            Some((_, '}')) => return Ok(Value::Object(fields)),
+            _ => return Err("expected `,` or `}` in object".to_string()),
+          }
+        }
+      },
+      '[' => {
+        self.chars.next();
+        let mut values = Vec::new();
+        self.whitespace();
+        if self.chars.peek().is_some_and( |(_, c)| *c == ']' ) {
+          self.chars.next();
+          return Ok(Value::Array(values));
+        }
+        loop {
+          values.push(self.value()?);
+          self.whitespace();
+          match self.chars.next() {
+            Some((_, ',')) => continue,
+            Some((_, ']')) => return Ok(Value::Array(values)),
+            _ => return Err("expected `,` or `]` in array".to_string()),
+          }
+        }
+      },
+      '"' => Ok(Value::String(self.string()?)),
+      _ => {
+        let mut end = start;
+        while let Some((i, c)) = self.chars.peek().copied() {
+          if !(c.is_ascii_alphanumeric() || c == '-' || c == '+' || c == '.') {
+            break;
+          }
+          end = i + c.len_utf8();
+          self.chars.next();
+        }
+        match &self.text[start..end] {
+          "null" => Ok(Value::Null),
+          "true" => Ok(Value::Bool(true)),
+          "false" => Ok(Value::Bool(false)),
+          token => token.parse::<f64>().map(Value::Number).map_err( |_| format!("can't make sense of `{}` at {}", token, start) ),
+        }
+      },
+    };
+  }
+
+  fn string(&mut self) -> Result<String, String> {
+    self.expect('"')?;
+    let mut value = String::new();
+    loop {
+      match self.chars.next().ok_or("unclosed string")?.1 {
+        '"' => return Ok(value),
+        '\\' => match self.chars.next().ok_or("unclosed string")?.1 {
+          'n' => value.push('\n'),
+          't' => value.push('\t'),
+          'r' => value.push('\r'),
+          'b' => value.push('\u{8}'),
+          'f' => value.push('\u{c}'),
+          'u' => {
+            let hex: String = (0..4).filter_map( |_| self.chars.next().map( |(_, c)| c ) ).collect();
+            let code = u32::from_str_radix(&hex, 16).map_err( |_| format!("bad unicode escape \\u{}", hex) )?;
+            // surrogate pairs are two escapes in a row
+            let code = if (0xd800..0xdc00).contains(&code) {
+              let low: String = (0..6).filter_map( |_| self.chars.next().map( |(_, c)| c ) ).collect();
+              let low = low.strip_prefix("\\u").and_then( |hex| u32::from_str_radix(hex, 16).ok() ).ok_or("unpaired surrogate")?;
+              0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff)
+            } else {
+              code
+            };
+            value.push(char::from_u32(code).ok_or_else( || format!("bad unicode escape \\u{}", hex) )?);
+          },
+          c => value.push(c),
+        },
+        c => value.push(c),
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn escapes_strings() {
+    assert_eq!(string("\"wow\"\n\\"), "\"\\\"wow\\\"\\n\\\\\"");
+    assert_eq!(number(f64::INFINITY), "null");
+  }
+
+  #[test]
+  fn reads_back_what_we_write() {
+    let text = format!("{{\"text\":{},\"at\":1700000000,\"tags\":[true,null,-1.5],\"emoji\":\"\\ud83d\\ude80\"}}", string("\"wow\"\n\\"));
+    let value = parse(&text).unwrap();
+
+    assert_eq!(value.get("text").and_then(Value::as_str), Some("\"wow\"\n\\"));
+    assert_eq!(value.get("at").and_then(Value::as_u64), Some(1700000000));
+    assert_eq!(value.get("tags"), Some(&Value::Array(vec!(Value::Bool(true), Value::Null, Value::Number(-1.5)))));
+    assert_eq!(value.get("emoji").and_then(Value::as_str), Some("\u{1f680}"));
+    assert!(parse("{\"a\":1} nope").is_err());
+  }
+}
@@ -0,0 +1,49 @@
+// guesses a document's language from how many of its words are common function words. it's a sanity check for
+// "did a german forum dump sneak into my corpus", not real language identification
+const STOPWORDS: &[(&str, &[&str])] = &[
+  ("english", &["the", "and", "of", "to", "a", "in", "is", "it", "that", "i", "was", "my", "with", "for"]),
+  ("spanish", &["el", "la", "de", "que", "y", "en", "los", "las", "un", "una", "por", "con", "es", "mi"]),
+  ("french", &["le", "la", "les", "de", "et", "un", "une", "des", "est", "que", "je", "pas", "dans", "mon"]),
+  ("german", &["der", "die", "das", "und", "ich", "nicht", "ist", "ein", "eine", "zu", "mit", "mein", "auf", "es"]),
+  ("portuguese", &["o", "a", "os", "as", "de", "que", "e", "um", "uma", "não", "com", "meu", "para", "em"]),
+  ("dutch", &["de", "het", "een", "en", "van", "ik", "is", "dat", "niet", "met", "mijn", "op", "te", "zijn"]),
+];
+
+// below this share of stopwords we don't want to guess
+const MIN_SHARE: f64 = 0.1;
+
+pub fn detect(text: &str) -> Option<&'static str> {
+  let words: Vec<String> = text.split_whitespace()
+    .map( |w| w.trim_matches( |c: char| !c.is_alphanumeric() ).to_lowercase() )
+    .filter( |w| !w.is_empty() )
+    .collect();
+  if words.is_empty() {
+    return None;
+  }
+
+  let mut best: Option<(&str, usize)> = None;
+  for (language, stopwords) in STOPWORDS {
+    let hits = words.iter().filter( |w| stopwords.contains(&w.as_str()) ).count();
+    if best.is_none_or( |(_, most)| hits > most ) {
+      best = Some((language, hits));
+    }
+  }
+
+  let (language, hits) = best?;
+  if (hits as f64) / (words.len() as f64) < MIN_SHARE {
+    return None;
+  }
+  return Some(language);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn detects_a_few_languages() {
+    assert_eq!(detect("I put the flame on the bowl and drew in slowly, to avoid torching it."), Some("english"));
+    assert_eq!(detect("Ich habe das nicht gewusst und es ist mir egal."), Some("german"));
+    assert_eq!(detect("0x1f 0x2e 0x3d"), None);
+  }
+}
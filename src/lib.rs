@@ -0,0 +1,67 @@
+// the generator as a library, for embedding it in your own bot instead of shelling out to the binary:
+//
+//   let mut mchain = MarkovChain::builder().corpus("./txt").seed(42).max_chars(280).build()?;
+//   mchain.train()?;
+//   let tweet = mchain.generate()?;
+//
+// everything the binary does is in here too, main.rs is just the flag parsing
+
+// I like my explicit returns, thank you clippy
+#![allow(clippy::needless_return)]
+
+pub mod audit;
+pub mod auth;
+pub mod backfill;
+pub mod bluesky;
+pub mod casing;
+pub mod clock;
+pub mod compare;
+pub mod config;
+pub mod daemon;
+pub mod detokenize;
+pub mod example;
+pub mod filter;
+pub mod flavor;
+pub mod frontmatter;
+pub mod glob;
+pub mod http;
+pub mod history;
+pub mod ingest;
+pub mod interactive;
+pub mod json;
+pub mod language;
+pub mod locale;
+pub mod manifest;
+pub mod markov_chain;
+pub mod mastodon;
+pub mod milestones;
+pub mod model;
+pub mod numbers;
+pub mod observer;
+pub mod pause;
+pub mod plugin;
+pub mod profile;
+pub mod publish;
+pub mod quality;
+pub mod queue;
+pub mod rate_limit;
+pub mod replies;
+pub mod sanitize;
+pub mod scrub;
+pub mod selftest;
+pub mod sensitive;
+pub mod server;
+pub mod sha1;
+pub mod sha256;
+pub mod stats;
+pub mod storage;
+pub mod style;
+pub mod syllables;
+pub mod taxonomy;
+pub mod timings;
+pub mod tokenize;
+pub mod toml;
+pub mod truncate;
+pub mod twitter;
+
+pub use markov_chain::{ErowidCoinError, GenerationError, MarkovChain, MarkovChainBuilder};
@@ -0,0 +1,132 @@
+use std::str::FromStr;
+
+// how a corpus's language does capitalization. that decides which words can start a tweet (entry words) and how
+// text gets case folded for keyword matching
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Locale {
+  // capital A-Z starts a sentence, what english (and the original corpus) needs
+  #[default]
+  Ascii,
+  // any capital letter does: É, Ω, Ж
+  Unicode,
+  // turkish and azerbaijani, where I lowercases to ı and İ to i
+  Turkic,
+  // scripts without capitals (devanagari, arabic, hebrew, thai..), the word after a full stop starts a sentence
+  Uncased,
+}
+
+// the danda, the arabic question mark and the full width forms end sentences too
+const UNCASED_TERMINATORS: &[char] = &['!', '|', '.', '?', '।', '॥', '؟', '。', '！', '？'];
+const TERMINATORS: &[char] = &['!', '|', '.', '?'];
+// quotes and brackets can close a sentence after its full stop (`it."`), UAX #29 calls them Close
+const CLOSE: &[char] = &['"', '\'', ')', ']', '}', '»', '”', '’'];
+// a full stop after one of these doesn't end a sentence. UAX #29 leaves abbreviations to tailoring, these are the
+// ones that turn up in trip reports and crypto threads. "etc." stays out, it ends sentences as often as not
+const ABBREVIATIONS: &[&str] = &[
+  "approx", "ca", "cf", "dr", "e.g", "eg", "esp", "fig", "i.e", "ie", "jr", "mg", "mr", "mrs", "ms", "mt", "no", "prof",
+  "sr", "st", "vs",
+];
+
+impl Locale {
+  // what it's called when written down, parses back to the same locale
+  pub fn name(&self) -> &'static str {
+    return match self {
+      Locale::Ascii => "ascii",
+      Locale::Unicode => "unicode",
+      Locale::Turkic => "turkic",
+      Locale::Uncased => "uncased",
+    };
+  }
+
+  // does `word` start a sentence, given the word before it (None at the start of a document). it has to come after a
+  // sentence boundary, and in a cased script start with a capital too
+  pub fn starts_sentence(&self, word: &str, previous: Option<&str>) -> bool {
+    let after_boundary = previous.is_none_or( |previous| self.ends_sentence_before(previous, Some(word)) );
+    return after_boundary && match self {
+      Locale::Ascii => word.starts_with( |c: char| c.is_ascii_uppercase() ),
+      Locale::Unicode | Locale::Turkic => word.starts_with(char::is_uppercase),
+      Locale::Uncased => true,
+    };
+  }
+
+  // with nothing after it to go on, the way generation sees the last word of a walk
+  pub fn ends_sentence(&self, word: &str) -> bool {
+    return self.ends_sentence_before(word, None);
+  }
+
+  // is there a sentence boundary between `word` and `next`, going by the UAX #29 sentence rules that still mean
+  // something once text is split on whitespace: a terminator can be followed by closing quotes and brackets (SB9), and
+  // a full stop followed by a lowercase word isn't the end of anything (SB8). an abbreviation or an initial ("J.")
+  // doesn't end a sentence either
+  pub fn ends_sentence_before(&self, word: &str, next: Option<&str>) -> bool {
+    let terminators = match self {
+      Locale::Uncased => UNCASED_TERMINATORS,
+      _ => TERMINATORS,
+    };
+    let Some(stem) = full_stop_stem(word) else {
+      return word.trim_end_matches(CLOSE).ends_with(terminators);
+    };
+    let initial = stem.chars().count() == 1 && stem.starts_with(char::is_uppercase);
+    let lowercase_next = next.is_some_and( |next| next.trim_start_matches( |c: char| !c.is_alphanumeric() ).starts_with(char::is_lowercase) );
+    return !initial && !lowercase_next && !ABBREVIATIONS.contains(&stem.to_lowercase().as_str());
+  }
+
+  pub fn fold(&self, text: &str) -> String {
+    return match self {
+      Locale::Turkic => text.replace('I', "ı").replace('İ', "i").to_lowercase(),
+      _ => text.to_lowercase(),
+    };
+  }
+}
+
+// what's in front of a word's full stop, without the quotes and brackets around it: "approx" for "(approx.". None if it
+// doesn't end in one
+pub fn full_stop_stem(word: &str) -> Option<&str> {
+  let stem = word.trim_end_matches(CLOSE).strip_suffix('.')?;
+  return Some(stem.trim_start_matches( |c: char| CLOSE.contains(&c) || c == '(' || c == '“' || c == '«' ));
+}
+
+// a casing rule by name, or a language code
+impl FromStr for Locale {
+  type Err = String;
+
+  fn from_str(value: &str) -> Result<Locale, String> {
+    return match value {
+      "ascii" | "en" => Ok(Locale::Ascii),
+      "unicode" | "de" | "el" | "es" | "fr" | "it" | "nl" | "pl" | "pt" | "ru" | "uk" => Ok(Locale::Unicode),
+      "turkic" | "tr" | "az" => Ok(Locale::Turkic),
+      "uncased" | "ar" | "bn" | "fa" | "he" | "hi" | "ka" | "mr" | "ne" | "th" | "ur" => Ok(Locale::Uncased),
+      other => Err(format!("unknown locale {:?}, expected ascii, unicode, turkic, uncased or a language code", other)),
+    };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn casing_rules_differ() {
+    assert!(Locale::Ascii.starts_sentence("Bitcoin", Some("up.")));
+    assert!(!Locale::Ascii.starts_sentence("Bitcoin", Some("the")));
+    assert!(!Locale::Ascii.starts_sentence("Ωραία", None));
+    assert!(Locale::Unicode.starts_sentence("Ωραία", Some("τέλος.")));
+    assert!(Locale::Uncased.starts_sentence("बाहर", Some("गया।")));
+    assert!(!Locale::Uncased.starts_sentence("बाहर", Some("और")));
+
+    assert_eq!(Locale::Turkic.fold("İSTANBUL IŞIK"), "istanbul ışık");
+    assert_eq!(Locale::Ascii.fold("ISTANBUL"), "istanbul");
+  }
+
+  #[test]
+  fn abbreviations_and_quotes_dont_fool_the_sentence_rules() {
+    let ascii = Locale::Ascii;
+    assert!(!ascii.ends_sentence("Dr.") && !ascii.starts_sentence("Smith", Some("Dr.")));
+    assert!(!ascii.ends_sentence("e.g.") && !ascii.ends_sentence("J."));
+    assert!(ascii.ends_sentence("it.\"") && ascii.ends_sentence("(really?)"));
+    assert!(ascii.ends_sentence_before("moon.", Some("Then")));
+    assert!(!ascii.ends_sentence_before("approx.", Some("Then")));
+    assert!(!ascii.ends_sentence_before("p.m.", Some("sharp")));
+    assert!(Locale::Uncased.ends_sentence("गया।\""));
+  }
+}
@@ -2,42 +2,1270 @@
 "Erowid Coin" is a markov chain generator for tweeting about the unholy marriage of erowid trip
 reports + cryptocurrency - it's build using local text files.
 
-Usage: erowidcoin <directory> <number of tweets (optional)>
+Usage: erowidcoin <command> [<args>], `erowidcoin help` lists the commands and `erowidcoin <command> --help` says
+what each one takes. The usual loop is
+
+       erowidcoin train ./txt -o model.bin --order 2
+       erowidcoin generate -n 5 --model model.bin
+       erowidcoin post --model model.bin --target twitter --dry-run
+
+and `erowidcoin <directory> <number of tweets>` still works the way it always has, it's generate.
 */
 
-pub mod markov_chain;
+// I like my explicit returns, thank you clippy
+#![allow(clippy::needless_return)]
 
-use std::env;
-use markov_chain::MarkovChain;
-use std::path::Path;
+use std::{env, fs, io};
+use std::str::FromStr;
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+use std::collections::{BTreeMap, HashMap};
+use erowidcoin::{audit, auth, backfill, compare, config, daemon, example, interactive, json, milestones, publish, queue, selftest, server, toml};
+use erowidcoin::markov_chain::{Corpus, DeadEndPolicy, MarkovChain, RetryPolicy, RngSource, Sampling, Suppression};
+use erowidcoin::quality::QualityReport;
+use erowidcoin::flavor::FlavorReport;
+use erowidcoin::compare::ModelReport;
+use erowidcoin::filter::{BannedWords, Syllables};
+use erowidcoin::scrub::{Rule, Scrubber};
+use erowidcoin::sensitive::Sensitivity;
+use erowidcoin::style::Styling;
+use erowidcoin::profile::Mix;
+use erowidcoin::milestones::Milestones;
+use erowidcoin::observer::Trace;
+use erowidcoin::detokenize::Spaces;
+use erowidcoin::history::{self, History};
+use erowidcoin::storage::{self, MemoryStorage, Storage};
+use erowidcoin::pause::KillSwitch;
+use erowidcoin::replies::{ReplyGate, ReplyLimits};
+use erowidcoin::taxonomy::Taxonomy;
+use erowidcoin::plugin::{self, Kind, PluginFilter, PluginTokenizer};
+use erowidcoin::tokenize::Punctuation;
+use erowidcoin::stats::CorpusStats;
+use std::path::{Path, PathBuf};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use erowidcoin::clock::{Clock, FakeClock, SystemClock};
 
-fn main() {
-  let args: Vec<String> = env::args().collect();
-  let mut num_tweets: i32 = 1;
+// what `help` lists, in this order. the usage is everything after "erowidcoin "
+struct Command {
+  name: &'static str,
+  about: &'static str,
+  usage: &'static str,
+}
 
-  if args.len() < 2 {
-    println!("usage: erowidcoin <text directory> <number of tweets>");
-    return;
+const COMMANDS: &[Command] = &[
+  Command { name: "train", about: "trains a chain and saves it for --model",
+    usage: "train [<text directory>] -o <model file> [chain flags]" },
+  Command { name: "generate", about: "prints tweets (the default, if the first argument is a directory)",
+    usage: "generate [<text directory>] [-n <number>] [--start <words>] [--thread <n>] [--haiku] [--acrostic <word>] [--by-line]
+                  [--style random|shout,no-punctuation,lowercase,clap] [--format text|json]
+                  [--sensitive always|never|<keywords>] [--probabilities] [--trace] [--report] [chain flags]" },
+  Command { name: "post", about: "generates one post and publishes it now, the way the daemon would",
+    usage: "post [<text directory>] [--config <file>] [--target twitter|mastodon|bluesky|<name>] [--queue <file>] [--audit-log <file>]
+                  [--pause-file <file>] [--style random|<styles>] [--profiles normal=70,shout=20,haiku=10] [--dry-run]
+                  [--sensitive always|never|<keywords>] [chain flags]" },
+  Command { name: "stats", about: "what the chain was trained on and how big it is",
+    usage: "stats [<text directory>] [--samples <n>] [chain flags]" },
+  Command { name: "export-ngrams", about: "counted n-grams from the chain as csv, for analysis somewhere else",
+    usage: "export-ngrams [<text directory>] [--n <words>] [--min-count <n>] [--out <file>] [chain flags]" },
+  Command { name: "export-corpus-stats", about: "the chain as n-gram counts, to share instead of the corpus",
+    usage: "export-corpus-stats [<text directory>] [--min-count <n>] [--min-documents <n>] [--out <file>] [chain flags]" },
+  Command { name: "flavor", about: "which words from each blended corpus end up in tweets together",
+    usage: "flavor --corpus <dir>:<weight> --corpus <dir>:<weight>.. [--samples <n>] [--top <k>] [chain flags]" },
+  Command { name: "quality", about: "diversity metrics over a batch of tweets",
+    usage: "quality [<text directory>] [-n <number>] [chain flags]" },
+  Command { name: "compare", about: "evaluates two corpora against held-out text",
+    usage: "compare <model a> <model b> --holdout <directory> [--samples <n>] [--format markdown|json] [chain flags]" },
+  Command { name: "continue", about: "tweets that start the way you say",
+    usage: "continue [<text directory>] \"<start of a tweet>\" [-n <number>] [chain flags]" },
+  Command { name: "interactive", about: "pick the next word yourself",
+    usage: "interactive [<text directory>] [--top <k>] [chain flags]" },
+  Command { name: "serve", about: "the http api",
+    usage: "serve [<text directory>] [--addr <host:port>] [--config <file>] [--api-key <key>] [--queue <file>]
+                  [--audit-log <file>] [--pause-file <file>] [--target production|test|<name>]
+                  [--sensitive always|never|<keywords>] [chain flags]" },
+  Command { name: "daemon", about: "posts on a schedule",
+    usage: "daemon [<text directory>] [--interval 6h | --cron \"0 */6 * * *\"] [--jitter 30m] [--quiet-hours 22-7]
+                  [--themes 6-11=coffee+gm,2-5=temperature:1.6] [--catch-up skip|all] [--retries 3] [--backoff 1m]
+                  [--style random|<styles>] [--profiles normal=70,shout=20,haiku=10] [--anniversary <0-1>]
+                  [--milestones 100,1k,10k --followers-file <file>]
+                  [--sensitive always|never|<keywords>] [--dry-run] [--iterations <n>] [--config <file>] [--target <name>]
+                  [--queue <file>] [--audit-log <file>] [--pause-file <file>] [chain flags]" },
+  Command { name: "backfill", about: "queues up posts ahead of time",
+    usage: "backfill [<text directory>] [--days <n>] [--per-day <n>] [--queue <file>] [--max-similarity <0-1>]
+                  [--sensitive always|never|<keywords>] [chain flags]" },
+  Command { name: "queue", about: "moves the queue around, or publishes what's due",
+    usage: "queue export|import <file> [--queue <file>]
+       erowidcoin queue post [--every 1m] [--iterations <n>] [--queue <file>] [--config <file>] [--target <name>]
+                  [--audit-log <file>] [--pause-file <file>]" },
+  Command { name: "history", about: "moves the --history file around",
+    usage: "history export|import <file> --history <file>" },
+  Command { name: "audit", about: "checks the audit log hasn't been tampered with",
+    usage: "audit verify <audit log>" },
+  Command { name: "pause", about: "stops anything from being published",
+    usage: "pause [reason] [--config <file>] [--pause-file <file>]" },
+  Command { name: "resume", about: "undoes pause",
+    usage: "resume [--config <file>] [--pause-file <file>]" },
+  Command { name: "check-config", about: "points out problems in a config file",
+    usage: "check-config <config file>" },
+  Command { name: "init-example", about: "writes a sample corpus and config to start from",
+    usage: "init-example <dir>" },
+  Command { name: "plugins", about: "lists the plugins in a directory, and what they are",
+    usage: "plugins [<dir>]" },
+  Command { name: "selftest", about: "checks this install works end to end, on a corpus built into the binary",
+    usage: "selftest" },
+  Command { name: "help", about: "this, or the flags a command takes",
+    usage: "help [<command>]" },
+];
+
+// every command that trains (or loads) a chain takes these
+const CHAIN_FLAGS: &str = "chain flags:
+  --model <file>      load the chain from here if it exists, otherwise train and save it here
+  --stats <file>      rebuild the chain from export-corpus-stats in place of the text directory
+  --seed <u64>        same seed, same tweets. --rng thread|os otherwise
+  --order <n>         words per state, 1 by default
+  --doc-cap <weight>  max total edge weight one document contributes
+  --doc-separator <pattern>
+                      a line that's all this starts a new document, like \"-{5,}\" for one big file of reports
+  --min-documents <n> --min-count <n>
+                      leave out transitions fewer documents (or less weight) than that back up
+  --temperature <t> --top-k <k>
+                      how weird it gets: under 1 sticks closer to the corpus, over 1 wanders off, 0 is always the
+                      likeliest word. top-k only picks from each word's k likeliest next words
+  --locale ascii|unicode|turkic|uncased|<language code>
+  --abbreviations <words>
+                      a full stop after these doesn't end a sentence, like \"mcg,tsp\"
+  --corpus <dir>:<weight>
+                      in place of the text directory, once per corpus to blend: --corpus erowid:0.7 --corpus crypto:0.3
+  --include <globs> --exclude <globs>
+                      which files under the text directory get read, like \"*.txt\" or \"drafts/**\"
+  --poetry            keep line breaks, --syllables 5,7,5 checks each line
+  --fold-case         \"The\" and \"the\" are one word, tweets get their capitals back from how the corpus wrote them
+  --scrub all|emails,phones,handles,names
+  --numbers keep|bucket|spell[,<tag>=<style>..]
+                      what numbers turn into, for the whole corpus or files with a manifest tag
+  --about <tags>      start on (and lean towards) words from documents with one of these tags, like lsd,monero. tags
+                      come from the manifest, frontmatter and the words in each document
+  --taxonomy <file>   which words tag a document, on top of the built in substances and coins: lsd = [\"acid\"]
+  --plugins <dir>     filter and tokenizer plugins from here, see `erowidcoin plugins`
+  --long              paragraphs of sentences up to --max-chars, for somewhere like mastodon or discord
+  --max-chars <n> --banned <words> --max-attempts <n> --retry same|random|avoid-failed
+  --min-words <n> --max-words <n>
+                      tweets that come out shorter or longer get thrown away and tried again, up to --max-attempts
+  --blocklist <file>  words (and /regexes/) no tweet can have, one a line. --profanity adds the built in list
+  --ban-in-training   drop the banned words from the corpus too, so the chain never learns them
+  --max-overlap <n>   throw away tweets that copy more than n words in a row from the corpus
+  --history <file>    every tweet goes in here, and nothing already in it comes out again
+  --history-similarity <0-1>
+                      how close to something in the history is too close, 0.9 by default. 1 only catches exact repeats
+  --dead-end end|resample|restart
+                      what happens at a word with nowhere to go that doesn't end a sentence
+  --tokenizer whitespace|punctuation
+                      punctuation splits commas, quotes and brackets off words and keeps the brackets matched
+  --joiner detokenize|spaces
+                      how words go back together, spaces is one between every two even around \"it 's\"
+  --timings           how long training and each tweet took, on stderr
+  --config <file>     any of these under [chain] (max_chars = 280), ./erowidcoin.toml if there is one. flags win";
+
+fn usage(name: &str) -> String {
+  let command = COMMANDS.iter().find( |command| command.name == name ).unwrap();
+  return format!("usage: erowidcoin {}", command.usage);
+}
+
+// help for one command, or the list of them
+fn help(name: Option<&str>) -> Result<String, String> {
+  let Some(name) = name else {
+    let width = COMMANDS.iter().map( |command| command.name.len() ).max().unwrap_or(0);
+    let commands: Vec<String> = COMMANDS.iter().map( |command| format!("  {:width$}  {}", command.name, command.about, width = width) ).collect();
+    return Ok(format!(
+      "usage: erowidcoin <command> [<args>]\n\ncommands:\n{}\n\n`erowidcoin <command> --help` for what a command takes",
+      commands.join("\n")
+    ));
+  };
+  let command = COMMANDS.iter().find( |command| command.name == name ).ok_or_else( || unknown(name) )?;
+  let mut text = format!("{}\n\n{}", usage(name), command.about);
+  if command.usage.contains("[chain flags]") {
+    text = format!("{}\n\n{}", text, CHAIN_FLAGS);
   }
+  return Ok(text);
+}
 
-  if args.len() == 3 {
-    let integer = args[2].parse::<i32>();
-    num_tweets = match integer {
-      Ok(val) => val,
-      Err(error) => {
-        println!("could not parse number of tweets: {}", error);
-        return;
+fn unknown(name: &str) -> String {
+  return format!("unknown command {:?}, `erowidcoin help` lists them", name);
+}
+
+// every --flag some usage (or the chain flags) mentions, -n and the rest as the long names they stand for
+fn flag_names(text: &'static str) -> impl Iterator<Item = &'static str> {
+  return text.split( |c: char| !(c.is_ascii_alphanumeric() || c == '-') ).filter_map( |word| match word.strip_prefix("--") {
+    Some(name) => Some(name),
+    None => SHORT_FLAGS.iter().find( |(short, _)| word.strip_prefix('-') == Some(short) ).map( |(_, name)| *name ),
+  }).filter( |name| !name.is_empty() );
+}
+
+// what a command says it takes is all it takes, so a typo is an error instead of a flag that quietly does nothing
+fn check_flags(name: &str, flags: &HashMap<String, String>) -> Result<(), String> {
+  let Some(command) = COMMANDS.iter().find( |command| command.name == name ) else {
+    return Ok(());
+  };
+  let chain_flags = if command.usage.contains("[chain flags]") { CHAIN_FLAGS } else { "" };
+  let known: Vec<&str> = flag_names(command.usage).chain(flag_names(chain_flags)).collect();
+  let mut unknown: Vec<&String> = flags.keys().filter( |flag| *flag != "help" && !known.contains(&flag.as_str()) ).collect();
+  unknown.sort();
+  return match unknown.first() {
+    Some(flag) => Err(format!("{} doesn't take --{}, `erowidcoin {} --help` lists what it does", name, flag, name)),
+    None => Ok(()),
+  };
+}
+
+// pulls a --flag out and parses it, None if it wasn't passed at all
+fn flag<T: FromStr>(flags: &HashMap<String, String>, name: &str) -> Result<Option<T>, String> where T::Err: Display {
+  return match flags.get(name) {
+    Some(value) => value.parse::<T>().map(Some).map_err( |error| format!("could not parse --{}: {}", name, error) ),
+    None => Ok(None),
+  };
+}
+
+// for the choices made around the chain (jitter, styles), seeded along with it so --seed reproduces everything
+fn side_rng(flags: &HashMap<String, String>) -> Result<ChaCha8Rng, String> {
+  return match flag(flags, "seed")? {
+    Some(seed) => Ok(ChaCha8Rng::seed_from_u64(seed)),
+    None => ChaCha8Rng::from_rng(rand::thread_rng()).map_err( |error| error.to_string() ),
+  };
+}
+
+fn chain(flags: &HashMap<String, String>) -> Result<MarkovChain, String> {
+  // is there some way to avoid having to pass mut all the way down :|
+  let mut mchain = MarkovChain::new();
+  if let Some(order) = flag(flags, "order")? {
+    mchain = mchain.with_order(order).map_err( |_| "--order has to be at least 1".to_string() )?;
+  }
+  if let Some(locale) = flag(flags, "locale")? {
+    mchain = mchain.with_locale(locale);
+  }
+  if flags.contains_key("poetry") {
+    mchain = mchain.with_line_breaks();
+  }
+  if flags.contains_key("fold-case") {
+    mchain = mchain.with_case_folding();
+  }
+  if let Some(cap) = flag(flags, "doc-cap")? {
+    mchain = mchain.with_document_cap(cap);
+  }
+  if let Some(pattern) = flags.get("doc-separator") {
+    mchain = mchain.with_document_separator(pattern).map_err( |error| format!("could not parse --doc-separator: {}", error) )?;
+  }
+  match flags.get("rng").map( |r| r.as_str() ) {
+    Some("thread") | None => {},
+    Some("os") => mchain = mchain.with_rng_source(RngSource::OsRandom),
+    Some(other) => return Err(format!("unknown rng: {}", other)),
+  }
+  let min_documents = flag(flags, "min-documents")?;
+  let min_count = flag(flags, "min-count")?;
+  if min_documents.is_some() || min_count.is_some() {
+    let defaults = Suppression::default();
+    mchain = mchain.with_suppression(Suppression {
+      min_documents: min_documents.unwrap_or(defaults.min_documents),
+      min_count: min_count.unwrap_or(defaults.min_count),
+    });
+  }
+  let temperature = flag(flags, "temperature")?;
+  let top_k = flag(flags, "top-k")?;
+  if temperature.is_some() || top_k.is_some() {
+    let temperature: f64 = temperature.unwrap_or(Sampling::default().temperature);
+    if temperature.is_nan() || temperature < 0.0 {
+      return Err(format!("--temperature should be 0 or more, not {}", temperature));
+    }
+    mchain = mchain.with_sampling(Sampling { temperature, top_k });
+  }
+  match flags.get("scrub").map( |s| s.as_str() ) {
+    None => {},
+    Some("all") => mchain = mchain.with_scrubber(Scrubber::all()),
+    Some(rules) => {
+      let rules = rules.split(',').map( |rule| rule.trim().parse::<Rule>() ).collect::<Result<Vec<Rule>, String>>()?;
+      mchain = mchain.with_scrubber(Scrubber::new(&rules));
+    },
+  }
+  for style in flags.get("numbers").iter().flat_map( |styles| styles.split(',') ) {
+    mchain = match style.trim().split_once('=') {
+      Some((tag, style)) => mchain.with_tag_numbers(tag.trim(), style.trim().parse()?),
+      None => mchain.with_numbers(style.trim().parse()?),
+    };
+  }
+  if let Some(max) = flag(flags, "max-overlap")? {
+    mchain = mchain.with_max_overlap(max);
+  }
+  if let Some(min_words) = flag(flags, "min-words")? {
+    mchain = mchain.with_min_words(min_words);
+  }
+  if let Some(max_words) = flag(flags, "max-words")? {
+    mchain = mchain.with_max_words(max_words);
+  }
+  match (flag(flags, "max-chars")?, flags.contains_key("long")) {
+    (Some(max_chars), true) => mchain = mchain.with_long_form(max_chars),
+    (Some(max_chars), false) => mchain = mchain.with_max_chars(max_chars),
+    (None, true) => return Err("--long goes on for as long as --max-chars lets it, it needs one".to_string()),
+    (None, false) => {},
+  }
+  if let Some(path) = flags.get("history") {
+    // a dry run reads the history but doesn't add to it
+    let file = storage::open(Path::new(path), storage::HISTORY);
+    let storage: Box<dyn Storage> = match flags.contains_key("dry-run") {
+      true => {
+        let memory = MemoryStorage::default();
+        memory.append(&file.load().map_err( |error| format!("couldn't read the history {}: {}", path, error) )?).unwrap();
+        Box::new(memory)
       },
+      false => file,
+    };
+    let max_similarity = flag(flags, "history-similarity")?.unwrap_or(history::DEFAULT_MAX_SIMILARITY);
+    let history = History::open(storage, max_similarity).map_err( |error| format!("couldn't read the history {}: {}", path, error) )?;
+    mchain = mchain.with_history(Arc::new(history));
+  }
+  if let Some(counts) = flags.get("syllables") {
+    if !flags.contains_key("poetry") {
+      return Err("--syllables counts syllables per line, it needs --poetry".to_string());
+    }
+    let counts = counts.split(',').map( |n| n.trim().parse::<usize>().map_err( |_| format!("could not parse --syllables: {}", counts) ) ).collect::<Result<Vec<usize>, String>>()?;
+    mchain = mchain.with_filter(Syllables(counts));
+  }
+  // --banned, --blocklist and --profanity all go into the one filter
+  let mut banned = BannedWords::new(&[]);
+  if let Some(words) = flags.get("banned") {
+    let words: Vec<String> = words.split(',').map( |word| word.to_string() ).collect();
+    banned.extend(BannedWords::new(&words));
+  }
+  if let Some(path) = flags.get("blocklist") {
+    let text = fs::read_to_string(path).map_err( |error| format!("could not read {}: {}", path, error) )?;
+    banned.extend(BannedWords::parse(&text).map_err( |error| format!("could not parse {}: {}", path, error) )?);
+  }
+  if flags.contains_key("profanity") {
+    banned.extend(BannedWords::profanity());
+  }
+  if flags.contains_key("ban-in-training") {
+    if banned.is_empty() {
+      return Err("--ban-in-training needs words to ban, from --banned, --blocklist or --profanity".to_string());
+    }
+    mchain = mchain.with_training_blocklist(banned.clone());
+  }
+  if !banned.is_empty() {
+    mchain = mchain.with_filter(banned);
+  }
+  if let Some(attempts) = flag(flags, "max-attempts")? {
+    mchain = mchain.with_max_attempts(attempts);
+  }
+  match flags.get("retry").map( |r| r.as_str() ) {
+    Some("random") | None => {},
+    Some("same") => mchain = mchain.with_retry_policy(RetryPolicy::SameEntry),
+    Some("avoid-failed") => mchain = mchain.with_retry_policy(RetryPolicy::AvoidFailedEntries),
+    Some(other) => return Err(format!("unknown retry policy: {}", other)),
+  }
+  match flags.get("dead-end").map( |d| d.as_str() ) {
+    Some("end") | None => {},
+    Some("resample") => mchain = mchain.with_dead_end_policy(DeadEndPolicy::Resample),
+    Some("restart") => mchain = mchain.with_dead_end_policy(DeadEndPolicy::Restart),
+    Some(other) => return Err(format!("unknown dead end policy: {}", other)),
+  }
+  match flags.get("tokenizer").map( |t| t.as_str() ) {
+    Some("whitespace") | None => {},
+    Some("punctuation") => mchain = mchain.with_tokenizer(Punctuation),
+    Some(other) => return Err(format!("unknown tokenizer: {}", other)),
+  }
+  match flags.get("joiner").map( |j| j.as_str() ) {
+    Some("detokenize") | None => {},
+    Some("spaces") => mchain = mchain.with_joiner(Spaces),
+    Some(other) => return Err(format!("unknown joiner: {}", other)),
+  }
+  for abbreviation in flags.get("abbreviations").iter().flat_map( |abbreviations| abbreviations.split(',') ) {
+    mchain = mchain.with_abbreviation(abbreviation);
+  }
+  if let Some(path) = flags.get("taxonomy") {
+    let text = fs::read_to_string(path).map_err( |error| format!("could not read {}: {}", path, error) )?;
+    mchain = mchain.with_taxonomy(Taxonomy::parse(&text).map_err( |error| format!("{}: {}", path, error) )?);
+  }
+  // every filter plugin in there, and the tokenizer if there is one. publishers are for [target.<name>] plugin = ..
+  if let Some(dir) = flags.get("plugins") {
+    let mut tokenizer = None;
+    for found in plugin::discover(Path::new(dir)).map_err( |error| error.to_string() )? {
+      match found.kind {
+        Kind::Filter => mchain = mchain.with_filter(PluginFilter(found)),
+        Kind::Tokenizer if tokenizer.is_some() => return Err(format!("{} has more than one tokenizer plugin", dir)),
+        Kind::Tokenizer => tokenizer = Some(found),
+        Kind::Publisher => {},
+      }
+    }
+    if let Some(found) = tokenizer {
+      if flags.contains_key("tokenizer") {
+        return Err("--tokenizer and the tokenizer plugin would both split the documents, pick one".to_string());
+      }
+      mchain = mchain.with_tokenizer(PluginTokenizer(found));
+    }
+  }
+  for tag in flags.get("about").iter().flat_map( |tags| tags.split(',') ) {
+    mchain = mchain.with_about(tag.trim());
+  }
+  for pattern in flags.get("include").iter().flat_map( |patterns| patterns.split(',') ) {
+    mchain = mchain.with_include(pattern.trim());
+  }
+  for pattern in flags.get("exclude").iter().flat_map( |patterns| patterns.split(',') ) {
+    mchain = mchain.with_exclude(pattern.trim());
+  }
+  // a seed wins over --rng, asking for reproducibility is the more specific request
+  if let Some(seed) = flag(flags, "seed")? {
+    mchain = mchain.with_seed(seed);
+  }
+  return Ok(mchain);
+}
+
+// the chain from chain(flags), trained on dir. with --model the training is skipped if that file exists, otherwise
+// it's written once training's done so the next run can skip it
+fn trained(flags: &HashMap<String, String>, dir: Option<&String>) -> Result<MarkovChain, String> {
+  let mut mchain = chain(flags)?;
+  match flags.get("model").map(Path::new) {
+    Some(model) if storage::open(model, storage::MODEL).written().map_err( |error| format!("could not read {}: {}", model.display(), error) )? => {
+      mchain = mchain.with_model(model).map_err( |error| format!("could not load {}: {}", model.display(), error) )?;
+    },
+    model => {
+      train_on(&mut mchain, dir, flags)?;
+      if let Some(model) = model {
+        mchain.save(model).map_err( |error| format!("could not save {}: {}", model.display(), error) )?;
+      }
+    },
+  }
+  return Ok(mchain);
+}
+
+// the text directory, --stats or the --corpus blend in its place. files that couldn't be read get a warning each,
+// the rest of the corpus still goes in
+fn train_on(mchain: &mut MarkovChain, dir: Option<&String>, flags: &HashMap<String, String>) -> Result<(), String> {
+  let blend = blend(flags)?;
+  if let Some(path) = flags.get("stats") {
+    if dir.is_some() || !blend.is_empty() {
+      return Err("--stats takes the place of the text directory, give one or the other".to_string());
+    }
+    let text = fs::read_to_string(path).map_err( |error| format!("could not read {}: {}", path, error) )?;
+    let stats = CorpusStats::parse(&text).map_err( |error| format!("could not read {}: {}", path, error) )?;
+    *mchain = std::mem::replace(mchain, MarkovChain::new()).with_corpus_stats(&stats);
+    return Ok(());
+  }
+  // text from [chain] when there's no directory on the command line
+  let dir = dir.or(flags.get("text").filter( |_| blend.is_empty() ));
+  match (dir, blend.is_empty()) {
+    (Some(dir), true) => mchain.add_corpus(Corpus::Dir(PathBuf::from(dir))),
+    (None, false) => {
+      for (dir, weight) in blend {
+        mchain.add_corpus(Corpus::Weighted(dir, weight));
+      }
+    },
+    (Some(_), false) => return Err("--corpus takes the place of the text directory, give one or the other".to_string()),
+    (None, true) => return Err("needs a text directory to train on, or a --model that's already been trained".to_string()),
+  }
+  mchain.train().map_err( |error| error.to_string() )?;
+  for (path, reason) in mchain.ingestion_report().skipped {
+    eprintln!("warning: skipped {}, it {}", path.display(), reason);
+  }
+  return Ok(());
+}
+
+// --corpus erowid:0.7 --corpus crypto:0.3. a directory without a weight gets 1
+fn blend(flags: &HashMap<String, String>) -> Result<Vec<(PathBuf, f64)>, String> {
+  let sources = flags.get("corpus").into_iter().flat_map( |sources| sources.split(',') ).map( |source| {
+    let source = source.trim();
+    return match source.rsplit_once(':').map( |(dir, weight)| (dir, weight.parse::<f64>()) ) {
+      Some((dir, Ok(weight))) if weight.is_finite() && weight > 0.0 => Ok((PathBuf::from(dir), weight)),
+      Some((_, Ok(_))) => Err(format!("--corpus {}: the weight has to be more than 0", source)),
+      // no weight, or a colon that's part of the path
+      _ => Ok((PathBuf::from(source), 1.0)),
     };
+  });
+  return sources.collect();
+}
+
+// on stderr, so --timings doesn't end up in whatever the tweets are piped into
+fn print_timings(flags: &HashMap<String, String>, mchain: &MarkovChain) {
+  if flags.contains_key("timings") {
+    eprintln!("{}", mchain.timings());
   }
+}
 
-  let directory = Path::new(&args[1]);
+fn train(args: &[String], flags: &HashMap<String, String>) -> Result<(), String> {
+  let dir = match args {
+    [] => None,
+    [dir] => Some(dir),
+    _ => return Err(usage("train")),
+  };
+  let Some(output) = flags.get("output") else {
+    return Err("train needs -o <model file> to save to".to_string());
+  };
 
-  // is there some way to avoid having to pass mut all the way down :|
-  let mut mchain = MarkovChain::new();
-  let tweets = mchain.create_tweets(directory, num_tweets);
+  let mut mchain = chain(flags)?;
+  train_on(&mut mchain, dir, flags)?;
+  mchain.save(Path::new(output)).map_err( |error| format!("could not save {}: {}", output, error) )?;
+  println!("trained on {} documents ({} nodes, {} edges), saved to {}", mchain.ingestion_report().documents, mchain.node_count(), mchain.edge_count(), output);
+  print_timings(flags, &mchain);
+  return Ok(());
+}
+
+fn compare(args: &[String], flags: &HashMap<String, String>) -> Result<(), String> {
+  if args.len() != 2 {
+    return Err(usage("compare"));
+  }
+  let holdout = match flags.get("holdout") {
+    Some(holdout) => Path::new(holdout),
+    None => return Err("compare needs a --holdout directory".to_string()),
+  };
+  let samples = flag(flags, "samples")?.unwrap_or(100);
+
+  let mut reports = Vec::new();
+  for model in args {
+    let report = ModelReport::new(&mut chain(flags)?, Path::new(model), holdout, samples);
+    reports.push(report.map_err( |error| format!("could not evaluate {}: {}", model, error) )?);
+  }
+
+  match flags.get("format").map( |f| f.as_str() ) {
+    Some("json") => println!("{}", compare::render_json(&reports)),
+    Some("markdown") | None => print!("{}", compare::render_markdown(&reports)),
+    Some(other) => return Err(format!("unknown format: {}", other)),
+  }
+  return Ok(());
+}
+
+// ngram,count with the most common first, quoted the way spreadsheets expect
+fn export_ngrams(args: &[String], flags: &HashMap<String, String>) -> Result<(), String> {
+  if args.len() > 1 {
+    return Err(usage("export-ngrams"));
+  }
+  let mchain = trained(flags, args.first())?;
+  let n = flag(flags, "n")?.unwrap_or(2);
+  if n == 0 || n > mchain.order() + 1 {
+    return Err(format!("an order {} chain knows n-grams of 1 to {} words, retrain with a higher --order for longer ones", mchain.order(), mchain.order() + 1));
+  }
+  let min_count = flag(flags, "min-count")?.unwrap_or(1);
+
+  let mut csv = String::from("ngram,count\n");
+  for (ngram, count) in mchain.ngrams(n).into_iter().filter( |(_, count)| *count >= min_count ) {
+    csv.push_str(&format!("\"{}\",{}\n", ngram.replace('"', "\"\""), count));
+  }
+  match flags.get("out") {
+    Some(out) => fs::write(out, csv).map_err( |error| format!("could not write {}: {}", out, error) )?,
+    None => print!("{}", csv),
+  }
+  print_timings(flags, &mchain);
+  return Ok(());
+}
+
+// everything a chain needs apart from the text, see stats.rs. a transition has to come up twice to go in unless
+// --min-count says otherwise, anything said once is more quoting than statistics
+fn export_corpus_stats(args: &[String], flags: &HashMap<String, String>) -> Result<(), String> {
+  if args.len() > 1 {
+    return Err(usage("export-corpus-stats"));
+  }
+  let mchain = trained(flags, args.first())?;
+  let threshold = Suppression { min_documents: flag(flags, "min-documents")?.unwrap_or(1), min_count: flag(flags, "min-count")?.unwrap_or(2) };
+  let stats = mchain.corpus_stats(threshold);
+  match flags.get("out") {
+    Some(out) => {
+      fs::write(out, stats.to_string()).map_err( |error| format!("could not write {}: {}", out, error) )?;
+      println!("{} of {} transitions and {} entry states, saved to {}. rebuild with --stats {}", stats.ngrams.len(), mchain.edge_count(), stats.entries.len(), out, out);
+    },
+    None => print!("{}", stats),
+  }
+  print_timings(flags, &mchain);
+  return Ok(());
+}
+
+// generates a batch and looks for words only one of the blended corpora ever used showing up next to another's
+fn flavor(args: &[String], flags: &HashMap<String, String>) -> Result<(), String> {
+  if !args.is_empty() || flags.contains_key("model") {
+    return Err(format!("{}\n(it needs the corpora themselves, a saved model doesn't know which words came from where)", usage("flavor")));
+  }
+  let mut mchain = trained(flags, None)?;
+  if mchain.sources().len() < 2 {
+    return Err("the flavor report needs at least two --corpus directories to compare".to_string());
+  }
+  let samples = flag(flags, "samples")?.unwrap_or(200);
+  let top = flag(flags, "top")?.unwrap_or(10);
+
+  let tweets = (0..samples).map( |_| mchain.generate_tweet().map_err( |error| error.to_string() ) ).collect::<Result<Vec<String>, String>>()?;
+  println!("{}", FlavorReport::new(&tweets, |word| mchain.source_of(word), top));
+  print_timings(flags, &mchain);
+  return Ok(());
+}
+
+fn stats(args: &[String], flags: &HashMap<String, String>) -> Result<(), String> {
+  if args.len() > 1 {
+    return Err(usage("stats"));
+  }
+
+  let mut mchain = trained(flags, args.first())?;
+  let memory = mchain.memory_footprint();
+
+  println!("{}", mchain.ingestion_report());
+  println!("nodes: {}", mchain.node_count());
+  println!("edges: {}", mchain.edge_count());
+  println!("memory (estimated bytes):");
+  println!("  nodes: {}", memory.nodes);
+  println!("  edges: {}", memory.edges);
+  println!("  interner: {}", memory.interner);
+  println!("  entry words: {}", memory.entry_words);
+  println!("  csr: {}", memory.csr);
+  println!("  total: {}", memory.total());
+
+  if !mchain.provenance().is_empty() {
+    print!("{}", mchain.provenance());
+  }
+
+  // a batch through the filters, to see which of them are turning everything down
+  if let Some(samples) = flag::<usize>(flags, "samples")? {
+    let mut gave_up = 0;
+    for _ in 0..samples {
+      if mchain.generate_tweet().is_err() {
+        gave_up += 1;
+      }
+    }
+    let filters = mchain.filter_stats();
+    println!("filters ({} tweets, {} given up on):", samples, gave_up);
+    println!("  candidates: {}", filters.candidates);
+    for (reason, count) in filters.sorted() {
+      println!("  rejected by {}: {} ({:.1}%)", reason, count, 100.0 * count as f64 / filters.candidates.max(1) as f64);
+    }
+  }
+  print_timings(flags, &mchain);
+  return Ok(());
+}
+
+fn interactive(args: &[String], flags: &HashMap<String, String>) -> Result<(), String> {
+  if args.len() > 1 {
+    return Err(usage("interactive"));
+  }
+
+  let mut mchain = trained(flags, args.first())?;
+
+  let top_k = flag(flags, "top")?.unwrap_or(5);
+  interactive::run(&mut mchain, io::stdin().lock(), io::stdout(), top_k).map_err( |error| error.to_string() )?;
+  return Ok(());
+}
+
+fn serve(args: &[String], flags: &HashMap<String, String>) -> Result<(), String> {
+  if args.len() > 1 {
+    return Err(usage("serve"));
+  }
+
+  // config mistakes should show up before we spend time training
+  let settings = server_settings(flags)?;
+  let mut mchain = trained(flags, args.first())?;
+
+  let addr = flags.get("addr").map( |a| a.as_str() ).unwrap_or("127.0.0.1:8080");
+  return server::serve(&mut mchain, addr, &settings).map_err( |error| error.to_string() );
+}
+
+// the [server] table of --config, with --api-key / EROWIDCOIN_API_KEY and --queue on top. --target picks one of
+// the [target.<name>] tables as the publisher, without it a [crosspost] table posts to all of its targets
+fn server_settings(flags: &HashMap<String, String>) -> Result<server::Settings, String> {
+  let mut settings = server::Settings::default();
+
+  let target = flags.get("target").map( |t| t.as_str() ).unwrap_or("production");
+  let mut target_configured = false;
+
+  if let Some(path) = flags.get("config") {
+    let text = fs::read_to_string(path).map_err( |error| format!("could not read {}: {}", path, error) )?;
+    let config = toml::parse(&text).map_err( |error| format!("{}: {}", path, error) )?;
+    let problems = config::check(&config);
+    if !problems.is_empty() {
+      return Err(format!("{} has problems (see erowidcoin check-config):\n  {}", path, problems.join("\n  ")));
+    }
+    let targets = config.get("target").and_then( |targets| targets.as_table() );
+    if let (Some(crosspost), None) = (config.get("crosspost"), flags.get("target")) {
+      let crosspost = crosspost.as_table().ok_or_else( || format!("{}: [crosspost] should be a table", path) )?;
+      settings.publisher = publish::from_crosspost(crosspost, targets.unwrap_or(&BTreeMap::new())).map_err( |error| format!("{}: {}", path, error) )?.into();
+      target_configured = true;
+    } else if let Some(table) = targets.and_then( |targets| targets.get(target) ) {
+      let table = table.as_table().ok_or_else( || format!("{}: [target.{}] should be a table", path, target) )?;
+      if table.get("enabled").and_then( |enabled| enabled.as_bool() ) == Some(false) {
+        return Err(format!("{}: [target.{}] is disabled", path, target));
+      }
+      settings.publisher = publish::from_table(target, table).map_err( |error| format!("{}: {}", path, error) )?.into();
+      target_configured = true;
+    }
+    if let Some(table) = config.get("server") {
+      let table = table.as_table().ok_or_else( || format!("{}: [server] should be a table", path) )?;
+      settings.auth = auth::Auth::from_table(table).map_err( |error| format!("{}: {}", path, error) )?;
+      if let Some(queue) = table.get("queue") {
+        settings.queue = Some(queue.as_str().ok_or_else( || format!("{}: queue should be a string", path) )?.into());
+      }
+      if let Some(pause_file) = table.get("pause_file") {
+        settings.kill_switch = KillSwitch::new(Path::new(pause_file.as_str().ok_or_else( || format!("{}: pause_file should be a string", path) )?));
+      }
+      if let Some(audit_log) = table.get("audit_log") {
+        settings.audit_log = Some(audit_log.as_str().ok_or_else( || format!("{}: audit_log should be a string", path) )?.into());
+      }
+      let limit = |key: &str| -> Result<Option<i64>, String> {
+        return match table.get(key) {
+          Some(value) => match value.as_integer() {
+            Some(n) if n > 0 => Ok(Some(n)),
+            _ => Err(format!("{}: {} should be a positive integer", path, key)),
+          },
+          None => Ok(None),
+        };
+      };
+      settings.requests_per_minute = limit("requests_per_minute")?.map( |n| n as u32 );
+      settings.global_requests_per_minute = limit("global_requests_per_minute")?.map( |n| n as u32 );
+      if let Some(n) = limit("max_concurrent")? {
+        settings.max_concurrent = n as usize;
+      }
+
+      let mut replies = ReplyLimits::default();
+      if let Some(cooldown) = table.get("reply_cooldown") {
+        replies.cooldown = daemon::parse_interval(cooldown.as_str().ok_or_else( || format!("{}: reply_cooldown should be a string", path) )?)?;
+      }
+      if let Some(n) = limit("replies_per_hour")? {
+        replies.max_per_hour = n as usize;
+      }
+      let handles = |key: &str| -> Vec<String> {
+        let users = table.get(key).and_then( |users| users.as_array() );
+        return users.into_iter().flatten().filter_map( |user| user.as_str() ).map(String::from).collect();
+      };
+      (replies.allow, replies.deny) = (handles("reply_allow"), handles("reply_deny"));
+      settings.replies = Arc::new(Mutex::new(ReplyGate::new(replies)));
+    }
+  }
+
+  // better kept out of the shell history
+  if let Some(key) = flags.get("api-key").cloned().or_else( || env::var("EROWIDCOIN_API_KEY").ok() ) {
+    settings.auth.api_keys.push(key);
+  }
+  if let Some(queue) = flags.get("queue") {
+    settings.queue = Some(queue.into());
+  }
+  if let Some(audit_log) = flags.get("audit-log") {
+    settings.audit_log = Some(audit_log.into());
+  }
+  if let Some(pause_file) = flags.get("pause-file") {
+    settings.kill_switch = KillSwitch::new(Path::new(pause_file));
+  }
+  if let Some(sensitivity) = flag(flags, "sensitive")? {
+    settings.sensitivity = sensitivity;
+  }
+  // an api works without a table of its own ([target.twitter], [target.mastodon]..), the keys can all come from the
+  // environment
+  if publish::APIS.contains(&target) && !target_configured {
+    let table = BTreeMap::from([("api".to_string(), toml::Value::String(target.to_string()))]);
+    settings.publisher = Arc::from(publish::from_table(target, &table)?);
+    target_configured = true;
+  }
+  // production falls back to stdout, but a sandbox that isn't configured must not end up posting for real
+  if target != "production" && !target_configured {
+    return Err(format!("--target {} needs a [target.{}] table in the --config file", target, target));
+  }
+  return Ok(settings);
+}
+
+// post is the daemon for one post, right away
+fn daemon(args: &[String], flags: &HashMap<String, String>, once: bool) -> Result<(), String> {
+  let name = if once { "post" } else { "daemon" };
+  if args.len() > 1 {
+    return Err(usage(name));
+  }
+
+  let settings = server_settings(flags)?;
+  let iterations = flag(flags, "iterations")?;
+  let mut schedule = match (flags.get("interval"), flag(flags, "cron")?) {
+    (Some(_), Some(_)) => return Err("--interval and --cron both say when to post, pick one".to_string()),
+    (_, Some(cron)) => daemon::Schedule::cron(cron),
+    (interval, None) => daemon::Schedule::every(daemon::parse_interval(interval.map( |i| i.as_str() ).unwrap_or("6h"))?),
+  };
+  if let Some(jitter) = flags.get("jitter") {
+    schedule.jitter = daemon::parse_interval(jitter)?;
+    // a cron schedule's gaps can be anything, keeping them apart is up to whoever wrote it
+    if schedule.cron.is_none() && schedule.jitter * 2 >= schedule.interval {
+      return Err("the jitter has to be less than half the interval, or posts could swap places".to_string());
+    }
+  }
+  if let Some(retries) = flag(flags, "retries")? {
+    schedule.retries = retries;
+  }
+  if let Some(backoff) = flags.get("backoff") {
+    schedule.backoff = daemon::parse_interval(backoff)?;
+  }
+  if let Some(hours) = flags.get("quiet-hours") {
+    schedule.quiet_hours = Some(daemon::parse_quiet_hours(hours)?);
+  }
+  if let Some(themes) = flags.get("themes") {
+    schedule.themes = daemon::parse_themes(themes)?;
+  }
+  if let Some(policy) = flags.get("catch-up") {
+    schedule.catch_up = policy.parse()?;
+  }
+  // --profiles says how often each kind of post comes up, --style alone is a mix too
+  let profiles = match (flags.get("profiles"), flag::<Styling>(flags, "style")?) {
+    (Some(_), Some(_)) => return Err("--profiles already says which styles to use, drop --style".to_string()),
+    (Some(_), None) => flag(flags, "profiles")?.unwrap(),
+    (None, styling) => Mix::from(&styling.unwrap_or_default()),
+  };
+  let milestones = match (flags.get("followers-file"), flags.get("milestones")) {
+    (Some(path), Some(list)) => Some(Milestones::new(Path::new(path), milestones::parse(list)?)),
+    (None, None) => None,
+    _ => return Err("--milestones and --followers-file go together".to_string()),
+  };
+  let anniversary: Option<f64> = flag(flags, "anniversary")?;
+  if anniversary.is_some_and( |chance| !(0.0..=1.0).contains(&chance) ) {
+    return Err("--anniversary is a chance per post, between 0 and 1".to_string());
+  }
+  let rng = side_rng(flags)?;
+
+  let mut mchain = trained(flags, args.first())?;
+
+  // a dry run starts now and fast-forwards from there
+  let dry_run = flags.contains_key("dry-run");
+  let clock: Box<dyn Clock> = if dry_run { Box::new(FakeClock::new(SystemClock.now())) } else { Box::new(SystemClock) };
+  let mut daemon = daemon::Daemon { mchain: &mut mchain, settings: &settings, schedule, profiles, milestones, anniversary, clock: clock.as_ref(), rng, dry_run };
+  if once {
+    // what went wrong is in the log line, the exit code is for cron
+    return match daemon.post_now(&mut io::stdout()).map_err( |error| error.to_string() )? {
+      true => Ok(()),
+      false => Err("nothing was posted".to_string()),
+    };
+  }
+  return daemon.run(iterations, &mut io::stdout()).map_err( |error| error.to_string() );
+}
+
+fn init_example(args: &[String]) -> Result<(), String> {
+  let [dir] = args else {
+    return Err(usage("init-example"));
+  };
+  let written = example::write(Path::new(dir)).map_err( |error| error.to_string() )?;
+  for path in written {
+    println!("wrote {}", path.display());
+  }
+  println!("\nnext, from {}:", dir);
+  println!("  erowidcoin generate -n 3                  a few tweets off the sample corpus");
+  println!("  erowidcoin generate --report              what got read, and how it was tagged");
+  println!("  erowidcoin daemon --dry-run --iterations 4");
+  println!("                                            what the schedule would post, without waiting for it");
+  println!("  erowidcoin post --dry-run                 one post, the way it would go out");
+  println!("then swap txt/ for your own text and erowidcoin.toml for your own settings");
+  return Ok(());
+}
+
+// ./plugins unless it's told otherwise, the same place [target.<name>] plugin = "nostr" looks
+fn plugins(args: &[String]) -> Result<(), String> {
+  let dir = match args {
+    [] => plugin::PLUGIN_DIR,
+    [dir] => dir.as_str(),
+    _ => return Err(usage("plugins")),
+  };
+  let found = plugin::discover(Path::new(dir)).map_err( |error| error.to_string() )?;
+  if found.is_empty() {
+    println!("no plugins in {}", dir);
+  }
+  for plugin in found {
+    println!("{:9}  {}  ({})", plugin.kind.to_string(), plugin.name, plugin.path.display());
+  }
+  return Ok(());
+}
+
+fn selftest(args: &[String]) -> Result<(), String> {
+  if !args.is_empty() {
+    return Err(usage("selftest"));
+  }
+  return match selftest::run(&mut io::stdout()).map_err( |error| error.to_string() )? {
+    true => Ok(()),
+    false => Err("self test failed".to_string()),
+  };
+}
+
+fn check_config(args: &[String]) -> Result<(), String> {
+  let path = match args {
+    [path] => path,
+    _ => return Err(usage("check-config")),
+  };
+
+  let text = fs::read_to_string(path).map_err( |error| format!("could not read {}: {}", path, error) )?;
+  let config = toml::parse(&text).map_err( |error| format!("{}: {}", path, error) )?;
+  let problems = config::check(&config);
+  if !problems.is_empty() {
+    return Err(format!("problems in {}:\n  {}", path, problems.join("\n  ")));
+  }
+
+  println!("{} looks good", path);
+  return Ok(());
+}
+
+// goes through the same settings as serve, so --config finds the same pause file the server is watching
+fn pause(args: &[String], flags: &HashMap<String, String>, paused: bool) -> Result<(), String> {
+  let switch = server_settings(flags)?.kill_switch;
+  if paused {
+    let reason = if args.is_empty() { "paused from the cli".to_string() } else { args.join(" ") };
+    switch.pause(&reason).map_err( |error| error.to_string() )?;
+    println!("publishing paused");
+  } else {
+    switch.resume().map_err( |error| error.to_string() )?;
+    println!("publishing resumed");
+  }
+  return Ok(());
+}
+
+fn backfill(args: &[String], flags: &HashMap<String, String>) -> Result<(), String> {
+  if args.len() > 1 {
+    return Err(usage("backfill"));
+  }
+
+  let queue = queue::Queue::new(Path::new(flags.get("queue").map( |q| q.as_str() ).unwrap_or("queue.jsonl")));
+  let existing = queue.load().map_err( |error| error.to_string() )?;
+  let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map( |d| d.as_secs() ).unwrap_or(0);
+  let plan = backfill::Plan {
+    days: flag(flags, "days")?.unwrap_or(30),
+    per_day: flag(flags, "per-day")?.unwrap_or(4),
+    start: now,
+    max_similarity: flag(flags, "max-similarity")?.unwrap_or(0.5),
+    sensitivity: flag(flags, "sensitive")?.unwrap_or_default(),
+  };
+
+  let mut mchain = trained(flags, args.first())?;
+  let posts = backfill::backfill(&mut mchain, &existing, &plan).map_err( |error| error.to_string() )?;
+  queue.append(&posts).map_err( |error| error.to_string() )?;
+
+  println!("queued {} posts over {} days ({} already queued)", posts.len(), plan.days, existing.len());
+  return Ok(());
+}
+
+// export goes to stdout like queue export's, import takes one of those
+fn history(args: &[String], flags: &HashMap<String, String>) -> Result<(), String> {
+  let path = flags.get("history").ok_or_else( || usage("history") )?;
+  let history = History::open(storage::open(Path::new(path), storage::HISTORY), history::DEFAULT_MAX_SIMILARITY)
+    .map_err( |error| format!("couldn't read the history {}: {}", path, error) )?;
+
+  return match args {
+    [command] if command == "export" => {
+      history.export(&mut io::stdout()).map_err( |error| error.to_string() )?;
+      Ok(())
+    },
+    [command, export] if command == "import" => {
+      let text = fs::read_to_string(export).map_err( |error| format!("could not read {}: {}", export, error) )?;
+      let (imported, skipped) = history.import(&text).map_err( |error| format!("{}: {}", export, error) )?;
+      println!("imported {} tweets, skipped {} already in the history", imported, skipped);
+      Ok(())
+    },
+    _ => Err(usage("history")),
+  };
+}
+
+fn queue(args: &[String], flags: &HashMap<String, String>) -> Result<(), String> {
+  let queue = queue::Queue::new(Path::new(flags.get("queue").map( |q| q.as_str() ).unwrap_or("queue.jsonl")));
+
+  return match args {
+    [command] if command == "export" => {
+      queue.export(&mut io::stdout()).map_err( |error| error.to_string() )?;
+      Ok(())
+    },
+    [command, path] if command == "import" => {
+      let export = fs::read_to_string(path).map_err( |error| format!("could not read {}: {}", path, error) )?;
+      let (imported, skipped) = queue.import(&export).map_err( |error| format!("{}: {}", path, error) )?;
+      println!("imported {} posts, skipped {} already queued", imported, skipped);
+      Ok(())
+    },
+    // safe to run from cron as often as you like, anything that already went out is skipped. with --every it keeps
+    // going on its own, the publishing half to the daemon's --queue
+    [command] if command == "post" => {
+      let settings = server_settings(flags)?;
+      if settings.kill_switch.is_paused() {
+        return Err(format!("publishing is paused ({})", settings.kill_switch.reason().unwrap_or_default()));
+      }
+      // the [server] queue, unless --queue says otherwise
+      let mut queue = match &settings.queue {
+        Some(path) => queue::Queue::new(path),
+        None => queue,
+      };
+      if let Some(path) = &settings.audit_log {
+        queue = queue.with_audit_log(path);
+      }
+      if let Some(every) = flags.get("every") {
+        let every = daemon::parse_interval(every)?.as_secs();
+        return queue::publish_loop(&queue, settings.publisher.as_ref(), &settings.kill_switch, every, &SystemClock, flag(flags, "iterations")?, &mut io::stdout())
+          .map_err( |error| error.to_string() );
+      }
+      let now = SystemClock.now();
+      let posted = queue.post_due(settings.publisher.as_ref(), now).map_err( |error| error.to_string() )?;
+      println!("posted {} to {}", posted, settings.publisher.name());
+      Ok(())
+    },
+    _ => Err(usage("queue")),
+  };
+}
+
+fn audit(args: &[String]) -> Result<(), String> {
+  return match args {
+    [command, path] if command == "verify" => {
+      let entries = audit::verify(Path::new(path)).map_err( |error| format!("{}: {}", path, error) )?;
+      println!("{}: {} entries, chain intact", path, entries);
+      Ok(())
+    },
+    _ => Err(usage("audit")),
+  };
+}
+
+// -n, or the positional count the commands used to take
+fn number_of_tweets(positional: Option<&String>, flags: &HashMap<String, String>, default: i32) -> Result<i32, String> {
+  if let Some(number) = flag(flags, "number")? {
+    return Ok(number);
+  }
+  return match positional {
+    Some(number) => number.parse::<i32>().map_err( |error| format!("could not parse number of tweets: {}", error) ),
+    None => Ok(default),
+  };
+}
+
+fn continue_tweets(args: &[String], flags: &HashMap<String, String>) -> Result<(), String> {
+  // the directory can only be left out when there's a model to load instead
+  let (dir, prefix, number) = match args {
+    [prefix] => (None, prefix, None),
+    [dir, prefix] => (Some(dir), prefix, None),
+    [dir, prefix, number] => (Some(dir), prefix, Some(number)),
+    _ => return Err(usage("continue")),
+  };
+  let num_tweets = number_of_tweets(number, flags, 1)?;
+
+  let mut mchain = trained(flags, dir)?;
+  for _ in 0..num_tweets {
+    println!("{}\n", mchain.continue_tweet(prefix).map_err( |error| error.to_string() )?);
+  }
+  print_timings(flags, &mchain);
+  return Ok(());
+}
+
+fn generate(args: &[String], flags: &HashMap<String, String>, quality: bool) -> Result<(), String> {
+  if args.len() > 2 {
+    return Err(usage(if quality { "quality" } else { "generate" }));
+  }
+
+  // quality reports need a decent batch to mean anything
+  let num_tweets = number_of_tweets(args.get(1), flags, if quality { 100 } else { 1 })?;
+
+  let mut mchain = trained(flags, args.first())?;
+  let mut tweets = Vec::new();
+  if flags.contains_key("report") {
+    eprintln!("{}\n", mchain.ingestion_report());
+  }
+
+  // --trace walks through every pick on stderr so it doesn't get mixed up with the tweets
+  let mut trace = Trace::new(io::stderr(), 3);
+  let styling: Styling = flag(flags, "style")?.unwrap_or_default();
+  let mut rng = side_rng(flags)?;
+  let thread: Option<usize> = flag(flags, "thread")?;
+  if thread == Some(0) {
+    return Err("--thread is how many tweets go in it, at least 1".to_string());
+  }
+  for _ in 0..num_tweets {
+    mchain.set_styles(styling.pick(&mut rng));
+    // -n threads, one after the other
+    if let Some(length) = thread {
+      tweets.extend(mchain.generate_thread(length).map_err( |error| error.to_string() )?);
+      continue;
+    }
+    let tweet = if let Some(word) = flags.get("acrostic") {
+      mchain.generate_acrostic(word, flags.contains_key("by-line"))
+    } else if flags.contains_key("haiku") {
+      mchain.generate_haiku()
+    } else if let Some(prompt) = flags.get("start") {
+      mchain.generate_from(prompt)
+    } else if flags.contains_key("trace") {
+      mchain.generate_tweet_with(&mut trace)
+    } else {
+      mchain.generate_tweet()
+    };
+    tweets.push(tweet.map_err( |error| error.to_string() )?);
+  }
+  print_timings(flags, &mchain);
+
+  if quality {
+    let report = QualityReport::new(&tweets);
+    println!("samples: {}", report.samples);
+    println!("distinct-1: {:.4}", report.distinct_1);
+    println!("distinct-2: {:.4}", report.distinct_2);
+    println!("self-BLEU: {:.4}", report.self_bleu);
+    return Ok(());
+  }
+
+  match flags.get("format").map( |f| f.as_str() ) {
+    Some("json") => {
+      let sensitivity = flag(flags, "sensitive")?.unwrap_or_default();
+      println!("{}", tweets_json(&mchain, &tweets, &sensitivity, flags.contains_key("probabilities")));
+    },
+    Some("text") | None => {
+      for tweet in tweets.iter() {
+        println!("{}\n", tweet);
+      }
+    },
+    Some(other) => return Err(format!("unknown format: {}", other)),
+  }
+  return Ok(());
+}
+
+// the sensitive flag rides along for whatever does the posting, serve and the daemon hand it to the platform
+fn tweets_json(mchain: &MarkovChain, tweets: &[String], sensitivity: &Sensitivity, probabilities: bool) -> String {
+  let entries: Vec<String> = tweets.iter().map( |tweet| {
+    let sensitive = sensitivity.is_sensitive(tweet, mchain.locale());
+    if !probabilities {
+      return format!("{{\"text\":{},\"sensitive\":{}}}", json::string(tweet), sensitive);
+    }
+
+    let tokens = mchain.token_probabilities(tweet);
+    let log_probability: f64 = tokens.iter().map( |(_, p)| p.ln() ).sum();
+    let tokens: Vec<String> = tokens.iter()
+      .map( |(word, p)| format!("{{\"word\":{},\"probability\":{}}}", json::string(word), json::number(*p)) )
+      .collect();
+
+    return format!(
+      "{{\"text\":{},\"sensitive\":{},\"tokens\":[{}],\"log_probability\":{}}}",
+      json::string(tweet), sensitive, tokens.join(","), json::number(log_probability)
+    );
+  }).collect();
+
+  return format!("[{}]", entries.join(","));
+}
+
+// [chain] and [schedule] from --config, or from ./erowidcoin.toml without it, under whatever the command line already
+// says
+fn load_config(flags: &mut HashMap<String, String>) -> Result<(), String> {
+  let path = match flags.get("config") {
+    Some(path) => path.clone(),
+    None if Path::new(config::CONFIG_FILE).is_file() => config::CONFIG_FILE.to_string(),
+    None => return Ok(()),
+  };
+  let text = fs::read_to_string(&path).map_err( |error| format!("could not read {}: {}", path, error) )?;
+  let config = toml::parse(&text).map_err( |error| format!("{}: {}", path, error) )?;
+  let problems = config::check(&config);
+  if !problems.is_empty() {
+    return Err(format!("{} has problems (see erowidcoin check-config):\n  {}", path, problems.join("\n  ")));
+  }
+  for (name, value) in config::flags(&config) {
+    flags.entry(name).or_insert(value);
+  }
+  // so server_settings finds the same file
+  flags.insert("config".to_string(), path);
+  return Ok(());
+}
+
+// flags that can be given more than once, the values get joined with commas
+const REPEATABLE: &[&str] = &["corpus", "include", "exclude", "abbreviations", "numbers", "about"];
+
+// flags that don't take a value
+const SWITCHES: &[&str] = &["probabilities", "trace", "report", "dry-run", "poetry", "fold-case", "profanity", "ban-in-training", "haiku", "by-line", "timings", "long", "help"];
+
+// -n 5 is --number 5
+const SHORT_FLAGS: &[(&str, &str)] = &[("n", "number"), ("o", "output"), ("h", "help")];
+
+// the positional arguments, and the flags by name. a flag no command has never gets to take the argument after it
+// as its value, `--dryrun post` is an unknown flag and not a --dryrun of "post"
+fn parse_args(raw_args: impl Iterator<Item = String>) -> Result<(Vec<String>, HashMap<String, String>), String> {
+  let mut args: Vec<String> = Vec::new();
+  let mut flags: HashMap<String, String> = HashMap::new();
+
+  let mut raw_args = raw_args.peekable();
+  while let Some(arg) = raw_args.next() {
+    let name = match arg.strip_prefix("--") {
+      Some(name) => Some(name),
+      None => arg.strip_prefix('-').and_then( |short| SHORT_FLAGS.iter().find( |(s, _)| *s == short ) ).map( |(_, name)| *name ),
+    };
+    match name {
+      Some(name) if SWITCHES.contains(&name) => { flags.insert(name.to_string(), String::new()); },
+      Some(name) if !COMMANDS.iter().flat_map( |command| flag_names(command.usage) ).chain(flag_names(CHAIN_FLAGS)).any( |known| known == name ) => {
+        return Err(format!("unknown flag {}, `erowidcoin <command> --help` lists what each command takes", arg));
+      },
+      Some(name) => {
+        let value = match raw_args.next_if( |value| !value.starts_with("--") ) {
+          Some(value) => value,
+          None => return Err(format!("--{} needs a value", name)),
+        };
+        match flags.get_mut(name) {
+          Some(values) if REPEATABLE.contains(&name) => *values = format!("{},{}", values, value),
+          _ => { flags.insert(name.to_string(), value); },
+        }
+      },
+      // -5 is a number, -x is a typo
+      None if arg.starts_with('-') && arg.chars().nth(1).is_some_and(char::is_alphabetic) => {
+        return Err(format!("unknown flag {}, `erowidcoin <command> --help` lists what each command takes", arg));
+      },
+      None => args.push(arg),
+    }
+  }
+  return Ok((args, flags));
+}
+
+fn main() {
+  let (args, mut flags) = match parse_args(env::args().skip(1)) {
+    Ok(parsed) => parsed,
+    Err(error) => {
+      println!("{}", error);
+      std::process::exit(1);
+    },
+  };
+
+  // erowidcoin help stats, erowidcoin stats --help
+  let command = args.first().map( |a| a.as_str() );
+  if command == Some("help") || flags.contains_key("help") {
+    let name = if command == Some("help") { args.get(1) } else { args.first() };
+    match help(name.map( |n| n.as_str() )) {
+      Ok(text) => println!("{}", text),
+      Err(error) => {
+        println!("{}", error);
+        std::process::exit(1);
+      },
+    }
+    return;
+  }
+
+  // the original interface, a directory and maybe a count, is generate
+  let name = match command {
+    Some(dir) if !COMMANDS.iter().any( |command| command.name == dir ) && Path::new(dir).is_dir() => Some("generate"),
+    name => name,
+  };
+  // before the config file adds its own
+  if let Err(error) = check_flags(name.unwrap_or(""), &flags) {
+    println!("{}", error);
+    std::process::exit(1);
+  }
+
+  // check-config looks at a file that might not load
+  if command != Some("check-config") {
+    if let Err(error) = load_config(&mut flags) {
+      println!("{}", error);
+      std::process::exit(1);
+    }
+  }
+
+  let result = match command {
+    Some("train") => train(&args[1..], &flags),
+    Some("generate") => generate(&args[1..], &flags, false),
+    Some("post") => daemon(&args[1..], &flags, true),
+    Some("quality") => generate(&args[1..], &flags, true),
+    Some("compare") => compare(&args[1..], &flags),
+    Some("stats") => stats(&args[1..], &flags),
+    Some("export-ngrams") => export_ngrams(&args[1..], &flags),
+    Some("export-corpus-stats") => export_corpus_stats(&args[1..], &flags),
+    Some("flavor") => flavor(&args[1..], &flags),
+    Some("continue") => continue_tweets(&args[1..], &flags),
+    Some("interactive") => interactive(&args[1..], &flags),
+    Some("serve") => serve(&args[1..], &flags),
+    Some("audit") => audit(&args[1..]),
+    Some("backfill") => backfill(&args[1..], &flags),
+    Some("queue") => queue(&args[1..], &flags),
+    Some("history") => history(&args[1..], &flags),
+    Some("check-config") => check_config(&args[1..]),
+    Some("selftest") => selftest(&args[1..]),
+    Some("plugins") => plugins(&args[1..]),
+    Some("init-example") => init_example(&args[1..]),
+    Some("daemon") => daemon(&args[1..], &flags, false),
+    Some("pause") => pause(&args[1..], &flags, true),
+    Some("resume") => pause(&args[1..], &flags, false),
+    // the original interface, a directory and maybe a count
+    Some(dir) if Path::new(dir).is_dir() => generate(&args, &flags, false),
+    Some(other) => Err(unknown(other)),
+    None => help(None).and_then(Err),
+  };
 
-  for tweet in tweets.iter() {
-    println!("{}\n", tweet);
+  // nonzero so scripts (and whatever deploys the server) notice
+  if let Err(error) = result {
+    println!("{}", error);
+    std::process::exit(1);
   }
 }
@@ -0,0 +1,240 @@
+use std::{fmt, fs, io};
+use std::path::Path;
+use std::collections::BTreeMap;
+use crate::toml::{self, Value};
+
+pub const MANIFEST_FILE: &str = "corpus.toml";
+
+// an optional corpus.toml next to the text files, e.g.
+//
+//   description = "erowid experience vaults + the bitcoin whitepaper"
+//   license = "various, see each file"
+//
+//   [[file]]
+//   path = "dmt.txt"
+//   tags = ["erowid", "dmt"]
+//   weight = 2
+//   license = "CC BY-NC 4.0"
+//   source = "https://erowid.org/experiences/..."
+//
+// files that aren't listed just get a weight of 1 and no metadata, unless they have frontmatter of their own (see
+// frontmatter.rs)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Manifest {
+  pub description: Option<String>,
+  pub license: Option<String>,
+  pub files: Vec<FileEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileEntry {
+  pub path: String,
+  pub tags: Vec<String>,
+  // multiplies the edge weights the file contributes
+  pub weight: f64,
+  pub license: Option<String>,
+  pub source: Option<String>,
+  // the rest of the file's frontmatter: title, substance, dose, date..
+  pub metadata: BTreeMap<String, String>,
+}
+
+#[derive(Debug)]
+pub struct ManifestError(pub String);
+
+impl fmt::Display for ManifestError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    return write!(f, "{}: {}", MANIFEST_FILE, self.0);
+  }
+}
+
+impl std::error::Error for ManifestError {}
+
+// the attribution that has to travel with a model: what the corpus was, and what each file we trained on said
+// about where it came from
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Provenance {
+  pub description: Option<String>,
+  pub license: Option<String>,
+  pub files: Vec<FileEntry>,
+}
+
+impl Provenance {
+  pub fn is_empty(&self) -> bool {
+    return self.description.is_none() && self.license.is_none() && self.files.is_empty();
+  }
+}
+
+impl fmt::Display for Provenance {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, "provenance:")?;
+    if let Some(description) = &self.description {
+      writeln!(f, "  corpus: {}", description)?;
+    }
+    if let Some(license) = &self.license {
+      writeln!(f, "  license: {}", license)?;
+    }
+    for entry in &self.files {
+      writeln!(f, "  {} (weight {})", entry.path, entry.weight)?;
+      if !entry.tags.is_empty() {
+        writeln!(f, "    tags: {}", entry.tags.join(", "))?;
+      }
+      if let Some(license) = &entry.license {
+        writeln!(f, "    license: {}", license)?;
+      }
+      if let Some(source) = &entry.source {
+        writeln!(f, "    source: {}", source)?;
+      }
+      for (key, value) in &entry.metadata {
+        writeln!(f, "    {}: {}", key, value)?;
+      }
+    }
+    return Ok(());
+  }
+}
+
+// the trainer speaks io::Result, a broken manifest is just bad data as far as it's concerned
+impl From<ManifestError> for io::Error {
+  fn from(error: ManifestError) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, error.to_string());
+  }
+}
+
+impl Manifest {
+  // Ok(None) when the directory doesn't have a manifest at all
+  pub fn load(dir: &Path) -> io::Result<Option<Manifest>> {
+    let path = dir.join(MANIFEST_FILE);
+    if !path.is_file() {
+      return Ok(None);
+    }
+
+    let manifest = Manifest::parse(&fs::read_to_string(&path)?)?;
+    for entry in &manifest.files {
+      if !dir.join(&entry.path).is_file() {
+        return Err(ManifestError(format!("{} is listed but doesn't exist", entry.path)).into());
+      }
+    }
+    return Ok(Some(manifest));
+  }
+
+  pub fn parse(text: &str) -> Result<Manifest, ManifestError> {
+    let root = toml::parse(text).map_err( |error| ManifestError(error.to_string()) )?;
+    check_keys("the top level", &root, &["description", "license", "file"])?;
+    let description = optional_string(&root, "description", "description")?;
+    let license = optional_string(&root, "license", "license")?;
+
+    let mut files = Vec::new();
+    if let Some(entries) = root.get("file") {
+      let entries = entries.as_array().ok_or_else( || ManifestError("`file` should be a list of [[file]] tables".to_string()) )?;
+      for entry in entries {
+        let entry = entry.as_table().ok_or_else( || ManifestError("`file` should be a list of [[file]] tables".to_string()) )?;
+        files.push(FileEntry::parse(entry)?);
+      }
+    }
+
+    return Ok(Manifest { description, license, files });
+  }
+
+  pub fn entry(&self, file_name: &str) -> Option<&FileEntry> {
+    return self.files.iter().find( |entry| entry.path == file_name );
+  }
+}
+
+impl FileEntry {
+  // what a file the manifest doesn't list gets
+  pub fn new(path: &str) -> FileEntry {
+    return FileEntry { path: path.to_string(), tags: Vec::new(), weight: 1.0, license: None, source: None, metadata: BTreeMap::new() };
+  }
+
+  fn parse(table: &BTreeMap<String, Value>) -> Result<FileEntry, ManifestError> {
+    check_keys("[[file]]", table, &["path", "tags", "weight", "license", "source"])?;
+
+    let path = match table.get("path") {
+      Some(path) => expect(path.as_str(), "path", "string", path)?.to_string(),
+      None => return Err(ManifestError("every [[file]] needs a path".to_string())),
+    };
+    let context = |key: &str| format!("{} for {}", key, path);
+
+    let mut tags = Vec::new();
+    if let Some(value) = table.get("tags") {
+      for tag in expect(value.as_array(), &context("tags"), "list of strings", value)? {
+        tags.push(expect(tag.as_str(), &context("tags"), "list of strings", tag)?.to_string());
+      }
+    }
+
+    let weight = match table.get("weight") {
+      Some(value) => expect(value.as_float(), &context("weight"), "number", value)?,
+      None => 1.0,
+    };
+    if weight <= 0.0 || !weight.is_finite() {
+      return Err(ManifestError(format!("{} should be a positive number, got {}", context("weight"), weight)));
+    }
+
+    return Ok(FileEntry {
+      license: optional_string(table, "license", &context("license"))?,
+      source: optional_string(table, "source", &context("source"))?,
+      path,
+      tags,
+      weight,
+      metadata: BTreeMap::new(),
+    });
+  }
+}
+
+fn expect<T>(value: Option<T>, what: &str, expected: &str, actual: &Value) -> Result<T, ManifestError> {
+  return value.ok_or_else( || ManifestError(format!("{} should be a {}, not a {}", what, expected, actual.type_name())) );
+}
+
+fn optional_string(table: &BTreeMap<String, Value>, key: &str, what: &str) -> Result<Option<String>, ManifestError> {
+  return match table.get(key) {
+    Some(value) => Ok(Some(expect(value.as_str(), what, "string", value)?.to_string())),
+    None => Ok(None),
+  };
+}
+
+fn check_keys(context: &str, table: &BTreeMap<String, Value>, allowed: &[&str]) -> Result<(), ManifestError> {
+  for key in table.keys() {
+    if !allowed.contains(&key.as_str()) {
+      return Err(ManifestError(format!("unknown key `{}` in {} (expected one of: {})", key, context, allowed.join(", "))));
+    }
+  }
+  return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_file_entries() {
+    let manifest = Manifest::parse(r#"
+      description = "trip reports and whitepapers"
+
+      [[file]]
+      path = "dmt.txt"
+      tags = ["erowid", "dmt"]
+      weight = 2
+      license = "CC BY-NC 4.0"
+
+      [[file]]
+      path = "bitcoin.txt"
+      source = "https://bitcoin.org/bitcoin.pdf"
+    "#).unwrap();
+
+    assert_eq!(manifest.description.as_deref(), Some("trip reports and whitepapers"));
+    assert_eq!(manifest.license, None);
+    assert_eq!(manifest.files[0].tags, vec!("erowid".to_string(), "dmt".to_string()));
+    assert_eq!(manifest.files[0].weight, 2.0);
+    assert_eq!(manifest.entry("bitcoin.txt").unwrap().weight, 1.0);
+    assert_eq!(manifest.entry("bitcoin.txt").unwrap().source.as_deref(), Some("https://bitcoin.org/bitcoin.pdf"));
+  }
+
+  #[test]
+  fn rejects_bad_manifests() {
+    let error = |text: &str| Manifest::parse(text).unwrap_err().0;
+
+    assert_eq!(error("[[file]]\npath = \"a.txt\"\nwieght = 2"), "unknown key `wieght` in [[file]] (expected one of: path, tags, weight, license, source)");
+    assert_eq!(error("[[file]]\ntags = [\"a\"]"), "every [[file]] needs a path");
+    assert_eq!(error("[[file]]\npath = \"a.txt\"\nweight = \"heavy\""), "weight for a.txt should be a number, not a string");
+    assert_eq!(error("[[file]]\npath = \"a.txt\"\nweight = -1"), "weight for a.txt should be a positive number, got -1");
+  }
+}
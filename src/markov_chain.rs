@@ -1,165 +1,4034 @@
-use std::{io, fs};
-use std::path::Path;
+use std::{cmp, fmt, io, fs, mem, thread};
+use std::borrow::Cow;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::collections::HashMap;
-use rand::Rng;
+use std::time::Instant;
+use rand::{Rng, RngCore, SeedableRng};
+use rand::rngs::OsRng;
 use rand::seq::SliceRandom;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand_chacha::ChaCha8Rng;
 use regex::Regex;
+use crate::filter::{self, Acrostic, BannedWords, Filter, FilterStats, GaveUp, MaxLength, MaxWords, MinWords, Syllables, Verbatim};
+use crate::observer::{CancellationToken, Control, Observer};
+use crate::ingest::{self, IngestionReport, Skipped};
+use crate::language;
+use crate::locale::{self, Locale};
+use crate::sanitize;
+use crate::syllables;
+use crate::manifest::{self, FileEntry, Manifest, Provenance};
+use crate::scrub::Scrubber;
+use crate::style::{self, Style};
+use crate::sha256::{self, Sha256};
+use crate::model::{self, Reader, Writer};
+use crate::timings::Timings;
+use crate::glob::Patterns;
+use crate::flavor;
+use crate::frontmatter;
+use crate::numbers::{NumberStyle, Numbers};
+use crate::detokenize::{Detokenizer, Joiner, Tokenizer};
+use crate::history::History;
+use crate::taxonomy::Taxonomy;
+use crate::casing::Casing;
+use crate::stats::{CorpusStats, Ngram};
+use crate::storage::{self, Storage};
 
-// contains a graph structure
+// rough byte counts for where the model's memory goes. these are estimates from capacities and struct sizes,
+// they don't know about allocator overhead
+pub struct MemoryFootprint {
+  pub nodes: usize,
+  pub edges: usize,
+  pub interner: usize,
+  pub entry_words: usize,
+  pub csr: usize,
+}
+
+impl MemoryFootprint {
+  pub fn total(&self) -> usize {
+    return self.nodes + self.edges + self.interner + self.entry_words + self.csr;
+  }
+}
+
+// hashbrown stores one control byte next to every bucket
+fn hashmap_bytes<K, V>(map: &HashMap<K, V>) -> usize {
+  return map.capacity() * (mem::size_of::<K>() + mem::size_of::<V>() + 1);
+}
+
+fn vec_bytes<T>(vec: &Vec<T>) -> usize {
+  return vec.capacity() * mem::size_of::<T>();
+}
+
+// where the chain's randomness comes from. the daemon wants unpredictable output, tests and --seed want the opposite
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RngSource {
+  // a ChaCha keyed off the thread rng, this is the default
+  Thread,
+  // ChaCha is specified bit-for-bit, unlike StdRng, so the same seed gives the same tweets everywhere
+  Seeded(u64),
+  // straight from the operating system every time
+  OsRandom,
+}
+
+// concrete rng behind an RngSource. an enum so the built in ones don't go through a vtable on every pick, only
+// somebody else's rng from with_rng does. there's only ever one of these per chain, so boxing the ChaCha to keep
+// the enum small isn't worth it
+#[allow(clippy::large_enum_variant)]
+enum ChainRng {
+  ChaCha(ChaCha8Rng),
+  Os(OsRng),
+  // Send + Sync so the chain stays that way
+  Custom(Box<dyn RngCore + Send + Sync>),
+}
+
+impl ChainRng {
+  fn new(source: RngSource) -> ChainRng {
+    return match source {
+      RngSource::Thread => ChainRng::ChaCha(ChaCha8Rng::from_rng(rand::thread_rng()).unwrap()),
+      RngSource::Seeded(seed) => ChainRng::ChaCha(ChaCha8Rng::seed_from_u64(seed)),
+      RngSource::OsRandom => ChainRng::Os(OsRng),
+    };
+  }
+}
+
+impl RngCore for ChainRng {
+  fn next_u32(&mut self) -> u32 {
+    return match self {
+      ChainRng::ChaCha(rng) => rng.next_u32(),
+      ChainRng::Os(rng) => rng.next_u32(),
+      ChainRng::Custom(rng) => rng.next_u32(),
+    };
+  }
+
+  fn next_u64(&mut self) -> u64 {
+    return match self {
+      ChainRng::ChaCha(rng) => rng.next_u64(),
+      ChainRng::Os(rng) => rng.next_u64(),
+      ChainRng::Custom(rng) => rng.next_u64(),
+    };
+  }
+
+  fn fill_bytes(&mut self, dest: &mut [u8]) {
+    match self {
+      ChainRng::ChaCha(rng) => rng.fill_bytes(dest),
+      ChainRng::Os(rng) => rng.fill_bytes(dest),
+      ChainRng::Custom(rng) => rng.fill_bytes(dest),
+    }
+  }
+
+  fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+    return match self {
+      ChainRng::ChaCha(rng) => rng.try_fill_bytes(dest),
+      ChainRng::Os(rng) => rng.try_fill_bytes(dest),
+      ChainRng::Custom(rng) => rng.try_fill_bytes(dest),
+    };
+  }
+}
+
+// 280 characters can't fit more words than this once you count the spaces, and it stops cycles that
+// never reach punctuation ("hodl hodl hodl") from looping forever
+const MAX_WORDS: usize = 140;
+
+const HAIKU: [usize; 3] = [5, 7, 5];
+
+// how long each tweet of a thread gets, number and all, when there's no max_chars
+const THREAD_CHARS: usize = 280;
+
+// long-form paragraphs run to between this many sentences
+const PARAGRAPH_SENTENCES: (usize, usize) = (2, 5);
+// and it's finished once there's less room left than this, another sentence would only be a fragment
+const LONG_FORM_MIN_ROOM: usize = 20;
+
+// walks per line before the haiku is given up on and started over
+const HAIKU_LINE_ATTEMPTS: usize = 50;
+
+// how far an acrostic search goes before it counts as a failed attempt
+const ACROSTIC_STEPS: usize = 10_000;
+
+// the tokens poetry mode uses for line ends. whitespace never ends up inside a word, so they can't clash with one
+const LINE_BREAK: &str = "\n";
+const STANZA_BREAK: &str = "\n\n";
+
+fn is_break(word: &str) -> bool {
+  return word == LINE_BREAK || word == STANZA_BREAK;
+}
+
+// a state is the last `order` words joined by spaces, which can't be inside a word. with order 1 it's just the word
+fn first_word(state: &str) -> &str {
+  return state.split(' ').next().unwrap_or(state);
+}
+
+fn last_word(state: &str) -> &str {
+  return state.rsplit(' ').next().unwrap_or(state);
+}
+
+// words back into text, the joiner puts the spaces in. none around the line breaks, and a trailing stanza break is
+// the end, not more text
+fn join<'a>(joiner: &dyn Joiner, words: impl Iterator<Item = &'a str>) -> String {
+  let mut text = String::new();
+  join_into(joiner, &mut text, words);
+  return text;
+}
+
+// join, over whatever was in text before. generation keeps reusing the one buffer
+fn join_into<'a>(joiner: &dyn Joiner, text: &mut String, words: impl Iterator<Item = &'a str>) {
+  text.clear();
+  for word in words {
+    match is_break(word) {
+      true => text.push_str(word),
+      false => joiner.push(text, word),
+    }
+  }
+  text.truncate(text.trim_end_matches('\n').len());
+}
+
+// the graph's joiner, with the words of a folded graph written the way the corpus mostly had them and a capital
+// wherever a sentence starts: at the start, after a stanza break, and after a word that ends one
+struct Cased<'a>(&'a Graph);
+
+impl Joiner for Cased<'_> {
+  fn push(&self, text: &mut String, word: &str) {
+    let graph = self.0;
+    if !graph.folded {
+      return graph.joiner.push(text, word);
+    }
+    let starts = match text.split_whitespace().next_back() {
+      None => true,
+      Some(_) if text.ends_with(STANZA_BREAK) => true,
+      Some(last) => graph.ends_sentence(last),
+    };
+    graph.joiner.push(text, &graph.casing.restore(word, starts));
+  }
+}
+
+const DEFAULT_MAX_ATTEMPTS: usize = 100;
+
+// transitions that don't clear both thresholds are left out of generation. trip reports are personal, and a
+// transition only one author ever wrote is how we'd end up quoting them word for word
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Suppression {
+  // how many documents have to contain the transition
+  pub min_documents: usize,
+  // total edge weight it needs, after doc caps and manifest weights
+  pub min_count: i32,
+}
+
+impl Default for Suppression {
+  // keeps everything
+  fn default() -> Self {
+    return Suppression { min_documents: 1, min_count: 1 };
+  }
+}
+
+// how the next word gets picked out of the ones the corpus has. top_k keeps only the k heaviest edges of every word,
+// then the weights left are raised to 1 / temperature: under 1 the likely picks get likelier and the output
+// parrots the corpus more, over 1 the weights even out and it gets weirder. a temperature of 0 (or less) is always
+// the heaviest edge. unlike suppression it's only how generation reads the graph, it can change after training
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sampling {
+  pub temperature: f64,
+  pub top_k: Option<usize>,
+}
+
+impl Default for Sampling {
+  // by frequency, every edge
+  fn default() -> Self {
+    return Sampling { temperature: 1.0, top_k: None };
+  }
+}
+
+// reweighted edges are out of this much for a word's heaviest edge, every edge keeping at least 1. plenty of
+// precision, and "the" with its thousands of edges still can't overflow an i32
+const SAMPLING_SCALE: f64 = 1000.0;
+
+impl Sampling {
+  // a word's edges, sorted by id, the way generation should see them. still sorted by id after
+  fn apply(&self, edges: &mut Vec<(u32, i32)>) {
+    let top_k = match self.temperature > 0.0 {
+      true => self.top_k,
+      false => Some(1),
+    };
+    if let Some(k) = top_k.filter( |&k| k < edges.len() ) {
+      // ties go to the lower id, so it doesn't depend on anything but the graph
+      edges.sort_by( |a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)) );
+      edges.truncate(k.max(1));
+      edges.sort();
+    }
+    if self.temperature > 0.0 && self.temperature != 1.0 {
+      let max = edges.iter().map( |(_, weight)| *weight ).max().unwrap_or(1) as f64;
+      for (_, weight) in edges.iter_mut() {
+        *weight = cmp::max(1, ((*weight as f64 / max).powf(1.0 / self.temperature) * SAMPLING_SCALE).round() as i32);
+      }
+    }
+  }
+}
+
+// where the next attempt starts after one gets rejected
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RetryPolicy {
+  // keep the entry word, only the walk after it changes
+  SameEntry,
+  // any entry word, failed ones included. this is the default
+  RandomEntry,
+  // entry words get less likely the more often they've failed this tweet
+  AvoidFailedEntries,
+}
+
+// what to do about a word with nowhere to go that doesn't end a sentence either, like the last word of a file that
+// didn't end in punctuation
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DeadEndPolicy {
+  // the tweet just ends there, unfinished. this is the default
+  #[default]
+  End,
+  // back up a word and pick one that goes somewhere instead, if the word before had any other choice
+  Resample,
+  // throw the walk away and start again from a new entry word, up to DEAD_END_RESTARTS times
+  Restart,
+}
+
+const DEAD_END_RESTARTS: usize = 10;
+
+// tags are bits in a u64 on every node, past this many a corpus's tags are only in the report
+const MAX_TAGS: usize = 64;
+
+// with --about, how often a word none of the matching documents used gets picked again from the ones they did
+const ABOUT_BOOST: f64 = 0.75;
+
+// the same for the daemon's theme words, a word that isn't one of them gets picked again from the ones that are
+const WORD_BOOST: f64 = 0.75;
+
+// everything the library hands back instead of panicking
+#[derive(Debug)]
+pub enum ErowidCoinError {
+  GaveUp(GaveUp),
+  // a prompt ended on a word that isn't in the corpus, or on one that goes nowhere
+  UnknownWord(String),
+  Cancelled,
+  // the corpus directory (or its manifest) couldn't be read
+  Corpus(io::Error),
+  // trained, but on nothing a tweet can be made of (empty or whitespace only files)
+  EmptyCorpus,
+  // a saved model couldn't be read or written, or isn't one (InvalidData)
+  Model(io::Error),
+  // generate got called before train
+  Untrained,
+  // a tweet couldn't be added to the history, so it wasn't handed out
+  History(io::Error),
+  // with_about asked for tags nothing in the corpus has
+  UnknownTags(Vec<String>),
+  // with_order(0), there has to be at least one word to pick the next one from
+  ZeroOrder,
+}
+
+// what it was called before it covered more than generation
+pub type GenerationError = ErowidCoinError;
+
+impl fmt::Display for ErowidCoinError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    return match self {
+      ErowidCoinError::GaveUp(gave_up) => gave_up.fmt(f),
+      ErowidCoinError::UnknownWord(word) => write!(f, "\"{}\" never shows up in the corpus, so there's nowhere to go from it", word),
+      ErowidCoinError::Cancelled => write!(f, "generation was cancelled"),
+      ErowidCoinError::Corpus(error) => write!(f, "couldn't read the corpus: {}", error),
+      ErowidCoinError::EmptyCorpus => write!(f, "the corpus doesn't have any words in it"),
+      ErowidCoinError::Model(error) => write!(f, "bad model file: {}", error),
+      ErowidCoinError::Untrained => write!(f, "the chain hasn't been trained on anything yet"),
+      ErowidCoinError::History(error) => write!(f, "couldn't record the tweet in the history: {}", error),
+      ErowidCoinError::UnknownTags(tags) => write!(f, "nothing in the corpus is tagged {}", tags.join(" or ")),
+      ErowidCoinError::ZeroOrder => write!(f, "the order of a chain is at least 1"),
+    };
+  }
+}
+
+impl Error for ErowidCoinError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    return match self {
+      ErowidCoinError::Corpus(error) | ErowidCoinError::Model(error) | ErowidCoinError::History(error) => Some(error),
+      _ => None,
+    };
+  }
+}
+
+
+// what candidates get written into. kept on the chain and reused, so after the first few tweets generating one
+// doesn't allocate anything until it's handed back
+#[derive(Default)]
+struct Scratch {
+  text: String,
+  states: Vec<u32>,
+}
+
+// contains a graph structure. the graph itself never holds an rng, so it (and the chain) stay Send + Sync
 pub struct MarkovChain {
   graph: Graph,
+  rng: ChainRng,
+  // max total edge weight a single document can contribute, so one huge trip report doesn't drown out the rest
+  document_cap: Option<i32>,
+  // a line that's all this splits a file into documents of their own, for dumps of many reports in one file
+  document_separator: Option<Regex>,
+  filters: Vec<Box<dyn Filter>>,
+  // filled in while training, so it isn't one of the filters until then
+  verbatim: Option<Verbatim>,
+  // what's been generated before, shared with whoever else wants to look at it. every tweet that gets through is
+  // recorded in it
+  history: Option<Arc<History>>,
+  // applied to every candidate before the filters see it
+  styles: Vec<Style>,
+  max_attempts: usize,
+  // generation aims to finish a sentence inside this, see Walk
+  max_chars: Option<usize>,
+  // sentences after sentences in paragraphs up to max_chars, instead of a tweet's one. see Graph::long_form
+  long_form: bool,
+  retry_policy: RetryPolicy,
+  dead_end_policy: DeadEndPolicy,
+  // every candidate and rejection since the chain was made, across tweets
+  filter_stats: FilterStats,
+  timings: Timings,
+  scratch: Scratch,
+  // unique and top tokens are filled in from the graph when somebody asks for the report
+  ingestion: IngestionReport,
+  // a blend's corpora by name, and the words only one of them uses (flavor::key'd) -> which. see source_of
+  sources: Vec<String>,
+  source_words: HashMap<String, usize>,
+  // runs over every document before it's trained on
+  scrubber: Option<Scrubber>,
+  // splits documents up instead of their whitespace, see with_tokenizer
+  tokenizer: Option<Arc<dyn Tokenizer>>,
+  // words that never make it into the graph, see with_training_blocklist
+  training_blocklist: Option<BannedWords>,
+  // kept, bucketed or spelled out, per manifest tag
+  numbers: Numbers,
+  // tags documents by the words in them, on top of their manifest or frontmatter tags
+  taxonomy: Taxonomy,
+  // generation sticks to documents with one of these tags, see with_about
+  about: Vec<String>,
+  // generation leans towards these words (flavor::key'd), see set_boost
+  boost: Vec<String>,
+  // which files in a corpus directory get read
+  patterns: Patterns,
+  // how many threads read a corpus directory, None for one per core
+  threads: Option<usize>,
+  // what the manifest said about the corpus and the files we trained on, in the order we read them
+  provenance: Provenance,
+  // waiting for train
+  corpora: Vec<Corpus>,
+}
+
+// something to train on
+#[derive(Clone, Debug)]
+pub enum Corpus {
+  // every file in it, with its manifest
+  Dir(PathBuf),
+  // a directory blended in with every other weighted one, see blend_in. only the weights' ratios matter
+  Weighted(PathBuf, f64),
+  // a single document
+  Text(String),
+}
+
+// a file's path alongside its contents
+type Document = (PathBuf, String);
+
+// everything save wrote, in the same order
+fn read_model(reader: &mut Reader) -> io::Result<(Graph, Provenance, Option<Verbatim>)> {
+  let version = reader.header()?;
+  let mut graph = Graph::read(reader)?;
+  let provenance = model::read_provenance(reader, version)?;
+  let verbatim = match version {
+    1 => None,
+    _ => read_verbatim(reader)?,
+  };
+  if version >= 4 {
+    graph.read_tags(reader)?;
+  }
+  if version >= 5 {
+    graph.folded = reader.u8()? != 0;
+    graph.casing = Casing::read(reader)?;
+  }
+  reader.finish()?;
+  return Ok((graph, provenance, verbatim));
+}
+
+// 0 for no guard, 1 for the hashes of the words as written (before version 6), 2 for normalized ones
+fn write_verbatim(out: &mut Writer, verbatim: Option<&Verbatim>) {
+  let Some(verbatim) = verbatim else {
+    out.u8(0);
+    return;
+  };
+  out.u8(if verbatim.normalized { 2 } else { 1 });
+  out.u32(verbatim.max as u32);
+  // sorted, so the same training writes the same file
+  let mut seen: Vec<&u64> = verbatim.seen.iter().collect();
+  seen.sort();
+  out.u64(seen.len() as u64);
+  for hash in seen {
+    out.u64(*hash);
+  }
+}
+
+fn read_verbatim(reader: &mut Reader) -> io::Result<Option<Verbatim>> {
+  let normalized = match reader.u8()? {
+    0 => return Ok(None),
+    1 => false,
+    2 => true,
+    other => return Err(model::invalid(format!("the verbatim guard is marked {}, not 0 to 2", other))),
+  };
+  let mut verbatim = Verbatim { normalized, ..Verbatim::new(reader.u32()? as usize) };
+  for _ in 0..reader.u64()? {
+    verbatim.seen.insert(reader.u64()?);
+  }
+  return Ok(Some(verbatim));
+}
+
+// reads every file under a directory as its own document
+pub fn read_documents(dir: &Path) -> io::Result<Vec<String>> {
+  return Ok(read_corpus(dir, &Patterns::default())?.0.into_iter().map( |(_, contents)| contents ).collect());
+}
+
+// same as read_documents, but only what the patterns allow, and files that aren't utf-8 text get skipped (with the
+// reason) instead of failing the whole run. the manifest isn't a document, so it's left out too
+fn read_corpus(dir: &Path, patterns: &Patterns) -> io::Result<(Vec<Document>, Vec<Skipped>)> {
+  let mut paths = Vec::new();
+  let mut skipped = Vec::new();
+  find_files(dir, dir, patterns, &mut paths, &mut skipped)?;
+  // read_dir order depends on the filesystem, and entry word order leaks into seeded output
+  paths.sort();
+
+  let mut documents = Vec::new();
+  for path in paths {
+    match fs::read_to_string(&path) {
+      // without the frontmatter, it isn't what gets trained on
+      Ok(contents) => match frontmatter::split(&contents) {
+        Ok((Some(_), body)) => documents.push((path, body.to_string())),
+        _ => documents.push((path, contents)),
+      },
+      Err(error) if error.kind() == io::ErrorKind::InvalidData => skipped.push((path, "not utf-8 text".to_string())),
+      Err(error) => return Err(error),
+    }
+  }
+  Ok((documents, skipped))
+}
+
+// every file under dir, all the way down. hidden directories (.git) aren't walked into, and neither are symlinks to
+// directories, so a link back up the tree can't go round forever
+fn find_files(top: &Path, dir: &Path, patterns: &Patterns, paths: &mut Vec<PathBuf>, skipped: &mut Vec<Skipped>) -> io::Result<()> {
+  for entry in fs::read_dir(dir)? {
+    let entry = entry?;
+    let path = entry.path();
+    let relative = path.strip_prefix(top).unwrap_or(&path);
+    if relative == Path::new(manifest::MANIFEST_FILE) {
+      continue;
+    }
+
+    let file_type = entry.file_type()?;
+    if file_type.is_dir() {
+      if !entry.file_name().to_string_lossy().starts_with('.') {
+        find_files(top, &path, patterns, paths, skipped)?;
+      }
+    } else if file_type.is_symlink() && path.is_dir() {
+      skipped.push((path, "is a symlink to a directory".to_string()));
+    } else if patterns.allows(relative) {
+      paths.push(path);
+    }
+  }
+  return Ok(());
+}
+
+// where a file is in the corpus, which is what the manifest calls it
+fn relative_name(dir: &Path, path: &Path) -> String {
+  return path.strip_prefix(dir).unwrap_or(path).to_string_lossy().replace('\\', "/");
 }
 
 impl MarkovChain {
-  // builds our graph
+  // builds our graph. the files get split into runs, one per thread, that are read and trained on in parallel. the
+  // partial graphs are merged back in file order, so it all comes out the same as reading them one at a time
   fn parse_in(&mut self, dir: &Path) -> io::Result<()> {
-    for entry in fs::read_dir(dir)? {
-      let entry = entry?;
-      let path = entry.path();
-      let contents = fs::read_to_string(path)?;
-      let contents = contents.split_whitespace();
+    let started = Instant::now();
+    let manifest = Manifest::load(dir)?.unwrap_or_default();
+    let mut paths = Vec::new();
+    find_files(dir, dir, &self.patterns, &mut paths, &mut self.ingestion.skipped)?;
+    // read_dir order depends on the filesystem, and entry word order leaks into seeded output
+    paths.sort();
+    self.timings.ingestion += started.elapsed();
+    self.provenance.description = self.provenance.description.take().or(manifest.description.clone());
+    self.provenance.license = self.provenance.license.take().or(manifest.license.clone());
 
-      let mut last_word: Option<String> = None;
+    let threads = self.threads.unwrap_or_else( || thread::available_parallelism().map_or(1, |n| n.get()) );
+    let run = paths.len().div_ceil(threads).max(1);
+    let (this, manifest) = (&*self, &manifest);
+    let partials: Vec<io::Result<MarkovChain>> = thread::scope( |scope| {
+      let workers: Vec<_> = paths.chunks(run).map( |paths| scope.spawn(move || this.train_files(dir, paths, manifest)) ).collect();
+      return workers.into_iter().map( |worker| worker.join().unwrap() ).collect();
+    });
 
-      for word in contents {
-        self.graph.add(word.to_string(), last_word);
-        last_word = Some(word.to_string());
-      }
+    for partial in partials {
+      self.absorb(partial?, 1.0);
     }
     Ok(())
   }
 
-  fn generate_tweet(&mut self) -> String {
-    return self.graph.generate_tweet();
+  // trains each directory on its own, then scales its edges so its share of all the edge weight is its share of the
+  // weights, before merging them all in. how big each corpus is stops mattering: at 0.7 / 0.3 the erowid reports
+  // get 70% of the say in every shared word's next pick however much crypto text there is
+  fn blend_in(&mut self, sources: &[(PathBuf, f64)]) -> io::Result<()> {
+    if let Some((dir, weight)) = sources.iter().find( |(_, weight)| !(weight.is_finite() && *weight > 0.0) ) {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} has a weight of {}, it has to be more than 0", dir.display(), weight)));
+    }
+
+    let mut partials = Vec::new();
+    for (dir, weight) in sources {
+      let mut partial = self.partial();
+      partial.parse_in(dir)?;
+      self.add_source(dir, &partial.graph);
+      let total: i64 = partial.graph.nodes.iter().map( |node| node.sum as i64 ).sum();
+      partials.push((partial, *weight, total));
+    }
+
+    let weights: f64 = sources.iter().map( |(_, weight)| weight ).sum();
+    let total: i64 = partials.iter().map( |(_, _, total)| total ).sum();
+    for (partial, weight, own) in partials {
+      let scale = match own {
+        0 => 1.0,
+        own => weight / weights * total as f64 / own as f64,
+      };
+      self.absorb(partial, scale);
+    }
+    return Ok(());
+  }
+
+  // remembers which words the corpus in `dir` used, for source_of. a word another corpus used too stops belonging to
+  // either
+  fn add_source(&mut self, dir: &Path, graph: &Graph) {
+    let source = self.sources.len();
+    self.sources.push(dir.file_name().map_or(dir.display().to_string(), |name| name.to_string_lossy().to_string()));
+    for state in &graph.symbols.words {
+      for word in state.split(' ').map(flavor::key).filter( |word| !word.is_empty() ) {
+        let owner = self.source_words.entry(word).or_insert(source);
+        if *owner != source {
+          *owner = usize::MAX;
+        }
+      }
+    }
   }
 
-  pub fn create_tweets(&mut self, dir: &Path, number: i32) -> Vec<String> {
-    self.parse_in(dir).unwrap();
+  // the names of the corpora blended in this run, in the order they were given. a loaded model doesn't remember
+  pub fn sources(&self) -> &[String] {
+    return &self.sources;
+  }
 
-    let mut vec = Vec::new();
+  // the one blended corpus that uses `word` (looked up through flavor::key), None if more than one does or none
+  pub fn source_of(&self, word: &str) -> Option<&str> {
+    return self.source_words.get(&flavor::key(word)).and_then( |&source| self.sources.get(source) ).map( |name| name.as_str() );
+  }
 
-    for _ in 0..number {
-      vec.push(self.generate_tweet());
+  // merges a partial chain's graph in, edges scaled by `scale`, along with everything it counted while training
+  fn absorb(&mut self, partial: MarkovChain, scale: f64) {
+    let started = Instant::now();
+    self.graph.merge(partial.graph, scale);
+    self.timings.graph_build += started.elapsed();
+    self.timings.add_training(&partial.timings);
+    self.ingestion.add(partial.ingestion);
+    self.provenance.description = self.provenance.description.take().or(partial.provenance.description);
+    self.provenance.license = self.provenance.license.take().or(partial.provenance.license);
+    self.provenance.files.extend(partial.provenance.files);
+    if let (Some(verbatim), Some(partial)) = (&mut self.verbatim, partial.verbatim) {
+      verbatim.seen.extend(partial.seen);
     }
+  }
 
-    return vec;
+  // untrained, with this one's training settings
+  fn partial(&self) -> MarkovChain {
+    let mut partial = MarkovChain::new();
+    partial.graph = self.graph.empty_like();
+    partial.document_cap = self.document_cap;
+    partial.document_separator = self.document_separator.clone();
+    partial.scrubber = self.scrubber.clone();
+    partial.tokenizer = self.tokenizer.clone();
+    partial.training_blocklist = self.training_blocklist.clone();
+    partial.numbers = self.numbers.clone();
+    partial.taxonomy = self.taxonomy.clone();
+    partial.patterns = self.patterns.clone();
+    partial.threads = self.threads;
+    partial.verbatim = self.verbatim.as_ref().map(Verbatim::empty);
+    return partial;
   }
 
-  pub fn new() -> MarkovChain {
-    return MarkovChain {
-      graph: Graph::new(),
+  // a chain of its own for some of a directory's files, with this one's training settings
+  fn train_files(&self, dir: &Path, paths: &[PathBuf], manifest: &Manifest) -> io::Result<MarkovChain> {
+    let mut partial = self.partial();
+
+    for path in paths {
+      let started = Instant::now();
+      let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::InvalidData => {
+          partial.ingestion.skipped.push((path.clone(), "not utf-8 text".to_string()));
+          continue;
+        },
+        Err(error) => return Err(error),
+      };
+      partial.timings.ingestion += started.elapsed();
+
+      let name = relative_name(dir, path);
+      let frontmatter = frontmatter::split(&contents).and_then( |(fields, body)| match fields {
+        Some(fields) => Ok((Some(frontmatter::entry(&name, &fields, manifest.entry(&name))?), body)),
+        None => Ok((manifest.entry(&name).cloned(), body)),
+      });
+      let (entry, body) = match frontmatter {
+        Ok(split) => split,
+        Err(error) => {
+          partial.ingestion.skipped.push((path.clone(), format!("has frontmatter that doesn't parse: {}", error)));
+          continue;
+        },
+      };
+      for document in partial.split(body) {
+        let document = partial.clean(document, entry.as_ref().map_or(&[], |entry| &entry.tags));
+        partial.parse_weighted_document(&document, entry.as_ref().map_or(1.0, |entry| entry.weight), entry.as_ref().map_or(&[], |entry| &entry.tags));
+      }
+      if let Some(entry) = entry {
+        partial.provenance.files.push(entry);
+      }
+    }
+    return Ok(partial);
+  }
+
+  // the documents in a file: all of it, or what's between its separator lines. nothing but whitespace between two
+  // separators isn't a document
+  fn split<'a>(&self, contents: &'a str) -> Vec<&'a str> {
+    let Some(separator) = &self.document_separator else {
+      return vec!(contents);
     };
+    let mut documents = Vec::new();
+    let (mut start, mut end) = (0, 0);
+    for line in contents.split_inclusive('\n') {
+      if separator.is_match(line.trim()) {
+        documents.push(&contents[start..end]);
+        start = end + line.len();
+      }
+      end += line.len();
+    }
+    documents.push(&contents[start..]);
+    documents.retain( |document| !document.trim().is_empty() );
+    return documents;
   }
-}
 
-// we mostly care about fast lookups for adding new nodes / modifying edges for existing ones.
-// I might end up duplicating this to allow for faster random sampling, I think Rust is O(n) for randomly sampling
-// from a HashMap, but I only need to do that once for determining the first word in a tweet.
-struct Graph {
-  nodes: HashMap<String, Node>,
-  entry_words: Vec<String>, // storing capitalized words
-  rng: Box<dyn rand::RngCore>,
-}
+  // what every document goes through before it's trained on. tags are the document's in the manifest, if it has any
+  fn clean(&mut self, contents: &str, tags: &[String]) -> String {
+    let started = Instant::now();
+    // always, there's no reason to keep any of it and every reason not to
+    let (mut contents, sanitized) = sanitize::clean(contents);
+    self.ingestion.sanitized.add(sanitized);
 
-impl Graph {
-  fn generate_tweet(&mut self) -> String {
-    let mut words = vec!(self.random_entry_word());
+    if let Some(scrubber) = &self.scrubber {
+      let (scrubbed, counts) = scrubber.scrub(&contents);
+      for (rule, count) in counts {
+        *self.ingestion.scrubbed.entry(rule.name().to_string()).or_insert(0) += count;
+      }
+      contents = scrubbed;
+    }
+    // after scrubbing, so phone numbers are gone before they can turn into words
+    contents = self.numbers.style(tags).apply(&contents);
+    // last, so the tokenizer sees the text the way it's going to be trained on
+    if let Some(tokenizer) = &self.tokenizer {
+      match tokenizer.tokenize(&contents) {
+        Ok(tokens) => contents = tokens.join(" "),
+        Err(_) => self.ingestion.untokenized += 1,
+      }
+    }
+    // each word on its own, so a pattern has to match inside one word to drop it
+    if let Some(blocklist) = &self.training_blocklist {
+      let mut dropped = 0;
+      let lines: Vec<String> = contents.split('\n').map( |line| {
+        let kept: Vec<&str> = line.split_whitespace().filter( |word| blocklist.allows(word) ).collect();
+        dropped += line.split_whitespace().count() - kept.len();
+        kept.join(" ")
+      }).collect();
+      contents = lines.join("\n");
+      self.ingestion.banned += dropped;
+    }
+    self.timings.ingestion += started.elapsed();
+    return contents;
+  }
 
-    let mut current_word = words.last().unwrap().to_string();
+  // attribution from the manifest. files it didn't mention aren't in here
+  pub fn provenance(&self) -> &Provenance {
+    return &self.provenance;
+  }
 
-    let re = Regex::new(".*[!|.|?]$").unwrap();
-    while !re.is_match(&current_word) {
-      // TODO: change the hashmap key to str instead of String; it doesn't need to be mutable
-      let last_node = self.nodes.get(&current_word.to_string()).unwrap();
+  pub fn ingestion_report(&self) -> IngestionReport {
+    let mut report = self.ingestion.clone();
+    report.unique_tokens = self.graph.nodes.len();
 
-      current_word = last_node.next(&mut self.rng);
-      words.push(current_word.clone());
-    }
+    let mut counts: Vec<(&Arc<str>, usize)> = self.graph.symbols.words.iter().zip(&self.graph.nodes).map( |(word, node)| (word, node.count) ).collect();
+    counts.sort_by( |a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)) );
+    report.top_tokens = counts.iter().take(ingest::TOP_TOKENS).map( |(word, count)| (word.to_string(), *count) ).collect();
 
-    return words.iter().map( |w| w.to_string() ).collect::<Vec<String>>().join(" ");
+    return report;
   }
 
-  fn random_entry_word(&mut self) -> String {
-    let word = self.entry_words.choose(&mut self.rng).unwrap();
-   
-    return word.to_string();
+  fn parse_document(&mut self, contents: &str) {
+    self.parse_weighted_document(contents, 1.0, &[]);
   }
 
-  fn add(&mut self, word: String, last_word: Option<String>) -> () {
-    let uppercase = Regex::new(r"\A[A-Z]\w*").unwrap();
+  // counts a document's transitions on their own first, then scales them down if the document goes over the cap.
+  // the manifest weight is applied on top of that, so a weight of 2 counts the file twice. the document's states
+  // get its tags, with whatever the taxonomy finds in it
+  fn parse_weighted_document(&mut self, contents: &str, multiplier: f64, tags: &[String]) {
+    let mut transitions: HashMap<(u32, u32), i32> = HashMap::new();
+    let mut total = 0;
+    let mut last_state: Option<(&str, u32)> = None;
+
+    self.ingestion.documents += 1;
+    let language = language::detect(contents).unwrap_or("unknown");
+    *self.ingestion.languages.entry(language.to_string()).or_insert(0) += 1;
 
-    if !self.nodes.contains_key(&word) {
-      self.nodes.insert(word.clone(), Node::new());
+    let started = Instant::now();
+    let tokens = self.graph.tokens(contents);
+    self.ingestion.words += tokens.len();
+    if let Some(verbatim) = &mut self.verbatim {
+      verbatim.record(tokens.iter().copied().filter( |token| !is_break(token) ));
+    }
+    let mut tags = tags.to_vec();
+    for tag in self.taxonomy.tag(tokens.iter().copied()) {
+      if !tags.contains(&tag) {
+        tags.push(tag);
+      }
+    }
+    for tag in &tags {
+      *self.ingestion.tags.entry(tag.clone()).or_insert(0) += 1;
+    }
+    let tags = self.graph.tag_mask(&tags);
+    // the capital on "The" is only worth anything while it's still there, folding goes after
+    if self.graph.folded {
+      for (i, token) in tokens.iter().enumerate() {
+        if !is_break(token) && !self.graph.starts_sentence(token, i.checked_sub(1).map( |i| tokens[i] )) {
+          self.graph.casing.observe(&self.graph.locale.fold(token), token);
+        }
+      }
+    }
+    let states = self.graph.states(&tokens);
+    let tokenized = Instant::now();
+    self.timings.tokenization += tokenized - started;
 
-      if uppercase.is_match(word.as_str()) {
-        self.entry_words.push(word.clone());
+    for state in &states {
+      let starts = self.graph.starts_sentence(state, last_state.map( |(last_state, _)| last_state ));
+      let id = match self.graph.folded {
+        true => self.graph.add(&self.graph.locale.fold(state), starts, tags),
+        false => self.graph.add(state, starts, tags),
+      };
+
+      if let Some((_, last_id)) = last_state {
+        *transitions.entry((last_id, id)).or_insert(0) += 1;
+        total += 1;
       }
+      last_state = Some((state, id));
     }
 
-    if let Some(last_word) = last_word {
-      let last_node = self.nodes.get_mut(&last_word).unwrap();
-      last_node.strengthen_edge(word);
+    for ((from, to), count) in transitions {
+      let weight = match self.document_cap {
+        // every edge keeps at least a weight of 1 so we don't lose transitions entirely
+        Some(cap) if total > cap => cmp::max(1, count * cap / total),
+        _ => count,
+      };
+      let weight = cmp::max(1, (weight as f64 * multiplier).round() as i32);
+      self.graph.connect(from, to, weight);
     }
+    self.timings.graph_build += tokenized.elapsed();
   }
 
-  pub fn new() -> Graph {
-    return Graph {
-      nodes: HashMap::new(),
-      entry_words: Vec::new(),
-      rng: Box::new(rand::thread_rng()),
+  pub fn memory_footprint(&self) -> MemoryFootprint {
+    let graph = &self.graph;
+    let csr = &graph.csr;
+
+    // each token's bytes live once behind its Arc, next to the strong and weak counts, plus the table both ways
+    let words = graph.symbols.words.iter().map( |word| word.len() + 2 * mem::size_of::<usize>() ).sum::<usize>();
+    let interner = words + hashmap_bytes(&graph.symbols.ids) + vec_bytes(&graph.symbols.words);
+
+    return MemoryFootprint {
+      nodes: vec_bytes(&graph.nodes),
+      edges: graph.nodes.iter().map( |node| hashmap_bytes(&node.edges) + hashmap_bytes(&node.documents) ).sum(),
+      interner,
+      entry_words: vec_bytes(&graph.entry_words) + vec_bytes(&csr.entry_words) + vec_bytes(&csr.entry_tags),
+      csr: vec_bytes(&csr.words) + vec_bytes(&csr.offsets) + vec_bytes(&csr.targets) + vec_bytes(&csr.cumulative_weights)
+        + vec_bytes(&csr.cutoffs) + vec_bytes(&csr.aliases) + vec_bytes(&csr.tags),
     };
   }
-}
 
-// we need to store a weighted index (the 'strength' of an edge) for probabilistic sampling
-struct Node {
-  // can we have it store a reference to the next node? Would be way nicer than having the graph need to reach in for this ("tell, don't ask")
-  edges: HashMap<String, i32>,
-  sum: i32,
-}
+  // sha-256 over what generation actually reads, so two chains with the same fingerprint tweet the same things
+  // (given the same rng). only meaningful once trained
+  pub fn fingerprint(&self) -> String {
+    let csr = &self.graph.csr;
+    let mut hasher = Sha256::new();
 
-impl Node {
-  // randomly picks from weighted edges
-  // there's actually a way to do weighted randomization with rand::distributions::WeightedIndex, might want to use that instead
-  fn next(&self, rng: &mut Box<dyn rand::RngCore>) -> String {
-    let mut number = rng.gen_range(1..=self.sum);
+    for word in &csr.words {
+      hasher.update(&(word.len() as u64).to_le_bytes());
+      hasher.update(word.as_bytes());
+    }
+    for offset in &csr.offsets {
+      hasher.update(&(*offset as u64).to_le_bytes());
+    }
+    for target in &csr.targets {
+      hasher.update(&target.to_le_bytes());
+    }
+    for weight in &csr.cumulative_weights {
+      hasher.update(&weight.to_le_bytes());
+    }
+    for entry in &csr.entry_words {
+      hasher.update(&entry.to_le_bytes());
+    }
+    // with_about reads them. an untagged corpus keeps the fingerprint it had before there were tags
+    if !self.graph.tags.is_empty() {
+      for tag in &self.graph.tags {
+        hasher.update(&(tag.len() as u64).to_le_bytes());
+        hasher.update(tag.as_bytes());
+      }
+      for tags in csr.tags.iter().chain(&csr.entry_tags) {
+        hasher.update(&tags.to_le_bytes());
+      }
+    }
+    // and so does Cased
+    if self.graph.folded {
+      hasher.update(&[1]);
+      for (folded, written) in self.graph.casing.preferred() {
+        for word in [folded, written] {
+          hasher.update(&(word.len() as u64).to_le_bytes());
+          hasher.update(word.as_bytes());
+        }
+      }
+    }
 
-    for (word, weight) in &self.edges {
-      number -= weight;
+    return sha256::hex(&hasher.finish());
+  }
+
+  // writes the trained graph and its provenance out, so the next run can skip training. see model.rs for the format,
+  // and storage::open for the paths that are a database instead of a file
+  pub fn save(&self, path: &Path) -> Result<(), ErowidCoinError> {
+    return self.save_to(storage::open(path, storage::MODEL).as_ref());
+  }
+
+  pub fn save_to(&self, storage: &dyn Storage) -> Result<(), ErowidCoinError> {
+    let mut out = Writer::default();
+    out.bytes.extend_from_slice(model::MAGIC);
+    out.u32(model::VERSION);
+    self.graph.write(&mut out);
+    model::write_provenance(&mut out, &self.provenance);
+    write_verbatim(&mut out, self.verbatim.as_ref());
+    self.graph.write_tags(&mut out);
+    out.u8(self.graph.folded as u8);
+    self.graph.casing.write(&mut out);
+    return storage.write(&out.bytes).map_err(ErowidCoinError::Model);
+  }
+
+  // swaps the graph for a saved one. order, locale, poetry mode, case folding and suppression come from the file
+  // since the graph was built with them, and so does the verbatim guard if it was trained with one. the rng, filters
+  // and styles stay whatever this chain already had. a file from an older version is kept as <file>.v<n>.bak (or as
+  // model.v<n>.bak, in a database) and written again in this one, so the upgrade only happens the once
+  pub fn with_model(mut self, path: &Path) -> Result<MarkovChain, ErowidCoinError> {
+    let started = Instant::now();
+    let storage = storage::open(path, storage::MODEL);
+    let bytes = storage.read().map_err(ErowidCoinError::Model)?
+      .ok_or_else( || ErowidCoinError::Model(io::Error::new(io::ErrorKind::NotFound, format!("there's no model in {}", storage.describe()))) )?;
+    let version = Reader::new(&bytes).header().map_err(ErowidCoinError::Model)?;
+    let mut reader = Reader::new(&bytes);
+    let (mut graph, provenance, verbatim) = read_model(&mut reader).map_err(ErowidCoinError::Model)?;
+    self.verbatim = verbatim.or(self.verbatim.take());
+    graph.abbreviations = std::mem::take(&mut self.graph.abbreviations);
+    graph.joiner = self.graph.joiner.clone();
+    graph.sampling = self.graph.sampling;
+    self.graph = graph;
+    self.provenance = provenance;
+    self.timings.ingestion += started.elapsed();
+    self.finalize();
+
+    if version < model::VERSION {
+      let backup = match storage::is_database(path) {
+        true => storage::open(path, &format!("{}.v{}.bak", storage::MODEL, version)),
+        false => {
+          let mut backup = path.as_os_str().to_owned();
+          backup.push(format!(".v{}.bak", version));
+          storage::open(Path::new(&backup), storage::MODEL)
+        },
+      };
+      backup.write(&bytes).map_err(ErowidCoinError::Model)?;
+      self.save_to(storage.as_ref())?;
+    }
+    return Ok(self);
+  }
+
+  pub fn load(path: &Path) -> Result<MarkovChain, ErowidCoinError> {
+    return MarkovChain::new().with_model(path);
+  }
 
-      if number <= 0 {
-        return word.to_string();
+  // the training graph as n-grams, for sharing a model without the corpus (see stats.rs). only the transitions that
+  // clear the thresholds go in, the way suppression would leave them out, and only the entry states that still go
+  // somewhere. the provenance keeps its paths, licenses and sources, the tags and frontmatter stay here
+  pub fn corpus_stats(&self, threshold: Suppression) -> CorpusStats {
+    let graph = &self.graph;
+    let mut ngrams = Vec::new();
+    let mut kept = vec!(false; graph.nodes.len());
+    for (id, node) in graph.nodes.iter().enumerate() {
+      for (next, weight) in &node.edges {
+        let documents = node.documents[next];
+        if *weight < threshold.min_count || documents < threshold.min_documents {
+          continue;
+        }
+        let mut words: Vec<String> = graph.symbols.word(id as u32).split(' ').map(String::from).collect();
+        words.push(last_word(graph.symbols.word(*next)).to_string());
+        ngrams.push(Ngram { words, weight: *weight, documents });
+        kept[id] = true;
       }
     }
+    // so the same training writes the same file
+    ngrams.sort_by( |a, b| a.words.cmp(&b.words) );
 
-    panic!("the edge weights do not match the sum");
+    let files = self.provenance.files.iter().map( |file| FileEntry {
+      path: file.path.clone(),
+      tags: Vec::new(),
+      weight: 1.0,
+      license: file.license.clone(),
+      source: file.source.clone(),
+      metadata: Default::default(),
+    }).collect();
+    return CorpusStats {
+      order: graph.order,
+      locale: graph.locale,
+      poetry: graph.line_breaks,
+      folded: graph.folded,
+      provenance: Provenance { description: self.provenance.description.clone(), license: self.provenance.license.clone(), files },
+      entries: graph.entry_words.iter().filter( |&&id| kept[id as usize] ).map( |&id| graph.symbols.word(id).to_string() ).collect(),
+      ngrams,
+      casing: graph.casing.preferred().into_iter().map( |(folded, written)| (folded.to_string(), written.to_string()) ).collect(),
+    };
   }
 
-  // edges are node -> weight
-  fn strengthen_edge(&mut self, next: String) -> () {
-    let weight = self.edges.entry(next.clone()).or_insert(0);
-    *weight += 1;
-    self.sum += 1;
+  // swaps the graph for one rebuilt from corpus_stats. order, locale, poetry mode and case folding come from the
+  // stats, the rest stays this chain's, like with_model. the stats don't say how often each state came up, it gets
+  // the weight going into it or out of it, whichever is more
+  pub fn with_corpus_stats(mut self, stats: &CorpusStats) -> MarkovChain {
+    let started = Instant::now();
+    let mut graph = self.graph.empty_like();
+    graph.order = stats.order;
+    graph.locale = stats.locale;
+    graph.line_breaks = stats.poetry;
+    graph.folded = stats.folded;
+    let intern = |graph: &mut Graph, state: &str| {
+      let (id, new) = graph.symbols.intern(state);
+      if new {
+        graph.nodes.push(Node::new());
+      }
+      id
+    };
+
+    for ngram in &stats.ngrams {
+      let from = intern(&mut graph, &ngram.words[..stats.order].join(" "));
+      let to = intern(&mut graph, &ngram.words[1..].join(" "));
+      let node = &mut graph.nodes[from as usize];
+      *node.edges.entry(to).or_insert(0) += ngram.weight;
+      *node.documents.entry(to).or_insert(0) += ngram.documents;
+      node.sum += ngram.weight;
+    }
+    let mut incoming = vec!(0; graph.nodes.len());
+    for node in &graph.nodes {
+      for (next, weight) in &node.edges {
+        incoming[*next as usize] += *weight as usize;
+      }
+    }
+    for (node, incoming) in graph.nodes.iter_mut().zip(incoming) {
+      node.count = cmp::max(incoming, node.sum as usize);
+    }
+    for entry in &stats.entries {
+      let id = intern(&mut graph, entry);
+      let node = &mut graph.nodes[id as usize];
+      if !node.entry {
+        node.entry = true;
+        graph.entry_words.push(id);
+      }
+    }
+    for (folded, written) in &stats.casing {
+      graph.casing.prefer(folded.clone(), written.clone());
+    }
+
+    self.graph = graph;
+    self.provenance = stats.provenance.clone();
+    self.timings.ingestion += started.elapsed();
+    self.finalize();
+    return self;
   }
 
-  pub fn new() -> Node {
-    return Node {
-      edges: HashMap::new(),
-      sum: 0,
+  // every tweet that comes out of generate_tweet has passed all of these
+  pub fn filter_names(&self) -> Vec<String> {
+    return self.checks().map( |filter| filter.name().to_string() ).collect();
+  }
+
+  // the filters, the verbatim guard and the history
+  fn checks(&self) -> impl Iterator<Item = &dyn Filter> {
+    return self.filters.iter().map( |filter| filter.as_ref() )
+      .chain(self.verbatim.as_ref().map( |verbatim| verbatim as &dyn Filter ))
+      .chain(self.history.as_deref().map( |history| history as &dyn Filter ));
+  }
+
+  pub fn filter_stats(&self) -> &FilterStats {
+    return &self.filter_stats;
+  }
+
+  // how long training took, phase by phase, and how long tweets are taking
+  pub fn timings(&self) -> &Timings {
+    return &self.timings;
+  }
+
+  // for checks that happen outside the chain's own filters, so they show up in the same totals
+  pub fn record_rejection(&mut self, reason: &str) {
+    self.filter_stats.reject(reason);
+  }
+
+  pub fn node_count(&self) -> usize {
+    return self.graph.nodes.len();
+  }
+
+  pub fn edge_count(&self) -> usize {
+    return self.graph.nodes.iter().map( |node| node.edges.len() ).sum();
+  }
+
+  // how surprised the chain is by held-out text (lower is better)
+  pub fn perplexity(&self, dir: &Path) -> io::Result<f64> {
+    let mut log_probability = 0.0;
+    let mut transitions = 0;
+
+    for contents in read_documents(dir)? {
+      let tokens = self.graph.tokens(&contents);
+      let states = self.graph.states(&tokens);
+
+      for pair in states.windows(2) {
+        log_probability += self.graph.probability(&pair[0], &pair[1]).ln();
+        transitions += 1;
+      }
     }
+
+    if transitions == 0 {
+      return Ok(f64::INFINITY);
+    }
+    return Ok((-log_probability / transitions as f64).exp());
   }
-}
 
-#[cfg(test)]
-mod tests {
-  use super::*;
+  pub fn with_rng_source(mut self, source: RngSource) -> MarkovChain {
+    self.rng = ChainRng::new(source);
+    return self;
+  }
 
-  #[test]
-  fn create_a_tweet() {
-    let test_path: &Path = Path::new("./txt");
-    let mut mchain = MarkovChain::new();
+  pub fn with_seed(self, seed: u64) -> MarkovChain {
+    return self.with_rng_source(RngSource::Seeded(seed));
+  }
+
+  // any rng at all, for tests that want to script the picks or somebody who'd rather use their own generator.
+  // seed it yourself to get the same tweets back, with_seed(n) is with_rng(ChaCha8Rng::seed_from_u64(n))
+  pub fn with_rng(mut self, rng: impl RngCore + Send + Sync + 'static) -> MarkovChain {
+    self.rng = ChainRng::Custom(Box::new(rng));
+    return self;
+  }
+
+  // has to come before training, it decides which words are entry words
+  pub fn with_locale(mut self, locale: Locale) -> MarkovChain {
+    self.graph.locale = locale;
+    return self;
+  }
+
+  // "The" and "the" become one node instead of splitting their transitions between them. a sentence starts after
+  // one ends rather than at any capital, so "Bitcoin" halfway through one isn't an entry word, and it keeps its
+  // capital in the tweets because that's how the middles of sentences mostly had it. has to come before training
+  pub fn with_case_folding(mut self) -> MarkovChain {
+    self.graph.folded = true;
+    return self;
+  }
+
+  pub fn locale(&self) -> Locale {
+    return self.graph.locale;
+  }
+
+  // words that a full stop doesn't end a sentence after, on top of the locale's ("mg", "St." and "approx." are
+  // already there). generation won't stop on one, and the word after one isn't an entry word. a loaded model keeps
+  // the entry words it was trained with
+  pub fn with_abbreviation(mut self, abbreviation: &str) -> MarkovChain {
+    let abbreviation = self.graph.locale.fold(abbreviation.trim().trim_end_matches('.'));
+    if !abbreviation.is_empty() && !self.graph.abbreviations.contains(&abbreviation) {
+      self.graph.abbreviations.push(abbreviation);
+    }
+    return self;
+  }
+
+  // how generated words get put back together, Detokenizer unless told otherwise. detokenize::Spaces is a plain
+  // space between every two
+  pub fn with_joiner(mut self, joiner: impl Joiner + 'static) -> MarkovChain {
+    self.graph.joiner = Arc::new(joiner);
+    return self;
+  }
+
+  // how many words make up the state the next word is picked from. 2 reads a lot better than 1, 3 starts quoting
+  // the corpus back unless it's big. has to come before training, and ZeroOrder for 0
+  pub fn with_order(mut self, order: usize) -> Result<MarkovChain, ErowidCoinError> {
+    if order == 0 {
+      return Err(ErowidCoinError::ZeroOrder);
+    }
+    self.graph.order = order;
+    return Ok(self);
+  }
+
+  pub fn order(&self) -> usize {
+    return self.graph.order;
+  }
+
+  // poetry mode: line breaks are learned and reproduced, and a tweet ends at the end of a stanza instead of at a
+  // full stop. has to come before training
+  pub fn with_line_breaks(mut self) -> MarkovChain {
+    self.graph.line_breaks = true;
+    return self;
+  }
+
+  pub fn with_document_cap(mut self, cap: i32) -> MarkovChain {
+    self.document_cap = Some(cap);
+    return self;
+  }
+
+  // every line (trimmed) that matches the whole pattern ends one document and starts the next, like "-{5,}" for
+  // reports split up by -----. the separator lines themselves aren't trained on
+  pub fn with_document_separator(mut self, pattern: &str) -> Result<MarkovChain, regex::Error> {
+    self.document_separator = Some(Regex::new(&format!("^(?:{})$", pattern))?);
+    return Ok(self);
+  }
+
+  // what tags documents by their words, Taxonomy::default unless it's set
+  pub fn with_taxonomy(mut self, taxonomy: Taxonomy) -> MarkovChain {
+    self.taxonomy = taxonomy;
+    return self;
+  }
+
+  // tweets start on words from documents tagged `tag` (by the manifest, frontmatter or taxonomy), and lean towards
+  // the words those documents use. call it more than once for documents with any of the tags
+  pub fn with_about(mut self, tag: &str) -> MarkovChain {
+    self.about.push(tag.to_lowercase());
+    self.graph.about = self.graph.about_mask(&self.about);
+    return self;
+  }
+
+  pub fn with_suppression(mut self, suppression: Suppression) -> MarkovChain {
+    self.graph.suppression = suppression;
+    return self;
+  }
+
+  // see Sampling. a trained chain gets its csr rebuilt, so it goes for the next tweet
+  pub fn with_sampling(mut self, sampling: Sampling) -> MarkovChain {
+    self.set_sampling(sampling);
+    return self;
+  }
+
+  // the daemon's themes change it per post. nothing gets rebuilt if it's the same as before
+  pub fn set_sampling(&mut self, sampling: Sampling) {
+    if sampling == self.graph.sampling {
+      return;
+    }
+    self.graph.sampling = sampling;
+    if !self.graph.nodes.is_empty() {
+      self.finalize();
+    }
+  }
+
+  pub fn sampling(&self) -> Sampling {
+    return self.graph.sampling;
+  }
+
+  // tweets lean towards these words the way --about leans towards its documents' words. the daemon's themes set
+  // them per post, an empty list is no boost
+  pub fn set_boost(&mut self, words: Vec<String>) {
+    self.boost = words.iter().map( |word| flavor::key(word) ).filter( |key| !key.is_empty() ).collect();
+    self.graph.boosted = self.graph.boosted(&self.boost);
+  }
+
+  // what happens to numbers in documents without a tag that says otherwise. has to come before training
+  pub fn with_numbers(mut self, style: NumberStyle) -> MarkovChain {
+    self.numbers.default = style;
+    return self;
+  }
+
+  // numbers in files the manifest gives this tag. a file with more than one of them goes by the first one set
+  pub fn with_tag_numbers(mut self, tag: &str, style: NumberStyle) -> MarkovChain {
+    self.numbers.tags.push((tag.to_string(), style));
+    return self;
+  }
+
+  pub fn with_scrubber(mut self, scrubber: Scrubber) -> MarkovChain {
+    self.scrubber = Some(scrubber);
+    return self;
+  }
+
+  // documents get split into words by this instead of at their whitespace. has to come before training. a document
+  // it fails on is trained on as it is, and counted in the ingestion report
+  pub fn with_tokenizer(mut self, tokenizer: impl Tokenizer + 'static) -> MarkovChain {
+    self.tokenizer = Some(Arc::new(tokenizer));
+    return self;
+  }
+
+  // the words a blocklist would throw a tweet out for get dropped from every document instead, so the chain never
+  // learns them and there's nothing to regenerate. the words either side end up next to each other. has to come
+  // before training, and it's no substitute for the same list with_filter, which also catches what the walk puts
+  // together
+  pub fn with_training_blocklist(mut self, blocklist: BannedWords) -> MarkovChain {
+    self.training_blocklist = Some(blocklist);
+    return self;
+  }
+
+  pub fn with_styles(mut self, styles: Vec<Style>) -> MarkovChain {
+    self.styles = styles;
+    return self;
+  }
+
+  // the daemon can pick different styles for every post
+  pub fn set_styles(&mut self, styles: Vec<Style>) {
+    self.styles = styles;
+  }
+
+  // tweets can't repeat more than `max` words in a row from the training text. has to come before training, every
+  // run of max + 1 words gets recorded while it goes. a guard saved with a model comes back with it when it's loaded
+  pub fn with_max_overlap(mut self, max: usize) -> MarkovChain {
+    self.verbatim = Some(Verbatim::new(max));
+    return self;
+  }
+
+  // nothing the same as (or as close as history.max_similarity to) anything in the history comes out again, and
+  // everything that does come out goes into it
+  pub fn with_history(mut self, history: Arc<History>) -> MarkovChain {
+    self.history = Some(history);
+    return self;
+  }
+
+  pub fn with_filter(mut self, filter: impl Filter + 'static) -> MarkovChain {
+    self.filters.push(Box::new(filter));
+    return self;
+  }
+
+  // tweets stay inside this many chars. the walk steers towards the end of a sentence as it gets close and cuts
+  // back to the last one that fit if it can't get there, and a length filter makes sure of the rest
+  pub fn with_max_chars(mut self, max_chars: usize) -> MarkovChain {
+    self.max_chars = Some(max_chars);
+    return self.with_filter(MaxLength(max_chars));
+  }
+
+  // tweets of at least (or at most) this many words, as they come out. anything else is thrown away and tried again,
+  // max_attempts times in all
+  pub fn with_min_words(self, min_words: usize) -> MarkovChain {
+    return self.with_filter(MinWords(min_words));
+  }
+
+  pub fn with_max_words(self, max_words: usize) -> MarkovChain {
+    return self.with_filter(MaxWords(max_words));
+  }
+
+  // for somewhere without a tweet's limits (mastodon, discord): posts go on for as many sentences as fit in
+  // max_chars, broken up into paragraphs. stanzas in poetry mode
+  pub fn with_long_form(mut self, max_chars: usize) -> MarkovChain {
+    self.long_form = true;
+    return self.with_max_chars(max_chars);
+  }
+
+  // how many candidates we'll throw away for a single tweet before giving up
+  pub fn with_max_attempts(mut self, attempts: usize) -> MarkovChain {
+    self.max_attempts = attempts;
+    return self;
+  }
+
+  // one filtered tweet from an already trained chain
+  pub fn generate_tweet(&mut self) -> Result<String, ErowidCoinError> {
+    return self.generate_tweet_with(&mut Unobserved);
+  }
+
+  // same as generate_tweet, but the observer hears about every word as it's picked
+  pub fn generate_tweet_with(&mut self, observer: &mut dyn Observer) -> Result<String, ErowidCoinError> {
+    let policy = self.retry_policy;
+    let (max_chars, dead_ends, long_form) = (self.max_chars, self.dead_end_policy, self.long_form);
+    let mut failures: HashMap<u32, usize> = HashMap::new();
+    let mut previous: Option<u32> = None;
+
+    return self.filtered( |graph, rng, observer, scratch| {
+      // getting called again means the last attempt's entry word didn't make it
+      if let Some(previous) = previous {
+        *failures.entry(previous).or_insert(0) += 1;
+      }
+      let start = graph.retry_entry_word(rng, policy, previous, &failures);
+      previous = Some(start);
+
+      match (long_form, max_chars) {
+        (true, Some(max_chars)) => graph.long_form(start, max_chars, dead_ends, rng, observer, scratch),
+        _ => graph.generate_tweet(start, max_chars, dead_ends, rng, observer, scratch),
+      }
+    }, observer);
+  }
+
+  pub fn generate_tweet_cancellable(&mut self, token: &CancellationToken) -> Result<String, ErowidCoinError> {
+    return self.generate_tweet_with(&mut token.clone());
+  }
+
+  // the first letters of the words spell out `word` (or of the lines, which are whole sentences then). the words are a
+  // depth first search through the graph that backs up out of dead ends, and gives up on a candidate after
+  // ACROSTIC_STEPS steps
+  pub fn generate_acrostic(&mut self, word: &str, by_line: bool) -> Result<String, ErowidCoinError> {
+    let acrostic = Acrostic::new(word, by_line);
+    let letters = acrostic.letters.clone();
+    self.filters.insert(0, Box::new(acrostic));
+    let tweet = self.filtered( |graph, rng, _, scratch| {
+      scratch.text = match by_line {
+        true => graph.acrostic_lines(&letters, rng),
+        false => graph.acrostic_words(&letters, rng).unwrap_or_default(),
+      };
+      true
+    }, &mut Unobserved );
+    self.filters.remove(0);
+    return tweet;
+  }
+
+  // 5/7/5, built a line at a time so a line that misses its count gets walked again instead of the whole poem.
+  // the usual filters still apply to the result, and so does a syllable check in case a line never worked out
+  pub fn generate_haiku(&mut self) -> Result<String, ErowidCoinError> {
+    self.filters.insert(0, Box::new(Syllables(HAIKU.to_vec())));
+    let haiku = self.filtered( |graph, rng, _, scratch| {
+      scratch.text = graph.haiku(rng, &HAIKU);
+      true
+    }, &mut Unobserved );
+    self.filters.remove(0);
+    return haiku;
+  }
+
+  pub fn with_dead_end_policy(mut self, policy: DeadEndPolicy) -> MarkovChain {
+    self.dead_end_policy = policy;
+    return self;
+  }
+
+  pub fn with_retry_policy(mut self, policy: RetryPolicy) -> MarkovChain {
+    self.retry_policy = policy;
+    return self;
+  }
+
+  // for text that was put together outside the chain, but is going out under its filters anyway
+  pub fn passes_filters(&self, text: &str) -> bool {
+    return self.checks().all( |filter| filter.allows(text) );
+  }
+
+  // keeps generating until a candidate gets through every filter. generate writes each candidate into the scratch
+  // text, and says false for one that ran out of room before it could end, that counts against the length filter
+  fn filtered<F>(&mut self, generate: F, observer: &mut dyn Observer) -> Result<String, ErowidCoinError>
+  where F: FnMut(&Graph, &mut ChainRng, &mut dyn Observer, &mut Scratch) -> bool {
+    if self.graph.csr.words.is_empty() {
+      return Err(ErowidCoinError::EmptyCorpus);
+    }
+    if !self.about.is_empty() && self.graph.about == 0 {
+      return Err(ErowidCoinError::UnknownTags(self.about.clone()));
+    }
+    let started = Instant::now();
+    let tweet = self.first_allowed(generate, observer);
+    self.timings.tweet(started.elapsed());
+    return tweet;
+  }
+
+  fn first_allowed<F>(&mut self, mut generate: F, observer: &mut dyn Observer) -> Result<String, ErowidCoinError>
+  where F: FnMut(&Graph, &mut ChainRng, &mut dyn Observer, &mut Scratch) -> bool {
+    let mut rejections: HashMap<String, usize> = HashMap::new();
+
+    for _ in 0..self.max_attempts {
+      let finished = generate(&self.graph, &mut self.rng, observer, &mut self.scratch);
+      // whatever we got was cut short, don't let it look like a real tweet
+      if observer.cancelled() {
+        return Err(ErowidCoinError::Cancelled);
+      }
+
+      self.filter_stats.candidates += 1;
+      if !finished {
+        let name = MaxLength(0).name().to_string();
+        *rejections.entry(name.clone()).or_insert(0) += 1;
+        self.filter_stats.reject(&name);
+        observer.on_rejected(&self.scratch.text, &name);
+        continue;
+      }
+      // the words the way the walk put them together, before tidying and the styles can change what they look like
+      let copied = self.verbatim.as_ref().filter( |verbatim| !verbatim.allows(&self.scratch.text) );
+      if let Some(tokenizer) = &self.tokenizer {
+        tokenizer.tidy(&mut self.scratch.text);
+      }
+      if !self.styles.is_empty() {
+        self.scratch.text = style::apply(&self.styles, &self.scratch.text);
+      }
+      let tweet = self.scratch.text.as_str();
+      let rejected = self.filters.iter().map( |filter| filter.as_ref() ).find( |filter| !filter.allows(tweet) )
+        .or(copied.map( |verbatim| verbatim as &dyn Filter ))
+        .or_else( || self.history.as_deref().map( |history| history as &dyn Filter ).filter( |history| !history.allows(tweet) ) );
+      match rejected {
+        Some(filter) => {
+          *rejections.entry(filter.name().to_string()).or_insert(0) += 1;
+          self.filter_stats.reject(filter.name());
+          observer.on_rejected(tweet, filter.name());
+        },
+        None => {
+          if let Some(history) = &self.history {
+            history.record(tweet).map_err(ErowidCoinError::History)?;
+          }
+          observer.on_accepted(tweet);
+          // the one allocation, the buffer stays here for the next tweet
+          return Ok(tweet.to_string());
+        },
+      }
+    }
+
+    return Err(ErowidCoinError::GaveUp(GaveUp::new(self.max_attempts, rejections)));
+  }
+
+  pub fn builder() -> MarkovChainBuilder {
+    return MarkovChainBuilder { mchain: MarkovChain::new(), order: None };
+  }
+
+  // only files matching one of these get read from a corpus directory, see glob.rs. call it more than once for
+  // more patterns
+  pub fn with_include(mut self, pattern: &str) -> MarkovChain {
+    self.patterns.include.push(pattern.to_string());
+    return self;
+  }
+
+  // reading a corpus directory uses a thread per core unless told otherwise. the result's the same either way
+  pub fn with_threads(mut self, threads: usize) -> MarkovChain {
+    self.threads = Some(threads.max(1));
+    return self;
+  }
+
+  // files matching any of these are left out, even if an include matched them
+  pub fn with_exclude(mut self, pattern: &str) -> MarkovChain {
+    self.patterns.exclude.push(pattern.to_string());
+    return self;
+  }
+
+  // queues up more to train on, nothing's read until train
+  pub fn add_corpus(&mut self, corpus: Corpus) {
+    self.corpora.push(corpus);
+  }
+
+  // reads everything that's been queued up. calling it again later trains on whatever got added since, on top of
+  // what's already there
+  pub fn train(&mut self) -> Result<(), ErowidCoinError> {
+    let corpora = mem::take(&mut self.corpora);
+    // the weighted ones all go in together, first
+    let blend: Vec<(PathBuf, f64)> = corpora.iter().filter_map( |corpus| match corpus {
+      Corpus::Weighted(dir, weight) => Some((dir.clone(), *weight)),
+      _ => None,
+    }).collect();
+    if !blend.is_empty() {
+      self.blend_in(&blend).map_err(ErowidCoinError::Corpus)?;
+    }
+
+    for corpus in corpora {
+      match corpus {
+        Corpus::Weighted(..) => {},
+        Corpus::Dir(dir) => self.parse_in(&dir).map_err(ErowidCoinError::Corpus)?,
+        Corpus::Text(text) => self.train_split(&text),
+      }
+    }
+    self.finalize();
+    return Ok(());
+  }
+
+  // trains on documents from anywhere (database rows, api pages..) without them having to be files first. each one
+  // is its own document the way a file would be: nothing carries over from the end of one to the start of the
+  // next, and the document cap applies to each. whatever add_corpus queued up isn't touched, that still waits for
+  // train
+  pub fn train_documents(&mut self, documents: impl IntoIterator<Item = impl AsRef<str>>) {
+    for document in documents {
+      self.train_split(document.as_ref());
+    }
+    self.finalize();
+  }
+
+  // text that didn't come from a file, split up the way a file would be
+  fn train_split(&mut self, text: &str) {
+    for document in self.split(text) {
+      let contents = self.clean(document, &[]);
+      self.parse_document(&contents);
+    }
+  }
+
+  fn finalize(&mut self) {
+    let started = Instant::now();
+    self.graph.finalize();
+    self.graph.about = self.graph.about_mask(&self.about);
+    self.graph.boosted = self.graph.boosted(&self.boost);
+    self.timings.finalize += started.elapsed();
+  }
+
+  // a tweet from the trained chain, through the filters. generate_tweet plus a check that there's a trained chain to
+  // generate from, which the binary never needed
+  pub fn generate(&mut self) -> Result<String, ErowidCoinError> {
+    if self.graph.csr.words.is_empty() && self.ingestion.documents == 0 {
+      return Err(ErowidCoinError::Untrained);
+    }
+    return self.generate_tweet();
+  }
+
+  // trains on dir first, unless the chain's already trained (by an earlier call, or from a model). then dir is
+  // ignored, training again would count every transition twice
+  pub fn create_tweets(&mut self, dir: &Path, number: i32) -> Result<Vec<String>, ErowidCoinError> {
+    self.train_once(dir)?;
+
+    let mut vec = Vec::new();
+
+    for _ in 0..number {
+      vec.push(self.generate_tweet()?);
+    }
+
+    return Ok(vec);
+  }
+
+  fn train_once(&mut self, dir: &Path) -> Result<(), ErowidCoinError> {
+    if self.graph.csr.words.is_empty() && self.ingestion.documents == 0 {
+      self.add_corpus(Corpus::Dir(dir.to_path_buf()));
+      self.train()?;
+    }
+    return Ok(());
+  }
+
+  // the pieces the interactive mode drives one word at a time. these all expect the chain to already be trained
+  pub fn random_entry_word(&mut self) -> Result<String, ErowidCoinError> {
+    if self.graph.csr.words.is_empty() {
+      return Err(ErowidCoinError::EmptyCorpus);
+    }
+    return Ok(self.graph.csr.words[self.graph.random_entry_word(&mut self.rng) as usize].to_string());
+  }
+
+  // every run of n words the training graph saw, with how often, most common first. a transition is order + 1
+  // words, so that's as long as n goes (anything longer comes back empty). shorter ones are counted off the front of
+  // the transitions, which leaves out the last few words of each document. counts are edge weights: after document
+  // caps, manifest weights and blending, before suppression
+  pub fn ngrams(&self, n: usize) -> Vec<(String, u64)> {
+    let graph = &self.graph;
+    if n == 0 || n > graph.order + 1 {
+      return Vec::new();
+    }
+
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    for (id, node) in graph.nodes.iter().enumerate() {
+      let from: Vec<&str> = graph.symbols.word(id as u32).split(' ').collect();
+      for (next, weight) in &node.edges {
+        let mut words = from.clone();
+        words.push(last_word(graph.symbols.word(*next)));
+        *counts.entry(words[..n].join(" ")).or_insert(0) += *weight as u64;
+      }
+    }
+
+    let mut counts: Vec<(String, u64)> = counts.into_iter().collect();
+    counts.sort_by( |a, b| b.1.cmp(&a.1).then_with( || a.0.cmp(&b.0) ) );
+    return counts;
+  }
+
+  // the k most likely next words after `text` with their probabilities. only the last `order` words of it matter
+  pub fn candidates(&self, text: &str, k: usize) -> Vec<(String, f64)> {
+    let csr = &self.graph.csr;
+    return match csr.id(&self.graph.state_at_end(text)) {
+      Some(id) => csr.candidates(id, k).into_iter().map( |(next, p)| (last_word(&csr.words[next as usize]).to_string(), p) ).collect(),
+      None => Vec::new(),
+    };
+  }
+
+  pub fn sample_next(&mut self, text: &str) -> Option<String> {
+    let csr = &self.graph.csr;
+    let next = csr.next(csr.id(&self.graph.state_at_end(text))?, &mut self.rng)?;
+    return Some(last_word(&csr.words[next as usize]).to_string());
+  }
+
+  // a fresh tweet, word by word. filters need the whole tweet so they can't run on this
+  pub fn stream_tweet(&mut self) -> Result<impl Iterator<Item = &str> + '_, ErowidCoinError> {
+    if self.graph.csr.words.is_empty() {
+      return Err(ErowidCoinError::EmptyCorpus);
+    }
+    let start = self.graph.random_entry_word(&mut self.rng);
+    let graph = &self.graph;
+    let mut walk = Walk::new(graph, start, MAX_WORDS, &mut self.rng);
+    walk.dead_ends = self.dead_end_policy;
+
+    return Ok(walk.enumerate()
+      .flat_map( |(i, id)| graph.csr.words[id as usize].split(' ').skip(if i == 0 { 0 } else { graph.order - 1 }) ));
+  }
+
+  // how likely the chain was to produce each word of a tweet, the words of the first state all getting the odds of
+  // picking it as the entry state. words the chain couldn't have produced get a 0
+  pub fn token_probabilities(&self, tweet: &str) -> Vec<(String, f64)> {
+    let csr = &self.graph.csr;
+    let tokens = self.graph.tokens(tweet);
+    let folded = self.graph.folding(tweet);
+    let ids: Vec<Option<u32>> = self.graph.states(&self.graph.tokens(&folded)).iter().map( |state| csr.id(state) ).collect();
+    let mut probabilities = Vec::new();
+
+    for (i, word) in tokens.iter().enumerate() {
+      // the state this word is the last word of
+      let probability = match i.saturating_sub(self.graph.order - 1) {
+        0 => ids.first().copied().flatten().map( |id| csr.entry_probability(id) ).unwrap_or(0.0),
+        state => match (ids[state - 1], ids[state]) {
+          (Some(last), Some(id)) => csr.transition_probability(last, id),
+          _ => 0.0,
+        },
+      };
+      probabilities.push((word.to_string(), probability));
+    }
+
+    return probabilities;
+  }
+
+  // same as create_tweets, but every tweet starts with the prefix and the chain picks up from its last words
+  pub fn continue_tweets(&mut self, dir: &Path, prefix: &str, number: i32) -> Result<Vec<String>, ErowidCoinError> {
+    self.train_once(dir)?;
+
+    let mut vec = Vec::new();
+
+    for _ in 0..number {
+      vec.push(self.continue_tweet(prefix)?);
+    }
+
+    return Ok(vec);
+  }
+
+  // one tweet starting with the prefix, from an already trained chain. see generate_from
+  pub fn continue_tweet(&mut self, prefix: &str) -> Result<String, ErowidCoinError> {
+    return self.generate_from(prefix);
+  }
+
+  // a tweet that starts with the prompt ("I bought"), through the filters and inside max_chars like any other. it
+  // carries on from the prompt's last state, or from one that starts with the prompt when it's shorter than a
+  // state. UnknownWord if the corpus never goes there
+  pub fn generate_from(&mut self, prompt: &str) -> Result<String, ErowidCoinError> {
+    // the prompt goes out as-is, so it gets the same treatment as the corpus
+    let (prompt, _) = sanitize::clean(prompt);
+    let starts = self.graph.prompt_starts(&prompt);
+    if starts.is_empty() {
+      return Err(ErowidCoinError::UnknownWord(self.graph.state_at_end(&prompt)));
+    }
+
+    let (max_chars, dead_ends) = (self.max_chars, self.dead_end_policy);
+    return self.filtered( |graph, rng, observer, scratch| {
+      let start = starts[rng.gen_range(0..starts.len())];
+      graph.continue_tweet(&prompt, start, max_chars, dead_ends, rng, observer, scratch)
+    }, &mut Unobserved );
+  }
+
+  // n tweets that read as one longer post, numbered "1/4", "2/4"... on the end. each one carries on from the state
+  // the one before it ended in, or starts fresh if the corpus never went anywhere from there. every tweet goes
+  // through the filters and fits in max_chars (or THREAD_CHARS) with its number
+  pub fn generate_thread(&mut self, n: usize) -> Result<Vec<String>, ErowidCoinError> {
+    let (limit, dead_ends) = (self.max_chars.unwrap_or(THREAD_CHARS), self.dead_end_policy);
+    let mut thread = Vec::new();
+    let mut tail: Option<u32> = None;
+
+    for i in 1..=n {
+      let number = format!("{}/{}", i, n);
+      let max_chars = Some(limit.saturating_sub(number.chars().count() + 1));
+      let tweet = self.filtered( |graph, rng, observer, scratch| {
+        let start = tail.and_then( |tail| graph.carry_on(tail, rng) ).unwrap_or_else( || graph.random_entry_word(rng) );
+        graph.generate_tweet(start, max_chars, dead_ends, rng, observer, scratch)
+      }, &mut Unobserved )?;
+      // the last candidate walked is the one that got through
+      tail = self.scratch.states.last().copied();
+      thread.push(format!("{} {}", tweet, number));
+    }
+    return Ok(thread);
+  }
+
+  pub fn new() -> MarkovChain {
+    return MarkovChain {
+      graph: Graph::new(),
+      rng: ChainRng::new(RngSource::Thread),
+      document_cap: None,
+      document_separator: None,
+      filters: Vec::new(),
+      verbatim: None,
+      history: None,
+      styles: Vec::new(),
+      max_attempts: DEFAULT_MAX_ATTEMPTS,
+      max_chars: None,
+      long_form: false,
+      retry_policy: RetryPolicy::RandomEntry,
+      dead_end_policy: DeadEndPolicy::default(),
+      filter_stats: FilterStats::default(),
+      timings: Timings::default(),
+      scratch: Scratch::default(),
+      ingestion: IngestionReport::default(),
+      sources: Vec::new(),
+      source_words: HashMap::new(),
+      scrubber: None,
+      tokenizer: None,
+      training_blocklist: None,
+      numbers: Numbers::default(),
+      taxonomy: Taxonomy::default(),
+      about: Vec::new(),
+      boost: Vec::new(),
+      patterns: Patterns::default(),
+      threads: None,
+      provenance: Provenance::default(),
+      corpora: Vec::new(),
+    };
+  }
+}
+
+impl Default for MarkovChain {
+  fn default() -> Self {
+    return MarkovChain::new();
+  }
+}
+
+// the with_ methods under the names you'd expect from the outside, plus where the corpus comes from. see lib.rs
+pub struct MarkovChainBuilder {
+  mchain: MarkovChain,
+  // checked by build, so the rest of the chain of calls doesn't have to unwrap
+  order: Option<usize>,
+}
+
+impl MarkovChainBuilder {
+  // a directory of text files, can be given more than once
+  pub fn corpus(mut self, dir: impl AsRef<Path>) -> MarkovChainBuilder {
+    self.mchain.add_corpus(Corpus::Dir(dir.as_ref().to_path_buf()));
+    return self;
+  }
+
+  // a directory blended with the other weighted ones, so each gets its weight's share of the say whatever its size
+  pub fn weighted_corpus(mut self, dir: impl AsRef<Path>, weight: f64) -> MarkovChainBuilder {
+    self.mchain.add_corpus(Corpus::Weighted(dir.as_ref().to_path_buf(), weight));
+    return self;
+  }
+
+  // one document that isn't on disk
+  pub fn text(mut self, text: &str) -> MarkovChainBuilder {
+    self.mchain.add_corpus(Corpus::Text(text.to_string()));
+    return self;
+  }
+
+  pub fn seed(mut self, seed: u64) -> MarkovChainBuilder {
+    self.mchain = self.mchain.with_seed(seed);
+    return self;
+  }
+
+  pub fn rng(mut self, source: RngSource) -> MarkovChainBuilder {
+    self.mchain = self.mchain.with_rng_source(source);
+    return self;
+  }
+
+  pub fn order(mut self, order: usize) -> MarkovChainBuilder {
+    self.order = Some(order);
+    return self;
+  }
+
+  pub fn locale(mut self, locale: Locale) -> MarkovChainBuilder {
+    self.mchain = self.mchain.with_locale(locale);
+    return self;
+  }
+
+  pub fn history(mut self, history: Arc<History>) -> MarkovChainBuilder {
+    self.mchain = self.mchain.with_history(history);
+    return self;
+  }
+
+  pub fn max_overlap(mut self, max: usize) -> MarkovChainBuilder {
+    self.mchain = self.mchain.with_max_overlap(max);
+    return self;
+  }
+
+  pub fn numbers(mut self, style: NumberStyle) -> MarkovChainBuilder {
+    self.mchain = self.mchain.with_numbers(style);
+    return self;
+  }
+
+  pub fn abbreviation(mut self, abbreviation: &str) -> MarkovChainBuilder {
+    self.mchain = self.mchain.with_abbreviation(abbreviation);
+    return self;
+  }
+
+  pub fn joiner(mut self, joiner: impl Joiner + 'static) -> MarkovChainBuilder {
+    self.mchain = self.mchain.with_joiner(joiner);
+    return self;
+  }
+
+  pub fn document_cap(mut self, cap: i32) -> MarkovChainBuilder {
+    self.mchain = self.mchain.with_document_cap(cap);
+    return self;
+  }
+
+  pub fn max_chars(mut self, max_chars: usize) -> MarkovChainBuilder {
+    self.mchain = self.mchain.with_max_chars(max_chars);
+    return self;
+  }
+
+  pub fn min_words(mut self, min_words: usize) -> MarkovChainBuilder {
+    self.mchain = self.mchain.with_min_words(min_words);
+    return self;
+  }
+
+  pub fn max_words(mut self, max_words: usize) -> MarkovChainBuilder {
+    self.mchain = self.mchain.with_max_words(max_words);
+    return self;
+  }
+
+  pub fn filter(mut self, filter: impl Filter + 'static) -> MarkovChainBuilder {
+    self.mchain = self.mchain.with_filter(filter);
+    return self;
+  }
+
+  pub fn max_attempts(mut self, attempts: usize) -> MarkovChainBuilder {
+    self.mchain = self.mchain.with_max_attempts(attempts);
+    return self;
+  }
+
+  pub fn sampling(mut self, sampling: Sampling) -> MarkovChainBuilder {
+    self.mchain = self.mchain.with_sampling(sampling);
+    return self;
+  }
+
+  pub fn dead_ends(mut self, policy: DeadEndPolicy) -> MarkovChainBuilder {
+    self.mchain = self.mchain.with_dead_end_policy(policy);
+    return self;
+  }
+
+  // untrained, call train on it. ZeroOrder if order(0) was asked for
+  pub fn build(self) -> Result<MarkovChain, ErowidCoinError> {
+    return match self.order {
+      Some(order) => self.mchain.with_order(order),
+      None => Ok(self.mchain),
+    };
+  }
+}
+
+// we mostly care about fast lookups for adding new nodes / modifying edges for existing ones.
+// I might end up duplicating this to allow for faster random sampling, I think Rust is O(n) for randomly sampling
+// from a HashMap, but I only need to do that once for determining the first word in a tweet.
+// every token is stored exactly once, in the symbol table. nodes, edges and entry words all go by its id
+struct Graph {
+  symbols: Symbols,
+  // indexed by symbol id, every symbol is a node
+  nodes: Vec<Node>,
+  entry_words: Vec<u32>, // storing words that start sentences
+  locale: Locale,
+  // lowercase and without the full stop, on top of the locale's own. see abbreviated
+  abbreviations: Vec<String>,
+  // poetry mode, see tokens
+  line_breaks: bool,
+  // how generated words go back into text
+  joiner: Arc<dyn Joiner>,
+  // states are stored folded, "The" and "the" are one node. casing has how the words were written, see Cased
+  folded: bool,
+  casing: Casing,
+  // words per state, see states
+  order: usize,
+  suppression: Suppression,
+  // how finalize weighs the edges for the csr. not saved with the model, it's the caller's
+  sampling: Sampling,
+  // tag names, bit i of a node's tags is tags[i]
+  tags: Vec<String>,
+  // generation only ever reads these, everything above is just for training. about is the tags with_about asked
+  // for, as bits
+  about: u64,
+  // by csr id, whether the state ends on one of the chain's boost words. empty without any
+  boosted: Vec<bool>,
+  csr: Csr,
+}
+
+// states <-> the u32s the training graph uses for them. ids are handed out in the order states are first seen,
+// finalize renumbers them for the csr
+#[derive(Default)]
+struct Symbols {
+  ids: HashMap<Arc<str>, u32>,
+  words: Vec<Arc<str>>,
+}
+
+impl Symbols {
+  // the word's id, and whether it's new
+  fn intern(&mut self, word: &str) -> (u32, bool) {
+    if let Some(&id) = self.ids.get(word) {
+      return (id, false);
+    }
+    let id = self.words.len() as u32;
+    let word: Arc<str> = Arc::from(word);
+    self.ids.insert(word.clone(), id);
+    self.words.push(word);
+    return (id, true);
+  }
+
+  fn id(&self, word: &str) -> Option<u32> {
+    return self.ids.get(word).copied();
+  }
+
+  fn word(&self, id: u32) -> &Arc<str> {
+    return &self.words[id as usize];
+  }
+}
+
+impl Graph {
+  // into scratch.text. false if the walk ran into max_chars without ever getting to the end of a sentence, the text
+  // is what there was. a restart after a dead end gets told to the observer like any other start
+  fn generate_tweet(&self, mut start: u32, max_chars: Option<usize>, dead_ends: DeadEndPolicy, rng: &mut impl Rng, observer: &mut dyn Observer, scratch: &mut Scratch) -> bool {
+    let states = &mut scratch.states;
+    let mut restarts = DEAD_END_RESTARTS;
+
+    loop {
+      states.clear();
+      if let Control::Stop = observer.on_transition(&Transition { csr: &self.csr, previous: None, current: start }) {
+        states.push(start);
+        break;
+      }
+      let mut walk = Walk::new(self, start, MAX_WORDS, rng);
+      walk.max_chars = max_chars;
+      walk.dead_ends = dead_ends;
+
+      match self.walk(states, walk, observer) {
+        Stopped::OutOfRoom => {
+          // back to the last full sentence
+          match states.iter().rposition( |&id| self.ends_tweet(last_word(&self.csr.words[id as usize])) ) {
+            Some(end) => states.truncate(end + 1),
+            None => {
+              join_into(&Cased(self), &mut scratch.text, self.words(states));
+              return false;
+            },
+          }
+        },
+        Stopped::DeadEnd if dead_ends == DeadEndPolicy::Restart && restarts > 0 => {
+          restarts -= 1;
+          start = self.random_entry_word(rng);
+          continue;
+        },
+        _ => {},
+      }
+      break;
+    }
+    join_into(&Cased(self), &mut scratch.text, self.words(states));
+    return true;
+  }
+
+  // tweets back to back, as many as fit in max_chars, with a paragraph break (a stanza break, which is what poetry
+  // mode's tweets end on anyway) every few. false if not even the first one fit, like generate_tweet
+  fn long_form(&self, start: u32, max_chars: usize, dead_ends: DeadEndPolicy, rng: &mut impl Rng, observer: &mut dyn Observer, scratch: &mut Scratch) -> bool {
+    let mut text = String::new();
+    let mut start = start;
+    let mut paragraph = 0;
+    let mut length = rng.gen_range(PARAGRAPH_SENTENCES.0..=PARAGRAPH_SENTENCES.1);
+
+    loop {
+      let breaks = self.line_breaks || paragraph == length;
+      let separator = match (text.is_empty(), breaks) {
+        (true, _) => "",
+        (false, true) => STANZA_BREAK,
+        (false, false) => " ",
+      };
+      let room = max_chars.saturating_sub(text.chars().count() + separator.chars().count());
+      if !self.generate_tweet(start, Some(room), dead_ends, rng, observer, scratch) || observer.cancelled() {
+        // what didn't fit is dropped, unless there's nothing else
+        if text.is_empty() {
+          return false;
+        }
+        break;
+      }
+      if breaks {
+        paragraph = 0;
+        length = rng.gen_range(PARAGRAPH_SENTENCES.0..=PARAGRAPH_SENTENCES.1);
+      }
+      text.push_str(separator);
+      text.push_str(&scratch.text);
+      paragraph += 1;
+      if max_chars.saturating_sub(text.chars().count()) < LONG_FORM_MIN_ROOM {
+        break;
+      }
+      start = self.random_entry_word(rng);
+    }
+    scratch.text = text;
+    return true;
+  }
+
+  // where a tweet starting with the prompt can carry on from: its last state, or every state that starts with it
+  // when it's fewer words than a state
+  fn prompt_starts(&self, prompt: &str) -> Vec<u32> {
+    let prompt = &*self.folding(prompt);
+    let words: Vec<&str> = prompt.split_whitespace().collect();
+    if words.is_empty() {
+      return Vec::new();
+    }
+    if words.len() >= self.order {
+      return self.csr.id(&self.state_at_end(prompt)).into_iter().collect();
+    }
+    return (0..self.csr.words.len() as u32).filter( |&id| self.csr.words[id as usize].split(' ').take(words.len()).eq(words.iter().copied()) ).collect();
+  }
+
+  // a state a thread's next tweet can start on after one that ended in tail: `order` steps on, so none of its words
+  // are the last tweet's. None at a dead end
+  fn carry_on(&self, tail: u32, rng: &mut impl Rng) -> Option<u32> {
+    let mut state = tail;
+    for _ in 0..self.order {
+      state = self.csr.next(state, rng)?;
+    }
+    return Some(state);
+  }
+
+  // the prompt, then the walk on from start (one of prompt_starts) into scratch.text. false if max_chars ran out
+  // before a sentence got finished, like generate_tweet
+  #[allow(clippy::too_many_arguments)]
+  fn continue_tweet(&self, prompt: &str, start: u32, max_chars: Option<usize>, dead_ends: DeadEndPolicy, rng: &mut impl Rng, observer: &mut dyn Observer, scratch: &mut Scratch) -> bool {
+    let prompt: Vec<&str> = prompt.split_whitespace().collect();
+    // the start state is the end of the prompt, or the prompt and then some
+    let overlap = prompt.len().min(self.order);
+    let before = &prompt[..prompt.len() - overlap];
+
+    let states = &mut scratch.states;
+    states.clear();
+    let mut walk = Walk::new(self, start, MAX_WORDS.saturating_sub(before.len()), rng);
+    walk.max_chars = max_chars.map( |max| max.saturating_sub(join(&Cased(self), before.iter().copied()).chars().count() + 1) );
+    walk.dead_ends = dead_ends;
+    let mut finished = true;
+    if self.walk(states, walk, observer) == Stopped::OutOfRoom {
+      // back to the last full sentence
+      match states.iter().rposition( |&id| self.ends_tweet(last_word(&self.csr.words[id as usize])) ) {
+        Some(end) => states.truncate(end + 1),
+        None => finished = false,
+      }
+    }
+
+    let first = self.csr.words[start as usize].split(' ').skip(overlap);
+    let rest = states[1..].iter().map( |&id| last_word(&self.csr.words[id as usize]) );
+    join_into(&Cased(self), &mut scratch.text, prompt.iter().copied().chain(first).chain(rest));
+    return finished;
+  }
+
+  // what a walk through these states says: all of the first one, then the one word each state after it adds
+  fn words<'a>(&'a self, states: &'a [u32]) -> impl Iterator<Item = &'a str> + 'a {
+    return states.iter().enumerate()
+      .flat_map( |(i, &id)| self.csr.words[id as usize].split(' ').skip(if i == 0 { 0 } else { self.order - 1 }) );
+  }
+
+  // every run of `order` tokens in a document, in order. a document shorter than that has no states at all
+  fn states<'a>(&self, tokens: &[&'a str]) -> Vec<Cow<'a, str>> {
+    if self.order == 1 {
+      return tokens.iter().map( |&token| Cow::Borrowed(token) ).collect();
+    }
+    return tokens.windows(self.order).map( |window| Cow::Owned(window.join(" ")) ).collect();
+  }
+
+  // the state the last words of a text end in, or all of the text if it's shorter than a state (which won't be
+  // in the graph)
+  fn state_at_end(&self, text: &str) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    return words[words.len().saturating_sub(self.order)..].join(" ");
+  }
+
+  // words to start on that begin with `letter`, in random order. sentence starts come first, the rest are there
+  // for when none of those work out
+  fn starts_with_letter(&self, letter: char, rng: &mut impl Rng) -> Vec<u32> {
+    let matches = |id: &u32| filter::initial(first_word(&self.csr.words[*id as usize])) == Some(letter);
+    let mut starts: Vec<u32> = self.csr.entry_words.iter().copied().filter(matches).collect();
+    let mut rest: Vec<u32> = (0..self.csr.words.len() as u32).filter(matches).filter( |id| !starts.contains(id) ).collect();
+    starts.shuffle(rng);
+    rest.shuffle(rng);
+    starts.extend(rest);
+    return starts;
+  }
+
+  fn acrostic_words(&self, letters: &[char], rng: &mut impl Rng) -> Option<String> {
+    let first = *letters.first()?;
+    let mut steps = ACROSTIC_STEPS;
+
+    for start in self.starts_with_letter(first, rng) {
+      // the rest of a longer first state has to spell too
+      let words: Vec<&str> = self.csr.words[start as usize].split(' ').collect();
+      if words.len() > letters.len() || !words.iter().zip(letters).all( |(word, &letter)| filter::initial(word) == Some(letter) ) {
+        continue;
+      }
+      let mut path = vec!(start);
+      if self.spell(&mut path, letters, rng, &mut steps) {
+        return Some(join(&Cased(self), self.words(&path)));
+      }
+      if steps == 0 {
+        return None;
+      }
+    }
+    return None;
+  }
+
+  // extends path one letter at a time, trying a node's edges in random order and backing up when none of them work
+  fn spell(&self, path: &mut Vec<u32>, letters: &[char], rng: &mut impl Rng, steps: &mut usize) -> bool {
+    // the first state says `order` words, every one after it one more
+    let spelled = self.order + path.len() - 1;
+    if spelled == letters.len() {
+      return true;
+    }
+    if *steps == 0 {
+      return false;
+    }
+    *steps -= 1;
+
+    let last = *path.last().unwrap() as usize;
+    let letter = letters[spelled];
+    let mut next: Vec<u32> = self.csr.targets[self.csr.offsets[last]..self.csr.offsets[last + 1]].iter().copied()
+      .filter( |&id| {
+        let word = last_word(&self.csr.words[id as usize]);
+        !is_break(word) && filter::initial(word) == Some(letter)
+      })
+      .collect();
+    next.shuffle(rng);
+
+    for id in next {
+      path.push(id);
+      if self.spell(path, letters, rng, steps) {
+        return true;
+      }
+      path.pop();
+    }
+    return false;
+  }
+
+  // a sentence per letter
+  fn acrostic_lines(&self, letters: &[char], rng: &mut impl Rng) -> String {
+    let mut lines = Vec::new();
+    for &letter in letters {
+      let Some(&start) = self.starts_with_letter(letter, rng).first() else {
+        break;
+      };
+      let states: Vec<u32> = Walk::new(self, start, MAX_WORDS, rng).collect();
+      lines.push(join(&Cased(self), self.words(&states)));
+    }
+    return lines.join("\n");
+  }
+
+  // one line from start that lands exactly on the syllable count, None if it overshoots or runs into a dead end.
+  // sentences can end in the middle of a line, line breaks from poetry mode are dropped. a line that carries on
+  // from the one before only gets the last word of its first state, the rest was already said. comes back with
+  // the state the line ended on
+  fn haiku_line(&self, start: u32, carries_on: bool, syllables: usize, rng: &mut impl Rng) -> Option<(Vec<&str>, u32)> {
+    let mut words = Vec::new();
+    let mut count = 0;
+    let mut current = Some(start);
+    let mut last = start;
+
+    while let Some(id) = current {
+      let said = match words.is_empty() && !carries_on {
+        true => self.csr.words[id as usize].split(' ').collect(),
+        false => vec!(last_word(&self.csr.words[id as usize])),
+      };
+      for word in said.into_iter().filter( |word| !is_break(word) ) {
+        words.push(word);
+        count += syllables::count(word);
+      }
+      last = id;
+      if count >= syllables || words.len() >= MAX_WORDS {
+        break;
+      }
+      current = self.csr.next(id, rng);
+    }
+    return if count == syllables { Some((words, last)) } else { None };
+  }
+
+  // every line picks up from the last word of the one before, so it still reads like one walk through the chain
+  fn haiku(&self, rng: &mut impl Rng, syllables: &[usize]) -> String {
+    let mut lines = Vec::new();
+    let mut last: Option<u32> = None;
+
+    for &count in syllables {
+      let mut line = None;
+      for _ in 0..HAIKU_LINE_ATTEMPTS {
+        let (start, carries_on) = match last.and_then( |last| self.csr.next(last, rng) ) {
+          Some(next) => (next, true),
+          None => (self.random_entry_word(rng), false),
+        };
+        line = self.haiku_line(start, carries_on, count, rng);
+        if line.is_some() {
+          break;
+        }
+      }
+      // short a line, the syllable filter throws it out
+      let Some((words, end)) = line else {
+        break;
+      };
+      last = Some(end);
+      lines.push(join(&Cased(self), words.into_iter()));
+    }
+    return lines.join("\n");
+  }
+
+  // the words of a text. in poetry mode line ends are tokens too, LINE_BREAK for the end of a line and STANZA_BREAK
+  // for blank lines, so the chain learns where lines go like it learns everything else
+  fn tokens<'a>(&self, text: &'a str) -> Vec<&'a str> {
+    if !self.line_breaks {
+      return text.split_whitespace().collect();
+    }
+
+    let mut tokens: Vec<&str> = Vec::new();
+    for line in text.lines() {
+      let words: Vec<&str> = line.split_whitespace().collect();
+      if words.is_empty() {
+        if tokens.last() == Some(&LINE_BREAK) {
+          tokens.pop();
+          tokens.push(STANZA_BREAK);
+        }
+        continue;
+      }
+      tokens.extend(words);
+      tokens.push(LINE_BREAK);
+    }
+    // a poem ends like a stanza does
+    if tokens.last() == Some(&LINE_BREAK) {
+      tokens.pop();
+      tokens.push(STANZA_BREAK);
+    }
+    return tokens;
+  }
+
+  fn ends_tweet(&self, word: &str) -> bool {
+    if self.line_breaks {
+      return word == STANZA_BREAK;
+    }
+    return self.ends_sentence(word);
+  }
+
+  // a word the next one starts a sentence after, poetry or not
+  fn ends_sentence(&self, word: &str) -> bool {
+    return self.locale.ends_sentence(word) && !self.abbreviated(word);
+  }
+
+  // the text the way the graph stores it, folded or not
+  fn folding<'a>(&self, text: &'a str) -> Cow<'a, str> {
+    return match self.folded {
+      true => Cow::Owned(self.locale.fold(text)),
+      false => Cow::Borrowed(text),
+    };
+  }
+
+  // one of the chain's own abbreviations, which never end a sentence
+  fn abbreviated(&self, word: &str) -> bool {
+    return locale::full_stop_stem(word).is_some_and( |stem| self.abbreviations.contains(&self.locale.fold(stem)) );
+  }
+
+  // follows the walk onto the end of words until it hits punctuation, a dead end, runs out of words or chars, or the
+  // observer says stop. the observer only hears about the words after the first
+  fn walk<R: Rng>(&self, words: &mut Vec<u32>, mut walk: Walk<R>, observer: &mut dyn Observer) -> Stopped {
+    for id in walk.by_ref() {
+      if observer.cancelled() {
+        return Stopped::Done;
+      }
+      let previous = words.last().copied();
+      words.push(id);
+
+      if previous.is_some() {
+        if let Control::Stop = observer.on_transition(&Transition { csr: &self.csr, previous, current: id }) {
+          return Stopped::Done;
+        }
+      }
+    }
+
+    return walk.stopped;
+  }
+
+  // nowhere to go from here, and it doesn't end a sentence
+  fn dead_end(&self, id: u32) -> bool {
+    let id = id as usize;
+    return self.csr.offsets[id] == self.csr.offsets[id + 1] && !self.ends_tweet(last_word(&self.csr.words[id]));
+  }
+
+  fn retry_entry_word(&self, rng: &mut impl Rng, policy: RetryPolicy, previous: Option<u32>, failures: &HashMap<u32, usize>) -> u32 {
+    let entries = self.entries();
+    return match (policy, previous) {
+      (RetryPolicy::SameEntry, Some(previous)) => previous,
+      (RetryPolicy::AvoidFailedEntries, Some(_)) if !entries.is_empty() => {
+        let weights = entries.iter().map( |id| 1.0 / (1 + failures.get(id).unwrap_or(&0)) as f64 );
+        entries[WeightedIndex::new(weights).unwrap().sample(rng)]
+      },
+      _ => self.random_entry_word(rng),
+    };
+  }
+
+  // the entry words, or with about only the ones that started a sentence in a matching document (all of them if
+  // none did)
+  fn entries(&self) -> Cow<'_, [u32]> {
+    let csr = &self.csr;
+    if self.about == 0 {
+      return Cow::Borrowed(&csr.entry_words);
+    }
+    let about: Vec<u32> = csr.entry_words.iter().zip(&csr.entry_tags).filter( |(_, tags)| *tags & self.about != 0 ).map( |(id, _)| *id ).collect();
+    return match about.is_empty() {
+      true => Cow::Borrowed(&csr.entry_words),
+      false => Cow::Owned(about),
+    };
+  }
+
+  // the chain checks for an empty graph before it gets this far
+  fn random_entry_word(&self, rng: &mut impl Rng) -> u32 {
+    if self.csr.words.is_empty() {
+      panic!("can't generate a tweet from an empty corpus");
+    }
+
+    // an all lowercase corpus has no entry words, any word is better than nothing
+    return match self.entries().choose(rng) {
+      Some(word) => *word,
+      None => rng.gen_range(0..self.csr.words.len() as u32),
+    };
+  }
+
+  // whether the state's first word starts a sentence, with previous the state before it in the document. it depends
+  // on where the word is, so a state only has to start a sentence once. poems start after a stanza break. folded,
+  // only the end of the last sentence counts and not the capital, which generation puts back anyway
+  fn starts_sentence(&self, state: &str, previous: Option<&str>) -> bool {
+    let (word, previous) = (first_word(state), previous.map(first_word));
+    let sentence = match self.folded {
+      true => previous.is_none_or( |previous| self.locale.ends_sentence_before(previous, Some(word)) ),
+      false => self.locale.starts_sentence(word, previous),
+    };
+    return match self.line_breaks {
+      true => !is_break(word) && (previous.is_none_or( |previous| previous == STANZA_BREAK ) || sentence),
+      false => sentence,
+    } && !previous.is_some_and( |previous| self.abbreviated(previous) );
+  }
+
+  // only allocates the first time we see a state. a state is an entry state if it starts a sentence once, see
+  // starts_sentence. tags are the document's, see tag_mask
+  fn add(&mut self, state: &str, starts: bool, tags: u64) -> u32 {
+    let (id, new) = self.symbols.intern(state);
+    if new {
+      self.nodes.push(Node::new());
+    }
+
+    let node = &mut self.nodes[id as usize];
+    node.count += 1;
+    node.tags |= tags;
+    if starts {
+      node.entry_tags |= tags;
+    }
+    if !node.entry && starts {
+      node.entry = true;
+      self.entry_words.push(id);
+    }
+    return id;
+  }
+
+  // tags as bits, new ones taken on up to MAX_TAGS
+  fn tag_mask(&mut self, tags: &[String]) -> u64 {
+    let mut mask = 0;
+    for tag in tags {
+      let bit = match self.tags.iter().position( |known| known == tag ) {
+        Some(bit) => bit,
+        None if self.tags.len() < MAX_TAGS => {
+          self.tags.push(tag.clone());
+          self.tags.len() - 1
+        },
+        None => continue,
+      };
+      mask |= 1 << bit;
+    }
+    return mask;
+  }
+
+  // the same without taking anything on, 0 if the graph has none of them
+  fn about_mask(&self, tags: &[String]) -> u64 {
+    return self.tags.iter().enumerate().filter( |(_, tag)| tags.contains(tag) ).fold(0, |mask, (bit, _)| mask | 1 << bit);
+  }
+
+  // see boosted
+  fn boosted(&self, words: &[String]) -> Vec<bool> {
+    if words.is_empty() {
+      return Vec::new();
+    }
+    return self.csr.words.iter().map( |state| words.contains(&flavor::key(last_word(state))) ).collect();
+  }
+
+  fn node(&self, word: &str) -> Option<&Node> {
+    return self.symbols.id(word).map( |id| &self.nodes[id as usize] );
+  }
+
+  // the weight of last_word -> word, if the training graph has it
+  fn edge(&self, last_word: &str, word: &str) -> Option<i32> {
+    return self.node(last_word)?.edges.get(&self.symbols.id(word)?).copied();
+  }
+
+  // add-one smoothed so transitions we've never seen don't send perplexity to infinity. suppressed ones count as never
+  // seen, generation can't take them either
+  fn probability(&self, last_word: &str, word: &str) -> f64 {
+    let vocabulary = self.nodes.len() as f64 + 1.0;
+
+    let (weight, sum) = match self.node(last_word) {
+      Some(node) => {
+        let kept = |next: &u32, weight: i32| weight >= self.suppression.min_count && node.documents[next] >= self.suppression.min_documents;
+        let weight = match (self.symbols.id(word), self.edge(last_word, word)) {
+          (Some(id), Some(weight)) if kept(&id, weight) => weight,
+          _ => 0,
+        };
+        (weight, node.edges.iter().filter( |(next, weight)| kept(next, **weight) ).map( |(_, weight)| weight ).sum())
+      },
+      None => (0, 0),
+    };
+
+    return (weight as f64 + 1.0) / (sum as f64 + vocabulary);
+  }
+
+  // flattens everything into the csr layout. ids are handed out in sorted word order and edges are sorted by id,
+  // so sampling doesn't depend on HashMap iteration order. suppressed transitions don't make it in at all
+  fn finalize(&mut self) {
+    self.casing.finish();
+    let (order, ids) = self.sorted_ids();
+    let words = order.iter().map( |&id| self.symbols.word(id).clone() ).collect();
+
+    let mut offsets = vec!(0);
+    let mut targets = Vec::new();
+    let mut cumulative_weights = Vec::new();
+    let (mut cutoffs, mut aliases) = (Vec::new(), Vec::new());
+
+    for &id in &order {
+      let node = &self.nodes[id as usize];
+      let mut edges: Vec<(u32, i32)> = node.edges.iter()
+        .filter( |(next, weight)| **weight >= self.suppression.min_count && node.documents[*next] >= self.suppression.min_documents )
+        .map( |(next, weight)| (ids[*next as usize], *weight) )
+        .collect();
+      edges.sort();
+      self.sampling.apply(&mut edges);
+
+      let (node_cutoffs, node_aliases) = alias_table(&edges);
+      cutoffs.extend(node_cutoffs);
+      aliases.extend(node_aliases);
+
+      let mut running = 0;
+      for (target, weight) in edges {
+        running += weight;
+        targets.push(target);
+        cumulative_weights.push(running);
+      }
+      offsets.push(targets.len());
+    }
+
+    let entry_words = self.entry_words.iter().map( |&id| ids[id as usize] ).collect();
+    let entry_tags = self.entry_words.iter().map( |&id| self.nodes[id as usize].entry_tags ).collect();
+    let tags = order.iter().map( |&id| self.nodes[id as usize].tags ).collect();
+
+    self.csr = Csr { words, offsets, targets, cumulative_weights, cutoffs, aliases, entry_words, tags, entry_tags };
+  }
+
+  // symbol ids in sorted word order, and for each symbol id where it ends up in that order. the csr and the model
+  // file both go in sorted order so neither depends on the order states were first seen
+  fn sorted_ids(&self) -> (Vec<u32>, Vec<u32>) {
+    let mut order: Vec<u32> = (0..self.symbols.words.len() as u32).collect();
+    order.sort_by( |a, b| self.symbols.word(*a).cmp(self.symbols.word(*b)) );
+    let mut ids = vec!(0; order.len());
+    for (sorted, &id) in order.iter().enumerate() {
+      ids[id as usize] = sorted as u32;
+    }
+    return (order, ids);
+  }
+
+  // both ids come from add. called once per distinct transition in a document
+  fn connect(&mut self, last_word: u32, word: u32, weight: i32) {
+    self.nodes[last_word as usize].strengthen_edge(word, weight);
+  }
+
+  // the training side (nodes, edges, document counts), not the csr. finalize rebuilds that, and keeping everything
+  // means suppression still works and more training can go on top. words are written once in sorted order and
+  // everything else points at them by index
+  fn write(&self, out: &mut Writer) {
+    out.u32(self.order as u32);
+    out.u8(self.line_breaks as u8);
+    out.string(self.locale.name());
+    out.u64(self.suppression.min_documents as u64);
+    out.i32(self.suppression.min_count);
+
+    let (order, ids) = self.sorted_ids();
+
+    out.u32(order.len() as u32);
+    for &id in &order {
+      out.string(self.symbols.word(id));
+    }
+    for &id in &order {
+      let node = &self.nodes[id as usize];
+      out.u64(node.count as u64);
+
+      let mut edges: Vec<(u32, u32)> = node.edges.keys().map( |&next| (ids[next as usize], next) ).collect();
+      edges.sort();
+      out.u32(edges.len() as u32);
+      for (target, next) in edges {
+        out.u32(target);
+        out.i32(node.edges[&next]);
+        out.u64(node.documents[&next] as u64);
+      }
+    }
+    // in the order they were found, that order is what the rng picks from
+    out.u32(self.entry_words.len() as u32);
+    for &id in &self.entry_words {
+      out.u32(ids[id as usize]);
+    }
+  }
+
+  fn read(reader: &mut Reader) -> io::Result<Graph> {
+    let mut graph = Graph::new();
+    graph.order = reader.u32()? as usize;
+    if graph.order == 0 {
+      return Err(model::invalid("the model has an order of 0".to_string()));
+    }
+    graph.line_breaks = reader.u8()? != 0;
+    graph.locale = reader.string()?.parse().map_err(model::invalid)?;
+    graph.suppression = Suppression { min_documents: reader.u64()? as usize, min_count: reader.i32()? };
+
+    // words come in sorted order, so the ids in the file are the symbol ids
+    let words = reader.u32()?;
+    for _ in 0..words {
+      let word = reader.string()?;
+      if !graph.symbols.intern(&word).1 {
+        return Err(model::invalid(format!("the model has {:?} twice", word)));
+      }
+    }
+    let word = |id: u32| match id < words {
+      true => Ok(id),
+      false => Err(model::invalid(format!("the model points at word {}, it only has {}", id, words))),
+    };
+
+    for _ in 0..words {
+      let mut node = Node::new();
+      node.count = reader.u64()? as usize;
+      for _ in 0..reader.u32()? {
+        let next = word(reader.u32()?)?;
+        let weight = reader.i32()?;
+        node.documents.insert(next, reader.u64()? as usize);
+        node.edges.insert(next, weight);
+        node.sum += weight;
+      }
+      graph.nodes.push(node);
+    }
+    for _ in 0..reader.u32()? {
+      let entry = word(reader.u32()?)?;
+      graph.nodes[entry as usize].entry = true;
+      graph.entry_words.push(entry);
+    }
+    return Ok(graph);
+  }
+
+  // what another graph learned, as if its documents had been trained on here after everything else. both have to
+  // have been built with the same settings. other's new words get ids after this graph's, in the order other saw
+  // them, the same ids training on it all in one go would have handed out. other's edge weights are multiplied by
+  // scale on the way in, every edge keeping at least 1
+  fn merge(&mut self, other: Graph, scale: f64) {
+    // other's tag bits -> this graph's
+    let bits: Vec<u64> = other.tags.iter().map( |tag| self.tag_mask(std::slice::from_ref(tag)) ).collect();
+    let tags = |mask: u64| bits.iter().enumerate().filter( |(bit, _)| mask & 1 << bit != 0 ).fold(0, |mine, (_, bit)| mine | bit);
+    let ids: Vec<u32> = other.symbols.words.iter().map( |word| {
+      let (id, new) = self.symbols.intern(word);
+      if new {
+        self.nodes.push(Node::new());
+      }
+      id
+    }).collect();
+
+    for (id, node) in other.nodes.into_iter().enumerate() {
+      let mine = &mut self.nodes[ids[id] as usize];
+      for (next, weight) in node.edges {
+        let weight = match scale {
+          1.0 => weight,
+          scale => cmp::max(1, (weight as f64 * scale).round() as i32),
+        };
+        *mine.documents.entry(ids[next as usize]).or_insert(0) += node.documents[&next];
+        *mine.edges.entry(ids[next as usize]).or_insert(0) += weight;
+        mine.sum += weight;
+      }
+      mine.count += node.count;
+      mine.tags |= tags(node.tags);
+      mine.entry_tags |= tags(node.entry_tags);
+    }
+
+    // in the order other found them, after the ones already here. that's what training on it all in one go does
+    for id in other.entry_words {
+      let node = &mut self.nodes[ids[id as usize] as usize];
+      if !node.entry {
+        node.entry = true;
+        self.entry_words.push(ids[id as usize]);
+      }
+    }
+    self.casing.merge(other.casing);
+  }
+
+  // the tag names, then each node's tags in the same sorted order as write. model version 4 on
+  fn write_tags(&self, out: &mut Writer) {
+    out.u32(self.tags.len() as u32);
+    for tag in &self.tags {
+      out.string(tag);
+    }
+    for id in self.sorted_ids().0 {
+      let node = &self.nodes[id as usize];
+      out.u64(node.tags);
+      out.u64(node.entry_tags);
+    }
+  }
+
+  fn read_tags(&mut self, reader: &mut Reader) -> io::Result<()> {
+    for _ in 0..reader.u32()? {
+      self.tags.push(reader.string()?);
+    }
+    if self.tags.len() > MAX_TAGS {
+      return Err(model::invalid(format!("the model has {} tags, there can only be {}", self.tags.len(), MAX_TAGS)));
+    }
+    for node in &mut self.nodes {
+      node.tags = reader.u64()?;
+      node.entry_tags = reader.u64()?;
+    }
+    return Ok(());
+  }
+
+  // nothing in it, but built the same way as this one
+  fn empty_like(&self) -> Graph {
+    let mut graph = Graph::new();
+    graph.locale = self.locale;
+    graph.abbreviations = self.abbreviations.clone();
+    graph.line_breaks = self.line_breaks;
+    graph.joiner = self.joiner.clone();
+    graph.folded = self.folded;
+    graph.order = self.order;
+    graph.suppression = self.suppression;
+    graph.sampling = self.sampling;
+    return graph;
+  }
+
+  pub fn new() -> Graph {
+    return Graph {
+      symbols: Symbols::default(),
+      nodes: Vec::new(),
+      entry_words: Vec::new(),
+      locale: Locale::default(),
+      abbreviations: Vec::new(),
+      line_breaks: false,
+      joiner: Arc::new(Detokenizer),
+      folded: false,
+      casing: Casing::default(),
+      order: 1,
+      suppression: Suppression::default(),
+      sampling: Sampling::default(),
+      tags: Vec::new(),
+      about: 0,
+      boosted: Vec::new(),
+      csr: Csr::new(),
+    };
+  }
+}
+
+// one step of generation, handed to observers. the probability and alternatives are only worked out if asked for
+pub struct Transition<'a> {
+  csr: &'a Csr,
+  // None for the entry word
+  previous: Option<u32>,
+  current: u32,
+}
+
+impl<'a> Transition<'a> {
+  // all of the entry state's words, then the one word each step adds
+  pub fn word(&self) -> &'a str {
+    let state = &self.csr.words[self.current as usize];
+    return match self.previous {
+      Some(_) => last_word(state),
+      None => state,
+    };
+  }
+
+  pub fn previous(&self) -> Option<&'a str> {
+    return self.previous.map( |id| last_word(&self.csr.words[id as usize]) );
+  }
+
+  pub fn probability(&self) -> f64 {
+    return match self.previous {
+      Some(previous) => self.csr.transition_probability(previous, self.current),
+      None => self.csr.entry_probability(self.current),
+    };
+  }
+
+  // the k most likely words that could have come here instead (the chosen word included). empty for entry words
+  pub fn alternatives(&self, k: usize) -> Vec<(&'a str, f64)> {
+    return match self.previous {
+      Some(previous) => self.csr.candidates(previous, k).into_iter().map( |(id, p)| (last_word(&self.csr.words[id as usize]), p) ).collect(),
+      None => Vec::new(),
+    };
+  }
+}
+
+// for when nobody's listening
+struct Unobserved;
+
+impl Observer for Unobserved {
+  fn on_transition(&mut self, _transition: &Transition) -> Control {
+    return Control::Continue;
+  }
+}
+
+// yields a tweet one word id at a time instead of all at once, so it can be streamed out while it's generated
+struct Walk<'a, R: Rng> {
+  graph: &'a Graph,
+  rng: &'a mut R,
+  next: Option<u32>,
+  remaining: usize,
+  max_chars: Option<usize>,
+  // what's been walked so far comes to this many chars, joined up
+  chars: usize,
+  // the last word of the last state, for the space before the next one
+  last: Option<&'a str>,
+  dead_ends: DeadEndPolicy,
+  stopped: Stopped,
+}
+
+// why a walk ended
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Stopped {
+  // punctuation, the word limit, or the observer
+  Done,
+  // the next word wouldn't have fit in max_chars
+  OutOfRoom,
+  // nowhere to go from the last word, see DeadEndPolicy
+  DeadEnd,
+}
+
+impl<'a, R: Rng> Walk<'a, R> {
+  fn new(graph: &'a Graph, start: u32, max_words: usize, rng: &'a mut R) -> Walk<'a, R> {
+    return Walk {
+      graph,
+      rng,
+      next: Some(start),
+      remaining: max_words,
+      max_chars: None,
+      chars: 0,
+      last: None,
+      dead_ends: DeadEndPolicy::End,
+      stopped: Stopped::Done,
+    };
+  }
+
+  // the weighted pick. with the resample policy an edge into a dead end gets picked again from the ones that
+  // aren't, if there are any
+  fn pick(&mut self, current: u32) -> Option<u32> {
+    let graph = self.graph;
+    let next = graph.csr.next(current, self.rng);
+    return match next {
+      None => {
+        self.stopped = Stopped::DeadEnd;
+        None
+      },
+      Some(id) if self.dead_ends == DeadEndPolicy::Resample && graph.dead_end(id) => {
+        graph.csr.next_where(current, self.rng, |id| !graph.dead_end(id) ).or(next)
+      },
+      // about's boost: the matching documents' words get another go at it
+      Some(id) if graph.about != 0 && graph.csr.tags[id as usize] & graph.about == 0 && self.rng.gen_bool(ABOUT_BOOST) => {
+        graph.csr.next_where(current, self.rng, |id| graph.csr.tags[id as usize] & graph.about != 0 ).or(next)
+      },
+      Some(id) if !graph.boosted.is_empty() && !graph.boosted[id as usize] && self.rng.gen_bool(WORD_BOOST) => {
+        graph.csr.next_where(current, self.rng, |id| graph.boosted[id as usize] ).or(next)
+      },
+      next => next,
+    };
+  }
+
+  // how much longer the tweet gets with this word on the end. breaks don't get a space in front, and neither does
+  // anything right after one
+  fn added_chars(last: Option<&str>, word: &str) -> usize {
+    let space = last.is_some_and( |last| !is_break(last) ) && !is_break(word);
+    return word.chars().count() + space as usize;
+  }
+
+  // the weighted pick, minus anything that wouldn't fit. once the tweet's into the last quarter of its room an edge
+  // that ends it gets picked if there is one, so it finishes instead of running into the limit mid sentence
+  fn step(&mut self, current: u32, max_chars: usize) -> Option<u32> {
+    let (graph, csr) = (self.graph, &self.graph.csr);
+    let room = max_chars.saturating_sub(self.chars);
+    let last = self.last;
+    let fits = |id: u32| Self::added_chars(last, last_word(&csr.words[id as usize])) <= room;
+
+    if room <= max_chars / 4 {
+      let ending = csr.next_where(current, self.rng, |id| fits(id) && graph.ends_tweet(last_word(&csr.words[id as usize])) );
+      if ending.is_some() {
+        return ending;
+      }
+    }
+    let next = match self.pick(current) {
+      Some(id) if fits(id) => Some(id),
+      Some(_) => csr.next_where(current, self.rng, fits),
+      None => return None,
+    };
+    if next.is_none() {
+      self.stopped = Stopped::OutOfRoom;
+    }
+    return next;
+  }
+}
+
+impl<'a, R: Rng> Iterator for Walk<'a, R> {
+  type Item = u32;
+
+  fn next(&mut self) -> Option<u32> {
+    let current = self.next?;
+    let csr = &self.graph.csr;
+    self.remaining = self.remaining.saturating_sub(1);
+
+    let state = &csr.words[current as usize];
+    self.chars += match self.last {
+      // the whole first state
+      None => join(&*self.graph.joiner, state.split(' ')).chars().count(),
+      last => Self::added_chars(last, last_word(state)),
+    };
+    self.last = Some(last_word(state));
+
+    self.next = if self.graph.ends_tweet(last_word(state)) || self.remaining == 0 {
+      None
+    } else {
+      match self.max_chars {
+        Some(max_chars) => self.step(current, max_chars),
+        None => self.pick(current),
+      }
+    };
+
+    return Some(current);
+  }
+}
+
+// compressed sparse row storage: node i's edges live at targets[offsets[i]..offsets[i + 1]], next to a running
+// total of their weights. it's all flat vectors of integers, which is nice for the cache and trivial to dump to disk
+struct Csr {
+  words: Vec<Arc<str>>, // id -> word
+  offsets: Vec<usize>,
+  targets: Vec<u32>,
+  cumulative_weights: Vec<i32>,
+  // a walker alias table per node, next to the edges: see alias_table
+  cutoffs: Vec<u64>,
+  aliases: Vec<u32>,
+  entry_words: Vec<u32>,
+  // id -> the tags of the documents it was in, and the tags of the documents each entry word started a sentence in
+  tags: Vec<u64>,
+  entry_tags: Vec<u64>,
+}
+
+// Vose's alias method, in integers so the probabilities come out exactly the weights. every edge gets a column of
+// height `total`, filled up to its cutoff by itself and the rest of the way by its alias (an index into the same
+// edges). sampling is then one column and one height, however many edges there are
+fn alias_table(edges: &[(u32, i32)]) -> (Vec<u64>, Vec<u32>) {
+  let n = edges.len() as u64;
+  let total: u64 = edges.iter().map( |(_, weight)| *weight as u64 ).sum();
+  let mut heights: Vec<u64> = edges.iter().map( |(_, weight)| *weight as u64 * n ).collect();
+  let (mut cutoffs, mut aliases) = (vec!(total; edges.len()), (0..edges.len() as u32).collect::<Vec<u32>>());
+
+  let (mut small, mut large): (Vec<usize>, Vec<usize>) = (0..edges.len()).partition( |&i| heights[i] < total );
+  while let (Some(s), Some(&l)) = (small.pop(), large.last()) {
+    cutoffs[s] = heights[s];
+    aliases[s] = l as u32;
+    heights[l] -= total - heights[s];
+    if heights[l] < total {
+      large.pop();
+      small.push(l);
+    }
+  }
+  // whatever's left is exactly full already
+  return (cutoffs, aliases);
+}
+
+impl Csr {
+  // ids were handed out in sorted order, so no need to keep a second map around
+  fn id(&self, word: &str) -> Option<u32> {
+    return self.words.binary_search_by( |w| w.as_ref().cmp(word) ).ok().map( |id| id as u32 );
+  }
+
+  fn entry_probability(&self, id: u32) -> f64 {
+    if self.entry_words.is_empty() {
+      return 1.0 / self.words.len() as f64;
+    }
+    if self.entry_words.contains(&id) {
+      return 1.0 / self.entry_words.len() as f64;
+    }
+    return 0.0;
+  }
+
+  fn transition_probability(&self, from: u32, to: u32) -> f64 {
+    let start = self.offsets[from as usize];
+    let end = self.offsets[from as usize + 1];
+
+    // targets are sorted within a node
+    return match self.targets[start..end].binary_search(&to) {
+      Ok(i) => {
+        let previous = if i == 0 { 0 } else { self.cumulative_weights[start + i - 1] };
+        (self.cumulative_weights[start + i] - previous) as f64 / self.cumulative_weights[end - 1] as f64
+      },
+      Err(_) => 0.0,
+    };
+  }
+
+  fn candidates(&self, id: u32, k: usize) -> Vec<(u32, f64)> {
+    let start = self.offsets[id as usize];
+    let end = self.offsets[id as usize + 1];
+    if start == end {
+      return Vec::new();
+    }
+    let total = self.cumulative_weights[end - 1] as f64;
+
+    let mut edges: Vec<(u32, i32)> = (start..end).map( |edge| {
+      let previous = if edge == start { 0 } else { self.cumulative_weights[edge - 1] };
+      (self.targets[edge], self.cumulative_weights[edge] - previous)
+    }).collect();
+    edges.sort_by( |a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)) );
+
+    return edges.into_iter().take(k).map( |(target, weight)| (target, weight as f64 / total) ).collect();
+  }
+
+  // randomly picks from weighted edges, None if the word has no edges at all
+  fn next(&self, id: u32, rng: &mut impl Rng) -> Option<u32> {
+    let start = self.offsets[id as usize];
+    let end = self.offsets[id as usize + 1];
+    if start == end {
+      return None;
+    }
+    // one draw for the column and the height both. "the" and friends have thousands of edges, this doesn't care
+    let total = self.cumulative_weights[end - 1] as u64;
+    let number = rng.gen_range(0..(end - start) as u64 * total);
+    let column = start + (number / total) as usize;
+    let edge = match number % total < self.cutoffs[column] {
+      true => column,
+      false => start + self.aliases[column] as usize,
+    };
+    return Some(self.targets[edge]);
+  }
+
+  // same as next, but only out of the edges to words `allowed` lets through
+  fn next_where(&self, id: u32, rng: &mut impl Rng, allowed: impl Fn(u32) -> bool) -> Option<u32> {
+    let start = self.offsets[id as usize];
+    let end = self.offsets[id as usize + 1];
+    // twice over the edges rather than collecting the allowed ones, this runs on most steps near the limit
+    let edges = || (start..end)
+      .map( |edge| (self.targets[edge], self.cumulative_weights[edge] - if edge == start { 0 } else { self.cumulative_weights[edge - 1] }) )
+      .filter( |(target, _)| allowed(*target) );
+    let total: i32 = edges().map( |(_, weight)| weight ).sum();
+    if total == 0 {
+      return None;
+    }
+
+    let mut number = rng.gen_range(1..=total);
+    for (target, weight) in edges() {
+      if number <= weight {
+        return Some(target);
+      }
+      number -= weight;
+    }
+    panic!("the edge weights do not match the sum");
+  }
+
+  pub fn new() -> Csr {
+    return Csr {
+      words: Vec::new(),
+      offsets: vec!(0),
+      targets: Vec::new(),
+      cumulative_weights: Vec::new(),
+      cutoffs: Vec::new(),
+      aliases: Vec::new(),
+      entry_words: Vec::new(),
+      tags: Vec::new(),
+      entry_tags: Vec::new(),
+    };
+  }
+}
+
+// we need to store a weighted index (the 'strength' of an edge) for probabilistic sampling
+struct Node {
+  // can we have it store a reference to the next node? Would be way nicer than having the graph need to reach in for this ("tell, don't ask")
+  edges: HashMap<u32, i32>,
+  sum: i32,
+  // how many times the word (or state, with a higher order) showed up, regardless of document caps
+  count: usize,
+  // node -> how many documents the edge showed up in
+  documents: HashMap<u32, usize>,
+  // already in the graph's entry words
+  entry: bool,
+  // the tags of the documents it showed up in, and the ones it started a sentence in. bits, see Graph::tags
+  tags: u64,
+  entry_tags: u64,
+}
+
+impl Node {
+  // edges are node -> weight
+  fn strengthen_edge(&mut self, next: u32, amount: i32) {
+    *self.documents.entry(next).or_insert(0) += 1;
+    let weight = self.edges.entry(next).or_insert(0);
+    *weight += amount;
+    self.sum += amount;
+  }
+
+  pub fn new() -> Node {
+    return Node {
+      edges: HashMap::new(),
+      sum: 0,
+      count: 0,
+      documents: HashMap::new(),
+      entry: false,
+      tags: 0,
+      entry_tags: 0,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::storage::MemoryStorage;
+  use crate::tokenize::Punctuation;
+
+  #[test]
+  fn builder_trains_then_generates() {
+    let mut mchain = MarkovChain::builder().text("Hodl the moon.").max_words(3).seed(1).build().unwrap();
+    assert!(matches!(mchain.generate(), Err(ErowidCoinError::Untrained)));
+
+    mchain.train().unwrap();
+    assert_eq!(mchain.generate().unwrap(), "Hodl the moon.");
+
+    // training again adds to what's there
+    mchain.add_corpus(Corpus::Dir(PathBuf::from("./txt")));
+    mchain.train().unwrap();
+    assert!(MarkovChain::builder().corpus("./txt").build().unwrap().node_count() == 0);
+    assert!(mchain.node_count() > 3 && mchain.passes_filters("Hodl the moon.") && !mchain.passes_filters("Hodl the big moon."));
+
+    // an order of 0 is an error from build, not a panic
+    assert!(matches!(MarkovChain::builder().text("Hodl the moon.").order(0).build(), Err(ErowidCoinError::ZeroOrder)));
+    assert!(matches!(MarkovChain::new().with_order(0), Err(ErowidCoinError::ZeroOrder)));
+    assert_eq!(MarkovChain::builder().order(3).build().unwrap().order(), 3);
+  }
+
+  #[test]
+  fn create_a_tweet() {
+    let test_path: &Path = Path::new("./txt");
+    let mut mchain = MarkovChain::new();
+
+    let response = mchain.create_tweets(test_path, 1).unwrap();
+    assert!(response[0].starts_with("The syntactic component of a"));
+    assert!(response[0].ends_with("interpretation."));
+  }
+
+  #[test]
+  fn same_seed_same_tweets() {
+    let seed_path: &Path = Path::new("./seed");
+
+    let first = MarkovChain::new().with_seed(42).create_tweets(seed_path, 5).unwrap();
+    let second = MarkovChain::new().with_seed(42).create_tweets(seed_path, 5).unwrap();
+    assert_eq!(first, second);
+  }
+
+  #[test]
+  fn times_training_and_generation() {
+    let mut mchain = MarkovChain::new().with_seed(42);
+    mchain.create_tweets(Path::new("./seed"), 3).unwrap();
+
+    let timings = mchain.timings();
+    assert!(timings.phases().iter().all( |(_, took)| !took.is_zero() ), "{:?}", timings);
+    assert_eq!(timings.generated, 3);
+    assert!(timings.slowest <= timings.generation && !timings.slowest.is_zero());
+  }
+
+  #[test]
+  fn any_rng_can_drive_the_chain() {
+    let seeded = MarkovChain::new().with_seed(42).create_tweets(Path::new("./seed"), 5).unwrap();
+    let custom = MarkovChain::new().with_rng(ChaCha8Rng::seed_from_u64(42)).create_tweets(Path::new("./seed"), 5).unwrap();
+    assert_eq!(seeded, custom);
+
+    // always the lowest number there is, so always the first entry word and edge (edges are sorted by word)
+    let mut mchain = MarkovChain::new().with_rng(rand::rngs::mock::StepRng::new(0, 0));
+    mchain.parse_document("Hodl the moon. Buy the dip.");
+    mchain.graph.finalize();
+    assert_eq!(mchain.generate_tweet().unwrap(), "Hodl the dip.");
+  }
+
+  #[test]
+  fn trains_on_documents_from_anywhere() {
+    let mut mchain = MarkovChain::new();
+    let rows = vec!(Cow::Borrowed("Hodl the moon"), Cow::Owned("Buy the dip.".to_string()));
+    mchain.train_documents(rows);
+
+    assert_eq!(mchain.ingestion_report().documents, 2);
+    // the end of one document doesn't lead into the next
+    let probabilities = mchain.token_probabilities("Hodl the moon Buy");
+    assert!(probabilities[2].1 > 0.0 && probabilities[3].1 == 0.0, "{:?}", probabilities);
+    assert!(mchain.generate().is_ok());
+  }
+
+  #[test]
+  fn abbreviations_dont_end_tweets() {
+    let documents = ["Add a tsp. Of salt now."];
+    let mut plain = MarkovChain::new().with_seed(1);
+    plain.train_documents(documents);
+    let mut abbreviated = MarkovChain::new().with_seed(1).with_abbreviation("Tsp.");
+    abbreviated.train_documents(documents);
+
+    let tweets: Vec<String> = (0..10).map( |_| plain.generate().unwrap() ).collect();
+    assert!(tweets.iter().any( |tweet| tweet == "Add a tsp." ), "{:?}", tweets);
+    for _ in 0..10 {
+      assert_eq!(abbreviated.generate().unwrap(), "Add a tsp. Of salt now.");
+    }
+  }
+
+  #[test]
+  fn short_limits_still_end_sentences() {
+    let mut mchain = MarkovChain::new().with_seed(42).with_max_chars(80);
+    let tweets = mchain.create_tweets(Path::new("./seed"), 20).unwrap();
+
+    for tweet in tweets {
+      assert!(tweet.chars().count() <= 80, "{}", tweet);
+      assert!(mchain.locale().ends_sentence(tweet.split_whitespace().last().unwrap()), "{}", tweet);
+    }
+  }
+
+  #[test]
+  fn long_form_fills_paragraphs_up_to_the_limit() {
+    let mut mchain = MarkovChain::new().with_seed(42).with_long_form(1000);
+    let posts = mchain.create_tweets(Path::new("./seed"), 5).unwrap();
+
+    for post in posts {
+      assert!(post.chars().count() <= 1000, "{}", post);
+      let paragraphs: Vec<&str> = post.split(STANZA_BREAK).collect();
+      assert!(paragraphs.len() > 1, "{}", post);
+      for paragraph in paragraphs {
+        assert!(mchain.locale().ends_sentence(paragraph.split_whitespace().last().unwrap()), "{}", paragraph);
+      }
+    }
+  }
+
+  // not a real benchmark harness, run with `cargo test --release -- --ignored --nocapture bench_training`
+  // sharing tokens through Arc<str> took this from a median of 100ms to 80ms over 40 runs. peak memory stayed at
+  // 10.5MB either way, the corpus is the seed repeated so there are only a few thousand distinct words to share
+  #[test]
+  #[ignore]
+  fn bench_training() {
+    let corpus = read_documents(Path::new("./seed")).unwrap().join("\n").repeat(500);
+
+    let start = std::time::Instant::now();
+    let mut mchain = MarkovChain::new();
+    mchain.parse_document(&corpus);
+    mchain.graph.finalize();
+
+    println!("trained {} words in {:?}", corpus.split_whitespace().count(), start.elapsed());
+  }
+
+  // what Csr::next did before the binary search, the slowest baseline bench_sampling times the alias table against
+  fn next_linear(csr: &Csr, id: u32, rng: &mut impl Rng) -> Option<u32> {
+    let (start, end) = (csr.offsets[id as usize], csr.offsets[id as usize + 1]);
+    let number = rng.gen_range(1..=*csr.cumulative_weights[start..end].last()?);
+    return (start..end).find( |&edge| number <= csr.cumulative_weights[edge] ).map( |edge| csr.targets[edge] );
+  }
+
+  // one node with `edges` ways out, all weighted differently
+  fn wide_node(edges: usize) -> (MarkovChain, u32) {
+    let mut mchain = MarkovChain::new();
+    let text: Vec<String> = (0..edges).map( |i| format!("The w{}.", i).repeat(1 + i % 7) ).collect();
+    mchain.parse_document(&text.join(" "));
+    mchain.graph.finalize();
+    let id = mchain.graph.csr.id("The").unwrap();
+    return (mchain, id);
+  }
+
+  // what Csr::next did before the alias table, the other baseline in bench_sampling
+  fn next_binary_search(csr: &Csr, id: u32, rng: &mut impl Rng) -> Option<u32> {
+    let (start, end) = (csr.offsets[id as usize], csr.offsets[id as usize + 1]);
+    let number = rng.gen_range(1..=*csr.cumulative_weights[start..end].last()?);
+    return Some(csr.targets[start + csr.cumulative_weights[start..end].partition_point( |&total| total < number )]);
+  }
+
+  #[test]
+  fn alias_tables_give_every_edge_its_weight() {
+    let (mchain, id) = wide_node(3000);
+    let csr = &mchain.graph.csr;
+    let (start, end) = (csr.offsets[id as usize], csr.offsets[id as usize + 1]);
+    let (n, total) = ((end - start) as u64, csr.cumulative_weights[end - 1] as u64);
+
+    // each edge's share of all the columns, which is what sampling picks from
+    let mut mass = vec!(0; end - start);
+    for column in start..end {
+      mass[column - start] += csr.cutoffs[column];
+      mass[csr.aliases[column] as usize] += total - csr.cutoffs[column];
+    }
+    for edge in start..end {
+      let weight = csr.cumulative_weights[edge] - if edge == start { 0 } else { csr.cumulative_weights[edge - 1] };
+      assert_eq!(mass[edge - start], weight as u64 * n);
+    }
+
+    let mut rng = ChaCha8Rng::seed_from_u64(5);
+    assert!((0..1000).all( |_| csr.next(id, &mut rng).is_some_and( |next| csr.transition_probability(id, next) > 0.0 ) ));
+    assert_eq!(alias_table(&[(0, 5)]), (vec!(5), vec!(0)));
+  }
+
+  // `cargo test --release -- --ignored --nocapture bench_sampling`, on a node with thousands of edges
+  #[test]
+  #[ignore]
+  fn bench_sampling() {
+    let (mchain, id) = wide_node(5000);
+    let csr = &mchain.graph.csr;
+    let mut rng = ChaCha8Rng::seed_from_u64(5);
+
+    let start = std::time::Instant::now();
+    let picked: u64 = (0..1_000_000).map( |_| csr.next(id, &mut rng).unwrap() as u64 ).sum();
+    println!("alias table: {:?} ({})", start.elapsed(), picked);
+    let start = std::time::Instant::now();
+    let picked: u64 = (0..1_000_000).map( |_| next_binary_search(csr, id, &mut rng).unwrap() as u64 ).sum();
+    println!("binary search: {:?} ({})", start.elapsed(), picked);
+    let start = std::time::Instant::now();
+    let picked: u64 = (0..1_000_000).map( |_| next_linear(csr, id, &mut rng).unwrap() as u64 ).sum();
+    println!("linear scan: {:?} ({})", start.elapsed(), picked);
+  }
+
+  // `cargo test --release -- --ignored --nocapture bench_generation`, a big batch through the reused buffers
+  #[test]
+  #[ignore]
+  fn bench_generation() {
+    let mut mchain = MarkovChain::new().with_seed(42).with_max_chars(280);
+    mchain.create_tweets(Path::new("./seed"), 0).unwrap();
+
+    let start = std::time::Instant::now();
+    let chars: usize = (0..100_000).map( |_| mchain.generate_tweet().unwrap().len() ).sum();
+    println!("generated 100000 tweets ({} bytes) in {:?}", chars, start.elapsed());
+  }
+
+  // stops the tweet after a fixed number of words and remembers what it saw
+  struct StopAfter(usize, Vec<(String, f64)>);
+
+  impl Observer for StopAfter {
+    fn on_transition(&mut self, transition: &Transition) -> Control {
+      self.1.push((transition.word().to_string(), transition.probability()));
+      if self.1.len() == self.0 {
+        return Control::Stop;
+      }
+      return Control::Continue;
+    }
+  }
+
+  #[test]
+  fn observers_see_every_word_and_can_stop_early() {
+    let mut mchain = MarkovChain::new();
+    mchain.create_tweets(Path::new("./txt"), 0).unwrap();
+
+    let mut observer = StopAfter(3, Vec::new());
+    let tweet = mchain.generate_tweet_with(&mut observer).unwrap();
+
+    assert_eq!(tweet, "The syntactic component");
+    assert_eq!(observer.1, vec!(("The".to_string(), 1.0), ("syntactic".to_string(), 1.0), ("component".to_string(), 1.0)));
+  }
+
+  fn tweet_from(text: &str) -> String {
+    let mut mchain = MarkovChain::new().with_seed(1);
+    mchain.parse_document(text);
+    mchain.graph.finalize();
+    return mchain.generate_tweet().unwrap();
+  }
+
+  #[test]
+  fn tiny_corpora() {
+    // the entry word is already a whole sentence
+    assert_eq!(tweet_from("Hi."), "Hi.");
+    // no punctuation anywhere, so we stop when we run out of words
+    assert_eq!(tweet_from("Hi"), "Hi");
+    assert_eq!(tweet_from("Hello world"), "Hello world");
+    // nothing capitalized to start from
+    assert_eq!(tweet_from("hodl."), "hodl.");
+    // a cycle with no way out
+    assert_eq!(tweet_from("Hodl hodl hodl").split(' ').count(), MAX_WORDS);
+  }
+
+  #[test]
+  fn empty_corpus() {
+    let mut mchain = MarkovChain::builder().text("   ").build().unwrap();
+    assert!(matches!(mchain.generate(), Err(ErowidCoinError::Untrained)));
+    mchain.train().unwrap();
+    assert!(matches!(mchain.generate(), Err(ErowidCoinError::EmptyCorpus)));
+    assert!(matches!(mchain.random_entry_word(), Err(ErowidCoinError::EmptyCorpus)));
+    assert!(mchain.stream_tweet().is_err());
+  }
+
+  #[test]
+  fn os_random_still_generates() {
+    let mut mchain = MarkovChain::new().with_rng_source(RngSource::OsRandom);
+
+    let response = mchain.create_tweets(Path::new("./txt"), 1).unwrap();
+    assert!(response[0].ends_with("interpretation."));
+  }
+
+  #[test]
+  fn higher_orders_only_say_what_the_corpus_said() {
+    let corpus: Vec<String> = read_documents(Path::new("./txt")).unwrap().iter().flat_map( |document| document.split_whitespace().map(String::from).collect::<Vec<String>>() ).collect();
+    let mut mchain = MarkovChain::new().with_order(2).unwrap().with_seed(3);
+
+    for tweet in mchain.create_tweets(Path::new("./txt"), 20).unwrap() {
+      let words: Vec<&str> = tweet.split(' ').collect();
+      assert!(mchain.locale().starts_sentence(words[0], None), "{}", tweet);
+      // any three words in a row were three words in a row somewhere in the corpus
+      for window in words.windows(3) {
+        assert!(corpus.windows(3).any( |c| c == window ), "{:?} isn't in the corpus", window);
+      }
+      assert_eq!(mchain.token_probabilities(&tweet).len(), words.len());
+    }
+
+    let continued = mchain.continue_tweet("my favorite grammar must").unwrap();
+    assert!(continued.starts_with("my favorite grammar must specify, for each sentence,"));
+    assert!(matches!(mchain.continue_tweet("grammar can"), Err(ErowidCoinError::UnknownWord(state)) if state == "grammar can"));
+  }
+
+  #[test]
+  fn continues_from_the_last_word() {
+    let mut mchain = MarkovChain::new().with_seed(1);
+
+    let response = mchain.continue_tweets(Path::new("./txt"), "my favorite grammar must", 1).unwrap();
+    assert!(response[0].starts_with("my favorite grammar must specify, for each sentence,"));
+    assert!(response[0].ends_with("interpretation."));
+
+    let error = mchain.continue_tweet("to the moon").unwrap_err();
+    assert!(matches!(error, ErowidCoinError::UnknownWord(word) if word == "moon"));
+
+    // already trained, the second call doesn't count the corpus again
+    let fingerprint = mchain.fingerprint();
+    mchain.create_tweets(Path::new("./txt"), 1).unwrap();
+    assert_eq!(mchain.fingerprint(), fingerprint);
+  }
+
+  #[test]
+  fn prompts_start_tweets() {
+    let mut mchain = MarkovChain::new().with_order(2).unwrap().with_seed(5).with_max_chars(40);
+    mchain.train_documents(["Buy the dip. Buy the top.", "I bought the top and the dip."]);
+
+    for _ in 0..5 {
+      let tweet = mchain.generate_from("Buy").unwrap();
+      assert!(tweet == "Buy the dip." || tweet == "Buy the top.", "{}", tweet);
+    }
+    assert!(mchain.generate_from("Then I bought").unwrap().starts_with("Then I bought the "));
+    assert!(matches!(mchain.generate_from("Sell"), Err(ErowidCoinError::UnknownWord(word)) if word == "Sell"));
+    assert!(matches!(mchain.generate_from(""), Err(ErowidCoinError::UnknownWord(_))));
+  }
+
+  #[test]
+  fn threads_carry_on_from_the_last_tweet() {
+    let mut mchain = MarkovChain::new().with_seed(2).with_max_chars(24);
+    mchain.train_documents(["Acid is tasty. Bitcoin is money. Doge is life."]);
+    let next = HashMap::from([("tasty.", "Bitcoin"), ("money.", "Doge")]);
+
+    for _ in 0..5 {
+      let thread = mchain.generate_thread(3).unwrap();
+      assert_eq!(thread.len(), 3);
+      for (i, tweet) in thread.iter().enumerate() {
+        assert!(tweet.ends_with(&format!(" {}/3", i + 1)) && tweet.chars().count() <= 24, "{:?}", thread);
+      }
+      for pair in thread.windows(2) {
+        let last = pair[0].split(' ').rev().nth(1).unwrap();
+        if let Some(word) = next.get(last) {
+          assert!(pair[1].starts_with(word), "{:?}", thread);
+        }
+      }
+    }
+    assert!(mchain.generate_thread(0).unwrap().is_empty());
+  }
+
+  #[test]
+  fn word_counts_get_resampled() {
+    let mut mchain = MarkovChain::builder().text("Bitcoin. Buy the dip. Buy the dip and the top.").min_words(4).max_words(6).seed(3).build().unwrap();
+    mchain.train().unwrap();
+    for _ in 0..10 {
+      assert_eq!(mchain.generate().unwrap().split(' ').count(), 6);
+    }
+    let mut mchain = MarkovChain::new().with_min_words(2).with_max_attempts(3);
+    mchain.train_documents(["Bitcoin."]);
+    assert!(matches!(mchain.generate(), Err(ErowidCoinError::GaveUp(gave_up)) if gave_up.to_string().contains("min word count")));
+  }
+
+  #[test]
+  fn sampling_reweighs_the_edges() {
+    let train = |sampling: Sampling| {
+      let mut mchain = MarkovChain::new().with_sampling(sampling);
+      mchain.parse_document("Buy the dip. Buy the dip. Buy the dip. Buy the top. Buy the moon.");
+      mchain.finalize();
+      return mchain;
+    };
+    let probabilities = |mchain: &MarkovChain| mchain.candidates("the", 5).into_iter().map( |(_, p)| (p * 100.0).round() / 100.0 ).collect::<Vec<f64>>();
+
+    assert_eq!(probabilities(&train(Sampling::default())), vec!(0.6, 0.2, 0.2));
+    assert_eq!(probabilities(&train(Sampling { temperature: 0.5, top_k: None })), vec!(0.82, 0.09, 0.09));
+    assert_eq!(probabilities(&train(Sampling { temperature: 2.0, top_k: None })), vec!(0.46, 0.27, 0.27));
+    assert_eq!(train(Sampling { temperature: 1.0, top_k: Some(2) }).candidates("the", 5), vec!(("dip.".to_string(), 0.75), ("moon.".to_string(), 0.25)));
+    assert_eq!(train(Sampling { temperature: 0.0, top_k: None }).candidates("the", 5), vec!(("dip.".to_string(), 1.0)));
+
+    // a trained chain takes it on straight away
+    let mut mchain = train(Sampling::default()).with_sampling(Sampling { temperature: 1.0, top_k: Some(1) }).with_seed(1);
+    assert!((0..5).all( |_| mchain.generate().unwrap() == "Buy the dip." ));
+  }
+
+  #[test]
+  fn candidates_are_most_likely_first() {
+    let mut mchain = MarkovChain::new();
+    mchain.parse_document("Buy the dip. Buy the dip. Buy the top.");
+    mchain.graph.finalize();
+
+    assert_eq!(mchain.candidates("the", 5), vec!(("dip.".to_string(), 2.0 / 3.0), ("top.".to_string(), 1.0 / 3.0)));
+    assert_eq!(mchain.candidates("the", 1).len(), 1);
+    assert!(mchain.candidates("top.", 5).is_empty());
+    assert!(mchain.candidates("moon", 5).is_empty());
+  }
+
+  #[test]
+  fn token_probabilities_follow_edge_weights() {
+    let mut mchain = MarkovChain::new();
+    mchain.parse_document("Buy the dip. Buy the dip. Sell the top.");
+    mchain.graph.finalize();
+
+    assert_eq!(mchain.token_probabilities("Buy the top."), vec!(
+      ("Buy".to_string(), 0.5),
+      ("the".to_string(), 1.0),
+      ("top.".to_string(), 1.0 / 3.0),
+    ));
+    assert_eq!(mchain.token_probabilities("Buy the moon.")[2].1, 0.0);
+  }
+
+  #[test]
+  fn gives_up_when_filters_reject_everything() {
+    let mut mchain = MarkovChain::new().with_filter(MaxLength(10)).with_max_attempts(5);
+
+    let error = match mchain.create_tweets(Path::new("./txt"), 1) {
+      Err(ErowidCoinError::GaveUp(error)) => error,
+      other => panic!("expected to give up, got {:?}", other),
+    };
+    assert_eq!(error.attempts, 5);
+    assert_eq!(error.rejections, vec!(("length".to_string(), 5)));
+  }
+
+  // tracks the entry word of every attempt, and rejects everything
+  struct EntryWords(Vec<String>);
+
+  impl Observer for EntryWords {
+    fn on_transition(&mut self, transition: &Transition) -> Control {
+      if transition.previous().is_none() {
+        self.0.push(transition.word().to_string());
+      }
+      return Control::Stop;
+    }
+  }
+
+  #[test]
+  fn dead_end_policies() {
+    let tweets = |policy: DeadEndPolicy| {
+      let mut mchain = MarkovChain::new().with_seed(3).with_dead_end_policy(policy);
+      // nothing comes after "dip"
+      mchain.parse_document("Hodl the moon. Buy the dip");
+      mchain.graph.finalize();
+      return (0..20).map( |_| mchain.generate_tweet().unwrap() ).collect::<Vec<String>>();
+    };
+
+    assert!(tweets(DeadEndPolicy::End).iter().any( |tweet| tweet.ends_with("the dip") ));
+    assert!(tweets(DeadEndPolicy::Resample).iter().all( |tweet| tweet.ends_with("the moon.") ));
+    assert!(tweets(DeadEndPolicy::Restart).iter().all( |tweet| tweet.ends_with("the moon.") ));
+  }
+
+  #[test]
+  fn retry_policies_pick_entry_words() {
+    let entries = |policy: RetryPolicy| {
+      let mut mchain = MarkovChain::new().with_seed(7).with_filter(MaxLength(0)).with_max_attempts(30).with_retry_policy(policy);
+      mchain.parse_document("Buy the dip. Sell the top. Hodl forever.");
+      mchain.graph.finalize();
+
+      let mut observer = EntryWords(Vec::new());
+      assert!(mchain.generate_tweet_with(&mut observer).is_err());
+      return observer.0;
+    };
+
+    let same = entries(RetryPolicy::SameEntry);
+    assert!(same.iter().all( |word| *word == same[0] ));
+
+    // picking at random would repeat the previous entry word about a third of the time, ~10 of these 29 pairs
+    let avoid = entries(RetryPolicy::AvoidFailedEntries);
+    let repeats = avoid.windows(2).filter( |pair| pair[0] == pair[1] ).count();
+    assert!(repeats < 10);
+  }
+
+  #[test]
+  fn cancelled_generation_returns_an_error() {
+    let mut mchain = MarkovChain::new();
+    mchain.create_tweets(Path::new("./txt"), 0).unwrap();
+
+    let token = CancellationToken::new();
+    assert!(mchain.generate_tweet_cancellable(&token).is_ok());
+
+    token.clone().cancel();
+    assert!(matches!(mchain.generate_tweet_cancellable(&token), Err(ErowidCoinError::Cancelled)));
+  }
+
+  #[test]
+  fn chain_is_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<MarkovChain>();
+    assert_send_sync::<Graph>();
+  }
+
+  #[test]
+  fn finalize_builds_csr() {
+    let mut mchain = MarkovChain::new();
+    mchain.parse_document("Buy the dip. Buy the top. Buy the dip.");
+    mchain.graph.finalize();
+
+    let csr = &mchain.graph.csr;
+    let id = |word: &str| csr.words.iter().position( |w| w.as_ref() == word ).unwrap();
+    let the = id("the");
+
+    // "the" goes to "dip." twice and "top." once, in id order
+    assert_eq!(&csr.targets[csr.offsets[the]..csr.offsets[the + 1]], &[id("dip.") as u32, id("top.") as u32]);
+    assert_eq!(&csr.cumulative_weights[csr.offsets[the]..csr.offsets[the + 1]], &[2, 3]);
+    assert_eq!(csr.entry_words, vec!(id("Buy") as u32));
+  }
+
+  #[test]
+  fn memory_footprint_grows_with_the_corpus() {
+    let mut small = MarkovChain::new();
+    small.parse_document("Buy the dip.");
+    small.graph.finalize();
+
+    let mut big = MarkovChain::new();
+    big.parse_document(&read_documents(Path::new("./seed")).unwrap().join(" "));
+    big.graph.finalize();
+
+    assert!(small.memory_footprint().total() > 0);
+    assert!(big.memory_footprint().total() > small.memory_footprint().total());
+    assert!(big.memory_footprint().interner > "Buy the dip.".len());
+  }
+
+  #[test]
+  fn ingestion_report_counts_the_corpus() {
+    let mut mchain = MarkovChain::new();
+    mchain.create_tweets(Path::new("./txt"), 0).unwrap();
+    let report = mchain.ingestion_report();
+
+    assert_eq!(report.documents, 1);
+    assert_eq!(report.words, 28);
+    assert_eq!(report.unique_tokens, 22);
+    assert_eq!(report.top_tokens[0], ("a".to_string(), 3));
+    assert_eq!(report.languages["english"], 1);
+    assert!(report.skipped.is_empty());
+  }
+
+  #[test]
+  fn parallel_ingestion_matches_one_file_at_a_time() {
+    let mut parallel = MarkovChain::new().with_seed(42).with_order(2).unwrap().with_threads(2);
+    parallel.create_tweets(Path::new("./seed"), 0).unwrap();
+    let mut serial = MarkovChain::new().with_seed(42).with_order(2).unwrap();
+    serial.train_documents(read_documents(Path::new("./seed")).unwrap());
+
+    assert_eq!(parallel.fingerprint(), serial.fingerprint());
+    assert_eq!(parallel.graph.entry_words, serial.graph.entry_words);
+    assert_eq!((parallel.ingestion_report().documents, parallel.ingestion_report().words), (2, serial.ingestion_report().words));
+    for _ in 0..5 {
+      assert_eq!(parallel.generate().unwrap(), serial.generate().unwrap());
+    }
+  }
+
+  #[test]
+  fn walks_subdirectories_through_the_patterns() {
+    let dir = std::env::temp_dir().join(format!("erowidcoin-nested-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    for sub in ["reports/2021", ".git", "drafts"] {
+      fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+    fs::write(dir.join("a.txt"), "Hodl the moon.").unwrap();
+    fs::write(dir.join("reports/2021/b.txt"), "Buy the dip.").unwrap();
+    fs::write(dir.join("reports/notes.md"), "Sell the top.").unwrap();
+    fs::write(dir.join("drafts/c.txt"), "Wen lambo.").unwrap();
+    fs::write(dir.join(".git/HEAD"), "ref: refs/heads/main").unwrap();
+    fs::write(dir.join("reports/cover.jpg"), [0xff, 0xd8, 0xff, 0xe0]).unwrap();
+
+    let trained = |mchain: MarkovChain| {
+      let mut mchain = mchain;
+      mchain.create_tweets(&dir, 0).unwrap();
+      return mchain.ingestion_report();
+    };
+    let everything = trained(MarkovChain::new());
+    assert_eq!(everything.documents, 4);
+    assert_eq!(everything.skipped, vec!((dir.join("reports/cover.jpg"), "not utf-8 text".to_string())));
+
+    let some = trained(MarkovChain::new().with_include("*.txt").with_exclude("drafts/**"));
+    assert_eq!((some.documents, some.skipped.len()), (2, 0));
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn document_cap_scales_down_long_documents() {
+    let mut mchain = MarkovChain::new().with_document_cap(2);
+    mchain.parse_document("a b a b a b a b");
+
+    // 7 transitions squeezed into a cap of 2
+    assert_eq!(mchain.graph.edge("a", "b"), Some(1));
+    assert_eq!(mchain.graph.edge("b", "a"), Some(1));
+    assert_eq!(mchain.graph.node("a").unwrap().sum, 1);
+  }
+
+  #[test]
+  fn suppression_drops_rare_transitions() {
+    let train = |suppression: Suppression| {
+      let mut mchain = MarkovChain::new().with_suppression(suppression);
+      mchain.parse_document("Buy the dip. Buy the top.");
+      mchain.parse_document("Buy the dip.");
+      mchain.graph.finalize();
+      mchain
+    };
+
+    // "the top." and "dip. Buy" only ever show up in the first document
+    let mchain = train(Suppression { min_documents: 2, min_count: 1 });
+    assert_eq!(mchain.candidates("the", 5), vec!(("dip.".to_string(), 1.0)));
+    assert!(mchain.candidates("dip.", 5).is_empty());
+    // perplexity only scores what generation could have done
+    assert_eq!(mchain.graph.probability("the", "top."), mchain.graph.probability("the", "moon"));
+    assert_eq!(mchain.graph.probability("the", "dip."), 3.0 / 7.0);
+
+    let mchain = train(Suppression { min_documents: 1, min_count: 3 });
+    assert_eq!(mchain.candidates("Buy", 5), vec!(("the".to_string(), 1.0)));
+    assert!(mchain.candidates("the", 5).is_empty());
+  }
+
+  #[test]
+  fn manifest_weights_files_and_keeps_provenance() {
+    let dir = std::env::temp_dir().join(format!("erowidcoin-manifest-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.txt"), "Hodl the moon.").unwrap();
+    fs::write(dir.join("b.txt"), "Hodl the dip.").unwrap();
+    fs::write(dir.join(manifest::MANIFEST_FILE), "[[file]]\npath = \"a.txt\"\nweight = 3\nlicense = \"CC0\"").unwrap();
+
+    let mut mchain = MarkovChain::new();
+    mchain.create_tweets(&dir, 0).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(mchain.graph.edge("the", "moon."), Some(3));
+    assert_eq!(mchain.graph.edge("the", "dip."), Some(1));
+    assert_eq!(mchain.ingestion_report().documents, 2);
+    assert_eq!(mchain.provenance().files.len(), 1);
+    assert_eq!(mchain.provenance().files[0].license.as_deref(), Some("CC0"));
+  }
+
+  #[test]
+  fn blends_weigh_corpora_the_same_whatever_their_size() {
+    let dir = std::env::temp_dir().join(format!("erowidcoin-blend-{}", std::process::id()));
+    fs::create_dir_all(dir.join("small")).unwrap();
+    fs::create_dir_all(dir.join("big")).unwrap();
+    fs::write(dir.join("small/a.txt"), "Buy the dip.").unwrap();
+    fs::write(dir.join("big/a.txt"), "Buy the moon. ".repeat(10)).unwrap();
+
+    let mut blended = MarkovChain::builder().weighted_corpus(dir.join("small"), 1.0).weighted_corpus(dir.join("big"), 1.0).build().unwrap();
+    blended.train().unwrap();
+    let mut lopsided = MarkovChain::builder().weighted_corpus(dir.join("small"), 1.0).weighted_corpus(dir.join("big"), 0.0).build().unwrap();
+    let refused = lopsided.train();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!((blended.source_of("Dip."), blended.source_of("moon"), blended.source_of("the")), (Some("small"), Some("big"), None));
+    // the small corpus's 2 edges come to as much as the big one's 29
+    assert_eq!((blended.graph.edge("the", "dip."), blended.graph.edge("the", "moon.")), (Some(8), Some(5)));
+    assert_eq!(blended.ingestion_report().documents, 2);
+    assert!(matches!(refused, Err(ErowidCoinError::Corpus(_))));
+  }
+
+  #[test]
+  fn verbatim_copies_get_regenerated_or_given_up_on() {
+    let mut copier = MarkovChain::new().with_seed(3).with_max_overlap(4);
+    copier.train_documents(["Then the walls started breathing at me."]);
+    let Err(ErowidCoinError::GaveUp(gave_up)) = copier.generate() else {
+      panic!("the only thing it can say is the whole document");
+    };
+    assert!(gave_up.to_string().contains("verbatim"), "{}", gave_up);
+
+    // shouting it, or splitting the punctuation off while training, is still the same words in a row
+    let document = "Mom called me, (Eventually) the walls melted.";
+    let mut shouter = MarkovChain::new().with_seed(1).with_max_overlap(3).with_styles(vec!(Style::Shout));
+    shouter.train_documents([document]);
+    assert!(matches!(shouter.generate(), Err(ErowidCoinError::GaveUp(_))));
+    let mut splitter = MarkovChain::new().with_seed(1).with_max_overlap(3).with_tokenizer(Punctuation).with_styles(vec!(Style::Clap));
+    splitter.train_documents([document]);
+    assert!(matches!(splitter.generate(), Err(ErowidCoinError::GaveUp(_))));
+
+    let path = std::env::temp_dir().join(format!("erowidcoin-verbatim-{}.bin", std::process::id()));
+    let documents = ["Then the walls started breathing.", "Then the floor started melting.", "The walls were melting."];
+    let mut mchain = MarkovChain::new().with_seed(3).with_max_overlap(3);
+    mchain.train_documents(documents);
+    mchain.save(&path).unwrap();
+    let mut loaded = MarkovChain::load(&path).unwrap().with_seed(3);
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.filter_names(), vec!("verbatim".to_string()));
+    for mchain in [&mut mchain, &mut loaded] {
+      let tweet = mchain.generate().unwrap();
+      assert!(!documents.iter().any( |document| document.contains(&tweet) ), "{}", tweet);
+    }
+  }
+
+  #[test]
+  fn history_keeps_tweets_from_coming_out_twice() {
+    let history = Arc::new(History::open(Box::new(MemoryStorage::default()), 1.0).unwrap());
+    let mut mchain = MarkovChain::new().with_seed(1).with_max_attempts(10).with_history(history.clone());
+    mchain.train_documents(["Hodl the moon."]);
+
+    assert_eq!(mchain.generate().unwrap(), "Hodl the moon.");
+    assert_eq!(history.len(), 1);
+    let Err(ErowidCoinError::GaveUp(gave_up)) = mchain.generate() else {
+      panic!("there's only the one tweet, and it's been said");
+    };
+    assert!(gave_up.to_string().contains("history"), "{}", gave_up);
+  }
+
+  #[test]
+  fn separators_split_a_dump_into_documents() {
+    let dump = "Hodl the moon.\n-----\nBuy the dip.\n  ------  \n\n-----\n";
+    let mut whole = MarkovChain::new();
+    whole.train_documents([dump]);
+    assert_eq!(whole.ingestion_report().documents, 1);
+    assert!(whole.candidates("moon.", 5).contains(&("-----".to_string(), 1.0)));
+
+    let mut split = MarkovChain::new().with_document_separator("-{5,}").unwrap();
+    split.train_documents([dump]);
+    assert_eq!(split.ingestion_report().documents, 2);
+    // the end of one report doesn't lead into the next
+    assert!(split.candidates("moon.", 5).is_empty());
+    assert!(split.ngrams(1).iter().all( |(word, _)| !word.starts_with('-') ));
+    assert!(MarkovChain::new().with_document_separator("(").is_err());
+  }
+
+  #[test]
+  fn about_sticks_to_tagged_documents() {
+    let path = std::env::temp_dir().join(format!("erowidcoin-about-{}", std::process::id()));
+    let mut mchain = MarkovChain::new().with_seed(2);
+    mchain.train_documents(["Took acid at noon. Then the walls went up.", "Bought xmr at noon. Then the price went up."]);
+    assert_eq!(mchain.ingestion_report().tags, HashMap::from([("lsd".to_string(), 1), ("monero".to_string(), 1)]));
+    mchain.save(&path).unwrap();
+
+    let mut about = MarkovChain::load(&path).unwrap().with_seed(2).with_about("Monero");
+    fs::remove_file(&path).unwrap();
+    let tweets: Vec<String> = (0..50).map( |_| about.generate().unwrap() ).collect();
+    assert!(tweets.iter().all( |tweet| tweet.starts_with("Bought") || tweet.starts_with("Then") ), "{:?}", tweets);
+    // "the" goes to either, but mostly to what the monero document said
+    let count = |word: &str| tweets.iter().filter( |tweet| tweet.contains(word) ).count();
+    assert!(count("price") > 2 * count("walls"), "{:?}", tweets);
+    let mut nothing = MarkovChain::new().with_about("cardano");
+    nothing.train_documents(["Took acid at noon."]);
+    assert!(matches!(nothing.generate(), Err(ErowidCoinError::UnknownTags(tags)) if tags == ["cardano"]));
+  }
+
+  #[test]
+  fn ngrams_count_transitions() {
+    let mut mchain = MarkovChain::new().with_order(2).unwrap();
+    mchain.train_documents(["Buy the dip. Buy the top.", "Buy the dip."]);
+
+    assert_eq!(mchain.ngrams(3)[0], ("Buy the dip.".to_string(), 2));
+    assert_eq!(mchain.ngrams(1), vec!(("Buy".to_string(), 3), ("dip.".to_string(), 1), ("the".to_string(), 1)));
+    assert!(mchain.ngrams(4).is_empty() && mchain.ngrams(0).is_empty());
+  }
+
+  #[test]
+  fn number_styles_go_by_manifest_tag() {
+    let dir = std::env::temp_dir().join(format!("erowidcoin-numbers-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.txt"), "Took 150 mg.").unwrap();
+    fs::write(dir.join("b.txt"), "Took 200 mg.").unwrap();
+    fs::write(dir.join(manifest::MANIFEST_FILE), "[[file]]\npath = \"a.txt\"\ntags = [\"erowid\"]").unwrap();
+
+    let mut mchain = MarkovChain::new().with_numbers(NumberStyle::Bucket).with_tag_numbers("erowid", NumberStyle::Spell);
+    mchain.create_tweets(&dir, 0).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(mchain.graph.edge("Took", "one"), Some(1));
+    assert_eq!(mchain.graph.edge("Took", "NUM"), Some(1));
+    assert!(mchain.graph.node("150").is_none() && mchain.graph.node("200").is_none());
+  }
+
+  #[test]
+  fn frontmatter_is_metadata_not_text() {
+    let dir = std::env::temp_dir().join(format!("erowidcoin-frontmatter-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.txt"), "---\nsubstance: DMT\ndose: 50 mg\n---\nTook 150 mg.").unwrap();
+    fs::write(dir.join("b.txt"), "+++\nweight = \"heavy\"\n+++\nTook 200 mg.").unwrap();
+
+    let mut mchain = MarkovChain::new().with_numbers(NumberStyle::Bucket).with_tag_numbers("dmt", NumberStyle::Keep);
+    mchain.create_tweets(&dir, 0).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(mchain.graph.node("substance:").is_none() && mchain.graph.node("---").is_none());
+    assert_eq!(mchain.graph.edge("Took", "150"), Some(1));
+    let entry = &mchain.provenance().files[0];
+    assert_eq!((entry.path.as_str(), &entry.tags[..]), ("a.txt", &["dmt".to_string()][..]));
+    assert_eq!(entry.metadata["dose"], "50 mg");
+    assert!(mchain.ingestion_report().skipped[0].1.contains("weight should be a positive number"));
+  }
+
+  #[test]
+  fn saved_models_load_back_the_same() {
+    let dir = std::env::temp_dir().join(format!("erowidcoin-model-corpus-{}", std::process::id()));
+    let path = std::env::temp_dir().join(format!("erowidcoin-model-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.txt"), "Hodl the moon. The moon is a dip.").unwrap();
+    fs::write(dir.join("b.txt"), "---\ntitle: Up only\n---\nHodl the dip. Number go up.").unwrap();
+    fs::write(dir.join(manifest::MANIFEST_FILE), "description = \"test\"\n[[file]]\npath = \"a.txt\"\ntags = [\"moon\"]\nweight = 2").unwrap();
+
+    let mut trained = MarkovChain::new().with_order(2).unwrap().with_seed(7);
+    trained.create_tweets(&dir, 0).unwrap();
+    trained.save(&path).unwrap();
+    let mut loaded = MarkovChain::load(&path).unwrap().with_seed(7);
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(loaded.fingerprint(), trained.fingerprint());
+    assert_eq!((loaded.order(), loaded.node_count(), loaded.edge_count()), (2, trained.node_count(), trained.edge_count()));
+    assert_eq!(loaded.provenance(), trained.provenance());
+    for _ in 0..5 {
+      assert_eq!(loaded.generate().unwrap(), trained.generate().unwrap());
+    }
+
+    // cut short, or not a model at all
+    let bytes = fs::read(&path).unwrap();
+    fs::write(&path, &bytes[..bytes.len() - 3]).unwrap();
+    assert!(matches!(MarkovChain::load(&path), Err(ErowidCoinError::Model(error)) if error.kind() == io::ErrorKind::InvalidData));
+    fs::write(&path, "Hodl the moon.").unwrap();
+    assert!(MarkovChain::load(&path).err().unwrap().to_string().contains("not a saved model"));
+
+    // a version 4 file is the same without the folded flag and the (empty) casing at the end
+    let mut old = bytes.clone();
+    old.truncate(bytes.len() - 5);
+    old[model::MAGIC.len()..model::MAGIC.len() + 4].copy_from_slice(&4u32.to_le_bytes());
+    fs::write(&path, &old).unwrap();
+    assert_eq!(MarkovChain::load(&path).unwrap().fingerprint(), trained.fingerprint());
+    let backup = path.with_file_name(format!("{}.v4.bak", path.file_name().unwrap().to_string_lossy()));
+    assert_eq!(fs::read(&backup).unwrap(), old);
+    assert_eq!(fs::read(&path).unwrap(), bytes);
+    fs::remove_file(&backup).unwrap();
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn folded_graphs_put_the_capitals_back() {
+    let path = std::env::temp_dir().join(format!("erowidcoin-folded-{}", std::process::id()));
+    let mut mchain = MarkovChain::new().with_case_folding().with_seed(5);
+    mchain.train_documents(["The moon loves Bitcoin. the dip is the moon.", "Buy the dip. Bitcoin is the moon, says Satoshi."]);
+    let unigrams = mchain.ngrams(1);
+    assert!(unigrams.iter().all( |(word, _)| *word == word.to_lowercase() ), "{:?}", unigrams);
+    assert_eq!(unigrams.iter().find( |(word, _)| word == "the" ).map( |(_, count)| *count ), Some(5));
+
+    for _ in 0..10 {
+      let tweet = mchain.generate().unwrap();
+      assert!(tweet.starts_with(char::is_uppercase) && !tweet.contains("bitcoin") && !tweet.contains(" The "), "{}", tweet);
+    }
+
+    mchain.save(&path).unwrap();
+    let mut loaded = MarkovChain::load(&path).unwrap().with_seed(5);
+    let mut mchain = mchain.with_seed(5);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.fingerprint(), mchain.fingerprint());
+    for _ in 0..5 {
+      assert_eq!(loaded.generate().unwrap(), mchain.generate().unwrap());
+    }
+  }
+
+  #[test]
+  fn training_blocklists_keep_words_out_of_the_graph() {
+    let mut mchain = MarkovChain::new().with_training_blocklist(BannedWords::new(&["rugpull".to_string()])).with_seed(1);
+    mchain.train_documents(["Another rugpull, lol. Hodl the RUGPULL.", "Hodl the moon."]);
+    assert!(mchain.ngrams(1).iter().all( |(word, _)| !word.to_lowercase().contains("rugpull") ));
+    assert!(mchain.ngrams(2).iter().any( |(ngram, _)| ngram == "Another lol." ));
+    assert_eq!(mchain.ingestion_report().banned, 2);
+  }
+
+  #[test]
+  fn uncased_corpora_start_after_full_stops() {
+    let mut mchain = MarkovChain::new().with_locale(Locale::Uncased).with_seed(3);
+    let tweets = mchain.create_tweets(Path::new("./fixtures/hindi"), 20).unwrap();
+
+    let starts: Vec<&str> = mchain.graph.entry_words.iter().map( |&id| mchain.graph.symbols.word(id).as_ref() ).collect();
+    for word in ["मैंने", "बाहर", "बाज़ार", "एक", "वह", "क्या", "मुझे"] {
+      assert!(starts.contains(&word), "{} should start a sentence", word);
+    }
+    assert!(!starts.contains(&"चाय"));
+    for tweet in &tweets {
+      assert!(starts.contains(&tweet.split_whitespace().next().unwrap()));
+      assert!(tweet.ends_with(['।', '?']), "{}", tweet);
+    }
+
+    // devanagari has no capitals, so with the default locale nothing starts a sentence
+    let mut ascii = MarkovChain::new();
+    ascii.create_tweets(Path::new("./fixtures/hindi"), 0).unwrap();
+    assert!(ascii.graph.entry_words.is_empty());
+  }
+
+  #[test]
+  fn mixed_direction_corpora_lose_their_bidi_controls() {
+    let dir = std::env::temp_dir().join(format!("erowidcoin-bidi-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.txt"), "Buy \u{202E}lles\u{202C} the שלום dip. Hodl \u{2067}מחר\u{2069} the moon.\u{200F}").unwrap();
+
+    let mut mchain = MarkovChain::new().with_seed(1);
+    let tweets = mchain.create_tweets(&dir, 10).unwrap();
+    let continued = MarkovChain::new().continue_tweets(&dir, "\u{202E}Hodl the", 3).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(mchain.ingestion_report().sanitized.bidi_controls, 5);
+    assert!(mchain.graph.node("מחר").is_some());
+    for tweet in tweets.iter().chain(&continued) {
+      assert!(!tweet.chars().any(sanitize::is_bidi_control), "{:?}", tweet);
+    }
+  }
+
+  #[test]
+  fn lookalikes_and_invisible_characters_dont_make_new_words() {
+    let dir = std::env::temp_dir().join(format!("erowidcoin-homoglyphs-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    // a cyrillic о, a zero width space, and the real thing
+    fs::write(dir.join("a.txt"), "Hоdl the moon. Ho\u{200B}dl the dip. Hodl the moon.").unwrap();
+
+    let mut mchain = MarkovChain::new();
+    mchain.create_tweets(&dir, 0).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(mchain.graph.node("Hodl").unwrap().count, 3);
+    assert_eq!(mchain.graph.entry_words.len(), 1);
+    let sanitized = mchain.ingestion_report().sanitized;
+    assert_eq!((sanitized.invisible, sanitized.homoglyph_words), (1, 1));
+  }
+
+  #[test]
+  fn poetry_mode_keeps_the_line_breaks() {
+    let dir = std::env::temp_dir().join(format!("erowidcoin-poetry-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.txt"), "The moon is near\nthe chart goes up.\n\n\nThe dip is here\nthe fear goes up.\n").unwrap();
+
+    let mut mchain = MarkovChain::new().with_line_breaks().with_seed(2);
+    let tweets = mchain.create_tweets(&dir, 10).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(mchain.graph.node("up.").unwrap().edges.len(), 1);
+    assert_eq!(mchain.graph.node("near").unwrap().edges.len(), 1);
+    assert!(mchain.graph.edge("near", LINE_BREAK).is_some());
+    for tweet in &tweets {
+      let lines: Vec<&str> = tweet.lines().collect();
+      assert_eq!(lines.len(), 2, "{:?}", tweet);
+      assert!(lines[0].starts_with("The ") && lines[1].starts_with("the ") && lines[1].ends_with(" goes up."));
+    }
+  }
+
+  #[test]
+  fn haiku_lines_hit_their_counts() {
+    let mut mchain = MarkovChain::new().with_seed(4).with_filter(MaxLength(140));
+    mchain.create_tweets(Path::new("./seed"), 0).unwrap();
+
+    for _ in 0..5 {
+      let haiku = mchain.generate_haiku().unwrap();
+      let counts: Vec<usize> = haiku.lines().map(syllables::line).collect();
+      assert_eq!(counts, vec!(5, 7, 5), "{}", haiku);
+    }
+    // only for as long as it's generating
+    assert_eq!(mchain.filter_names(), vec!("length"));
+  }
+
+  #[test]
+  fn acrostics_spell_the_word() {
+    let mut mchain = MarkovChain::new().with_seed(5);
+    mchain.create_tweets(Path::new("./seed"), 0).unwrap();
+
+    let tweet = mchain.generate_acrostic("BTC", false).unwrap();
+    let initials: String = tweet.split_whitespace().filter_map(filter::initial).collect();
+    assert_eq!(initials, "btc", "{}", tweet);
+
+    let lines = mchain.generate_acrostic("gm", true).unwrap();
+    assert_eq!(lines.lines().filter_map(filter::initial).collect::<String>(), "gm");
+
+    // nothing in the corpus starts with a q followed by a q
+    let error = mchain.with_max_attempts(3).generate_acrostic("qqqqq", false).unwrap_err();
+    assert_eq!(error.to_string(), "gave up after 3 attempts, most candidates failed the acrostic filter (3 times)");
+  }
+
+  #[test]
+  fn styles_apply_before_the_filters() {
+    let mut mchain = MarkovChain::new().with_seed(1).with_styles(vec!(Style::Clap)).with_filter(MaxLength(40));
+    mchain.create_tweets(Path::new("./seed"), 0).unwrap();
 
-    let response = mchain.create_tweets(test_path, 1);
-    assert_eq!(response[0], "implement me pls");
+    let tweet = mchain.generate_tweet().unwrap();
+    assert!(tweet.contains(" 👏 ") && tweet.chars().count() <= 40, "{}", tweet);
+    mchain.set_styles(vec!(Style::Shout));
+    let tweet = mchain.generate_tweet().unwrap();
+    assert_eq!(tweet, tweet.to_uppercase());
   }
 }
\ No newline at end of file
@@ -0,0 +1,108 @@
+use std::io;
+use std::collections::BTreeMap;
+use crate::{http, json};
+use crate::publish::{self, Format, Publisher};
+use crate::toml::Value;
+
+// posts a status to any mastodon instance (or anything else that speaks its api: pleroma, akkoma, gotosocial).
+// the access token comes from the instance's preferences, development, new application, with write:statuses
+pub struct MastodonPublisher {
+  // "https://mastodon.social", or just "mastodon.social"
+  instance: String,
+  access_token: String,
+}
+
+impl MastodonPublisher {
+  pub fn new(instance: &str, access_token: &str) -> MastodonPublisher {
+    return MastodonPublisher { instance: instance.to_string(), access_token: access_token.to_string() };
+  }
+
+  // instance and access_token from EROWIDCOIN_MASTODON_INSTANCE and EROWIDCOIN_MASTODON_ACCESS_TOKEN, or the table
+  pub fn from_table(table: &BTreeMap<String, Value>) -> Result<MastodonPublisher, String> {
+    let instance = publish::setting("mastodon", table, "instance", "EROWIDCOIN_MASTODON_INSTANCE")?;
+    let access_token = publish::setting("mastodon", table, "access_token", "EROWIDCOIN_MASTODON_ACCESS_TOKEN")?;
+    return Ok(MastodonPublisher::new(&instance, &access_token));
+  }
+
+  fn statuses_url(&self) -> String {
+    let instance = self.instance.trim().trim_end_matches('/');
+    return match instance.contains("://") {
+      true => format!("{}/api/v1/statuses", instance),
+      false => format!("https://{}/api/v1/statuses", instance),
+    };
+  }
+
+  // mastodon takes an idempotency key, the same key within an hour gives back the same status instead of a new one
+  fn send(&self, text: &str, key: Option<&str>, sensitive: bool) -> io::Result<Option<String>> {
+    let mut headers = vec!(format!("Authorization: Bearer {}", self.access_token));
+    if let Some(key) = key {
+      headers.push(format!("Idempotency-Key: {}", key));
+    }
+    let (status, body) = http::post_json(&self.statuses_url(), &headers, &status_body(text, sensitive))?;
+    let response = json::parse(&body).ok();
+    return match status.as_str() {
+      "200" => match response.as_ref().and_then( |response| response.get("url")?.as_str() ) {
+        Some(url) => Ok(Some(url.to_string())),
+        None => Err(io::Error::other(format!("mastodon said 200 but didn't send back a url: {}", body))),
+      },
+      status => {
+        let message = response.as_ref().and_then( |response| response.get("error")?.as_str() ).unwrap_or(body.trim());
+        Err(http::failed("mastodon", status, message))
+      },
+    };
+  }
+}
+
+// a sensitive status goes out behind a click, the way media marked sensitive does
+fn status_body(text: &str, sensitive: bool) -> String {
+  return match sensitive {
+    true => format!("{{\"status\":{},\"sensitive\":true}}", json::string(text)),
+    false => format!("{{\"status\":{}}}", json::string(text)),
+  };
+}
+
+// so the token doesn't end up in a log by accident
+impl std::fmt::Debug for MastodonPublisher {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    return write!(f, "MastodonPublisher({})", self.instance);
+  }
+}
+
+impl Publisher for MastodonPublisher {
+  fn name(&self) -> String {
+    return "mastodon".to_string();
+  }
+
+  // the link is the status's
+  fn publish(&self, text: &str) -> io::Result<Option<String>> {
+    return self.send(text, None, false);
+  }
+
+  fn publish_with_key(&self, text: &str, key: &str) -> io::Result<Option<String>> {
+    return self.send(text, Some(key), false);
+  }
+
+  fn publish_flagged(&self, text: &str, key: Option<&str>, sensitive: bool) -> io::Result<Option<String>> {
+    return self.send(text, key, sensitive);
+  }
+
+  fn format(&self) -> Format {
+    return Format::platform("mastodon").unwrap();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn finds_the_instances_api() {
+    assert_eq!(MastodonPublisher::new("mastodon.social", "t").statuses_url(), "https://mastodon.social/api/v1/statuses");
+    assert_eq!(MastodonPublisher::new("http://localhost:3000/", "t").statuses_url(), "http://localhost:3000/api/v1/statuses");
+
+    let table = BTreeMap::from([("instance".to_string(), Value::String("botsin.space".to_string()))]);
+    assert!(MastodonPublisher::from_table(&table).is_err_and( |error| error.contains("EROWIDCOIN_MASTODON_ACCESS_TOKEN") ));
+    assert_eq!(format!("{:?}", MastodonPublisher::new("botsin.space", "secret")), "MastodonPublisher(botsin.space)");
+    assert_eq!(status_body("Smoked dmt.", true), "{\"status\":\"Smoked dmt.\",\"sensitive\":true}");
+  }
+}
@@ -0,0 +1,64 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// follower milestones worth a post. we don't talk to any platform's api, so the count comes from a file with just
+// the number in it, kept up to date by whatever does (a cron job with curl is plenty)
+pub struct Milestones {
+  source: PathBuf,
+  at: Vec<u64>,
+  // the count at the last poll. the first poll only sets this, so restarting doesn't celebrate everything again
+  last: Option<u64>,
+}
+
+impl Milestones {
+  pub fn new(source: &Path, mut at: Vec<u64>) -> Milestones {
+    at.sort();
+    return Milestones { source: source.to_path_buf(), at, last: None };
+  }
+
+  // the biggest milestone crossed since the last poll, if any
+  pub fn poll(&mut self) -> io::Result<Option<u64>> {
+    let text = fs::read_to_string(&self.source)?;
+    let count: u64 = text.trim().parse().map_err( |_| io::Error::new(io::ErrorKind::InvalidData, format!("{} should just be a follower count", self.source.display())) )?;
+
+    let crossed = self.last.and_then( |last| self.at.iter().rev().find( |&&milestone| last < milestone && milestone <= count ).copied() );
+    self.last = Some(count);
+    return Ok(crossed);
+  }
+}
+
+// "100,1000,10k"
+pub fn parse(list: &str) -> Result<Vec<u64>, String> {
+  return list.split(',').map( |milestone| {
+    let milestone = milestone.trim().to_lowercase();
+    let (number, multiplier) = match milestone.strip_suffix('k') {
+      Some(number) => (number, 1000),
+      None => (milestone.as_str(), 1),
+    };
+    number.parse::<u64>().map( |n| n * multiplier ).map_err( |_| format!("could not parse milestone {:?}", milestone) )
+  }).collect();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn only_crossing_counts() {
+    let path = std::env::temp_dir().join(format!("erowidcoin-followers-{}", std::process::id()));
+    let mut milestones = Milestones::new(&path, parse("100, 1k, 250").unwrap());
+
+    let mut polls = Vec::new();
+    for count in ["240", "260\n", "270", "1200", "90", "150"] {
+      fs::write(&path, count).unwrap();
+      polls.push(milestones.poll().unwrap());
+    }
+    fs::write(&path, "lots").unwrap();
+    assert!(milestones.poll().is_err());
+    fs::remove_file(&path).unwrap();
+
+    // the first poll is the baseline. dropping below one and climbing back counts again
+    assert_eq!(polls, vec!(None, Some(250), None, Some(1000), None, Some(100)));
+  }
+}
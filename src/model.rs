@@ -0,0 +1,174 @@
+use std::io;
+use std::collections::BTreeMap;
+use crate::manifest::{FileEntry, Provenance};
+
+// a saved model starts with these, so a file that isn't one (or is one from a newer version) gets a clear error
+// instead of garbage. the version goes up whenever the layout after it changes. 2 added the verbatim guard's hashes
+// at the end, 3 each file's frontmatter metadata, 4 the documents' tags on every node after the hashes, 5 case
+// folding and the words' casing after those, 6 the verbatim guard hashing words lowercase and without punctuation.
+// older files still load without them
+pub const MAGIC: &[u8; 8] = b"EROWIDMC";
+pub const VERSION: u32 = 6;
+
+// little endian integers, strings as a u32 length and the utf-8 bytes. nothing clever, the csr it gets rebuilt into
+// is what's fast
+#[derive(Default)]
+pub struct Writer {
+  pub bytes: Vec<u8>,
+}
+
+impl Writer {
+  pub fn u8(&mut self, value: u8) {
+    self.bytes.push(value);
+  }
+
+  pub fn u32(&mut self, value: u32) {
+    self.bytes.extend_from_slice(&value.to_le_bytes());
+  }
+
+  pub fn u64(&mut self, value: u64) {
+    self.bytes.extend_from_slice(&value.to_le_bytes());
+  }
+
+  pub fn i32(&mut self, value: i32) {
+    self.bytes.extend_from_slice(&value.to_le_bytes());
+  }
+
+  pub fn f64(&mut self, value: f64) {
+    self.bytes.extend_from_slice(&value.to_le_bytes());
+  }
+
+  pub fn string(&mut self, value: &str) {
+    self.u32(value.len() as u32);
+    self.bytes.extend_from_slice(value.as_bytes());
+  }
+
+  pub fn optional_string(&mut self, value: Option<&str>) {
+    match value {
+      Some(value) => {
+        self.u8(1);
+        self.string(value);
+      },
+      None => self.u8(0),
+    }
+  }
+}
+
+pub struct Reader<'a> {
+  bytes: &'a [u8],
+  at: usize,
+}
+
+impl<'a> Reader<'a> {
+  pub fn new(bytes: &'a [u8]) -> Reader<'a> {
+    return Reader { bytes, at: 0 };
+  }
+
+  fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+    if self.bytes.len() - self.at < n {
+      return Err(invalid("the model file ends too soon".to_string()));
+    }
+    self.at += n;
+    return Ok(&self.bytes[self.at - n..self.at]);
+  }
+
+  pub fn u8(&mut self) -> io::Result<u8> {
+    return Ok(self.take(1)?[0]);
+  }
+
+  pub fn u32(&mut self) -> io::Result<u32> {
+    return Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()));
+  }
+
+  pub fn u64(&mut self) -> io::Result<u64> {
+    return Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()));
+  }
+
+  pub fn i32(&mut self) -> io::Result<i32> {
+    return Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()));
+  }
+
+  pub fn f64(&mut self) -> io::Result<f64> {
+    return Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()));
+  }
+
+  pub fn string(&mut self) -> io::Result<String> {
+    let len = self.u32()? as usize;
+    return String::from_utf8(self.take(len)?.to_vec()).map_err( |_| invalid("the model file has a string that isn't utf-8".to_string()) );
+  }
+
+  pub fn optional_string(&mut self) -> io::Result<Option<String>> {
+    return match self.u8()? {
+      0 => Ok(None),
+      _ => Ok(Some(self.string()?)),
+    };
+  }
+
+  // the magic and the file's format version, before anything else gets read
+  pub fn header(&mut self) -> io::Result<u32> {
+    if self.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+      return Err(invalid("not a saved model".to_string()));
+    }
+    let version = self.u32()?;
+    if !(1..=VERSION).contains(&version) {
+      return Err(invalid(format!("the model is format version {}, this build reads versions 1 to {}", version, VERSION)));
+    }
+    return Ok(version);
+  }
+
+  // trailing bytes mean we read it wrong, or it isn't what it says it is
+  pub fn finish(&self) -> io::Result<()> {
+    if self.at != self.bytes.len() {
+      return Err(invalid(format!("{} bytes left over at the end of the model file", self.bytes.len() - self.at)));
+    }
+    return Ok(());
+  }
+}
+
+// the attribution has to travel with the model, it's the whole point of keeping it
+pub fn write_provenance(out: &mut Writer, provenance: &Provenance) {
+  out.optional_string(provenance.description.as_deref());
+  out.optional_string(provenance.license.as_deref());
+  out.u32(provenance.files.len() as u32);
+  for entry in &provenance.files {
+    out.string(&entry.path);
+    out.u32(entry.tags.len() as u32);
+    for tag in &entry.tags {
+      out.string(tag);
+    }
+    out.f64(entry.weight);
+    out.optional_string(entry.license.as_deref());
+    out.optional_string(entry.source.as_deref());
+    out.u32(entry.metadata.len() as u32);
+    for (key, value) in &entry.metadata {
+      out.string(key);
+      out.string(value);
+    }
+  }
+}
+
+// version is the file's, see Reader::header
+pub fn read_provenance(reader: &mut Reader, version: u32) -> io::Result<Provenance> {
+  let mut provenance = Provenance { description: reader.optional_string()?, license: reader.optional_string()?, files: Vec::new() };
+  for _ in 0..reader.u32()? {
+    let path = reader.string()?;
+    let mut tags = Vec::new();
+    for _ in 0..reader.u32()? {
+      tags.push(reader.string()?);
+    }
+    let weight = reader.f64()?;
+    let (license, source) = (reader.optional_string()?, reader.optional_string()?);
+    let mut metadata = BTreeMap::new();
+    if version >= 3 {
+      for _ in 0..reader.u32()? {
+        metadata.insert(reader.string()?, reader.string()?);
+      }
+    }
+    provenance.files.push(FileEntry { path, tags, weight, license, source, metadata });
+  }
+  return Ok(provenance);
+}
+
+pub fn invalid(message: String) -> io::Error {
+  return io::Error::new(io::ErrorKind::InvalidData, message);
+}
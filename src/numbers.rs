@@ -0,0 +1,198 @@
+use std::str::FromStr;
+
+// what happens to numbers on the way in. every distinct number is its own word, so "150 mg" and "200 mg" never share
+// a transition, but the exact dose is half the joke in a trip report
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum NumberStyle {
+  // as written
+  #[default]
+  Keep,
+  // every number is NUM and every ordinal NUMth, so they all share their edges
+  Bucket,
+  // "150" -> "one hundred fifty", "21st" -> "twenty-first"
+  Spell,
+}
+
+// the bucketed forms
+pub const NUMBER: &str = "NUM";
+pub const ORDINAL: &str = "NUMth";
+
+impl NumberStyle {
+  pub fn name(&self) -> &'static str {
+    return match self {
+      NumberStyle::Keep => "keep",
+      NumberStyle::Bucket => "bucket",
+      NumberStyle::Spell => "spell",
+    };
+  }
+
+  // rewrites the numbers in `text` and leaves everything else (the whitespace included) alone
+  pub fn apply(&self, text: &str) -> String {
+    if *self == NumberStyle::Keep {
+      return text.to_string();
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while !rest.is_empty() {
+      let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+      let (token, after) = rest.split_at(end);
+      match self.token(token) {
+        Some(rewritten) => out.push_str(&rewritten),
+        None => out.push_str(token),
+      }
+      let space = after.find( |c: char| !c.is_whitespace() ).unwrap_or(after.len());
+      out.push_str(&after[..space]);
+      rest = &after[space..];
+    }
+    return out;
+  }
+
+  // None if the token isn't a number. punctuation around it stays, a unit stuck to the end ("150mg") becomes its
+  // own word
+  fn token(&self, token: &str) -> Option<String> {
+    let start = token.find( |c: char| c.is_alphanumeric() )?;
+    let (lead, token) = token.split_at(start);
+    let digits = token.find( |c: char| !(c.is_ascii_digit() || c == ',' || c == '.') ).unwrap_or(token.len());
+    // a full stop or comma after the number is punctuation, not part of it
+    let digits = token[..digits].trim_end_matches([',', '.']).len();
+    let (number, token) = token.split_at(digits);
+    let letters = token.find( |c: char| !c.is_alphabetic() ).unwrap_or(token.len());
+    let (suffix, trail) = token.split_at(letters);
+    if number.is_empty() || trail.contains(char::is_alphanumeric) {
+      return None;
+    }
+
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    // 1,000 but not 1,00 or 10,00,000
+    let groups: Vec<&str> = whole.split(',').collect();
+    if groups.iter().skip(1).any( |group| group.len() != 3 ) || groups[0].is_empty() || fraction.contains(['.', ',']) {
+      return None;
+    }
+    let value: u64 = groups.concat().parse().ok()?;
+    let ordinal = fraction.is_empty() && ["st", "nd", "rd", "th"].contains(&suffix.to_ascii_lowercase().as_str());
+
+    let word = match (self, ordinal) {
+      (NumberStyle::Bucket, true) => ORDINAL.to_string(),
+      (NumberStyle::Bucket, false) => NUMBER.to_string(),
+      (_, true) => spell_ordinal(value),
+      (_, false) if fraction.is_empty() => spell(value),
+      (_, false) => {
+        let fraction: Vec<&str> = fraction.chars().map( |digit| ONES[digit.to_digit(10).unwrap() as usize] ).collect();
+        format!("{} point {}", spell(value), fraction.join(" "))
+      },
+    };
+    return Some(match (ordinal, suffix) {
+      (true, _) | (false, "") => format!("{}{}{}", lead, word, trail),
+      (false, unit) => format!("{}{} {}{}", lead, word, unit, trail),
+    });
+  }
+}
+
+impl FromStr for NumberStyle {
+  type Err = String;
+
+  fn from_str(value: &str) -> Result<NumberStyle, String> {
+    return match value {
+      "keep" => Ok(NumberStyle::Keep),
+      "bucket" => Ok(NumberStyle::Bucket),
+      "spell" => Ok(NumberStyle::Spell),
+      other => Err(format!("unknown number style {:?}, expected keep, bucket or spell", other)),
+    };
+  }
+}
+
+// a style for the whole corpus, and different ones for files with some tag in the manifest
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Numbers {
+  pub default: NumberStyle,
+  // the first of these a file is tagged with wins
+  pub tags: Vec<(String, NumberStyle)>,
+}
+
+impl Numbers {
+  pub fn style(&self, tags: &[String]) -> NumberStyle {
+    return self.tags.iter().find( |(tag, _)| tags.contains(tag) ).map_or(self.default, |(_, style)| *style);
+  }
+}
+
+const ONES: [&str; 20] = [
+  "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten", "eleven", "twelve", "thirteen",
+  "fourteen", "fifteen", "sixteen", "seventeen", "eighteen", "nineteen",
+];
+const TENS: [&str; 10] = ["", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety"];
+const SCALES: [&str; 7] = ["", "thousand", "million", "billion", "trillion", "quadrillion", "quintillion"];
+
+pub fn spell(n: u64) -> String {
+  if n < 20 {
+    return ONES[n as usize].to_string();
+  }
+  if n < 100 {
+    return match n % 10 {
+      0 => TENS[n as usize / 10].to_string(),
+      ones => format!("{}-{}", TENS[n as usize / 10], ONES[ones as usize]),
+    };
+  }
+  if n < 1000 {
+    return match n % 100 {
+      0 => format!("{} hundred", ONES[n as usize / 100]),
+      rest => format!("{} hundred {}", ONES[n as usize / 100], spell(rest)),
+    };
+  }
+
+  let mut parts = Vec::new();
+  let mut rest = n;
+  for scale in SCALES {
+    let group = rest % 1000;
+    if group > 0 {
+      parts.push(match scale {
+        "" => spell(group),
+        scale => format!("{} {}", spell(group), scale),
+      });
+    }
+    rest /= 1000;
+  }
+  parts.reverse();
+  return parts.join(" ");
+}
+
+// "twenty-one" -> "twenty-first", only the last word changes
+pub fn spell_ordinal(n: u64) -> String {
+  let cardinal = spell(n);
+  let split = cardinal.rfind([' ', '-']).map_or(0, |i| i + 1);
+  let (head, last) = cardinal.split_at(split);
+  let last = match last {
+    "one" => "first".to_string(),
+    "two" => "second".to_string(),
+    "three" => "third".to_string(),
+    "five" => "fifth".to_string(),
+    "eight" => "eighth".to_string(),
+    "nine" => "ninth".to_string(),
+    "twelve" => "twelfth".to_string(),
+    tens if tens.ends_with('y') => format!("{}ieth", &tens[..tens.len() - 1]),
+    other => format!("{}th", other),
+  };
+  return format!("{}{}", head, last);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn numbers_get_kept_bucketed_or_spelled() {
+    let text = "Took 150mg at 10:30, then 2.5 more.\nMy 21st trip of 1,000 (approx.)";
+    assert_eq!(NumberStyle::Keep.apply(text), text);
+    assert_eq!(NumberStyle::Bucket.apply(text), "Took NUM mg at 10:30, then NUM more.\nMy NUMth trip of NUM (approx.)");
+    assert_eq!(
+      NumberStyle::Spell.apply(text),
+      "Took one hundred fifty mg at 10:30, then two point five more.\nMy twenty-first trip of one thousand (approx.)"
+    );
+    assert_eq!(spell(7_000_012), "seven million twelve");
+    assert_eq!(spell_ordinal(40), "fortieth");
+
+    let numbers = Numbers { default: NumberStyle::Bucket, tags: vec!(("erowid".to_string(), NumberStyle::Keep)) };
+    assert_eq!(numbers.style(&["dmt".to_string(), "erowid".to_string()]), NumberStyle::Keep);
+    assert_eq!(numbers.style(&[]), NumberStyle::Bucket);
+  }
+}
@@ -0,0 +1,88 @@
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::markov_chain::Transition;
+
+pub enum Control {
+  Continue,
+  // ends the tweet right after the word we were just told about
+  Stop,
+}
+
+// hooks into every step of generation so library users can trace, stream, or stop early without forking
+// generate_tweet. an observer sees every attempt, including the ones the filters end up throwing away
+pub trait Observer {
+  fn on_transition(&mut self, transition: &Transition) -> Control;
+
+  fn on_rejected(&mut self, _tweet: &str, _filter: &str) {}
+
+  fn on_accepted(&mut self, _tweet: &str) {}
+
+  // checked before every word, return true to abandon generation entirely (unlike Control::Stop, which keeps
+  // the tweet so far)
+  fn cancelled(&self) -> bool {
+    return false;
+  }
+}
+
+// the --trace output: every word, how likely it was, and the runners-up
+pub struct Trace<W: Write> {
+  out: W,
+  alternatives: usize,
+}
+
+impl<W: Write> Trace<W> {
+  pub fn new(out: W, alternatives: usize) -> Trace<W> {
+    return Trace { out, alternatives };
+  }
+}
+
+impl<W: Write> Observer for Trace<W> {
+  fn on_transition(&mut self, transition: &Transition) -> Control {
+    let others: Vec<String> = transition.alternatives(self.alternatives).iter()
+      .filter( |(word, _)| *word != transition.word() )
+      .map( |(word, p)| format!("{} {:.1}%", word, p * 100.0) )
+      .collect();
+
+    // tracing is best effort, a broken stderr shouldn't stop the tweet
+    let _ = writeln!(self.out, "  {:<20} {:>5.1}%   [{}]", transition.word(), transition.probability() * 100.0, others.join(", "));
+    return Control::Continue;
+  }
+
+  fn on_rejected(&mut self, tweet: &str, filter: &str) {
+    let _ = writeln!(self.out, "rejected by {}: {}\n", filter, tweet);
+  }
+
+  fn on_accepted(&mut self, _tweet: &str) {
+    let _ = writeln!(self.out);
+  }
+}
+
+// cheap to clone and hand to another thread, which flips it when e.g. the http request went away.
+// generation checks it between words and between attempts and bails out with ErowidCoinError::Cancelled
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+  pub fn new() -> CancellationToken {
+    return CancellationToken(Arc::new(AtomicBool::new(false)));
+  }
+
+  pub fn cancel(&self) {
+    self.0.store(true, Ordering::Relaxed);
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    return self.0.load(Ordering::Relaxed);
+  }
+}
+
+impl Observer for CancellationToken {
+  fn on_transition(&mut self, _transition: &Transition) -> Control {
+    return Control::Continue;
+  }
+
+  fn cancelled(&self) -> bool {
+    return self.is_cancelled();
+  }
+}
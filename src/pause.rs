@@ -0,0 +1,67 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_PAUSE_FILE: &str = "erowidcoin.paused";
+
+// the big red button. while the file exists nothing gets published, but everything keeps running so there's
+// nothing to restart once it's sorted. it's a file so `touch` works too when the cli isn't handy
+#[derive(Clone, Debug)]
+pub struct KillSwitch {
+  path: PathBuf,
+}
+
+impl KillSwitch {
+  pub fn new(path: &Path) -> KillSwitch {
+    return KillSwitch { path: path.to_path_buf() };
+  }
+
+  // checked right before publishing rather than cached, so pausing takes effect immediately
+  pub fn is_paused(&self) -> bool {
+    return self.path.exists();
+  }
+
+  pub fn pause(&self, reason: &str) -> io::Result<()> {
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).map( |d| d.as_secs() ).unwrap_or(0);
+    return fs::write(&self.path, format!("paused at {}: {}\n", time, reason));
+  }
+
+  // resuming when we weren't paused is fine
+  pub fn resume(&self) -> io::Result<()> {
+    return match fs::remove_file(&self.path) {
+      Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+      _ => Ok(()),
+    };
+  }
+
+  // whatever pause wrote, for showing why we're paused
+  pub fn reason(&self) -> Option<String> {
+    return fs::read_to_string(&self.path).ok().map( |reason| reason.trim().to_string() );
+  }
+}
+
+impl Default for KillSwitch {
+  fn default() -> Self {
+    return KillSwitch::new(Path::new(DEFAULT_PAUSE_FILE));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn pauses_until_resumed() {
+    let switch = KillSwitch::new(&std::env::temp_dir().join(format!("erowidcoin-paused-{}", std::process::id())));
+
+    assert!(!switch.is_paused());
+    switch.pause("went viral").unwrap();
+    assert!(switch.is_paused());
+    assert!(switch.reason().unwrap().ends_with(": went viral"));
+
+    switch.resume().unwrap();
+    switch.resume().unwrap();
+    assert!(!switch.is_paused());
+  }
+}
@@ -0,0 +1,343 @@
+use std::{fmt, fs, io};
+use std::path::{Path, PathBuf};
+use wasmi::{AsContext, AsContextMut, Caller, Config, Engine, Extern, Instance, Linker, Memory, Module, Store, TrapCode, TypedFunc};
+use crate::detokenize::Tokenizer;
+use crate::filter::Filter;
+use crate::http;
+use crate::publish::{Publisher, Transient};
+
+// plugins are webassembly modules, so a niche integration can live in its own repo in whatever language compiles to
+// wasm, and runs sandboxed: no files, no network, nothing but what's passed in. every .wasm in the plugins directory
+// is one. strings cross in the plugin's own memory, as a pointer and a length, and come back packed into an i64 as
+// ptr << 32 | len. a plugin exports
+//
+//   memory                       its linear memory
+//   alloc(len: i32) -> i32       somewhere to put len bytes, for what the host passes in
+//   describe() -> i64            "filter <name>", "tokenizer <name>" or "publisher <name>"
+//
+// and the function for its kind, which gets the tweet (or document, or post) as utf-8:
+//
+//   filter(ptr, len) -> i32      nonzero lets it through, zero throws it out
+//   tokenize(ptr, len) -> i64    its tokens, one per line (an empty line is a line break, for --poetry). tokens
+//                                can't have spaces in them
+//   publish(ptr, len) -> i64     "ok\n<link>" (or "ok\n" for no link), "retry\n<reason>" for a failure worth
+//                                retrying, "error\n<reason>" for one that isn't
+//
+// a publisher needs the network, so there's one import for it, the same curl the built in publishers go through:
+//
+//   erowidcoin.post_json(url_ptr, url_len, headers_ptr, headers_len, body_ptr, body_len) -> i64
+//
+// headers are one per line. what comes back is "<status>\n<body>", status 0 when the request never got an answer,
+// with the reason as the body
+//
+// every call gets a fresh instance, nothing carries over from one tweet to the next. a call that burns through its
+// fuel gets stopped, and counts as a failure that isn't worth retrying (a publisher that ran away might have posted
+// anyway)
+pub const PLUGIN_DIR: &str = "plugins";
+
+// how much fuel one call gets, about an instruction each. a tokenizer gets a whole document, so it's generous
+pub const FUEL: u64 = 10_000_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+  Filter,
+  Tokenizer,
+  Publisher,
+}
+
+impl fmt::Display for Kind {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let name = match self {
+      Kind::Filter => "filter",
+      Kind::Tokenizer => "tokenizer",
+      Kind::Publisher => "publisher",
+    };
+    return write!(f, "{}", name);
+  }
+}
+
+#[derive(Clone, Debug)]
+pub struct Plugin {
+  pub name: String,
+  pub kind: Kind,
+  pub path: PathBuf,
+  // see FUEL
+  pub fuel: u64,
+  engine: Engine,
+  module: Module,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PluginError(pub String);
+
+impl fmt::Display for PluginError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    return write!(f, "{}", self.0);
+  }
+}
+
+impl std::error::Error for PluginError {}
+
+impl Plugin {
+  // compiles the module and asks it what it is
+  pub fn load(path: &Path) -> Result<Plugin, PluginError> {
+    let failed = |reason: String| PluginError(format!("plugin {}: {}", path.display(), reason));
+    let wasm = fs::read(path).map_err( |error| failed(format!("couldn't read it: {}", error)) )?;
+    let mut config = Config::default();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config);
+    let module = Module::new(&engine, &wasm).map_err( |error| failed(format!("not a wasm module: {}", error)) )?;
+    let mut plugin = Plugin { name: String::new(), kind: Kind::Filter, path: path.to_path_buf(), fuel: FUEL, engine, module };
+
+    let answer = plugin.instance().and_then( |mut call| {
+      let packed = call.call::<(), i64>("describe", ())?;
+      call.take(packed)
+    }).map_err( |error| failed(format!("describe failed: {}", error)) )?;
+    let (kind, name) = answer.trim().split_once(' ').ok_or_else( || failed(format!("expected `<kind> <name>` from describe, got {:?}", answer.trim())) )?;
+    plugin.kind = match kind {
+      "filter" => Kind::Filter,
+      "tokenizer" => Kind::Tokenizer,
+      "publisher" => Kind::Publisher,
+      other => return Err(failed(format!("unknown kind {:?}, expected filter, tokenizer or publisher", other))),
+    };
+    plugin.name = name.trim().to_string();
+    return Ok(plugin);
+  }
+
+  // a fresh instance with a full tank, and post_json to call
+  fn instance(&self) -> Result<Call, wasmi::Error> {
+    let mut store = Store::new(&self.engine, ());
+    store.set_fuel(self.fuel)?;
+    let mut linker = Linker::new(&self.engine);
+    linker.func_wrap("erowidcoin", "post_json", post_json)?;
+    let instance = linker.instantiate_and_start(&mut store, &self.module)?;
+    let memory = instance.get_memory(&store, "memory").ok_or_else( || wasmi::Error::new("it doesn't export its memory") )?;
+    let alloc = instance.get_typed_func::<i32, i32>(&store, "alloc")?;
+    return Ok(Call { store, instance, memory, alloc });
+  }
+
+  // the plugin's `function` with `text` passed in, whatever it returned
+  fn run<R: wasmi::WasmResults>(&self, function: &str, text: &str) -> io::Result<(Call, R)> {
+    let mut call = self.instance().map_err(failure)?;
+    let (ptr, len) = pass(&mut call.store, call.alloc, call.memory, text).map_err(failure)?;
+    let result = call.call::<(i32, i32), R>(function, (ptr, len)).map_err(failure)?;
+    return Ok((call, result));
+  }
+}
+
+struct Call {
+  store: Store<()>,
+  instance: Instance,
+  memory: Memory,
+  alloc: TypedFunc<i32, i32>,
+}
+
+impl Call {
+  fn call<P: wasmi::WasmParams, R: wasmi::WasmResults>(&mut self, function: &str, params: P) -> Result<R, wasmi::Error> {
+    return self.instance.get_typed_func::<P, R>(&self.store, function)?.call(&mut self.store, params);
+  }
+
+  fn take(&self, packed: i64) -> Result<String, wasmi::Error> {
+    return take(&self.store, self.memory, packed);
+  }
+}
+
+// copies text into the plugin's memory, wherever its alloc says
+fn pass(mut context: impl AsContextMut, alloc: TypedFunc<i32, i32>, memory: Memory, text: &str) -> Result<(i32, i32), wasmi::Error> {
+  let len = i32::try_from(text.len()).map_err( |_| wasmi::Error::new("too much to pass a plugin") )?;
+  let ptr = alloc.call(&mut context, len)?;
+  memory.write(&mut context, ptr as u32 as usize, text.as_bytes())?;
+  return Ok((ptr, len));
+}
+
+// a string the plugin returned, packed ptr << 32 | len
+fn take(context: impl AsContext, memory: Memory, packed: i64) -> Result<String, wasmi::Error> {
+  let (ptr, len) = ((packed as u64 >> 32) as usize, packed as u32 as usize);
+  return read(context, memory, ptr as i32, len as i32);
+}
+
+fn read(context: impl AsContext, memory: Memory, ptr: i32, len: i32) -> Result<String, wasmi::Error> {
+  let mut bytes = vec!(0; len as u32 as usize);
+  memory.read(context, ptr as u32 as usize, &mut bytes)?;
+  return String::from_utf8(bytes).map_err( |_| wasmi::Error::new("it returned a string that isn't utf-8") );
+}
+
+// the host side of erowidcoin.post_json
+fn post_json(mut caller: Caller<'_, ()>, url_ptr: i32, url_len: i32, headers_ptr: i32, headers_len: i32, body_ptr: i32, body_len: i32) -> Result<i64, wasmi::Error> {
+  let memory = caller.get_export("memory").and_then(Extern::into_memory).ok_or_else( || wasmi::Error::new("it doesn't export its memory") )?;
+  let alloc = caller.get_export("alloc").and_then(Extern::into_func).ok_or_else( || wasmi::Error::new("it doesn't export alloc") )?.typed::<i32, i32>(&caller)?;
+  let url = read(&caller, memory, url_ptr, url_len)?;
+  let headers: Vec<String> = read(&caller, memory, headers_ptr, headers_len)?.lines().filter( |header| !header.is_empty() ).map(str::to_string).collect();
+  let body = read(&caller, memory, body_ptr, body_len)?;
+  let answer = match http::post_json(&url, &headers, &body) {
+    Ok((status, body)) => format!("{}\n{}", status, body),
+    Err(error) => format!("0\n{}", error),
+  };
+  let (ptr, len) = pass(&mut caller, alloc, memory, &answer)?;
+  return Ok(((ptr as u32 as u64) << 32 | len as u32 as u64) as i64);
+}
+
+// running out of fuel is the plugin's timeout, a TimedOut error. anything else it did wrong is just an error
+fn failure(error: wasmi::Error) -> io::Error {
+  if error.as_trap_code() == Some(TrapCode::OutOfFuel) {
+    return io::Error::new(io::ErrorKind::TimedOut, "ran out of fuel, stopped it");
+  }
+  return io::Error::other(error.to_string());
+}
+
+// every .wasm in the directory, by file name. one that doesn't answer describe is an error rather than left out, a
+// filter that silently isn't there is worse than not starting
+pub fn discover(dir: &Path) -> Result<Vec<Plugin>, PluginError> {
+  let entries = fs::read_dir(dir).map_err( |error| PluginError(format!("could not read {}: {}", dir.display(), error)) )?;
+  let mut paths: Vec<PathBuf> = entries.filter_map( |entry| entry.ok() ).map( |entry| entry.path() )
+    .filter( |path| path.is_file() && path.extension().is_some_and( |extension| extension == "wasm" ) ).collect();
+  paths.sort();
+  return paths.iter().map( |path| Plugin::load(path) ).collect();
+}
+
+// a publisher for [target.<name>] plugin = "...": a name in PLUGIN_DIR (nostr for plugins/nostr.wasm), or a path
+pub fn publisher(spec: &str) -> Result<PluginPublisher, PluginError> {
+  let path = match spec.contains('/') {
+    true => PathBuf::from(spec),
+    false => Path::new(PLUGIN_DIR).join(spec).with_extension("wasm"),
+  };
+  let plugin = Plugin::load(&path)?;
+  if plugin.kind != Kind::Publisher {
+    return Err(PluginError(format!("plugin {} is a {}, not a publisher", path.display(), plugin.kind)));
+  }
+  return Ok(PluginPublisher(plugin));
+}
+
+// one that fails doesn't let anything through, same as one that says no
+pub struct PluginFilter(pub Plugin);
+
+impl Filter for PluginFilter {
+  fn name(&self) -> &str {
+    return &self.0.name;
+  }
+
+  fn allows(&self, tweet: &str) -> bool {
+    return self.0.run::<i32>("filter", tweet).is_ok_and( |(_, allowed)| allowed != 0 );
+  }
+}
+
+pub struct PluginTokenizer(pub Plugin);
+
+impl Tokenizer for PluginTokenizer {
+  fn tokenize(&self, text: &str) -> io::Result<Vec<String>> {
+    let failed = |error: io::Error| io::Error::new(error.kind(), format!("{} failed: {}", self.0.name, error));
+    let (call, packed) = self.0.run::<i64>("tokenize", text).map_err(failed)?;
+    let tokens = call.take(packed).map_err(failure).map_err(failed)?.lines()
+      .map( |token| if token.trim().is_empty() { "\n".to_string() } else { token.trim().to_string() } )
+      .collect();
+    return Ok(tokens);
+  }
+}
+
+pub struct PluginPublisher(pub Plugin);
+
+impl Publisher for PluginPublisher {
+  fn name(&self) -> String {
+    return self.0.name.clone();
+  }
+
+  fn publish(&self, text: &str) -> io::Result<Option<String>> {
+    let failed = |error: io::Error| io::Error::new(error.kind(), format!("{} failed: {}", self.0.name, error));
+    let (call, packed) = self.0.run::<i64>("publish", text).map_err(failed)?;
+    let answer = call.take(packed).map_err(failure).map_err(failed)?;
+    let (outcome, rest) = answer.split_once('\n').unwrap_or((&answer, ""));
+    let rest = rest.trim().to_string();
+    return match outcome {
+      "ok" => Ok(if rest.is_empty() { None } else { Some(rest) }),
+      "retry" => Err(io::Error::other(Transient(format!("{} failed: {}", self.0.name, rest)))),
+      "error" => Err(io::Error::other(format!("{} failed: {}", self.0.name, rest))),
+      other => Err(io::Error::other(format!("{} failed: expected ok, retry or error, got {:?}", self.0.name, other))),
+    };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::publish;
+
+  // a module that bumps an allocator from 1024, describes itself as `describe`, and has `body` for its function
+  fn module(dir: &Path, name: &str, describe: &str, body: &str) {
+    let wat = format!(r#"(module
+      (import "erowidcoin" "post_json" (func $post_json (param i32 i32 i32 i32 i32 i32) (result i64)))
+      (memory (export "memory") 1)
+      (global $next (mut i32) (i32.const 1024))
+      (data (i32.const 0) "{describe}")
+      (func $alloc (export "alloc") (param $len i32) (result i32)
+        (local $ptr i32)
+        (local.set $ptr (global.get $next))
+        (global.set $next (i32.add (global.get $next) (local.get $len)))
+        (if (i32.gt_u (global.get $next) (i32.mul (memory.size) (i32.const 65536)))
+          (then (drop (memory.grow (i32.const 16)))))
+        (local.get $ptr))
+      (func $pack (param $ptr i32) (param $len i32) (result i64)
+        (i64.or (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32)) (i64.extend_i32_u (local.get $len))))
+      (func (export "describe") (result i64) (call $pack (i32.const 0) (i32.const {len})))
+      {body})"#, describe = describe, len = describe.len(), body = body);
+    fs::write(dir.join(format!("{}.wasm", name)), wat::parse_str(wat).unwrap()).unwrap();
+  }
+
+  #[test]
+  fn runs_plugins_from_a_directory() {
+    let dir = std::env::temp_dir().join(format!("erowidcoin-plugins-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    // anything without an r in it
+    module(&dir, "no-r", "filter no r", r#"(func (export "filter") (param $ptr i32) (param $len i32) (result i32)
+      (local $end i32)
+      (local.set $end (i32.add (local.get $ptr) (local.get $len)))
+      (block $done (loop $next
+        (br_if $done (i32.ge_u (local.get $ptr) (local.get $end)))
+        (if (i32.eq (i32.load8_u (local.get $ptr)) (i32.const 114)) (then (return (i32.const 0))))
+        (local.set $ptr (i32.add (local.get $ptr) (i32.const 1)))
+        (br $next)))
+      (i32.const 1))"#);
+    // every byte on a line of its own
+    module(&dir, "chars", "tokenizer chars", r#"(func (export "tokenize") (param $ptr i32) (param $len i32) (result i64)
+      (local $out i32) (local $i i32)
+      (local.set $out (call $alloc (i32.mul (local.get $len) (i32.const 2))))
+      (block $done (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+        (i32.store8 (i32.add (local.get $out) (i32.mul (local.get $i) (i32.const 2))) (i32.load8_u (i32.add (local.get $ptr) (local.get $i))))
+        (i32.store8 (i32.add (local.get $out) (i32.add (i32.mul (local.get $i) (i32.const 2)) (i32.const 1))) (i32.const 10))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+      (call $pack (local.get $out) (i32.mul (local.get $len) (i32.const 2))))"#);
+    module(&dir, "flaky", "publisher flaky", r#"(data (i32.const 100) "retry\ndown")
+      (func (export "publish") (param i32 i32) (result i64) (call $pack (i32.const 100) (i32.const 10)))"#);
+    fs::write(dir.join("README"), "not a plugin").unwrap();
+
+    let plugins = discover(&dir).unwrap();
+    assert_eq!(plugins.iter().map( |plugin| (plugin.name.as_str(), plugin.kind) ).collect::<Vec<_>>(), vec!(("chars", Kind::Tokenizer), ("flaky", Kind::Publisher), ("no r", Kind::Filter)));
+
+    let filter = PluginFilter(plugins[2].clone());
+    assert!(filter.allows("Moon soon.") && !filter.allows("Another rugpull."));
+    assert_eq!(PluginTokenizer(plugins[0].clone()).tokenize("gm").unwrap(), vec!("g".to_string(), "m".to_string()));
+    assert_eq!(PluginTokenizer(plugins[0].clone()).tokenize(&"hodl ".repeat(50_000)).unwrap().len(), 250_000);
+    let error = publisher(dir.join("flaky.wasm").to_str().unwrap()).unwrap().publish("hodl").unwrap_err();
+    assert!(publish::is_transient(&error) && error.to_string() == "flaky failed: down", "{}", error);
+
+    // posts to a port nothing listens on, and passes on what came back
+    module(&dir, "offline", "publisher offline", r#"(data (i32.const 100) "http://127.0.0.1:9/")
+      (func (export "publish") (param $ptr i32) (param $len i32) (result i64)
+        (call $post_json (i32.const 100) (i32.const 19) (i32.const 0) (i32.const 0) (local.get $ptr) (local.get $len)))"#);
+    let error = PluginPublisher(Plugin::load(&dir.join("offline.wasm")).unwrap()).publish("gm").unwrap_err();
+    assert!(error.to_string().starts_with("offline failed: expected ok, retry or error, got \"0\""), "{}", error);
+
+    // one that never finishes
+    module(&dir, "stuck", "filter stuck", r#"(func (export "filter") (param i32 i32) (result i32) (loop $forever (br $forever)) (i32.const 1))"#);
+    let mut stuck = Plugin::load(&dir.join("stuck.wasm")).unwrap();
+    stuck.fuel = 1_000_000;
+    assert_eq!(stuck.run::<i32>("filter", "gm").map( |(_, allowed)| allowed ).unwrap_err().kind(), io::ErrorKind::TimedOut);
+    assert!(!PluginFilter(stuck).allows("gm"));
+
+    fs::write(dir.join("broken.wasm"), wat::parse_str(r#"(module (memory (export "memory") 1) (data (i32.const 0) "what")
+      (func (export "alloc") (param i32) (result i32) (i32.const 0)) (func (export "describe") (result i64) (i64.const 4)))"#).unwrap()).unwrap();
+    assert!(discover(&dir).unwrap_err().0.contains("expected `<kind> <name>`"));
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,116 @@
+use std::str::FromStr;
+use rand::Rng;
+use rand::distributions::{Distribution, WeightedIndex};
+use crate::style::{Style, Styling};
+
+// one way a post can come out: a plain tweet or a haiku, with styles on top. written as "normal", "haiku",
+// "shout", "haiku+lowercase", "no-punctuation+clap"
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+  pub name: String,
+  pub haiku: bool,
+  pub styles: Vec<Style>,
+}
+
+impl Profile {
+  pub fn is_plain(&self) -> bool {
+    return !self.haiku && self.styles.is_empty();
+  }
+}
+
+impl FromStr for Profile {
+  type Err = String;
+
+  fn from_str(name: &str) -> Result<Profile, String> {
+    let mut profile = Profile { name: name.to_string(), haiku: false, styles: Vec::new() };
+    for part in name.split('+').map(str::trim) {
+      match part {
+        "normal" => {},
+        "haiku" => profile.haiku = true,
+        style => profile.styles.push(style.parse()?),
+      }
+    }
+    return Ok(profile);
+  }
+}
+
+// a distribution over profiles, sampled once per post so the feed isn't all one thing
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mix {
+  profiles: Vec<(Profile, f64)>,
+}
+
+impl Mix {
+  pub fn pick(&self, rng: &mut impl Rng) -> &Profile {
+    if self.profiles.len() == 1 {
+      return &self.profiles[0].0;
+    }
+    let weights = WeightedIndex::new(self.profiles.iter().map( |(_, weight)| *weight )).unwrap();
+    return &self.profiles[weights.sample(rng)].0;
+  }
+}
+
+impl Default for Mix {
+  fn default() -> Self {
+    return Mix { profiles: vec!(("normal".parse().unwrap(), 1.0)) };
+  }
+}
+
+// --style on its own is just a mix: one profile, or every style (and none) equally often
+impl From<&Styling> for Mix {
+  fn from(styling: &Styling) -> Mix {
+    let plain = Profile { name: "normal".to_string(), haiku: false, styles: Vec::new() };
+    let styled = |styles: Vec<Style>| {
+      let names: Vec<&str> = styles.iter().map( |style| style.name() ).collect();
+      return Profile { name: names.join("+"), haiku: false, styles };
+    };
+    return match styling {
+      Styling::Fixed(styles) if styles.is_empty() => Mix { profiles: vec!((plain, 1.0)) },
+      Styling::Fixed(styles) => Mix { profiles: vec!((styled(styles.clone()), 1.0)) },
+      Styling::Random => Mix { profiles: std::iter::once(plain).chain(Style::ALL.iter().map( |style| styled(vec!(*style)) )).map( |profile| (profile, 1.0) ).collect() },
+    };
+  }
+}
+
+// "normal=70,shout=20,haiku=10". the weights don't have to add up to anything
+impl FromStr for Mix {
+  type Err = String;
+
+  fn from_str(spec: &str) -> Result<Mix, String> {
+    let mut profiles = Vec::new();
+    for entry in spec.split(',') {
+      let (name, weight) = entry.split_once('=').ok_or_else( || format!("expected profile=weight, got {:?}", entry) )?;
+      let weight: f64 = weight.trim().parse().map_err( |_| format!("could not parse the weight of {}", name.trim()) )?;
+      if !(weight > 0.0 && weight.is_finite()) {
+        return Err(format!("the weight of {} has to be positive", name.trim()));
+      }
+      profiles.push((name.trim().parse()?, weight));
+    }
+    return Ok(Mix { profiles });
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::SeedableRng;
+  use rand_chacha::ChaCha8Rng;
+
+  #[test]
+  fn picks_profiles_by_weight() {
+    let mix: Mix = "normal=70, shout=20, haiku+lowercase=10".parse().unwrap();
+    assert_eq!(mix.profiles[2].0, Profile { name: "haiku+lowercase".to_string(), haiku: true, styles: vec!(Style::Lowercase) });
+
+    let mut rng = ChaCha8Rng::seed_from_u64(1);
+    let mut counts = [0; 3];
+    for _ in 0..10_000 {
+      let picked = mix.pick(&mut rng);
+      counts[mix.profiles.iter().position( |(profile, _)| profile == picked ).unwrap()] += 1;
+    }
+    assert!((6700..7300).contains(&counts[0]) && (1700..2300).contains(&counts[1]) && (800..1200).contains(&counts[2]), "{:?}", counts);
+
+    assert!("normal=70,yell=30".parse::<Mix>().is_err());
+    assert!("normal=0".parse::<Mix>().is_err());
+    assert_eq!(Mix::from(&Styling::Random).profiles.len(), 5);
+  }
+}
@@ -0,0 +1,473 @@
+use std::{env, fmt};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use crate::json;
+use crate::plugin;
+use crate::truncate;
+use crate::toml::Value;
+use crate::bluesky::BlueskyPublisher;
+use crate::mastodon::MastodonPublisher;
+use crate::twitter::{Credentials, TwitterPublisher};
+
+// everything a [target.<name>] table can have
+pub const TARGET_KEYS: &[&str] = &[
+  "file", "api", "platform", "max_length", "enabled", "consumer_key", "consumer_secret", "access_token", "access_secret",
+  "instance", "handle", "app_password", "service", "plugin",
+];
+
+// what `api` can be
+pub const APIS: &[&str] = &["twitter", "mastodon", "bluesky"];
+
+// somewhere a finished tweet can go. the server doesn't care which, so a test target runs through exactly the
+// same code as the real one
+pub trait Publisher: Send + Sync {
+  // shows up in the audit log
+  fn name(&self) -> String;
+  // where the post can be found afterwards, for the platforms that give it an address
+  fn publish(&self, text: &str) -> io::Result<Option<String>>;
+  // the same, for a post that might have been sent before: publishing the same key twice posts once and gives back
+  // the first one. platforms that take an idempotency key get it passed along, the rest can't do better than publish
+  fn publish_with_key(&self, text: &str, _key: &str) -> io::Result<Option<String>> {
+    return self.publish(text);
+  }
+  // either of those, for a post that might have to go out behind the platform's sensitive content flag (see
+  // sensitive.rs). platforms without one post it like any other
+  fn publish_flagged(&self, text: &str, key: Option<&str>, _sensitive: bool) -> io::Result<Option<String>> {
+    return match key {
+      Some(key) => self.publish_with_key(text, key),
+      None => self.publish(text),
+    };
+  }
+  // the limits and conventions of wherever this ends up, see adapt
+  fn format(&self) -> Format {
+    return Format::default();
+  }
+}
+
+// a failure that's worth trying again in a bit: the network, or the platform being down or rate limiting us.
+// publishers wrap their errors in one (io::Error::other(Transient(..))) when that's what it was
+#[derive(Debug)]
+pub struct Transient(pub String);
+
+impl fmt::Display for Transient {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    return write!(f, "{}", self.0);
+  }
+}
+
+impl std::error::Error for Transient {}
+
+// a Transient, or one of the io errors a dropped connection comes back as
+pub fn is_transient(error: &io::Error) -> bool {
+  use io::ErrorKind::*;
+  return matches!(error.kind(), TimedOut | ConnectionReset | ConnectionAborted | ConnectionRefused | NotConnected | BrokenPipe | Interrupted)
+    || error.get_ref().is_some_and( |inner| inner.is::<Transient>() );
+}
+
+// how a platform counts a post's length
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Counting {
+  #[default]
+  Chars,
+  // twitter counts most of latin, greek and cyrillic as 1 and everything past that (cjk, emoji) as 2
+  Twitter,
+  // bluesky counts what a reader would, a flag or a family emoji is 1
+  Graphemes,
+}
+
+impl Counting {
+  pub fn length(&self, text: &str) -> usize {
+    return match self {
+      Counting::Chars => text.chars().count(),
+      Counting::Twitter => text.chars().map( |c| match c as u32 {
+        0..=0x10ff | 0x2000..=0x200d | 0x2010..=0x201f | 0x2032..=0x2037 => 1,
+        _ => 2,
+      }).sum(),
+      Counting::Graphemes => truncate::graphemes(text).count(),
+    };
+  }
+}
+
+// a platform's limits and conventions, so one generated post comes out right everywhere it goes
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Format {
+  pub max_length: Option<usize>,
+  pub counting: Counting,
+  // discord renders markdown, so a stray * or _ would italicize half the post
+  pub escape_markdown: bool,
+}
+
+impl Format {
+  pub fn platform(name: &str) -> Result<Format, String> {
+    return match name {
+      "twitter" => Ok(Format { max_length: Some(280), counting: Counting::Twitter, escape_markdown: false }),
+      "mastodon" => Ok(Format { max_length: Some(500), counting: Counting::Chars, escape_markdown: false }),
+      "bluesky" => Ok(Format { max_length: Some(300), counting: Counting::Graphemes, escape_markdown: false }),
+      "discord" => Ok(Format { max_length: Some(2000), counting: Counting::Chars, escape_markdown: true }),
+      other => Err(format!("unknown platform {:?}, expected twitter, mastodon, bluesky or discord", other)),
+    };
+  }
+
+  // what actually gets posted. anything too long is cut down to fit, see truncate::fit
+  pub fn adapt(&self, text: &str) -> String {
+    let text = match self.escape_markdown {
+      true => text.chars().fold(String::with_capacity(text.len()), |mut escaped, c| {
+        if matches!(c, '*' | '_' | '~' | '`' | '|' | '>' | '\\') {
+          escaped.push('\\');
+        }
+        escaped.push(c);
+        return escaped;
+      }),
+      false => text.to_string(),
+    };
+    return match self.max_length {
+      Some(max_length) => truncate::fit(&text, max_length, |text| self.counting.length(text) ),
+      None => text,
+    };
+  }
+
+  // adapt, leaving room for a link to the same post somewhere else
+  pub fn with_link(&self, text: &str, link: &str) -> String {
+    let suffix = format!("\n\n{}", link);
+    let room = Format { max_length: self.max_length.map( |n| n.saturating_sub(self.counting.length(&suffix)).max(1) ), ..*self };
+    return room.adapt(text) + &suffix;
+  }
+}
+
+// any publisher, with a platform's format on top
+pub struct Formatted {
+  publisher: Box<dyn Publisher>,
+  format: Format,
+}
+
+impl Publisher for Formatted {
+  fn name(&self) -> String {
+    return self.publisher.name();
+  }
+
+  fn publish(&self, text: &str) -> io::Result<Option<String>> {
+    return self.publisher.publish(text);
+  }
+
+  fn publish_with_key(&self, text: &str, key: &str) -> io::Result<Option<String>> {
+    return self.publisher.publish_with_key(text, key);
+  }
+
+  fn publish_flagged(&self, text: &str, key: Option<&str>, sensitive: bool) -> io::Result<Option<String>> {
+    return self.publisher.publish_flagged(text, key, sensitive);
+  }
+
+  fn format(&self) -> Format {
+    return self.format;
+  }
+}
+
+pub struct StdoutPublisher;
+
+impl Publisher for StdoutPublisher {
+  fn name(&self) -> String {
+    return "stdout".to_string();
+  }
+
+  fn publish(&self, text: &str) -> io::Result<Option<String>> {
+    println!("{}\n", text);
+    return Ok(None);
+  }
+}
+
+// appends json lines, handy as a sandbox and for anything that wants to read posts back
+pub struct FilePublisher {
+  path: PathBuf,
+}
+
+impl FilePublisher {
+  pub fn new(path: &Path) -> FilePublisher {
+    return FilePublisher { path: path.to_path_buf() };
+  }
+}
+
+impl Publisher for FilePublisher {
+  fn name(&self) -> String {
+    return format!("file {}", self.path.display());
+  }
+
+  // the link is the line the post went on
+  fn publish(&self, text: &str) -> io::Result<Option<String>> {
+    return self.write(text, None, false);
+  }
+
+  fn publish_with_key(&self, text: &str, key: &str) -> io::Result<Option<String>> {
+    return self.write(text, Some(key), false);
+  }
+
+  fn publish_flagged(&self, text: &str, key: Option<&str>, sensitive: bool) -> io::Result<Option<String>> {
+    return self.write(text, key, sensitive);
+  }
+}
+
+impl FilePublisher {
+  fn write(&self, text: &str, key: Option<&str>, sensitive: bool) -> io::Result<Option<String>> {
+    let contents = match fs::read_to_string(&self.path) {
+      Ok(contents) => contents,
+      Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+      Err(error) => return Err(error),
+    };
+    let link = |line: usize| Some(format!("{}#{}", self.path.display(), line));
+    if let Some(key) = key {
+      let sent = contents.lines().position( |line| json::parse(line).is_ok_and( |post| post.get("key").and_then(json::Value::as_str) == Some(key) ) );
+      if let Some(line) = sent {
+        return Ok(link(line + 1));
+      }
+    }
+
+    let mut line = format!("{{\"text\":{}", json::string(text));
+    if let Some(key) = key {
+      line += &format!(",\"key\":{}", json::string(key));
+    }
+    if sensitive {
+      line += ",\"sensitive\":true";
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+    writeln!(file, "{}}}", line)?;
+    return Ok(link(contents.lines().count() + 1));
+  }
+}
+
+// the same post in several places. the canonical one goes first and everywhere else gets a link back to it, if
+// it gave us one. a mirror failing doesn't stop the others, the post is already out there
+pub struct CrossPost {
+  canonical: Box<dyn Publisher>,
+  mirrors: Vec<Box<dyn Publisher>>,
+}
+
+impl Publisher for CrossPost {
+  fn name(&self) -> String {
+    let mirrors: Vec<String> = self.mirrors.iter().map( |mirror| mirror.name() ).collect();
+    return format!("{}, then {}", self.canonical.name(), mirrors.join(", "));
+  }
+
+  fn publish(&self, text: &str) -> io::Result<Option<String>> {
+    return self.publish_flagged(text, None, false);
+  }
+
+  fn publish_with_key(&self, text: &str, key: &str) -> io::Result<Option<String>> {
+    return self.publish_flagged(text, Some(key), false);
+  }
+
+  // every mirror gets a key of its own, made from the canonical one
+  fn publish_flagged(&self, text: &str, key: Option<&str>, sensitive: bool) -> io::Result<Option<String>> {
+    return self.send(text, key, sensitive);
+  }
+}
+
+impl CrossPost {
+  fn send(&self, text: &str, key: Option<&str>, sensitive: bool) -> io::Result<Option<String>> {
+    let publish = |publisher: &dyn Publisher, text: &str, key: Option<String>| publisher.publish_flagged(text, key.as_deref(), sensitive);
+    let link = publish(self.canonical.as_ref(), &self.canonical.format().adapt(text), key.map(String::from))?;
+
+    let mut failures = Vec::new();
+    for mirror in &self.mirrors {
+      let format = mirror.format();
+      let post = match &link {
+        Some(link) => format.with_link(text, link),
+        None => format.adapt(text),
+      };
+      if let Err(error) = publish(mirror.as_ref(), &post, key.map( |key| format!("{} {}", key, mirror.name()) )) {
+        failures.push(format!("{}: {}", mirror.name(), error));
+      }
+    }
+    if !failures.is_empty() {
+      return Err(io::Error::other(format!("posted to {} but not to {}", self.canonical.name(), failures.join(", "))));
+    }
+    return Ok(link);
+  }
+}
+
+impl fmt::Debug for dyn Publisher {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    return write!(f, "Publisher({})", self.name());
+  }
+}
+
+// a [target.<name>] table from the config, e.g.
+//
+//   [target.test]
+//   file = "canary.jsonl"
+//   platform = "mastodon"
+//   max_length = 400
+//   enabled = false
+//
+//   [target.production]
+//   api = "twitter"
+//
+//   [target.fedi]
+//   api = "mastodon"
+//   instance = "botsin.space"
+//
+//   [target.sky]
+//   api = "bluesky"
+//   handle = "erowidcoin.bsky.social"
+//
+//   [target.nostr]
+//   plugin = "nostr"    a publisher plugin, see plugin.rs
+//
+// stdout is what you get without a file, an api or a plugin, no limits without a platform or max_length. enabled = false
+// keeps a target out of cross posting. each api's keys and tokens can go in the table too (see
+// twitter::Credentials, MastodonPublisher and BlueskyPublisher), but the environment is the better place for them
+pub fn from_table(name: &str, table: &BTreeMap<String, Value>) -> Result<Box<dyn Publisher>, String> {
+  for key in table.keys() {
+    if !TARGET_KEYS.contains(&key.as_str()) {
+      return Err(format!("unknown key `{}` in [target.{}]", key, name));
+    }
+  }
+
+  let in_target = |error: String| format!("[target.{}]: {}", name, error);
+  let plugin = match table.get("plugin") {
+    Some(_) if table.contains_key("file") || table.contains_key("api") => return Err(format!("[target.{}] is a plugin, it can't have a file or an api too", name)),
+    Some(spec) => Some(spec.as_str().ok_or_else( || format!("target.{}.plugin should be a string", name) )?),
+    None => None,
+  };
+  let publisher: Box<dyn Publisher> = match (table.get("file"), table.get("api"), plugin) {
+    (_, _, Some(spec)) => Box::new(plugin::publisher(spec).map_err( |error| in_target(error.to_string()) )?),
+    (Some(_), Some(_), None) => return Err(format!("[target.{}] can have a file or an api, not both", name)),
+    (Some(file), None, None) => Box::new(FilePublisher::new(Path::new(file.as_str().ok_or_else( || format!("target.{}.file should be a string", name) )?))),
+    (None, Some(api), None) => match api.as_str() {
+      Some("twitter") => Box::new(TwitterPublisher::new(Credentials::from_table(table).map_err(in_target)?)),
+      Some("mastodon") => Box::new(MastodonPublisher::from_table(table).map_err(in_target)?),
+      Some("bluesky") => Box::new(BlueskyPublisher::from_table(table).map_err(in_target)?),
+      _ => return Err(format!("target.{}.api should be one of {}", name, APIS.join(", "))),
+    },
+    (None, None, None) => Box::new(StdoutPublisher),
+  };
+  // an api target already knows its platform
+  let mut format = match table.get("platform") {
+    Some(platform) => Format::platform(platform.as_str().ok_or_else( || format!("target.{}.platform should be a string", name) )?)?,
+    None => publisher.format(),
+  };
+  if let Some(max_length) = table.get("max_length") {
+    let max_length = max_length.as_integer().filter( |n| *n > 0 ).ok_or_else( || format!("target.{}.max_length should be a positive integer", name) )?;
+    format.max_length = Some(max_length as usize);
+  }
+
+  if format == publisher.format() {
+    return Ok(publisher);
+  }
+  return Ok(Box::new(Formatted { publisher, format }));
+}
+
+// a target's setting from the environment, or its table if the variable isn't set. the environment wins so a
+// secret can be kept out of the config file (or swapped for a deploy) without touching it
+pub fn setting(platform: &str, table: &BTreeMap<String, Value>, key: &str, variable: &str) -> Result<String, String> {
+  if let Ok(value) = env::var(variable) {
+    return Ok(value);
+  }
+  return match table.get(key) {
+    Some(value) => Ok(value.as_str().ok_or_else( || format!("{} should be a string", key) )?.to_string()),
+    None => Err(format!("{} needs {} in the config or {} in the environment", platform, key, variable)),
+  };
+}
+
+// the [crosspost] table: every enabled target in `targets`, canonical first
+//
+//   [crosspost]
+//   canonical = "blog"
+//   targets = ["blog", "mastodon", "discord"]
+pub fn from_crosspost(crosspost: &BTreeMap<String, Value>, targets: &BTreeMap<String, Value>) -> Result<Box<dyn Publisher>, String> {
+  let canonical = crosspost.get("canonical").and_then( |c| c.as_str() ).ok_or("crosspost.canonical should be the name of a target")?;
+  let names = crosspost.get("targets").and_then( |t| t.as_array() ).ok_or("crosspost.targets should be a list of target names")?;
+
+  let target = |name: &str| -> Result<(&BTreeMap<String, Value>, bool), String> {
+    let table = targets.get(name).and_then( |t| t.as_table() ).ok_or_else( || format!("crosspost mentions {}, but there's no [target.{}]", name, name) )?;
+    return Ok((table, table.get("enabled").and_then( |e| e.as_bool() ).unwrap_or(true)));
+  };
+  let (table, enabled) = target(canonical)?;
+  if !enabled {
+    return Err(format!("the canonical target {} is disabled", canonical));
+  }
+  let canonical_publisher = from_table(canonical, table)?;
+
+  let mut mirrors = Vec::new();
+  for name in names {
+    let name = name.as_str().ok_or("crosspost.targets should be a list of target names")?;
+    let (table, enabled) = target(name)?;
+    if enabled && name != canonical {
+      mirrors.push(from_table(name, table)?);
+    }
+  }
+  if mirrors.is_empty() {
+    return Ok(canonical_publisher);
+  }
+  return Ok(Box::new(CrossPost { canonical: canonical_publisher, mirrors }));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn file_publisher_appends_lines() {
+    let path = std::env::temp_dir().join(format!("erowidcoin-publish-{}.jsonl", std::process::id()));
+    let publisher = FilePublisher::new(&path);
+
+    publisher.publish("Buy the dip.").unwrap();
+    publisher.publish("Hodl \"the\" moon.").unwrap();
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(contents, "{\"text\":\"Buy the dip.\"}\n{\"text\":\"Hodl \\\"the\\\" moon.\"}\n");
+  }
+
+  #[test]
+  fn cross_posts_link_back_to_the_canonical_post() {
+    let dir = std::env::temp_dir().join(format!("erowidcoin-crosspost-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let config = crate::toml::parse(&format!(r#"
+      [crosspost]
+      canonical = "blog"
+      targets = ["blog", "mastodon", "discord"]
+
+      [target.blog]
+      file = "{0}/blog.jsonl"
+
+      [target.mastodon]
+      file = "{0}/mastodon.jsonl"
+      max_length = 100
+
+      [target.discord]
+      file = "{0}/discord.jsonl"
+      enabled = false
+    "#, dir.display())).unwrap();
+
+    let publisher = from_crosspost(config["crosspost"].as_table().unwrap(), config["target"].as_table().unwrap()).unwrap();
+    publisher.publish("Buy the dip. Hodl.").unwrap();
+    let link = publisher.publish("The moon is next. Wagmi frens, number go up forever and ever and ever.").unwrap().unwrap();
+
+    let blog = fs::read_to_string(dir.join("blog.jsonl")).unwrap();
+    let mastodon = fs::read_to_string(dir.join("mastodon.jsonl")).unwrap();
+    let discord = dir.join("discord.jsonl").exists();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(link, format!("{}/blog.jsonl#2", dir.display()));
+    assert_eq!(blog.lines().nth(1), Some("{\"text\":\"The moon is next. Wagmi frens, number go up forever and ever and ever.\"}"));
+    // the link has to fit in the 100 characters too
+    assert_eq!(mastodon.lines().nth(1), Some(format!("{{\"text\":{}}}", json::string(&format!("The moon is next.\n\n{}", link))).as_str()));
+    assert!(!discord);
+  }
+
+  #[test]
+  fn adapts_to_each_platform() {
+    let tweet = "Buy the dip. The *moon* is next, wagmi";
+    let short = Format { max_length: Some(30), ..Format::default() };
+    assert_eq!(short.adapt(tweet), "Buy the dip.");
+    assert_eq!(Format { max_length: Some(12), ..Format::default() }.adapt("hodl hodl hodl hodl"), "hodl hodl…");
+    assert_eq!(Format::platform("mastodon").unwrap().adapt(tweet), tweet);
+    assert_eq!(Format::platform("discord").unwrap().adapt(tweet), "Buy the dip. The \\*moon\\* is next, wagmi");
+
+    // cjk and emoji weigh double on twitter, and so does the ellipsis
+    assert_eq!(Counting::Twitter.length("gm 🚀"), 5);
+    assert_eq!(Counting::Graphemes.length("gm 👨\u{200D}👩\u{200D}👧🇺🇸"), 5);
+    assert_eq!(Format { max_length: Some(7), counting: Counting::Twitter, ..Format::default() }.adapt("月 に 行く"), "月 に…");
+  }
+}
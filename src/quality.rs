@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+
+// diversity metrics over a batch of generated tweets, so we can tell how repetitive the model is
+pub struct QualityReport {
+  pub samples: usize,
+  pub distinct_1: f64,
+  pub distinct_2: f64,
+  pub self_bleu: f64,
+}
+
+impl QualityReport {
+  pub fn new(tweets: &[String]) -> QualityReport {
+    return QualityReport {
+      samples: tweets.len(),
+      distinct_1: distinct_n(tweets, 1),
+      distinct_2: distinct_n(tweets, 2),
+      self_bleu: self_bleu(tweets),
+    };
+  }
+}
+
+fn ngrams(words: &[&str], n: usize) -> Vec<Vec<String>> {
+  return words.windows(n).map( |w| w.iter().map( |s| s.to_string() ).collect() ).collect();
+}
+
+fn tokenize(tweet: &str) -> Vec<&str> {
+  return tweet.split_whitespace().collect();
+}
+
+// unique n-grams / total n-grams across the whole batch
+pub fn distinct_n(tweets: &[String], n: usize) -> f64 {
+  let mut total = 0;
+  let mut unique: HashMap<Vec<String>, ()> = HashMap::new();
+
+  for tweet in tweets {
+    for gram in ngrams(&tokenize(tweet), n) {
+      total += 1;
+      unique.insert(gram, ());
+    }
+  }
+
+  if total == 0 {
+    return 0.0;
+  }
+  return unique.len() as f64 / total as f64;
+}
+
+// average BLEU-4 of each tweet scored against every other tweet in the batch. higher means more repetitive
+pub fn self_bleu(tweets: &[String]) -> f64 {
+  if tweets.len() < 2 {
+    return 0.0;
+  }
+
+  let mut total = 0.0;
+  for (i, tweet) in tweets.iter().enumerate() {
+    let references: Vec<&String> = tweets.iter().enumerate().filter( |(j, _)| *j != i ).map( |(_, t)| t ).collect();
+    total += bleu(tweet, &references);
+  }
+
+  return total / tweets.len() as f64;
+}
+
+fn count(grams: Vec<Vec<String>>) -> HashMap<Vec<String>, usize> {
+  let mut counts = HashMap::new();
+  for gram in grams {
+    *counts.entry(gram).or_insert(0) += 1;
+  }
+  return counts;
+}
+
+// sentence-level BLEU-4 with add-one smoothing on the higher order precisions so short tweets don't all score 0
+pub fn bleu(candidate: &str, references: &[&String]) -> f64 {
+  let candidate = tokenize(candidate);
+  if candidate.is_empty() {
+    return 0.0;
+  }
+  let references: Vec<Vec<&str>> = references.iter().map( |r| tokenize(r) ).collect();
+
+  let mut log_precision = 0.0;
+  for n in 1..=4 {
+    let candidate_counts = count(ngrams(&candidate, n));
+
+    // clip each n-gram count by the most times it shows up in any single reference
+    let mut max_reference_counts: HashMap<Vec<String>, usize> = HashMap::new();
+    for reference in &references {
+      for (gram, c) in count(ngrams(reference, n)) {
+        let max = max_reference_counts.entry(gram).or_insert(0);
+        *max = (*max).max(c);
+      }
+    }
+
+    let mut matches = 0;
+    let mut total = 0;
+    for (gram, c) in &candidate_counts {
+      matches += (*c).min(*max_reference_counts.get(gram).unwrap_or(&0));
+      total += c;
+    }
+
+    let precision = if n == 1 {
+      if matches == 0 {
+        return 0.0;
+      }
+      matches as f64 / total as f64
+    } else {
+      (matches + 1) as f64 / (total + 1) as f64
+    };
+    log_precision += precision.ln() / 4.0;
+  }
+
+  // brevity penalty against whichever reference is closest in length
+  let length = candidate.len() as i64;
+  let closest = references.iter()
+    .map( |r| r.len() as i64 )
+    .min_by_key( |r| ((r - length).abs(), *r) )
+    .unwrap_or(0);
+  let brevity = if length > closest { 1.0 } else { (1.0 - closest as f64 / length as f64).exp() };
+
+  return brevity * log_precision.exp();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn identical_tweets_are_maximally_repetitive() {
+    let tweets = vec!["the ego dissolved into the blockchain.".to_string(); 3];
+    let report = QualityReport::new(&tweets);
+
+    assert!((report.self_bleu - 1.0).abs() < 1e-9);
+    assert!((report.distinct_1 - 5.0 / 18.0).abs() < 1e-9);
+  }
+
+  #[test]
+  fn unrelated_tweets_have_no_overlap() {
+    let tweets = vec!["I saw colors.".to_string(), "Buy the dip!".to_string()];
+
+    assert_eq!(self_bleu(&tweets), 0.0);
+    assert_eq!(distinct_n(&tweets, 2), 1.0);
+  }
+}
@@ -0,0 +1,354 @@
+use std::env;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use crate::audit::{AuditLog, Entry};
+use crate::clock::Clock;
+use crate::daemon::format_time;
+use crate::json;
+use crate::pause::KillSwitch;
+use crate::publish::Publisher;
+use crate::sha256::{hex, sha256};
+use crate::storage::{self, Storage};
+
+// a post waiting to go out. scheduled_at is unix seconds, None means as soon as possible
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueuedPost {
+  pub text: String,
+  pub scheduled_at: Option<u64>,
+  // goes to the publisher with every attempt, so a retry after a lost response is recognizably the same post
+  pub key: String,
+  // what the platform called it once it went out (empty if it doesn't say). nothing with one gets sent again
+  pub post_id: Option<String>,
+  // MarkovChain::fingerprint of what wrote it, for the audit entry it gets when it goes out
+  pub model: Option<String>,
+  // goes out behind the platform's sensitive content flag, see sensitive.rs
+  pub sensitive: bool,
+}
+
+impl QueuedPost {
+  pub fn new(text: String, scheduled_at: Option<u64>) -> QueuedPost {
+    return QueuedPost { text, scheduled_at, key: format!("{:016x}", rand::random::<u64>()), post_id: None, model: None, sensitive: false };
+  }
+
+  pub fn with_model(mut self, fingerprint: String) -> QueuedPost {
+    self.model = Some(fingerprint);
+    return self;
+  }
+
+  pub fn with_sensitive(mut self, sensitive: bool) -> QueuedPost {
+    self.sensitive = sensitive;
+    return self;
+  }
+
+  pub fn to_json(&self) -> String {
+    let mut json = format!("{{\"text\":{}", json::string(&self.text));
+    if let Some(at) = self.scheduled_at {
+      json += &format!(",\"scheduled_at\":{}", at);
+    }
+    json += &format!(",\"key\":{}", json::string(&self.key));
+    if let Some(post_id) = &self.post_id {
+      json += &format!(",\"post_id\":{}", json::string(post_id));
+    }
+    if let Some(model) = &self.model {
+      json += &format!(",\"model\":{}", json::string(model));
+    }
+    if self.sensitive {
+      json += ",\"sensitive\":true";
+    }
+    return json + "}";
+  }
+
+  pub fn from_json(line: &str) -> Result<QueuedPost, String> {
+    let value = json::parse(line)?;
+    let text = value.get("text").and_then(json::Value::as_str).ok_or("missing text")?.to_string();
+    let scheduled_at = match value.get("scheduled_at") {
+      Some(at) => Some(at.as_u64().ok_or("scheduled_at should be unix seconds")?),
+      None => None,
+    };
+    // posts queued before there were keys get one from what they say, so it's the same every time they're loaded
+    let key = match value.get("key") {
+      Some(key) => key.as_str().ok_or("key should be a string")?.to_string(),
+      None => hex(&sha256(format!("{:?}\n{}", scheduled_at, text).as_bytes()))[..16].to_string(),
+    };
+    let post_id = match value.get("post_id") {
+      Some(post_id) => Some(post_id.as_str().ok_or("post_id should be a string")?.to_string()),
+      None => None,
+    };
+    let model = match value.get("model") {
+      Some(model) => Some(model.as_str().ok_or("model should be a string")?.to_string()),
+      None => None,
+    };
+    let sensitive = match value.get("sensitive") {
+      Some(json::Value::Bool(sensitive)) => *sensitive,
+      Some(_) => return Err("sensitive should be true or false".to_string()),
+      None => false,
+    };
+    return Ok(QueuedPost { text, scheduled_at, key, post_id, model, sensitive });
+  }
+}
+
+// the storage is append only, so a post going out is a record of its own: {"posted":<key>,"post_id":<id>}
+fn posted_record(line: &str) -> Result<Option<(String, String)>, String> {
+  let value = json::parse(line)?;
+  let Some(key) = value.get("posted") else {
+    return Ok(None);
+  };
+  let key = key.as_str().ok_or("posted should be a key")?.to_string();
+  let post_id = value.get("post_id").and_then(json::Value::as_str).ok_or("missing post_id")?.to_string();
+  return Ok(Some((key, post_id)));
+}
+
+// the posts waiting to go out, one json record each
+pub struct Queue {
+  storage: Box<dyn Storage>,
+  // every post gets an entry here just before it goes out, see audit.rs
+  audit_log: Option<PathBuf>,
+}
+
+impl Queue {
+  // the usual json lines file, or a database, see storage::open
+  pub fn new(path: &Path) -> Queue {
+    return Queue::with_storage(storage::open(path, storage::QUEUE));
+  }
+
+  pub fn with_storage(storage: Box<dyn Storage>) -> Queue {
+    return Queue { storage, audit_log: None };
+  }
+
+  pub fn with_audit_log(mut self, path: &Path) -> Queue {
+    self.audit_log = Some(path.to_path_buf());
+    return self;
+  }
+
+  // with the post ids of everything that's gone out filled in
+  pub fn load(&self) -> io::Result<Vec<QueuedPost>> {
+    let mut posts: Vec<QueuedPost> = Vec::new();
+    for (i, record) in self.storage.load()?.iter().enumerate() {
+      let invalid = |error: String| io::Error::new(io::ErrorKind::InvalidData, format!("{} record {}: {}", self.storage.describe(), i + 1, error));
+      match posted_record(record).map_err(invalid)? {
+        Some((key, post_id)) => match posts.iter_mut().find( |post| post.key == key ) {
+          Some(post) => post.post_id = Some(post_id),
+          None => return Err(invalid(format!("{} was posted, but it was never queued", key))),
+        },
+        None => posts.push(QueuedPost::from_json(record).map_err(invalid)?),
+      }
+    }
+    return Ok(posts);
+  }
+
+  pub fn mark_posted(&self, key: &str, post_id: &str) -> io::Result<()> {
+    return self.storage.append(&[format!("{{\"posted\":{},\"post_id\":{}}}", json::string(key), json::string(post_id))]);
+  }
+
+  // sends everything that's due and hasn't gone out yet, marking each one as soon as it's out. stops at the first
+  // failure: running it again retries that post with the same key, and skips everything that made it. returns how
+  // many went out. with an audit log, posts are written to it as they're about to go out, the way the platform will
+  // get them. a retry writes another
+  pub fn post_due(&self, publisher: &dyn Publisher, now: u64) -> io::Result<usize> {
+    let mut posted = 0;
+    for post in self.load()? {
+      if post.post_id.is_some() || post.scheduled_at.is_some_and( |at| at > now ) {
+        continue;
+      }
+      let text = publisher.format().adapt(&post.text);
+      if let Some(path) = &self.audit_log {
+        let context = vec!(
+          ("operator".to_string(), env::var("USER").unwrap_or_default()),
+          ("endpoint".to_string(), "queue post".to_string()),
+          ("destination".to_string(), publisher.name()),
+          ("key".to_string(), post.key.clone()),
+        );
+        // the filters ran wherever it was generated
+        AuditLog::open(path)?.record(&Entry { text: &text, filters: &[], model: post.model.as_deref().unwrap_or_default(), context: &context })?;
+      }
+      let post_id = publisher.publish_flagged(&text, Some(&post.key), post.sensitive)?;
+      self.mark_posted(&post.key, &post_id.unwrap_or_default())?;
+      posted += 1;
+    }
+    return Ok(posted);
+  }
+
+  pub fn append(&self, posts: &[QueuedPost]) -> io::Result<()> {
+    let records: Vec<String> = posts.iter().map( |post| post.to_json() ).collect();
+    return self.storage.append(&records);
+  }
+
+  // queue export is just every post as a json line, re-serialized so a hand-edited queue comes out clean
+  pub fn export(&self, out: &mut impl Write) -> io::Result<usize> {
+    let posts = self.load()?;
+    for post in &posts {
+      writeln!(out, "{}", post.to_json())?;
+    }
+    return Ok(posts.len());
+  }
+
+  // takes a queue export, anything that's already queued (same text or key) is skipped so importing twice is
+  // harmless. returns (imported, skipped)
+  pub fn import(&self, export: &str) -> io::Result<(usize, usize)> {
+    let queued = self.load()?;
+
+    let mut posts: Vec<QueuedPost> = Vec::new();
+    let mut skipped = 0;
+    for (i, line) in export.lines().enumerate().filter( |(_, line)| !line.trim().is_empty() ) {
+      let post = QueuedPost::from_json(line).map_err( |error| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", i + 1, error)) )?;
+      if queued.iter().chain(&posts).any( |p| p.text == post.text || p.key == post.key ) {
+        skipped += 1;
+        continue;
+      }
+      posts.push(post);
+    }
+
+    // nothing gets written unless the whole export parsed
+    self.append(&posts)?;
+    return Ok((posts.len(), skipped));
+  }
+}
+
+// the publishing half of the outbox: every `every` seconds, whatever's due goes out, for `rounds` rounds (forever
+// without). a round that fails is logged and picked up again next time, the daemon generating into the queue never
+// notices
+pub fn publish_loop(queue: &Queue, publisher: &dyn Publisher, kill_switch: &KillSwitch, every: u64, clock: &dyn Clock, rounds: Option<usize>, log: &mut impl Write) -> io::Result<()> {
+  let mut round = 0;
+  while rounds.is_none_or( |rounds| round < rounds ) {
+    let now = clock.now();
+    if kill_switch.is_paused() {
+      writeln!(log, "[{}] paused, not posting ({})", format_time(now), kill_switch.reason().unwrap_or_default())?;
+    } else {
+      match queue.post_due(publisher, now) {
+        Ok(0) => {},
+        Ok(posted) => writeln!(log, "[{}] posted {} to {}", format_time(now), posted, publisher.name())?,
+        Err(error) => writeln!(log, "[{}] posting to {} failed, trying again in {}s: {}", format_time(now), publisher.name(), every, error)?,
+      }
+    }
+    round += 1;
+    clock.sleep_until(now + every);
+  }
+  return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+  use crate::publish::FilePublisher;
+  use crate::storage::MemoryStorage;
+
+  #[test]
+  fn round_trips_posts() {
+    let path = std::env::temp_dir().join(format!("erowidcoin-queue-roundtrip-{}.jsonl", std::process::id()));
+    let queue = Queue::new(&path);
+    assert!(queue.load().unwrap().is_empty());
+
+    let posts = vec!(
+      QueuedPost::new("Buy the \"dip\".".to_string(), Some(1700000000)),
+      QueuedPost::new("Hodl.".to_string(), None).with_sensitive(true),
+    );
+    queue.append(&posts[..1]).unwrap();
+    queue.append(&posts[1..]).unwrap();
+
+    let loaded = queue.load().unwrap();
+    let mut export = Vec::new();
+    queue.export(&mut export).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(loaded, posts);
+
+    // one new post, one that's already there
+    let export = String::from_utf8(export).unwrap().replace("Hodl.", "Wagmi.").replace(&posts[1].key, "fresh");
+    let other = Queue::with_storage(Box::new(MemoryStorage::default()));
+    other.append(&posts[..1]).unwrap();
+    assert_eq!(other.import(&export).unwrap(), (1, 1));
+    assert!(other.import("{\"text\":\"fine\"}\nnot json").is_err());
+    assert_eq!(other.load().unwrap().len(), 2);
+  }
+
+  #[test]
+  fn retries_never_post_twice() {
+    let path = std::env::temp_dir().join(format!("erowidcoin-queue-posted-{}.jsonl", std::process::id()));
+    let queue = Queue::with_storage(Box::new(MemoryStorage::default()));
+    queue.append(&[
+      QueuedPost::new("Buy the dip.".to_string(), Some(100)),
+      QueuedPost::new("Hodl.".to_string(), Some(200)),
+      QueuedPost::new("Wagmi.".to_string(), Some(300)),
+    ]).unwrap();
+    // keyless posts from before keys get the same one every time
+    queue.storage.append(&["{\"text\":\"Old.\"}".to_string()]).unwrap();
+    assert_eq!(queue.load().unwrap()[3].key, queue.load().unwrap()[3].key);
+
+    let publisher = FilePublisher::new(&path);
+    assert_eq!(queue.post_due(&publisher, 250).unwrap(), 3);
+    assert_eq!(queue.post_due(&publisher, 250).unwrap(), 0);
+    assert_eq!(queue.post_due(&publisher, 300).unwrap(), 1);
+
+    // the response got lost after the post went out: the retry hands over the same key and gets the same post back
+    let wagmi = &queue.load().unwrap()[2];
+    assert_eq!(publisher.publish_with_key(&wagmi.text, &wagmi.key).unwrap().as_ref(), wagmi.post_id.as_ref());
+
+    let published = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(published.lines().count(), 4);
+    assert_eq!(queue.load().unwrap()[1].post_id, Some(format!("{}#2", path.display())));
+  }
+
+  #[test]
+  fn audits_what_goes_out() {
+    let path = std::env::temp_dir().join(format!("erowidcoin-queue-audit-{}.jsonl", std::process::id()));
+    let queue = Queue::with_storage(Box::new(MemoryStorage::default())).with_audit_log(&path);
+    let imported = QueuedPost::new("Imported.".to_string(), None).to_json();
+    queue.import(&imported).unwrap();
+    queue.append(&[QueuedPost::new("wagmi and then some".to_string(), None).with_model("abc".to_string()).with_sensitive(true)]).unwrap();
+
+    let published = std::env::temp_dir().join(format!("erowidcoin-queue-audit-{}.txt", std::process::id()));
+    let target = crate::toml::parse(&format!("file = {:?}\nmax_length = 12", published.display().to_string())).unwrap();
+    let publisher = crate::publish::from_table("test", &target).unwrap();
+    assert_eq!(queue.post_due(publisher.as_ref(), 0).unwrap(), 2);
+    let records = crate::audit::read(&path).unwrap();
+    assert_eq!(crate::audit::verify(&path).unwrap(), 2);
+    fs::remove_file(&path).unwrap();
+    let sent = fs::read_to_string(&published).unwrap();
+    fs::remove_file(&published).unwrap();
+    assert!(!sent.lines().next().unwrap().contains("sensitive") && sent.lines().nth(1).unwrap().ends_with(",\"sensitive\":true}"), "{}", sent);
+    assert_eq!(records[0].text, "Imported.");
+    // what went out, not what was queued
+    assert_eq!(records[1].text, publisher.format().adapt("wagmi and then some"));
+    assert_ne!(records[1].text, "wagmi and then some");
+    assert_eq!(records[1].context["key"], queue.load().unwrap()[1].key);
+  }
+
+  // down for the first couple of tries
+  struct Flaky {
+    failures: std::sync::Mutex<usize>,
+    published: std::sync::Mutex<Vec<String>>,
+  }
+
+  impl Publisher for Flaky {
+    fn name(&self) -> String {
+      return "flaky".to_string();
+    }
+
+    fn publish(&self, text: &str) -> io::Result<Option<String>> {
+      let mut failures = self.failures.lock().unwrap();
+      if *failures > 0 {
+        *failures -= 1;
+        return Err(io::Error::other("503"));
+      }
+      self.published.lock().unwrap().push(text.to_string());
+      return Ok(None);
+    }
+  }
+
+  #[test]
+  fn publishing_outages_only_delay_posts() {
+    let queue = Queue::with_storage(Box::new(MemoryStorage::default()));
+    queue.append(&[QueuedPost::new("Gm.".to_string(), None), QueuedPost::new("Later.".to_string(), Some(1000 + 150))]).unwrap();
+    let publisher = Flaky { failures: std::sync::Mutex::new(2), published: std::sync::Mutex::new(Vec::new()) };
+    let clock = crate::clock::FakeClock::new(1000);
+
+    let mut log = Vec::new();
+    publish_loop(&queue, &publisher, &KillSwitch::new(Path::new("/definitely/not/paused")), 60, &clock, Some(4), &mut log).unwrap();
+
+    assert_eq!(*publisher.published.lock().unwrap(), vec!("Gm.", "Later."));
+    let log = String::from_utf8(log).unwrap();
+    assert_eq!(log.matches("failed, trying again in 60s").count(), 2, "{}", log);
+    assert!(queue.load().unwrap().iter().all( |post| post.post_id.is_some() ));
+  }
+}
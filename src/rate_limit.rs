@@ -0,0 +1,95 @@
+use std::time::{Duration, Instant};
+use std::collections::HashMap;
+
+// token buckets, one per client and one for everybody. limits are per minute and a full bucket allows a burst of
+// that many requests
+pub struct RateLimiter {
+  per_client: Option<u32>,
+  global: Option<u32>,
+  clients: HashMap<String, Bucket>,
+  everybody: Bucket,
+  // when the idle clients were last cleared out
+  swept: Instant,
+}
+
+// a bucket nobody's touched for this long has refilled all the way, it's no different from a new one
+const IDLE: Duration = Duration::from_secs(60);
+
+struct Bucket {
+  tokens: f64,
+  updated: Instant,
+}
+
+impl Bucket {
+  fn new(limit: u32, now: Instant) -> Bucket {
+    return Bucket { tokens: limit as f64, updated: now };
+  }
+
+  fn refill(&mut self, limit: u32, now: Instant) {
+    let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+    self.tokens = (self.tokens + elapsed * limit as f64 / 60.0).min(limit as f64);
+    self.updated = now;
+  }
+}
+
+impl RateLimiter {
+  pub fn new(per_client: Option<u32>, global: Option<u32>) -> RateLimiter {
+    let now = Instant::now();
+    return RateLimiter { per_client, global, clients: HashMap::new(), everybody: Bucket::new(global.unwrap_or(0), now), swept: now };
+  }
+
+  // takes a token from both buckets, or neither if either one is empty
+  pub fn allow(&mut self, client: &str, now: Instant) -> bool {
+    if let Some(limit) = self.global {
+      self.everybody.refill(limit, now);
+      if self.everybody.tokens < 1.0 {
+        return false;
+      }
+    }
+
+    if now.saturating_duration_since(self.swept) >= IDLE {
+      self.clients.retain( |_, bucket| now.saturating_duration_since(bucket.updated) < IDLE );
+      self.swept = now;
+    }
+
+    if let Some(limit) = self.per_client {
+      let bucket = self.clients.entry(client.to_string()).or_insert_with( || Bucket::new(limit, now) );
+      bucket.refill(limit, now);
+      if bucket.tokens < 1.0 {
+        return false;
+      }
+      bucket.tokens -= 1.0;
+    }
+
+    if self.global.is_some() {
+      self.everybody.tokens -= 1.0;
+    }
+    return true;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn buckets_refill_over_time() {
+    let start = Instant::now();
+    let mut limiter = RateLimiter::new(Some(2), Some(3));
+
+    assert!(limiter.allow("a", start));
+    assert!(limiter.allow("a", start));
+    assert!(!limiter.allow("a", start));
+    // a's rejection didn't cost the global bucket anything
+    assert!(limiter.allow("b", start));
+    assert!(!limiter.allow("c", start));
+
+    // one request a minute per client is one every 30s here, globally one every 20s
+    assert!(limiter.allow("a", start + Duration::from_secs(30)));
+    assert!(!limiter.allow("a", start + Duration::from_secs(31)));
+
+    // a minute on, everybody but the one asking is forgotten
+    assert!(limiter.allow("d", start + Duration::from_secs(92)));
+    assert_eq!(limiter.clients.keys().collect::<Vec<_>>(), vec!("d"));
+  }
+}
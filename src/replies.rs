@@ -0,0 +1,102 @@
+use std::time::{Duration, Instant};
+use std::collections::{HashMap, VecDeque};
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+
+// who we're willing to reply to and how often, so nobody can bait the bot into a reply loop
+#[derive(Clone, Debug)]
+pub struct ReplyLimits {
+  // per user, between two replies
+  pub cooldown: Duration,
+  // across everybody
+  pub max_per_hour: usize,
+  // if this isn't empty, only these users get replies
+  pub allow: Vec<String>,
+  pub deny: Vec<String>,
+}
+
+impl Default for ReplyLimits {
+  fn default() -> Self {
+    return ReplyLimits { cooldown: Duration::from_secs(10 * 60), max_per_hour: 20, allow: Vec::new(), deny: Vec::new() };
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Verdict {
+  Reply,
+  Denied,
+  NotAllowed,
+  // how much longer they have to wait
+  CoolingDown(Duration),
+  HourlyCap,
+}
+
+#[derive(Debug)]
+pub struct ReplyGate {
+  limits: ReplyLimits,
+  last_reply: HashMap<String, Instant>,
+  // when we replied during the last hour, oldest first
+  recent: VecDeque<Instant>,
+}
+
+// handles come in as "@Someone" or "someone" depending on the platform
+fn normalize(user: &str) -> String {
+  return user.trim_start_matches('@').to_lowercase();
+}
+
+impl ReplyGate {
+  pub fn new(mut limits: ReplyLimits) -> ReplyGate {
+    limits.allow = limits.allow.iter().map( |user| normalize(user) ).collect();
+    limits.deny = limits.deny.iter().map( |user| normalize(user) ).collect();
+
+    return ReplyGate { limits, last_reply: HashMap::new(), recent: VecDeque::new() };
+  }
+
+  // a Reply verdict counts as replying, so only ask when you're actually going to
+  pub fn check(&mut self, user: &str, now: Instant) -> Verdict {
+    let user = normalize(user);
+
+    if self.limits.deny.contains(&user) {
+      return Verdict::Denied;
+    }
+    if !self.limits.allow.is_empty() && !self.limits.allow.contains(&user) {
+      return Verdict::NotAllowed;
+    }
+    if let Some(last) = self.last_reply.get(&user) {
+      let waited = now.saturating_duration_since(*last);
+      if waited < self.limits.cooldown {
+        return Verdict::CoolingDown(self.limits.cooldown - waited);
+      }
+    }
+
+    while self.recent.front().is_some_and( |sent| now.saturating_duration_since(*sent) >= HOUR ) {
+      self.recent.pop_front();
+    }
+    if self.recent.len() >= self.limits.max_per_hour {
+      return Verdict::HourlyCap;
+    }
+
+    self.recent.push_back(now);
+    self.last_reply.insert(user, now);
+    return Verdict::Reply;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn limits_replies() {
+    let limits = ReplyLimits { cooldown: Duration::from_secs(60), max_per_hour: 2, allow: Vec::new(), deny: vec!("@Troll".to_string()) };
+    let mut gate = ReplyGate::new(limits);
+    let start = Instant::now();
+
+    assert_eq!(gate.check("troll", start), Verdict::Denied);
+    assert_eq!(gate.check("@alice", start), Verdict::Reply);
+    assert_eq!(gate.check("Alice", start + Duration::from_secs(20)), Verdict::CoolingDown(Duration::from_secs(40)));
+    assert_eq!(gate.check("bob", start), Verdict::Reply);
+    assert_eq!(gate.check("carol", start), Verdict::HourlyCap);
+    assert_eq!(gate.check("carol", start + HOUR), Verdict::Reply);
+  }
+}
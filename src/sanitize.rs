@@ -0,0 +1,137 @@
+// bidi embeddings, overrides, isolates and marks. in a post they can make the text around them render in a different
+// order than it's actually in, or hide it, and nobody writes them on purpose. right-to-left text doesn't need them,
+// the renderer works out direction from the letters themselves
+pub fn is_bidi_control(c: char) -> bool {
+  return matches!(c, '\u{061C}' | '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}');
+}
+
+// the text without bidi controls, and how many were taken out
+pub fn strip_bidi_controls(text: &str) -> (String, usize) {
+  let stripped: String = text.chars().filter( |&c| !is_bidi_control(c) ).collect();
+  let removed = text.chars().count() - stripped.chars().count();
+  return (stripped, removed);
+}
+
+// zero width spaces, word joiners, byte order marks, soft hyphens. they don't show up, they just make "hodl" and
+// "ho\u{200B}dl" two different words
+fn is_invisible(c: char) -> bool {
+  return matches!(c, '\u{00AD}' | '\u{180E}' | '\u{200B}' | '\u{2060}' | '\u{FEFF}');
+}
+
+// joiners mean something in emoji sequences and some scripts (persian needs its zwnj), next to ascii they're just spam
+fn is_joiner(c: char) -> bool {
+  return c == '\u{200C}' || c == '\u{200D}';
+}
+
+// the text without invisible characters, and how many were taken out
+pub fn strip_invisible(text: &str) -> (String, usize) {
+  let chars: Vec<char> = text.chars().collect();
+  let mut stripped = String::with_capacity(text.len());
+  let mut removed = 0;
+
+  for (i, &c) in chars.iter().enumerate() {
+    let next_to_ascii = (i > 0 && chars[i - 1].is_ascii()) || chars.get(i + 1).is_some_and( |c| c.is_ascii() );
+    if is_invisible(c) || (is_joiner(c) && next_to_ascii) {
+      removed += 1;
+      continue;
+    }
+    stripped.push(c);
+  }
+  return (stripped, removed);
+}
+
+// cyrillic and greek letters that look just like latin ones, what spam swaps in to dodge filters
+const CONFUSABLES: &[(char, char)] = &[
+  ('а', 'a'), ('е', 'e'), ('һ', 'h'), ('і', 'i'), ('ј', 'j'), ('о', 'o'), ('р', 'p'), ('с', 'c'), ('ѕ', 's'), ('у', 'y'),
+  ('х', 'x'), ('ԁ', 'd'), ('ԛ', 'q'), ('ԝ', 'w'),
+  ('А', 'A'), ('В', 'B'), ('Е', 'E'), ('Н', 'H'), ('І', 'I'), ('Ј', 'J'), ('К', 'K'), ('М', 'M'), ('О', 'O'), ('Р', 'P'),
+  ('С', 'C'), ('Ѕ', 'S'), ('Т', 'T'), ('Х', 'X'), ('Ү', 'Y'),
+  ('α', 'a'), ('ο', 'o'), ('ρ', 'p'), ('ν', 'v'), ('ι', 'i'), ('υ', 'u'),
+  ('Α', 'A'), ('Β', 'B'), ('Ε', 'E'), ('Ζ', 'Z'), ('Η', 'H'), ('Ι', 'I'), ('Κ', 'K'), ('Μ', 'M'), ('Ν', 'N'), ('Ο', 'O'),
+  ('Ρ', 'P'), ('Τ', 'T'), ('Υ', 'Y'), ('Χ', 'X'),
+];
+
+fn latin_lookalike(c: char) -> Option<char> {
+  // fullwidth forms are just ascii shifted up
+  if ('\u{FF01}'..='\u{FF5E}').contains(&c) {
+    return char::from_u32(c as u32 - 0xFEE0);
+  }
+  return CONFUSABLES.iter().find( |(from, _)| *from == c ).map( |(_, to)| *to );
+}
+
+// words that mix latin letters with lookalikes from other scripts get spelled in plain latin. a word that's all
+// cyrillic (or all greek) is left alone, that's just somebody writing russian. returns how many words changed
+pub fn fold_homoglyphs(text: &str) -> (String, usize) {
+  let mut folded = String::with_capacity(text.len());
+  let mut changed = 0;
+
+  for word in text.split_inclusive(char::is_whitespace) {
+    let mixed = word.chars().any( |c| c.is_ascii_alphabetic() ) && word.chars().any( |c| latin_lookalike(c).is_some() );
+    if mixed {
+      folded.extend(word.chars().map( |c| latin_lookalike(c).unwrap_or(c) ));
+      changed += 1;
+    } else {
+      folded.push_str(word);
+    }
+  }
+  return (folded, changed);
+}
+
+// what text looks like, for matching it against words we don't want: nothing invisible, every lookalike turned into
+// the latin letter it's pretending to be, lowercase
+pub fn skeleton(text: &str) -> String {
+  return text.chars()
+    .filter( |&c| !is_bidi_control(c) && !is_invisible(c) && !is_joiner(c) )
+    .map( |c| latin_lookalike(c).unwrap_or(c) )
+    .collect::<String>()
+    .to_lowercase();
+}
+
+// what clean took out of a text
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Sanitized {
+  pub bidi_controls: usize,
+  pub invisible: usize,
+  pub homoglyph_words: usize,
+}
+
+impl Sanitized {
+  pub fn add(&mut self, other: Sanitized) {
+    self.bidi_controls += other.bidi_controls;
+    self.invisible += other.invisible;
+    self.homoglyph_words += other.homoglyph_words;
+  }
+}
+
+// the whole pass ingestion does, in order
+pub fn clean(text: &str) -> (String, Sanitized) {
+  let (text, bidi_controls) = strip_bidi_controls(text);
+  let (text, invisible) = strip_invisible(&text);
+  let (text, homoglyph_words) = fold_homoglyphs(&text);
+  return (text, Sanitized { bidi_controls, invisible, homoglyph_words });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn strips_controls_but_keeps_rtl_text() {
+    // an override that would render "sell" backwards, and an isolate around hebrew
+    let (text, removed) = strip_bidi_controls("Buy \u{202E}sell\u{202C} now \u{2067}שלום\u{2069} מחר.");
+    assert_eq!(text, "Buy sell now שלום מחר.");
+    assert_eq!(removed, 4);
+    assert_eq!(strip_bidi_controls("مرحبا hodl"), ("مرحبا hodl".to_string(), 0));
+  }
+
+  #[test]
+  fn invisible_characters_and_lookalikes() {
+    assert_eq!(strip_invisible("ho\u{200B}dl\u{200D} \u{FEFF}moon"), ("hodl moon".to_string(), 3));
+    // a family emoji and persian keep their joiners
+    assert_eq!(strip_invisible("👨\u{200D}👩 می\u{200C}خواهم").1, 0);
+
+    // "scam" with a cyrillic с and а, next to actual russian
+    assert_eq!(fold_homoglyphs("ѕсam привет Ｈodl"), ("scam привет Hodl".to_string(), 2));
+    assert_eq!(skeleton("Ѕ\u{200B}САМ"), "scam");
+  }
+}
@@ -0,0 +1,99 @@
+use std::str::FromStr;
+use regex::Regex;
+
+// scraped trip reports sometimes have personal details in them, and the bot must never echo those back
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rule {
+  Emails,
+  PhoneNumbers,
+  Handles,
+  // "my friend Dave", keeps the "my friend" part
+  FriendNames,
+}
+
+impl Rule {
+  pub const ALL: [Rule; 4] = [Rule::Emails, Rule::PhoneNumbers, Rule::Handles, Rule::FriendNames];
+
+  pub fn name(&self) -> &'static str {
+    return match self {
+      Rule::Emails => "emails",
+      Rule::PhoneNumbers => "phones",
+      Rule::Handles => "handles",
+      Rule::FriendNames => "names",
+    };
+  }
+
+  // what gets matched, and what it's replaced with
+  fn pattern(&self) -> (&'static str, &'static str) {
+    return match self {
+      Rule::Emails => (r"[\w.+-]+@[\w-]+\.[\w.-]+", ""),
+      Rule::PhoneNumbers => (r"(?:\+\d{1,3}[\s.-]?)?\(?\b\d{3}\)?[\s.-]?\d{3}[\s.-]?\d{4}\b", ""),
+      Rule::Handles => (r"(^|\s)@\w+", "$1"),
+      Rule::FriendNames => (r"\b((?i:my)\s+(?i:friends?|buddy|boyfriend|girlfriend|partner|roommate|brother|sister)\s+)[A-Z][a-z]+", "$1"),
+    };
+  }
+}
+
+impl FromStr for Rule {
+  type Err = String;
+
+  fn from_str(name: &str) -> Result<Rule, String> {
+    return Rule::ALL.iter().find( |rule| rule.name() == name ).copied()
+      .ok_or_else( || format!("unknown scrub rule: {} (expected one of: emails, phones, handles, names)", name) );
+  }
+}
+
+#[derive(Clone)]
+pub struct Scrubber {
+  // emails go first, otherwise the handle rule eats their domains
+  rules: Vec<(Rule, Regex)>,
+}
+
+impl Scrubber {
+  pub fn new(rules: &[Rule]) -> Scrubber {
+    let rules = Rule::ALL.iter()
+      .filter( |rule| rules.contains(rule) )
+      .map( |rule| (*rule, Regex::new(rule.pattern().0).unwrap()) )
+      .collect();
+
+    return Scrubber { rules };
+  }
+
+  pub fn all() -> Scrubber {
+    return Scrubber::new(&Rule::ALL);
+  }
+
+  // the scrubbed text, plus how many matches each rule removed
+  pub fn scrub(&self, text: &str) -> (String, Vec<(Rule, usize)>) {
+    let mut text = text.to_string();
+    let mut counts = Vec::new();
+
+    for (rule, regex) in &self.rules {
+      let matches = regex.find_iter(&text).count();
+      if matches > 0 {
+        text = regex.replace_all(&text, rule.pattern().1).into_owned();
+        counts.push((*rule, matches));
+      }
+    }
+
+    return (text, counts);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn scrubs_personal_details() {
+    let text = "Emailed my friend Dave at dave.k@example.com, call 555-123-4567 or DM @dave_k about it.";
+    let (scrubbed, counts) = Scrubber::all().scrub(text);
+
+    assert_eq!(scrubbed.split_whitespace().collect::<Vec<&str>>().join(" "), "Emailed my friend at , call or DM about it.");
+    assert_eq!(counts, vec!((Rule::Emails, 1), (Rule::PhoneNumbers, 1), (Rule::Handles, 1), (Rule::FriendNames, 1)));
+
+    let (scrubbed, _) = Scrubber::new(&[Rule::Handles]).scrub(text);
+    assert!(scrubbed.contains("dave.k@example.com"));
+    assert!(!scrubbed.contains("@dave_k"));
+  }
+}
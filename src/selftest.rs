@@ -0,0 +1,153 @@
+use std::fs;
+use std::io::{self, Write};
+use std::process::Command;
+use std::sync::Mutex;
+use crate::example;
+use crate::markov_chain::{ErowidCoinError, MarkovChain};
+use crate::publish::Publisher;
+use crate::queue::{Queue, QueuedPost};
+use crate::storage::MemoryStorage;
+
+const SEED: u64 = 42;
+const TWEETS: usize = 20;
+const MAX_CHARS: usize = 280;
+
+fn chain() -> Result<MarkovChain, ErowidCoinError> {
+  let mut builder = MarkovChain::builder().seed(SEED).max_chars(MAX_CHARS);
+  // built in so an install can be checked without a corpus on the machine yet
+  for (_, text) in example::CORPUS {
+    builder = builder.text(text);
+  }
+  return builder.build();
+}
+
+// keeps what it's sent, and posts a key only once the way a real platform with idempotency keys would
+#[derive(Default)]
+struct MockPublisher {
+  posts: Mutex<Vec<(String, String)>>,
+}
+
+impl Publisher for MockPublisher {
+  fn name(&self) -> String {
+    return "mock".to_string();
+  }
+
+  fn publish(&self, text: &str) -> io::Result<Option<String>> {
+    return self.publish_with_key(text, "");
+  }
+
+  fn publish_with_key(&self, text: &str, key: &str) -> io::Result<Option<String>> {
+    let mut posts = self.posts.lock().unwrap();
+    if let Some(i) = posts.iter().position( |(_, k)| !key.is_empty() && k == key ) {
+      return Ok(Some(format!("mock#{}", i + 1)));
+    }
+    posts.push((text.to_string(), key.to_string()));
+    return Ok(Some(format!("mock#{}", posts.len())));
+  }
+}
+
+// train -> finalize -> generate -> check -> post, on the built in corpus with a fixed seed. every step gets a line
+// in `out`, true if they all passed
+pub fn run(out: &mut impl Write) -> io::Result<bool> {
+  let mut failures = 0;
+  let mut report = |name: &str, result: Result<String, String>| -> io::Result<()> {
+    return match result {
+      Ok(detail) => writeln!(out, "ok    {} ({})", name, detail),
+      Err(reason) => {
+        failures += 1;
+        writeln!(out, "FAIL  {}: {}", name, reason)
+      },
+    };
+  };
+
+  let mut mchain = chain().map_err(io::Error::other)?;
+  if let Err(error) = mchain.train() {
+    report("train", Err(error.to_string()))?;
+    return Ok(false);
+  }
+  report("train", Ok(format!("{} nodes, {} edges", mchain.node_count(), mchain.edge_count())))?;
+
+  let mut again = chain().map_err(io::Error::other)?;
+  again.train().map_err(io::Error::other)?;
+  report("finalize", match mchain.fingerprint() == again.fingerprint() {
+    true if mchain.node_count() > 0 => Ok(format!("fingerprint {}", &mchain.fingerprint()[..16])),
+    true => Err("the graph is empty".to_string()),
+    false => Err("training the same corpus twice gave different graphs".to_string()),
+  })?;
+
+  let tweets: Result<Vec<String>, String> = (0..TWEETS).map( |_| mchain.generate().map_err( |error| error.to_string() ) ).collect();
+  let tweets = match tweets {
+    Ok(tweets) => {
+      report("generate", Ok(format!("{} tweets with seed {}", tweets.len(), SEED)))?;
+      tweets
+    },
+    Err(error) => {
+      report("generate", Err(error))?;
+      return Ok(false);
+    },
+  };
+
+  let repeated: Result<Vec<String>, String> = (0..TWEETS).map( |_| again.generate().map_err( |error| error.to_string() ) ).collect();
+  report("seeded", match repeated {
+    Ok(repeated) if repeated == tweets => Ok("the same seed gives the same tweets".to_string()),
+    Ok(_) => Err("the same seed gave different tweets".to_string()),
+    Err(error) => Err(error),
+  })?;
+
+  // everything that comes out has to be something the chain could have said, and fit
+  let broken = tweets.iter().find( |tweet| {
+    tweet.trim().is_empty() || tweet.chars().count() > MAX_CHARS || !mchain.passes_filters(tweet)
+      || mchain.token_probabilities(tweet).iter().any( |(_, p)| *p <= 0.0 )
+  });
+  report("invariants", match broken {
+    Some(tweet) => Err(format!("{:?} shouldn't have come out", tweet)),
+    None => Ok(format!("non-empty, at most {} characters, every transition in the graph", MAX_CHARS)),
+  })?;
+
+  let path = std::env::temp_dir().join(format!("erowidcoin-selftest-{}.bin", std::process::id()));
+  let loaded = mchain.save(&path).and_then( |_| MarkovChain::load(&path) );
+  let _ = fs::remove_file(&path);
+  report("save and load", match loaded {
+    Ok(loaded) if loaded.fingerprint() == mchain.fingerprint() => Ok("same fingerprint after a round trip".to_string()),
+    Ok(_) => Err("the loaded model has a different fingerprint".to_string()),
+    Err(error) => Err(error.to_string()),
+  })?;
+
+  // through the queue twice, the second time has nothing left to send
+  let publisher = MockPublisher::default();
+  let queue = Queue::with_storage(Box::new(MemoryStorage::default()));
+  let posted = queue.append(&[QueuedPost::new(tweets[0].clone(), None)])
+    .and_then( |_| Ok((queue.post_due(&publisher, 0)?, queue.post_due(&publisher, 0)?)) );
+  report("post", match posted {
+    Ok((1, 0)) if publisher.posts.lock().unwrap()[0].0 == tweets[0] => Ok("queued, posted once to a mock publisher".to_string()),
+    Ok((first, second)) => Err(format!("posted {} times then {} more, expected once", first, second)),
+    Err(error) => Err(error.to_string()),
+  })?;
+
+  // only twitter needs it, so it's a warning rather than a failure
+  let curl = Command::new("curl").arg("--version").output().is_ok_and( |output| output.status.success() );
+  if !curl {
+    writeln!(out, "warn  curl isn't installed, posting to twitter won't work")?;
+  }
+
+  if failures == 0 {
+    writeln!(out, "\nall checks passed")?;
+  } else {
+    writeln!(out, "\n{} check(s) failed", failures)?;
+  }
+  return Ok(failures == 0);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn passes_on_a_working_build() {
+    let mut out = Vec::new();
+    let passed = run(&mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(passed, "{}", out);
+    assert_eq!(out.lines().filter( |line| line.starts_with("ok ") ).count(), 7, "{}", out);
+  }
+}
@@ -0,0 +1,71 @@
+use std::str::FromStr;
+use crate::locale::Locale;
+
+// whether a post should go out behind the platform's sensitive-media flag (twitter's possibly_sensitive,
+// mastodon's sensitive). decided per post, after generation, the chain doesn't care
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Sensitivity {
+  #[default]
+  Never,
+  Always,
+  // any of these words (case insensitive, punctuation ignored) marks the post
+  Keywords(Vec<String>),
+}
+
+impl Sensitivity {
+  // the locale decides what "case insensitive" means
+  pub fn is_sensitive(&self, text: &str, locale: Locale) -> bool {
+    return match self {
+      Sensitivity::Never => false,
+      Sensitivity::Always => true,
+      Sensitivity::Keywords(keywords) => {
+        let keywords: Vec<String> = keywords.iter().map( |keyword| locale.fold(keyword) ).collect();
+        text.split_whitespace()
+          .map( |word| locale.fold(word.trim_matches( |c: char| !c.is_alphanumeric() )) )
+          .any( |word| keywords.contains(&word) )
+      },
+    };
+  }
+}
+
+// "always", "never", or a comma separated list of keywords
+impl FromStr for Sensitivity {
+  type Err = String;
+
+  fn from_str(value: &str) -> Result<Sensitivity, String> {
+    return match value {
+      "always" => Ok(Sensitivity::Always),
+      "never" => Ok(Sensitivity::Never),
+      keywords => {
+        // folded when matching, lowercasing here would get turkish wrong
+        let keywords: Vec<String> = keywords.split(',').map( |k| k.trim().to_string() ).filter( |k| !k.is_empty() ).collect();
+        if keywords.is_empty() {
+          return Err("expected always, never, or a list of keywords".to_string());
+        }
+        Ok(Sensitivity::Keywords(keywords))
+      },
+    };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn keywords_mark_posts() {
+    let sensitivity: Sensitivity = "DMT, ketamine".parse().unwrap();
+
+    let ascii = Locale::Ascii;
+    assert!(sensitivity.is_sensitive("Smoked dmt. The blockchain spoke to me.", ascii));
+    assert!(sensitivity.is_sensitive("\"Ketamine\" is a store of value", ascii));
+    assert!(!sensitivity.is_sensitive("Number go up.", ascii));
+    assert!(!Sensitivity::Never.is_sensitive("dmt", ascii));
+    assert!(Sensitivity::Always.is_sensitive("Number go up.", ascii));
+
+    // dotless i only lowercases right in turkish
+    let sensitivity: Sensitivity = "ışık".parse().unwrap();
+    assert!(sensitivity.is_sensitive("IŞIK gördüm", Locale::Turkic));
+    assert!(!sensitivity.is_sensitive("IŞIK gördüm", Locale::Ascii));
+  }
+}
@@ -0,0 +1,482 @@
+use std::env;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::audit::{AuditLog, Entry};
+use crate::auth::Auth;
+use crate::json;
+use crate::markov_chain::MarkovChain;
+use crate::pause::KillSwitch;
+use crate::publish::{Publisher, StdoutPublisher};
+use crate::queue::{Queue, QueuedPost};
+use crate::rate_limit::RateLimiter;
+use crate::replies::{ReplyGate, ReplyLimits, Verdict};
+use crate::sensitive::Sensitivity;
+
+#[derive(Clone, Debug)]
+pub struct Settings {
+  // once this is configured every endpoint needs it. POST /trigger doesn't exist without it
+  pub auth: Auth,
+  // where triggered tweets go, see --target
+  pub publisher: Arc<dyn Publisher>,
+  // if set, triggered tweets get appended here as json lines instead of going straight to the publisher
+  pub queue: Option<PathBuf>,
+  // every triggered tweet gets an entry in here, see audit.rs
+  pub audit_log: Option<PathBuf>,
+  // which posts go out behind the platform's sensitive content flag, see --sensitive
+  pub sensitivity: Sensitivity,
+  // who POST /reply answers and how often. shared, every connection counts against the same limits
+  pub replies: Arc<Mutex<ReplyGate>>,
+  // stops /trigger from publishing, POST /pause and /resume flip it
+  pub kill_switch: KillSwitch,
+  // requests per minute, per api key (or ip address for anybody without one) and across everybody
+  pub requests_per_minute: Option<u32>,
+  pub global_requests_per_minute: Option<u32>,
+  // connections being handled at once, anybody past that gets a 503 straight away
+  pub max_concurrent: usize,
+}
+
+impl Default for Settings {
+  fn default() -> Self {
+    return Settings {
+      auth: Auth::default(),
+      publisher: Arc::new(StdoutPublisher),
+      queue: None,
+      audit_log: None,
+      sensitivity: Sensitivity::Never,
+      replies: Arc::new(Mutex::new(ReplyGate::new(ReplyLimits::default()))),
+      kill_switch: KillSwitch::default(),
+      requests_per_minute: None, global_requests_per_minute: None,
+      max_concurrent: 4,
+    };
+  }
+}
+
+// a tiny http/1.1 server on top of std, we only need a couple of endpoints and it's not worth an async runtime.
+// each connection gets a thread (up to max_concurrent of them) so a slow client can't hold everybody else up,
+// but generation itself takes turns on the one chain, it only takes microseconds anyway
+pub fn serve(mchain: &mut MarkovChain, addr: &str, settings: &Settings) -> io::Result<()> {
+  let listener = TcpListener::bind(addr)?;
+  println!("listening on http://{}", listener.local_addr()?);
+
+  let mchain = Mutex::new(mchain);
+  let limiter = Mutex::new(RateLimiter::new(settings.requests_per_minute, settings.global_requests_per_minute));
+  let active = AtomicUsize::new(0);
+
+  thread::scope( |scope| {
+    for stream in listener.incoming() {
+      let mut stream = match stream {
+        Ok(stream) => stream,
+        Err(error) => {
+          eprintln!("request failed: {}", error);
+          continue;
+        },
+      };
+
+      if active.fetch_add(1, Ordering::SeqCst) >= settings.max_concurrent {
+        active.fetch_sub(1, Ordering::SeqCst);
+        let _ = write_response(&mut stream, "503 Service Unavailable", "text/plain; charset=utf-8", "busy, try again in a bit");
+        continue;
+      }
+
+      let (mchain, limiter, active) = (&mchain, &limiter, &active);
+      scope.spawn(move || {
+        if let Err(error) = handle(mchain, limiter, settings, stream) {
+          eprintln!("request failed: {}", error);
+        }
+        active.fetch_sub(1, Ordering::SeqCst);
+      });
+    }
+  });
+  return Ok(());
+}
+
+fn handle(mchain: &Mutex<&mut MarkovChain>, limiter: &Mutex<RateLimiter>, settings: &Settings, mut stream: TcpStream) -> io::Result<()> {
+  stream.set_read_timeout(Some(READ_TIMEOUT))?;
+  let mut reader = BufReader::new(stream.try_clone()?);
+  let mut request = match Request::read_head(&mut reader) {
+    Ok(request) => request,
+    Err(error) => return refuse(&mut stream, error),
+  };
+
+  // before the body, somebody over their limit doesn't get to make us read anything else
+  let client = client(settings, &request, stream.peer_addr()?);
+  if !limiter.lock().unwrap().allow(&client, Instant::now()) {
+    return write_response(&mut stream, "429 Too Many Requests", "text/plain; charset=utf-8", "slow down");
+  }
+  if let Err(error) = request.read_body(&mut reader) {
+    return refuse(&mut stream, error);
+  }
+
+  let mut mchain = mchain.lock().unwrap();
+  return respond(&mut mchain, settings, &request, &mut stream);
+}
+
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+// tells the client what was wrong with its request, if it was one Request::read turned away
+fn refuse(stream: &mut TcpStream, error: io::Error) -> io::Result<()> {
+  return match error.get_ref().and_then( |inner| inner.downcast_ref::<Refused>() ) {
+    Some(Refused(status)) => write_response(stream, status, "text/plain; charset=utf-8", status),
+    None => Err(error),
+  };
+}
+
+// who a request counts against. only keys we know about count, otherwise anybody could make up a fresh key
+// for every request
+fn client(settings: &Settings, request: &Request, peer: SocketAddr) -> String {
+  return match request.header("authorization").and_then( |value| value.strip_prefix("Bearer ") ) {
+    Some(key) if settings.auth.api_keys.iter().any( |k| k == key ) => format!("key:{}", key),
+    _ => format!("ip:{}", peer.ip()),
+  };
+}
+
+// anything bigger is turned away before it's read. the only body anyone sends is /pause's reason
+const MAX_BODY: usize = 4 * 1024;
+const MAX_LINE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 64;
+
+// a request read gave up on, and the status to tell the client why. it comes back inside an io::Error
+#[derive(Debug)]
+pub struct Refused(pub &'static str);
+
+impl std::fmt::Display for Refused {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    return write!(f, "refused the request: {}", self.0);
+  }
+}
+
+impl std::error::Error for Refused {}
+
+fn refused(status: &'static str) -> io::Error {
+  return io::Error::new(io::ErrorKind::InvalidData, Refused(status));
+}
+
+// one line of the head, no longer than MAX_LINE. a line that doesn't end by then doesn't get read any further
+fn read_line(reader: &mut impl BufRead) -> io::Result<String> {
+  let mut line = String::new();
+  reader.by_ref().take(MAX_LINE as u64 + 1).read_line(&mut line)?;
+  if line.len() > MAX_LINE {
+    return Err(refused("431 Request Header Fields Too Large"));
+  }
+  return Ok(line);
+}
+
+pub struct Request {
+  pub method: String,
+  pub path: String,
+  pub headers: Vec<(String, String)>,
+  pub body: Vec<u8>,
+}
+
+impl Request {
+  // a request that goes over MAX_LINE, MAX_HEADERS or MAX_BODY is an error with a Refused in it
+  pub fn read(reader: &mut impl BufRead) -> io::Result<Request> {
+    let mut request = Request::read_head(reader)?;
+    request.read_body(reader)?;
+    return Ok(request);
+  }
+
+  // the request line and the headers, the body's left where it is
+  pub fn read_head(reader: &mut impl BufRead) -> io::Result<Request> {
+    let line = read_line(reader)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut headers = Vec::new();
+    loop {
+      let line = read_line(reader)?;
+      if line.is_empty() {
+        break;
+      }
+      let line = line.trim_end();
+      if line.is_empty() {
+        break;
+      }
+      if headers.len() == MAX_HEADERS {
+        return Err(refused("431 Request Header Fields Too Large"));
+      }
+      if let Some((name, value)) = line.split_once(':') {
+        headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+      }
+    }
+
+    return Ok(Request { method, path, headers, body: Vec::new() });
+  }
+
+  // as much as Content-Length says, after the head
+  pub fn read_body(&mut self, reader: &mut impl BufRead) -> io::Result<()> {
+    let length = match self.header("content-length").map( |l| l.parse::<usize>() ) {
+      None => 0,
+      Some(Ok(length)) if length <= MAX_BODY => length,
+      Some(Ok(_)) => return Err(refused("413 Payload Too Large")),
+      Some(Err(_)) => return Err(refused("400 Bad Request")),
+    };
+    self.body = vec![0; length];
+    return reader.read_exact(&mut self.body);
+  }
+
+  // header names are lowercased when we read them
+  pub fn header(&self, name: &str) -> Option<&str> {
+    return self.headers.iter().find( |(n, _)| n == name ).map( |(_, v)| v.as_str() );
+  }
+}
+
+pub fn respond(mchain: &mut MarkovChain, settings: &Settings, request: &Request, out: &mut impl Write) -> io::Result<()> {
+  let known = matches!(
+    (request.method.as_str(), request.path.as_str()),
+    ("GET", "/generate") | ("GET", "/stream") | ("GET", "/metrics") | ("POST", "/trigger") | ("POST", "/reply") | ("POST", "/pause") | ("POST", "/resume")
+  );
+  if known && settings.auth.is_configured() && !settings.auth.allows(&request.method, &request.path, &request.headers, &request.body) {
+    return write_response(out, "401 Unauthorized", "text/plain; charset=utf-8", "unauthorized");
+  }
+
+  return match (request.method.as_str(), request.path.as_str()) {
+    ("GET", "/generate") => match mchain.generate_tweet() {
+      Ok(tweet) => write_response(out, "200 OK", "text/plain; charset=utf-8", &tweet),
+      Err(error) => write_response(out, "503 Service Unavailable", "text/plain; charset=utf-8", &error.to_string()),
+    },
+    ("GET", "/stream") => stream_tweet(mchain, out),
+    ("GET", "/metrics") => write_response(out, "200 OK", "text/plain; version=0.0.4", &metrics(mchain)),
+    ("POST", "/trigger") if settings.auth.is_configured() => trigger(mchain, settings, out),
+    ("POST", "/reply") if settings.auth.is_configured() => reply(mchain, settings, &request.body, out),
+    ("POST", "/pause") if settings.auth.is_configured() => {
+      let reason = String::from_utf8_lossy(&request.body);
+      settings.kill_switch.pause(if reason.trim().is_empty() { "paused over the api" } else { reason.trim() })?;
+      write_response(out, "200 OK", "text/plain; charset=utf-8", "paused")
+    },
+    ("POST", "/resume") if settings.auth.is_configured() => {
+      settings.kill_switch.resume()?;
+      write_response(out, "200 OK", "text/plain; charset=utf-8", "resumed")
+    },
+    _ => write_response(out, "404 Not Found", "text/plain; charset=utf-8", "not found"),
+  };
+}
+
+// prometheus' text format, there's nothing to it. rejections are by filter, the way stats shows them
+fn metrics(mchain: &MarkovChain) -> String {
+  let stats = mchain.filter_stats();
+  let mut body = String::new();
+  body.push_str("# TYPE erowidcoin_candidates_total counter\n");
+  body.push_str(&format!("erowidcoin_candidates_total {}\n", stats.candidates));
+  body.push_str("# TYPE erowidcoin_rejections_total counter\n");
+  for (reason, count) in stats.sorted() {
+    body.push_str(&format!("erowidcoin_rejections_total{{reason=\"{}\"}} {}\n", reason.replace('\\', "\\\\").replace('"', "\\\""), count));
+  }
+
+  // the same numbers as --timings, see timings.rs
+  let timings = mchain.timings();
+  body.push_str("# TYPE erowidcoin_training_seconds gauge\n");
+  for (phase, took) in timings.phases() {
+    body.push_str(&format!("erowidcoin_training_seconds{{phase=\"{}\"}} {}\n", phase.replace(' ', "_"), took.as_secs_f64()));
+  }
+  body.push_str("# TYPE erowidcoin_generation_seconds summary\n");
+  body.push_str(&format!("erowidcoin_generation_seconds_sum {}\n", timings.generation.as_secs_f64()));
+  body.push_str(&format!("erowidcoin_generation_seconds_count {}\n", timings.generated));
+  return body;
+}
+
+pub fn write_response(out: &mut impl Write, status: &str, content_type: &str, body: &str) -> io::Result<()> {
+  write!(
+    out,
+    "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+    status, content_type, body.len(), body
+  )?;
+  return out.flush();
+}
+
+// lets other things (a chat command, a release hook) make the bot say something right now
+// already authenticated by the time we get here
+fn trigger(mchain: &mut MarkovChain, settings: &Settings, out: &mut impl Write) -> io::Result<()> {
+  if settings.kill_switch.is_paused() {
+    let reason = settings.kill_switch.reason().unwrap_or_default();
+    return write_response(out, "503 Service Unavailable", "text/plain; charset=utf-8", &format!("publishing is paused ({})", reason));
+  }
+
+  let tweet = match mchain.generate_tweet() {
+    Ok(tweet) => settings.publisher.format().adapt(&tweet),
+    Err(error) => return write_response(out, "503 Service Unavailable", "text/plain; charset=utf-8", &error.to_string()),
+  };
+
+  // written before it goes anywhere, nothing gets out without an audit entry. a queued one gets its entry when the
+  // queue posts it
+  if let (Some(path), None) = (&settings.audit_log, &settings.queue) {
+    let context = vec!(
+      ("operator".to_string(), env::var("USER").unwrap_or_default()),
+      ("endpoint".to_string(), "POST /trigger".to_string()),
+      ("destination".to_string(), settings.publisher.name()),
+    );
+    AuditLog::open(path)?.record(&Entry { text: &tweet, filters: &mchain.filter_names(), model: &mchain.fingerprint(), context: &context })?;
+  }
+
+  let sensitive = settings.sensitivity.is_sensitive(&tweet, mchain.locale());
+  match &settings.queue {
+    Some(path) => {
+      Queue::new(path).append(&[QueuedPost::new(tweet.clone(), None).with_model(mchain.fingerprint()).with_sensitive(sensitive)])?;
+      return write_response(out, "202 Accepted", "text/plain; charset=utf-8", &tweet);
+    },
+    None => {
+      settings.publisher.publish_flagged(&tweet, None, sensitive)?;
+      return write_response(out, "200 OK", "text/plain; charset=utf-8", &tweet);
+    },
+  }
+}
+
+// a mention, {"from":"@alice","text":"gm bot"}, from whatever's watching the platform for them. if the gate lets it
+// through the reply leans towards the mention's words and comes back for the watcher to post as a reply
+fn reply(mchain: &mut MarkovChain, settings: &Settings, body: &[u8], out: &mut impl Write) -> io::Result<()> {
+  if settings.kill_switch.is_paused() {
+    let reason = settings.kill_switch.reason().unwrap_or_default();
+    return write_response(out, "503 Service Unavailable", "text/plain; charset=utf-8", &format!("publishing is paused ({})", reason));
+  }
+  let mention = json::parse(&String::from_utf8_lossy(body)).ok();
+  let field = |name: &str| mention.as_ref()?.get(name)?.as_str().map(String::from);
+  let (Some(from), Some(text)) = (field("from"), field("text")) else {
+    return write_response(out, "400 Bad Request", "text/plain; charset=utf-8", "expected {\"from\":<user>,\"text\":<their post>}");
+  };
+
+  match settings.replies.lock().unwrap().check(&from, Instant::now()) {
+    Verdict::Reply => {},
+    Verdict::Denied | Verdict::NotAllowed => return write_response(out, "403 Forbidden", "text/plain; charset=utf-8", &format!("not replying to {}", from)),
+    Verdict::CoolingDown(left) => {
+      return write_response(out, "429 Too Many Requests", "text/plain; charset=utf-8", &format!("just replied to {}, try again in {}s", from, left.as_secs().max(1)));
+    },
+    Verdict::HourlyCap => return write_response(out, "429 Too Many Requests", "text/plain; charset=utf-8", "that's all the replies for this hour"),
+  }
+
+  // other people's handles aren't the point of the mention
+  mchain.set_boost(text.split_whitespace().filter( |word| !word.starts_with('@') ).map(String::from).collect());
+  let generated = mchain.generate_tweet();
+  mchain.set_boost(Vec::new());
+  return match generated {
+    Ok(tweet) => write_response(out, "200 OK", "text/plain; charset=utf-8", &settings.publisher.format().adapt(&tweet)),
+    Err(error) => write_response(out, "503 Service Unavailable", "text/plain; charset=utf-8", &error.to_string()),
+  };
+}
+
+// server-sent events, one word per event so the web ui can do the "typing" thing. these skip the filters,
+// there's no way to reject a tweet we've already sent half of
+fn stream_tweet(mchain: &mut MarkovChain, out: &mut impl Write) -> io::Result<()> {
+  let words = match mchain.stream_tweet() {
+    Ok(words) => words,
+    Err(error) => return write_response(out, "503 Service Unavailable", "text/plain; charset=utf-8", &error.to_string()),
+  };
+  write!(out, "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n")?;
+  out.flush()?;
+
+  for word in words {
+    write!(out, "data: {}\n\n", word)?;
+    out.flush()?;
+  }
+
+  write!(out, "event: done\ndata:\n\n")?;
+  return out.flush();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::path::Path;
+  use crate::filter::MaxLength;
+
+  #[test]
+  fn streams_words_as_events() {
+    let mut mchain = MarkovChain::new();
+    mchain.create_tweets(Path::new("./txt"), 0).unwrap();
+
+    let raw = "GET /stream HTTP/1.1\r\nHost: localhost\r\n\r\n";
+    let request = Request::read(&mut raw.as_bytes()).unwrap();
+    let mut out = Vec::new();
+    respond(&mut mchain, &Settings::default(), &request, &mut out).unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("text/event-stream"));
+    assert!(out.contains("data: The\n\ndata: syntactic\n\n"));
+    assert!(out.ends_with("data: interpretation.\n\nevent: done\ndata:\n\n"));
+  }
+
+  #[test]
+  fn metrics_count_rejections_by_filter() {
+    let mut mchain = MarkovChain::new().with_filter(MaxLength(1)).with_max_attempts(3);
+    mchain.create_tweets(Path::new("./txt"), 0).unwrap();
+
+    let mut get = |path: &str| {
+      let mut out = Vec::new();
+      respond(&mut mchain, &Settings::default(), &Request::read(&mut format!("GET {} HTTP/1.1\r\n\r\n", path).as_bytes()).unwrap(), &mut out).unwrap();
+      String::from_utf8(out).unwrap()
+    };
+
+    assert!(get("/generate").starts_with("HTTP/1.1 503"));
+    let metrics = get("/metrics");
+    assert!(metrics.contains("\r\n\r\n# TYPE erowidcoin_candidates_total counter\nerowidcoin_candidates_total 3\n"), "{}", metrics);
+    assert!(metrics.contains("erowidcoin_rejections_total{reason=\"length\"} 3\n"), "{}", metrics);
+    // the one that gave up still took time
+    assert!(metrics.contains("erowidcoin_training_seconds{phase=\"graph_build\"} ") && metrics.ends_with("erowidcoin_generation_seconds_count 1\n"), "{}", metrics);
+  }
+
+  #[test]
+  fn endpoints_need_a_key_once_auth_is_configured() {
+    let mut mchain = MarkovChain::new();
+    mchain.create_tweets(Path::new("./txt"), 0).unwrap();
+    let queue = std::env::temp_dir().join(format!("erowidcoin-queue-{}.jsonl", std::process::id()));
+    let auth = Auth { api_keys: vec!("hunter2".to_string()), hmac_secret: None };
+    let paused = std::env::temp_dir().join(format!("erowidcoin-server-paused-{}", std::process::id()));
+    let settings = Settings { auth, queue: Some(queue.clone()), kill_switch: KillSwitch::new(&paused), ..Settings::default() };
+
+    let mut trigger = |headers: &str| {
+      let raw = format!("POST /trigger HTTP/1.1\r\n{}\r\n", headers);
+      let mut out = Vec::new();
+      respond(&mut mchain, &settings, &Request::read(&mut raw.as_bytes()).unwrap(), &mut out).unwrap();
+      String::from_utf8(out).unwrap()
+    };
+
+    assert!(trigger("").starts_with("HTTP/1.1 401"));
+    assert!(trigger("Authorization: Bearer hunter3\r\n").starts_with("HTTP/1.1 401"));
+    assert!(trigger("Authorization: Bearer hunter2\r\n").starts_with("HTTP/1.1 202"));
+    settings.kill_switch.pause("testing").unwrap();
+    assert!(trigger("Authorization: Bearer hunter2\r\n").starts_with("HTTP/1.1 503"));
+    settings.kill_switch.resume().unwrap();
+
+    let mut out = Vec::new();
+    respond(&mut mchain, &settings, &Request::read(&mut "GET /generate HTTP/1.1\r\n\r\n".as_bytes()).unwrap(), &mut out).unwrap();
+    assert!(out.starts_with(b"HTTP/1.1 401"));
+
+    let refused = |raw: String| Request::read(&mut raw.as_bytes()).err().unwrap().get_ref().unwrap().downcast_ref::<Refused>().unwrap().0;
+    assert_eq!(refused("GET /generate HTTP/1.1\r\nContent-Length: 900000000000\r\n\r\n".to_string()), "413 Payload Too Large");
+    assert_eq!(refused(format!("GET /generate HTTP/1.1\r\nX-Padding: {}\r\n\r\n", "a".repeat(MAX_LINE))), "431 Request Header Fields Too Large");
+    assert_eq!(refused(format!("GET /generate HTTP/1.1\r\n{}\r\n", "X-A: b\r\n".repeat(MAX_HEADERS + 1))), "431 Request Header Fields Too Large");
+
+    let queued = std::fs::read_to_string(&queue).unwrap();
+    std::fs::remove_file(&queue).unwrap();
+    assert!(queued.starts_with("{\"text\":\"The syntactic component"));
+    assert_eq!(queued.lines().count(), 1);
+  }
+
+  #[test]
+  fn replies_go_through_the_gate() {
+    let mut mchain = MarkovChain::new();
+    mchain.create_tweets(Path::new("./txt"), 0).unwrap();
+    let limits = ReplyLimits { cooldown: Duration::from_secs(60), max_per_hour: 2, allow: Vec::new(), deny: vec!("troll".to_string()) };
+    let auth = Auth { api_keys: vec!("hunter2".to_string()), hmac_secret: None };
+    let settings = Settings { auth, replies: Arc::new(Mutex::new(ReplyGate::new(limits))), ..Settings::default() };
+
+    let mut reply = |body: &str| {
+      let raw = format!("POST /reply HTTP/1.1\r\nAuthorization: Bearer hunter2\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+      let mut out = Vec::new();
+      respond(&mut mchain, &settings, &Request::read(&mut raw.as_bytes()).unwrap(), &mut out).unwrap();
+      String::from_utf8(out).unwrap()
+    };
+
+    assert!(reply("{\"from\":\"@alice\",\"text\":\"@erowidcoin what is syntax\"}").starts_with("HTTP/1.1 200"));
+    assert!(reply("{\"from\":\"alice\",\"text\":\"again\"}").starts_with("HTTP/1.1 429"));
+    assert!(reply("{\"from\":\"@Troll\",\"text\":\"bait\"}").starts_with("HTTP/1.1 403"));
+    assert!(reply("{\"from\":\"bob\",\"text\":\"gm\"}").starts_with("HTTP/1.1 200"));
+    assert!(reply("{\"from\":\"carol\",\"text\":\"gm\"}").ends_with("that's all the replies for this hour"));
+    assert!(reply("not json").starts_with("HTTP/1.1 400"));
+  }
+}
@@ -0,0 +1,78 @@
+// FIPS 180-4 again. sha-1 is broken for collisions, but HMAC-SHA1 is what twitter's OAuth 1.0a signs with and
+// that's all it's used for here
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+  let mut state: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+
+  let mut message = data.to_vec();
+  message.push(0x80);
+  while message.len() % 64 != 56 {
+    message.push(0);
+  }
+  message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+  for block in message.chunks_exact(64) {
+    let mut w = [0u32; 80];
+    for i in 0..16 {
+      w[i] = u32::from_be_bytes(block[4 * i..4 * i + 4].try_into().unwrap());
+    }
+    for i in 16..80 {
+      w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e] = state;
+    for (i, word) in w.iter().enumerate() {
+      let (f, k) = match i {
+        0..=19 => ((b & c) | (!b & d), 0x5a827999),
+        20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+        40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+        _ => (b ^ c ^ d, 0xca62c1d6),
+      };
+      let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+      e = d;
+      d = c;
+      c = b.rotate_left(30);
+      b = a;
+      a = temp;
+    }
+    for (value, new) in state.iter_mut().zip([a, b, c, d, e]) {
+      *value = value.wrapping_add(new);
+    }
+  }
+
+  let mut digest = [0u8; 20];
+  for (i, word) in state.iter().enumerate() {
+    digest[4 * i..4 * i + 4].copy_from_slice(&word.to_be_bytes());
+  }
+  return digest;
+}
+
+// RFC 2104, same as auth::hmac_sha256 with a shorter digest
+pub fn hmac_sha1(key: &[u8], message: &[u8]) -> [u8; 20] {
+  let mut block = [0u8; 64];
+  if key.len() > 64 {
+    block[..20].copy_from_slice(&sha1(key));
+  } else {
+    block[..key.len()].copy_from_slice(key);
+  }
+
+  let mut inner: Vec<u8> = block.iter().map( |b| b ^ 0x36 ).collect();
+  inner.extend_from_slice(message);
+  let mut outer: Vec<u8> = block.iter().map( |b| b ^ 0x5c ).collect();
+  outer.extend_from_slice(&sha1(&inner));
+
+  return sha1(&outer);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::sha256::hex;
+
+  #[test]
+  fn matches_the_test_vectors() {
+    assert_eq!(hex(&sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    assert_eq!(hex(&sha1(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")), "84983e441c3bd26ebaae4aa1f95129e5e54670f1");
+    // RFC 2202 test case 2
+    assert_eq!(hex(&hmac_sha1(b"Jefe", b"what do ya want for nothing?")), "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79");
+  }
+}
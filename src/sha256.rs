@@ -0,0 +1,138 @@
+// FIPS 180-4. not worth a dependency for the one hash we need
+
+const K: [u32; 64] = [
+  0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+  0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+  0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+  0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+  0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+  0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+  0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+  0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+// fed in pieces, so hashing a whole model doesn't mean copying it into one buffer first
+pub struct Sha256 {
+  state: [u32; 8],
+  buffer: Vec<u8>,
+  length: u64,
+}
+
+impl Sha256 {
+  pub fn new() -> Sha256 {
+    return Sha256 {
+      state: [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19],
+      buffer: Vec::with_capacity(64),
+      length: 0,
+    };
+  }
+
+  pub fn update(&mut self, mut data: &[u8]) {
+    self.length += data.len() as u64;
+
+    if !self.buffer.is_empty() {
+      let take = data.len().min(64 - self.buffer.len());
+      self.buffer.extend_from_slice(&data[..take]);
+      data = &data[take..];
+      if self.buffer.len() < 64 {
+        return;
+      }
+      let block = std::mem::take(&mut self.buffer);
+      self.compress(&block);
+    }
+
+    let mut blocks = data.chunks_exact(64);
+    for block in &mut blocks {
+      self.compress(block);
+    }
+    self.buffer.extend_from_slice(blocks.remainder());
+  }
+
+  pub fn finish(mut self) -> [u8; 32] {
+    let bits = self.length * 8;
+    let mut padding = vec!(0x80);
+    while (self.buffer.len() + padding.len()) % 64 != 56 {
+      padding.push(0);
+    }
+    padding.extend_from_slice(&bits.to_be_bytes());
+    let length = self.length;
+    self.update(&padding);
+    self.length = length;
+
+    let mut digest = [0u8; 32];
+    for (i, word) in self.state.iter().enumerate() {
+      digest[4 * i..4 * i + 4].copy_from_slice(&word.to_be_bytes());
+    }
+    return digest;
+  }
+
+  fn compress(&mut self, block: &[u8]) {
+    let mut w = [0u32; 64];
+    for i in 0..16 {
+      w[i] = u32::from_be_bytes([block[4 * i], block[4 * i + 1], block[4 * i + 2], block[4 * i + 3]]);
+    }
+    for i in 16..64 {
+      let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+      let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+      w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+    for i in 0..64 {
+      let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+      let choice = (e & f) ^ (!e & g);
+      let temp1 = h.wrapping_add(s1).wrapping_add(choice).wrapping_add(K[i]).wrapping_add(w[i]);
+      let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+      let majority = (a & b) ^ (a & c) ^ (b & c);
+      let temp2 = s0.wrapping_add(majority);
+
+      h = g;
+      g = f;
+      f = e;
+      e = d.wrapping_add(temp1);
+      d = c;
+      c = b;
+      b = a;
+      a = temp1.wrapping_add(temp2);
+    }
+
+    for (state, value) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+      *state = state.wrapping_add(value);
+    }
+  }
+}
+
+impl Default for Sha256 {
+  fn default() -> Self {
+    return Sha256::new();
+  }
+}
+
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+  let mut hasher = Sha256::new();
+  hasher.update(data);
+  return hasher.finish();
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+  return bytes.iter().map( |byte| format!("{:02x}", byte) ).collect();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn matches_the_reference_vectors() {
+    assert_eq!(hex(&sha256(b"")), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+    assert_eq!(hex(&sha256(b"abc")), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+
+    // same thing fed in awkward pieces
+    let text = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq".repeat(3);
+    let mut hasher = Sha256::new();
+    for piece in text.chunks(7) {
+      hasher.update(piece);
+    }
+    assert_eq!(hasher.finish(), sha256(&text));
+  }
+}
@@ -0,0 +1,184 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::locale::Locale;
+use crate::manifest::{FileEntry, Provenance};
+
+// a model you can hand around without handing around the corpus: the transitions that came up often enough, as
+// counted n-grams, and nothing that was only ever written once. a chain rebuilt from it tweets like one trained on
+// what's left (see MarkovChain::corpus_stats and with_corpus_stats). it's text, one record a line with tabs between
+// the fields, so anyone can see for themselves there's no text in it:
+//
+//   erowidcoin corpus stats 1
+//   order	2
+//   locale	ascii
+//   description	the bitcoin whitepaper
+//   file	bitcoin.txt	<license>	https://bitcoin.org/bitcoin.pdf
+//   entry	The moon
+//   ngram	3	2	The moon is
+//   case	bitcoin	Bitcoin
+//
+// an ngram is its weight, how many documents it was in, then the state and the word after it. words can't have
+// tabs or spaces in them, line breaks (--poetry) are written \n
+pub const HEADER: &str = "erowidcoin corpus stats 1";
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CorpusStats {
+  pub order: usize,
+  pub locale: Locale,
+  pub poetry: bool,
+  pub folded: bool,
+  // the attribution, without the tags, weights and frontmatter
+  pub provenance: Provenance,
+  // the entry states, in the order training found them
+  pub entries: Vec<String>,
+  pub ngrams: Vec<Ngram>,
+  // folded word -> how it's written, see Casing
+  pub casing: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ngram {
+  // the state, then the word after it
+  pub words: Vec<String>,
+  pub weight: i32,
+  pub documents: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct StatsError(pub String);
+
+impl fmt::Display for StatsError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    return write!(f, "{}", self.0);
+  }
+}
+
+impl std::error::Error for StatsError {}
+
+fn escape(word: &str) -> String {
+  return word.replace('\\', "\\\\").replace('\n', "\\n");
+}
+
+fn unescape(word: &str) -> String {
+  let mut unescaped = String::with_capacity(word.len());
+  let mut chars = word.chars();
+  while let Some(c) = chars.next() {
+    match (c, c == '\\') {
+      (_, true) => match chars.next() {
+        Some('n') => unescaped.push('\n'),
+        Some(other) => unescaped.push(other),
+        None => unescaped.push('\\'),
+      },
+      (c, false) => unescaped.push(c),
+    }
+  }
+  return unescaped;
+}
+
+fn number<T: FromStr>(field: &str) -> Result<T, String> {
+  return field.parse().map_err( |_| format!("{:?} should be a number", field) );
+}
+
+impl fmt::Display for CorpusStats {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, "{}", HEADER)?;
+    writeln!(f, "order\t{}", self.order)?;
+    writeln!(f, "locale\t{}", self.locale.name())?;
+    writeln!(f, "poetry\t{}", self.poetry)?;
+    writeln!(f, "folded\t{}", self.folded)?;
+    let line = |text: &str| text.replace(['\t', '\n'], " ");
+    if let Some(description) = &self.provenance.description {
+      writeln!(f, "description\t{}", line(description))?;
+    }
+    if let Some(license) = &self.provenance.license {
+      writeln!(f, "license\t{}", line(license))?;
+    }
+    for file in &self.provenance.files {
+      writeln!(f, "file\t{}\t{}\t{}", line(&file.path), line(file.license.as_deref().unwrap_or("")), line(file.source.as_deref().unwrap_or("")))?;
+    }
+    for entry in &self.entries {
+      writeln!(f, "entry\t{}", escape(entry))?;
+    }
+    for ngram in &self.ngrams {
+      writeln!(f, "ngram\t{}\t{}\t{}", ngram.weight, ngram.documents, escape(&ngram.words.join(" ")))?;
+    }
+    for (folded, written) in &self.casing {
+      writeln!(f, "case\t{}\t{}", escape(folded), escape(written))?;
+    }
+    return Ok(());
+  }
+}
+
+impl CorpusStats {
+  // what Display wrote. every n-gram has to be order + 1 words long
+  pub fn parse(text: &str) -> Result<CorpusStats, StatsError> {
+    let mut lines = text.lines().enumerate();
+    if lines.next().map( |(_, line)| line.trim_end() ) != Some(HEADER) {
+      return Err(StatsError(format!("not corpus stats, they start with {:?}", HEADER)));
+    }
+    let mut stats = CorpusStats::default();
+    for (i, line) in lines {
+      let failed = |reason: String| StatsError(format!("line {}: {}", i + 1, reason));
+      let fields: Vec<&str> = line.split('\t').collect();
+      match fields.as_slice() {
+        [""] => {},
+        ["order", order] => stats.order = number(order).map_err(failed)?,
+        ["locale", locale] => stats.locale = locale.parse().map_err(failed)?,
+        ["poetry", poetry] => stats.poetry = *poetry == "true",
+        ["folded", folded] => stats.folded = *folded == "true",
+        ["description", description] => stats.provenance.description = Some(description.to_string()),
+        ["license", license] => stats.provenance.license = Some(license.to_string()),
+        ["file", path, license, source] => {
+          let optional = |field: &str| Some(field.to_string()).filter( |field| !field.is_empty() );
+          stats.provenance.files.push(FileEntry { path: path.to_string(), tags: Vec::new(), weight: 1.0, license: optional(license), source: optional(source), metadata: Default::default() });
+        },
+        ["entry", state] => stats.entries.push(unescape(state)),
+        ["ngram", weight, documents, state] => {
+          let words: Vec<String> = unescape(state).split(' ').map(String::from).collect();
+          if words.len() != stats.order + 1 {
+            return Err(failed(format!("an order {} chain's n-grams are {} words, this one's {}", stats.order, stats.order + 1, words.len())));
+          }
+          stats.ngrams.push(Ngram { words, weight: number(weight).map_err(failed)?, documents: number(documents).map_err(failed)? });
+        },
+        ["case", folded, written] => stats.casing.push((unescape(folded), unescape(written))),
+        _ => return Err(failed(format!("don't know what {:?} is", fields[0]))),
+      }
+    }
+    if stats.order == 0 {
+      return Err(StatsError("the stats need an order of at least 1".to_string()));
+    }
+    return Ok(stats);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::markov_chain::{MarkovChain, Suppression};
+
+  #[test]
+  fn rebuilds_a_chain_without_the_rare_bits() {
+    let documents = ["Hodl the moon. The moon is a dip.", "Hodl the dip. The moon is a meme.", "Number go up."];
+    let mut trained = MarkovChain::new().with_order(2).unwrap().with_seed(4);
+    trained.train_documents(documents);
+
+    let stats = trained.corpus_stats(Suppression { min_documents: 2, min_count: 2 });
+    let text = stats.to_string();
+    assert!(text.contains("ngram\t2\t2\tThe moon is") && !text.contains("Number") && !text.contains("meme"), "{}", text);
+    assert_eq!(CorpusStats::parse(&text).unwrap(), stats);
+
+    let mut rebuilt = MarkovChain::new().with_seed(4).with_corpus_stats(&CorpusStats::parse(&text).unwrap());
+    assert_eq!(rebuilt.order(), 2);
+    for _ in 0..5 {
+      let tweet = rebuilt.generate().unwrap();
+      assert!(!tweet.contains("meme") && !tweet.contains("Number"), "{}", tweet);
+    }
+    // everything kept, it's the same chain
+    let everything = MarkovChain::new().with_corpus_stats(&trained.corpus_stats(Suppression::default()));
+    assert_eq!(everything.fingerprint(), trained.fingerprint());
+
+    assert_eq!(CorpusStats::parse("order\t2").unwrap_err().0, format!("not corpus stats, they start with {:?}", HEADER));
+    let error = CorpusStats::parse(&format!("{}\norder\t2\nngram\t1\t1\tto the", HEADER)).unwrap_err();
+    assert_eq!(error.0, "line 3: an order 2 chain's n-grams are 3 words, this one's 2");
+  }
+}
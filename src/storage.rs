@@ -0,0 +1,320 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// where a list of records (queued posts, the history) lives, and the one value that gets replaced whole instead (a
+// --model, so the trained chain is a cache that can go in the same database). records are single-line strings,
+// whoever owns the storage decides what's in them. only appending and reading everything back, that's all a queue or
+// a history needs
+pub trait Storage: Send + Sync {
+  // in the order they were appended
+  fn load(&self) -> io::Result<Vec<String>>;
+  fn append(&self, records: &[String]) -> io::Result<()>;
+  // None until the first write
+  fn read(&self) -> io::Result<Option<Vec<u8>>>;
+  fn write(&self, value: &[u8]) -> io::Result<()>;
+  // whether there's a value, without reading it all in
+  fn written(&self) -> io::Result<bool> {
+    return Ok(self.read()?.is_some());
+  }
+  // shows up in error messages
+  fn describe(&self) -> String;
+}
+
+// what a queue, a history and a model are kept under in a database, so one file can have all three
+pub const QUEUE: &str = "queue";
+pub const HISTORY: &str = "history";
+pub const MODEL: &str = "model";
+
+// whether open gives a database for the path, not a file of its own
+pub fn is_database(path: &Path) -> bool {
+  let extension = path.extension().and_then( |extension| extension.to_str() ).unwrap_or("");
+  return (cfg!(feature = "sqlite") && (extension == "sqlite" || extension == "db")) || (cfg!(feature = "sled") && extension == "sled");
+}
+
+// the storage for a --queue, --history or --model path. built with the sqlite feature a .sqlite or .db file is a
+// sqlite database, with the sled feature a .sled directory is a sled one, and name says which of the things in there
+// this is. anything else is a FileStorage and there's nothing else in the file, the name doesn't matter
+pub fn open(path: &Path, name: &str) -> Box<dyn Storage> {
+  #[cfg(feature = "sqlite")]
+  if is_database(path) && path.extension().is_some_and( |extension| extension != "sled" ) {
+    return Box::new(SqliteStorage::new(path, name));
+  }
+  #[cfg(feature = "sled")]
+  if is_database(path) && path.extension().is_some_and( |extension| extension == "sled" ) {
+    return Box::new(SledStorage::new(path, name));
+  }
+  let _ = name;
+  return Box::new(FileStorage::new(path));
+}
+
+// one record per line. plain text so it survives restarts and can be looked at (or fixed) by hand. the value is the
+// whole file
+pub struct FileStorage {
+  path: PathBuf,
+}
+
+impl FileStorage {
+  pub fn new(path: &Path) -> FileStorage {
+    return FileStorage { path: path.to_path_buf() };
+  }
+}
+
+impl Storage for FileStorage {
+  // a file that doesn't exist yet is just empty
+  fn load(&self) -> io::Result<Vec<String>> {
+    return match fs::read_to_string(&self.path) {
+      Ok(contents) => Ok(contents.lines().filter( |line| !line.trim().is_empty() ).map( |line| line.to_string() ).collect()),
+      Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+      Err(error) => Err(error),
+    };
+  }
+
+  fn append(&self, records: &[String]) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+    for record in records {
+      writeln!(file, "{}", record)?;
+    }
+    return file.sync_data();
+  }
+
+  fn read(&self) -> io::Result<Option<Vec<u8>>> {
+    return match fs::read(&self.path) {
+      Ok(bytes) => Ok(Some(bytes)),
+      Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+      Err(error) => Err(error),
+    };
+  }
+
+  fn write(&self, value: &[u8]) -> io::Result<()> {
+    return fs::write(&self.path, value);
+  }
+
+  fn written(&self) -> io::Result<bool> {
+    return Ok(self.path.exists());
+  }
+
+  fn describe(&self) -> String {
+    return self.path.display().to_string();
+  }
+}
+
+// a table of records and one of values in a sqlite database, for a queue or history that something else wants to
+// query. every call opens the database again, like FileStorage does the file, so other processes can use it too
+#[cfg(feature = "sqlite")]
+pub struct SqliteStorage {
+  path: PathBuf,
+  name: String,
+}
+
+#[cfg(feature = "sqlite")]
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS records (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL, record TEXT NOT NULL);
+  CREATE TABLE IF NOT EXISTS saved (name TEXT PRIMARY KEY, value BLOB NOT NULL);";
+
+#[cfg(feature = "sqlite")]
+impl SqliteStorage {
+  pub fn new(path: &Path, name: &str) -> SqliteStorage {
+    return SqliteStorage { path: path.to_path_buf(), name: name.to_string() };
+  }
+
+  // a database that doesn't exist yet is created empty
+  fn connect(&self) -> io::Result<rusqlite::Connection> {
+    let connection = rusqlite::Connection::open(&self.path).map_err( |error| self.failed(error) )?;
+    connection.execute_batch(SCHEMA).map_err( |error| self.failed(error) )?;
+    return Ok(connection);
+  }
+
+  fn failed(&self, error: rusqlite::Error) -> io::Error {
+    return io::Error::other(format!("sqlite failed on {}: {}", self.path.display(), error));
+  }
+}
+
+#[cfg(feature = "sqlite")]
+impl Storage for SqliteStorage {
+  fn load(&self) -> io::Result<Vec<String>> {
+    let connection = self.connect()?;
+    let mut select = connection.prepare("SELECT record FROM records WHERE name = ?1 ORDER BY id").map_err( |error| self.failed(error) )?;
+    let rows = select.query_map([&self.name], |row| row.get(0) ).map_err( |error| self.failed(error) )?;
+    return rows.collect::<Result<Vec<String>, _>>().map_err( |error| self.failed(error) );
+  }
+
+  // all of them or none
+  fn append(&self, records: &[String]) -> io::Result<()> {
+    let mut connection = self.connect()?;
+    let transaction = connection.transaction().map_err( |error| self.failed(error) )?;
+    for record in records {
+      transaction.execute("INSERT INTO records (name, record) VALUES (?1, ?2)", [&self.name, record]).map_err( |error| self.failed(error) )?;
+    }
+    return transaction.commit().map_err( |error| self.failed(error) );
+  }
+
+  fn read(&self) -> io::Result<Option<Vec<u8>>> {
+    let connection = self.connect()?;
+    let mut select = connection.prepare("SELECT value FROM saved WHERE name = ?1").map_err( |error| self.failed(error) )?;
+    let mut rows = select.query_map([&self.name], |row| row.get(0) ).map_err( |error| self.failed(error) )?;
+    return rows.next().transpose().map_err( |error| self.failed(error) );
+  }
+
+  fn write(&self, value: &[u8]) -> io::Result<()> {
+    let connection = self.connect()?;
+    connection.execute("INSERT OR REPLACE INTO saved (name, value) VALUES (?1, ?2)", rusqlite::params![self.name, value]).map_err( |error| self.failed(error) )?;
+    return Ok(());
+  }
+
+  fn describe(&self) -> String {
+    return format!("sqlite {} ({})", self.path.display(), self.name);
+  }
+}
+
+// a tree of records (by a big endian id, so they iterate in the order they went in) and a value under the name in
+// the default tree, in a sled database. sled only lets one process have a database open, and inside the process
+// it's opened once and shared, so a queue and a history in the same directory don't lock each other out
+#[cfg(feature = "sled")]
+pub struct SledStorage {
+  path: PathBuf,
+  name: String,
+}
+
+#[cfg(feature = "sled")]
+static SLED: Mutex<Vec<(PathBuf, sled::Db)>> = Mutex::new(Vec::new());
+
+#[cfg(feature = "sled")]
+impl SledStorage {
+  pub fn new(path: &Path, name: &str) -> SledStorage {
+    return SledStorage { path: path.to_path_buf(), name: name.to_string() };
+  }
+
+  fn db(&self) -> io::Result<sled::Db> {
+    let mut open = SLED.lock().unwrap();
+    if let Some((_, db)) = open.iter().find( |(path, _)| *path == self.path ) {
+      return Ok(db.clone());
+    }
+    let db = sled::open(&self.path).map_err( |error| self.failed(error) )?;
+    open.push((self.path.clone(), db.clone()));
+    return Ok(db);
+  }
+
+  fn failed(&self, error: sled::Error) -> io::Error {
+    return io::Error::other(format!("sled failed on {}: {}", self.path.display(), error));
+  }
+}
+
+#[cfg(feature = "sled")]
+impl Storage for SledStorage {
+  fn load(&self) -> io::Result<Vec<String>> {
+    let records = self.db()?.open_tree(&self.name).map_err( |error| self.failed(error) )?;
+    return records.iter().values().map( |value| {
+      let value = value.map_err( |error| self.failed(error) )?;
+      String::from_utf8(value.to_vec()).map_err( |error| io::Error::new(io::ErrorKind::InvalidData, error) )
+    }).collect();
+  }
+
+  // one batch, so all of them or none
+  fn append(&self, records: &[String]) -> io::Result<()> {
+    let db = self.db()?;
+    let tree = db.open_tree(&self.name).map_err( |error| self.failed(error) )?;
+    let mut batch = sled::Batch::default();
+    for record in records {
+      let id = db.generate_id().map_err( |error| self.failed(error) )?;
+      batch.insert(&id.to_be_bytes(), record.as_bytes());
+    }
+    tree.apply_batch(batch).map_err( |error| self.failed(error) )?;
+    tree.flush().map_err( |error| self.failed(error) )?;
+    return Ok(());
+  }
+
+  fn read(&self) -> io::Result<Option<Vec<u8>>> {
+    let value = self.db()?.get(&self.name).map_err( |error| self.failed(error) )?;
+    return Ok(value.map( |value| value.to_vec() ));
+  }
+
+  fn write(&self, value: &[u8]) -> io::Result<()> {
+    let db = self.db()?;
+    db.insert(&self.name, value).map_err( |error| self.failed(error) )?;
+    db.flush().map_err( |error| self.failed(error) )?;
+    return Ok(());
+  }
+
+  fn written(&self) -> io::Result<bool> {
+    return self.db()?.contains_key(&self.name).map_err( |error| self.failed(error) );
+  }
+
+  fn describe(&self) -> String {
+    return format!("sled {} ({})", self.path.display(), self.name);
+  }
+}
+
+// for tests, and for dry runs that shouldn't leave anything behind
+#[derive(Default)]
+pub struct MemoryStorage {
+  records: Mutex<Vec<String>>,
+  value: Mutex<Option<Vec<u8>>>,
+}
+
+impl Storage for MemoryStorage {
+  fn load(&self) -> io::Result<Vec<String>> {
+    return Ok(self.records.lock().unwrap().clone());
+  }
+
+  fn append(&self, records: &[String]) -> io::Result<()> {
+    self.records.lock().unwrap().extend_from_slice(records);
+    return Ok(());
+  }
+
+  fn read(&self) -> io::Result<Option<Vec<u8>>> {
+    return Ok(self.value.lock().unwrap().clone());
+  }
+
+  fn write(&self, value: &[u8]) -> io::Result<()> {
+    *self.value.lock().unwrap() = Some(value.to_vec());
+    return Ok(());
+  }
+
+  fn describe(&self) -> String {
+    return "memory".to_string();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn every_backend_behaves_the_same() {
+    let path = std::env::temp_dir().join(format!("erowidcoin-storage-{}.jsonl", std::process::id()));
+    let model = std::env::temp_dir().join(format!("erowidcoin-storage-{}.bin", std::process::id()));
+    let mut backends: Vec<(Box<dyn Storage>, Box<dyn Storage>)> = vec!(
+      (Box::new(FileStorage::new(&path)), open(&model, MODEL)),
+      (Box::new(MemoryStorage::default()), Box::new(MemoryStorage::default())),
+    );
+    // a queue and a model in the same database stay out of each other's way
+    let databases: Vec<PathBuf> = ["sqlite", "sled"].iter().map( |extension| std::env::temp_dir().join(format!("erowidcoin-storage-{}.{}", std::process::id(), extension)) )
+      .filter( |database| is_database(database) ).collect();
+    for database in &databases {
+      backends.push((open(database, QUEUE), open(database, MODEL)));
+    }
+
+    for (records, value) in backends {
+      assert!(records.load().unwrap().is_empty());
+      records.append(&["a".to_string(), "b".to_string()]).unwrap();
+      records.append(&["it's | c');".to_string()]).unwrap();
+      assert_eq!(records.load().unwrap(), vec!("a", "b", "it's | c');"));
+
+      assert!(!value.written().unwrap() && value.read().unwrap().is_none());
+      value.write(&[0, 1, 2]).unwrap();
+      value.write(&[0xff, 0]).unwrap();
+      assert_eq!(value.read().unwrap(), Some(vec!(0xff, 0)));
+      assert!(value.written().unwrap());
+      assert_eq!(records.load().unwrap().len(), 3);
+    }
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&model).unwrap();
+    for database in databases {
+      match database.is_dir() {
+        true => fs::remove_dir_all(&database).unwrap(),
+        false => fs::remove_file(&database).unwrap(),
+      }
+    }
+  }
+}
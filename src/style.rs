@@ -0,0 +1,124 @@
+use std::str::FromStr;
+use rand::Rng;
+
+// what a post looks like on top of what it says. applied after generation and before the filters, so a max length
+// still holds for the clapped version
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Style {
+  // ALL CAPS
+  Shout,
+  NoPunctuation,
+  // ee cummings
+  Lowercase,
+  // number 👏 go 👏 up
+  Clap,
+}
+
+impl Style {
+  pub const ALL: [Style; 4] = [Style::Shout, Style::NoPunctuation, Style::Lowercase, Style::Clap];
+
+  pub fn name(&self) -> &'static str {
+    return match self {
+      Style::Shout => "shout",
+      Style::NoPunctuation => "no-punctuation",
+      Style::Lowercase => "lowercase",
+      Style::Clap => "clap",
+    };
+  }
+
+  pub fn apply(&self, text: &str) -> String {
+    return match self {
+      Style::Shout => text.to_uppercase(),
+      Style::Lowercase => text.to_lowercase(),
+      Style::NoPunctuation => text.lines().map(strip_punctuation).collect::<Vec<String>>().join("\n"),
+      Style::Clap => text.lines().map( |line| line.split_whitespace().collect::<Vec<&str>>().join(" 👏 ") ).collect::<Vec<String>>().join("\n"),
+    };
+  }
+}
+
+impl FromStr for Style {
+  type Err = String;
+
+  fn from_str(name: &str) -> Result<Style, String> {
+    return Style::ALL.iter().find( |style| style.name() == name ).copied()
+      .ok_or_else( || format!("unknown style: {} (expected one of: shout, no-punctuation, lowercase, clap)", name) );
+  }
+}
+
+// the stages in order, each one gets what the last one made
+pub fn apply(styles: &[Style], text: &str) -> String {
+  return styles.iter().fold(text.to_string(), |text, style| style.apply(&text) );
+}
+
+// "shout,clap"
+pub fn parse(list: &str) -> Result<Vec<Style>, String> {
+  return list.split(',').map( |name| name.trim().parse() ).collect();
+}
+
+// which styles a post gets: always the same ones, or a different one (or none) each time
+#[derive(Clone, Debug, PartialEq)]
+pub enum Styling {
+  Fixed(Vec<Style>),
+  Random,
+}
+
+impl Styling {
+  pub fn pick(&self, rng: &mut impl Rng) -> Vec<Style> {
+    return match self {
+      Styling::Fixed(styles) => styles.clone(),
+      // plain counts as one of the options
+      Styling::Random => match rng.gen_range(0..=Style::ALL.len()) {
+        0 => Vec::new(),
+        i => vec!(Style::ALL[i - 1]),
+      },
+    };
+  }
+}
+
+impl Default for Styling {
+  fn default() -> Self {
+    return Styling::Fixed(Vec::new());
+  }
+}
+
+// "random", or a list of styles
+impl FromStr for Styling {
+  type Err = String;
+
+  fn from_str(value: &str) -> Result<Styling, String> {
+    if value == "random" {
+      return Ok(Styling::Random);
+    }
+    return parse(value).map(Styling::Fixed);
+  }
+}
+
+// apostrophes inside words stay, "don't" isn't punctuation
+fn strip_punctuation(line: &str) -> String {
+  let chars: Vec<char> = line.chars().collect();
+  let mut stripped = String::with_capacity(line.len());
+
+  for (i, &c) in chars.iter().enumerate() {
+    let punctuation = c.is_ascii_punctuation() || matches!(c, '“' | '”' | '‘' | '’' | '…' | '—' | '–');
+    let apostrophe = matches!(c, '\'' | '’') && i > 0 && chars[i - 1].is_alphanumeric() && chars.get(i + 1).is_some_and( |c| c.is_alphanumeric() );
+    if !punctuation || apostrophe {
+      stripped.push(c);
+    }
+  }
+  return stripped.split_whitespace().collect::<Vec<&str>>().join(" ");
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn stages_run_in_order() {
+    let tweet = "Don't sell, \"hodl\" — number go up!";
+    assert_eq!(apply(&[Style::Shout], tweet), "DON'T SELL, \"HODL\" — NUMBER GO UP!");
+    assert_eq!(apply(&[Style::NoPunctuation], tweet), "Don't sell hodl number go up");
+    assert_eq!(apply(&[Style::NoPunctuation, Style::Lowercase, Style::Clap], tweet), "don't 👏 sell 👏 hodl 👏 number 👏 go 👏 up");
+    assert_eq!(apply(&[Style::Clap], "gm\nwagmi fren"), "gm\nwagmi 👏 fren");
+    assert!("shout,yell".parse::<Styling>().is_err());
+  }
+}
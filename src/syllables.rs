@@ -0,0 +1,50 @@
+// a rough english syllable count: groups of vowels, minus the ones you don't say. good enough to tell a 5 from a 7,
+// not good enough to win an argument about "fire"
+pub fn count(word: &str) -> usize {
+  let letters: Vec<char> = word.chars().filter( |c| c.is_alphabetic() ).flat_map(char::to_lowercase).collect();
+  if letters.is_empty() {
+    return 0;
+  }
+
+  let is_vowel = |c: char| "aeiouy".contains(c);
+  let mut syllables = 0;
+  let mut previous = false;
+  for &c in &letters {
+    let vowel = is_vowel(c);
+    if vowel && !previous {
+      syllables += 1;
+    }
+    previous = vowel;
+  }
+
+  let n = letters.len();
+  let ends_with = |suffix: &str| letters.iter().rev().take(suffix.len()).rev().copied().eq(suffix.chars());
+  // "make", but not "bubble"
+  let consonant_le = n > 2 && letters[n - 2] == 'l' && !is_vowel(letters[n - 3]);
+  let silent_e = n > 2 && letters[n - 1] == 'e' && !is_vowel(letters[n - 2]) && !consonant_le;
+  // "pumped", but not "traded"
+  let silent_ed = n > 3 && ends_with("ed") && !matches!(letters[n - 3], 't' | 'd') && !is_vowel(letters[n - 3]);
+  if silent_e || silent_ed {
+    syllables -= 1;
+  }
+  return syllables.max(1);
+}
+
+pub fn line(text: &str) -> usize {
+  return text.split_whitespace().map(count).sum();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn counts_roughly_right() {
+    let words = [("hodl", 1), ("the", 1), ("make", 1), ("moon", 1), ("bitcoin", 2), ("bubble", 2), ("pumped", 1), ("traded", 2), ("psychedelic", 4), ("decentralized", 4)];
+    for (word, syllables) in words {
+      assert_eq!(count(word), syllables, "{}", word);
+    }
+    assert_eq!(count("2021"), 0);
+    assert_eq!(line("An old silent pond,"), 5);
+  }
+}
@@ -0,0 +1,102 @@
+use std::fmt;
+use std::collections::{BTreeMap, HashSet};
+use crate::flavor;
+use crate::toml::{self, Value};
+
+// what a document is about, going by the words in it: a report that says "acid" gets tagged lsd, a thread that
+// says "xmr" gets tagged monero. the tags end up next to the manifest's and the frontmatter's, and --about picks
+// documents by any of them. words are looked up the way flavor::key has them, lowercase without the punctuation
+#[derive(Clone, Debug, PartialEq)]
+pub struct Taxonomy {
+  // tag -> the words that give it away, in tag order
+  pub tags: BTreeMap<String, Vec<String>>,
+}
+
+const SUBSTANCES: &[(&str, &[&str])] = &[
+  ("lsd", &["lsd", "acid", "lucy", "tabs"]),
+  ("psilocybin", &["psilocybin", "mushrooms", "shrooms", "cubensis"]),
+  ("dmt", &["dmt", "ayahuasca", "changa"]),
+  ("mdma", &["mdma", "molly", "ecstasy"]),
+  ("ketamine", &["ketamine", "k-hole"]),
+  ("mescaline", &["mescaline", "peyote", "san pedro"]),
+  ("salvia", &["salvia", "divinorum"]),
+  ("cannabis", &["cannabis", "marijuana", "weed", "thc"]),
+];
+
+const COINS: &[(&str, &[&str])] = &[
+  ("bitcoin", &["bitcoin", "btc", "satoshi", "sats"]),
+  ("ethereum", &["ethereum", "eth", "ether", "vitalik"]),
+  ("monero", &["monero", "xmr"]),
+  ("dogecoin", &["dogecoin", "doge"]),
+  ("solana", &["solana"]),
+  ("nft", &["nft", "nfts", "opensea"]),
+];
+
+#[derive(Debug, PartialEq)]
+pub struct TaxonomyError(pub String);
+
+impl fmt::Display for TaxonomyError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    return write!(f, "{}", self.0);
+  }
+}
+
+impl std::error::Error for TaxonomyError {}
+
+impl Default for Taxonomy {
+  // the substances and coins above
+  fn default() -> Taxonomy {
+    let tags = SUBSTANCES.iter().chain(COINS)
+      .map( |(tag, words)| (tag.to_string(), words.iter().map( |word| word.to_string() ).collect()) )
+      .collect();
+    return Taxonomy { tags };
+  }
+}
+
+impl Taxonomy {
+  // the default one, with a file's lists on top. a tag that's already there gets the file's words instead
+  //
+  //   lsd = ["acid", "blotter"]
+  //   cardano = ["cardano", "ada"]
+  pub fn parse(text: &str) -> Result<Taxonomy, TaxonomyError> {
+    let table = toml::parse(text).map_err( |error| TaxonomyError(error.to_string()) )?;
+    let mut taxonomy = Taxonomy::default();
+    for (tag, words) in table {
+      let words = match &words {
+        Value::Array(words) => words.iter().map( |word| word.as_str().map(flavor::key) ).collect::<Option<Vec<String>>>(),
+        _ => None,
+      };
+      let words = words.ok_or_else( || TaxonomyError(format!("{} should be a list of words", tag)) )?;
+      taxonomy.tags.insert(tag, words);
+    }
+    return Ok(taxonomy);
+  }
+
+  // the tags these words give away. two words count too ("san pedro"), anything longer isn't worth the lookups
+  pub fn tag<'a>(&self, words: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let keys: Vec<String> = words.into_iter().map(flavor::key).filter( |key| !key.is_empty() ).collect();
+    let mut seen: HashSet<String> = keys.windows(2).map( |pair| pair.join(" ") ).collect();
+    seen.extend(keys);
+    return self.tags.iter()
+      .filter( |(_, words)| words.iter().any( |word| seen.contains(word) ) )
+      .map( |(tag, _)| tag.clone() )
+      .collect();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn tags_by_keyword() {
+    let taxonomy = Taxonomy::default();
+    let tags = taxonomy.tag("Dropped two tabs, then bought XMR. San Pedro next".split_whitespace());
+    assert_eq!(tags, vec!("lsd".to_string(), "mescaline".to_string(), "monero".to_string()));
+    assert!(taxonomy.tag(["Numbers", "go", "up."]).is_empty());
+
+    let taxonomy = Taxonomy::parse("lsd = [\"blotter\"]\ncardano = [\"ADA\"]").unwrap();
+    assert_eq!(taxonomy.tag(["tabs", "ada"]), vec!("cardano".to_string()));
+    assert_eq!(Taxonomy::parse("lsd = \"acid\"").unwrap_err().0, "lsd should be a list of words");
+  }
+}
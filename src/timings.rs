@@ -0,0 +1,61 @@
+use std::fmt;
+use std::time::Duration;
+
+// where the time goes, coarse enough to leave on all the time. training phases add up across calls to train (and
+// across the threads a corpus directory gets split over, so they can come to more than the wall clock), generation
+// is every tweet asked for (given up on or not) since the chain was made
+#[derive(Clone, Debug, Default)]
+pub struct Timings {
+  // reading files and cleaning them up. for a loaded model, reading the model file
+  pub ingestion: Duration,
+  // splitting documents into words and states
+  pub tokenization: Duration,
+  // adding the states and edges to the graph
+  pub graph_build: Duration,
+  pub finalize: Duration,
+  pub generated: usize,
+  pub generation: Duration,
+  pub slowest: Duration,
+}
+
+impl Timings {
+  // another chain's training phases on top of these
+  pub fn add_training(&mut self, other: &Timings) {
+    self.ingestion += other.ingestion;
+    self.tokenization += other.tokenization;
+    self.graph_build += other.graph_build;
+    self.finalize += other.finalize;
+  }
+
+  pub fn tweet(&mut self, took: Duration) {
+    self.generated += 1;
+    self.generation += took;
+    self.slowest = self.slowest.max(took);
+  }
+
+  // name -> time, in the order they happen
+  pub fn phases(&self) -> [(&'static str, Duration); 4] {
+    return [
+      ("ingestion", self.ingestion),
+      ("tokenization", self.tokenization),
+      ("graph build", self.graph_build),
+      ("finalize", self.finalize),
+    ];
+  }
+}
+
+impl fmt::Display for Timings {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, "timings:")?;
+    for (phase, took) in self.phases() {
+      writeln!(f, "  {}: {:.2?}", phase, took)?;
+    }
+    return match self.generated {
+      0 => write!(f, "  generation: nothing generated"),
+      generated => write!(
+        f, "  generation: {} tweets in {:.2?}, {:.2?} each on average, {:.2?} at worst",
+        generated, self.generation, self.generation / generated as u32, self.slowest
+      ),
+    };
+  }
+}
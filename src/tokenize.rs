@@ -0,0 +1,139 @@
+use std::io;
+use crate::detokenize::Tokenizer;
+
+// splits punctuation off words, so `"suddenly,` and `suddenly` are the same node with a quote and a comma on either
+// side. smart quotes turn into straight ones on the way in, the detokenizer puts the spaces back right for those.
+// full stops, question marks and the like stay on their word: that's what tweets end on, and where the locale tells
+// "mg." from the end of a sentence. a word that's nothing but punctuation (":)", "--") stays whole
+pub struct Punctuation;
+
+// these come off the front of a word
+const OPENING: &[char] = &['(', '[', '{', '"', '«', '¿', '¡'];
+// and these off the end
+const CLOSING: &[char] = &[',', ';', ':', ')', ']', '}', '"', '»'];
+const BRACKETS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}')];
+
+fn straighten(c: char) -> char {
+  return match c {
+    '“' | '”' | '„' | '‟' => '"',
+    '‘' | '’' | '‚' | '‛' => '\'',
+    c => c,
+  };
+}
+
+impl Punctuation {
+  // the tokens of one whitespace separated word
+  fn split(word: &str, tokens: &mut Vec<String>) {
+    if !word.chars().any(char::is_alphanumeric) {
+      tokens.push(word.to_string());
+      return;
+    }
+    let mut word = word;
+    while let Some(c) = word.chars().next().filter( |c| OPENING.contains(c) ) {
+      tokens.push(c.to_string());
+      word = &word[c.len_utf8()..];
+    }
+    let mut closing = Vec::new();
+    while let Some(c) = word.chars().next_back().filter( |c| CLOSING.contains(c) ) {
+      closing.push(c.to_string());
+      word = &word[..word.len() - c.len_utf8()];
+    }
+    tokens.push(word.to_string());
+    tokens.extend(closing.into_iter().rev());
+  }
+}
+
+impl Tokenizer for Punctuation {
+  // line breaks are kept as tokens of their own, so poetry mode still sees its lines
+  fn tokenize(&self, text: &str) -> io::Result<Vec<String>> {
+    let text: String = text.chars().map(straighten).collect();
+    let mut tokens = Vec::new();
+    for line in text.lines() {
+      for word in line.split_whitespace() {
+        Punctuation::split(word, &mut tokens);
+      }
+      tokens.push("\n".to_string());
+    }
+    tokens.pop();
+
+    // brackets that never close (or open) in the document would teach the chain to leave them hanging
+    let keep = unmatched(tokens.iter().map( |token| token.as_str() ));
+    return Ok(tokens.into_iter().zip(keep).filter( |(_, keep)| *keep ).map( |(token, _)| token ).collect());
+  }
+
+  // the walk can still open a bracket or a quote and end the tweet before getting to the close
+  fn tidy(&self, text: &mut String) {
+    *text = balance(text);
+  }
+}
+
+// for each token, whether it stays: brackets without a partner go, and so does a last quote without one
+fn unmatched<'a>(tokens: impl Iterator<Item = &'a str>) -> Vec<bool> {
+  let tokens: Vec<&str> = tokens.collect();
+  let mut keep = vec!(true; tokens.len());
+  let mut open: Vec<(usize, char)> = Vec::new();
+  let mut quotes = Vec::new();
+  for (i, token) in tokens.iter().enumerate() {
+    match *token {
+      "\"" => quotes.push(i),
+      token => {
+        let Some(c) = token.chars().next().filter( |_| token.chars().count() == 1 ) else {
+          continue;
+        };
+        if BRACKETS.iter().any( |(opening, _)| *opening == c ) {
+          open.push((i, c));
+        } else if let Some((opening, _)) = BRACKETS.iter().find( |(_, closing)| *closing == c ) {
+          match open.last() {
+            Some((_, last)) if last == opening => { open.pop(); },
+            _ => keep[i] = false,
+          }
+        }
+      },
+    }
+  }
+  for (i, _) in open {
+    keep[i] = false;
+  }
+  if quotes.len() % 2 == 1 {
+    keep[*quotes.last().unwrap()] = false;
+  }
+  return keep;
+}
+
+// the same for joined up text, a char at a time. ":)" and ";)" are faces, not brackets
+pub fn balance(text: &str) -> String {
+  let chars: Vec<char> = text.chars().collect();
+  let tokens: Vec<String> = chars.iter().enumerate().map( |(i, c)| match c {
+    ')' if i > 0 && matches!(chars[i - 1], ':' | ';') => String::new(),
+    c => c.to_string(),
+  }).collect();
+  let keep = unmatched(tokens.iter().map( |token| token.as_str() ));
+  let kept: String = chars.iter().zip(keep).filter( |(_, keep)| *keep ).map( |(c, _)| *c ).collect();
+  // a bracket that had a space either side of it leaves two behind
+  let mut balanced = String::with_capacity(kept.len());
+  for c in kept.chars() {
+    if !(c == ' ' && balanced.ends_with(' ')) {
+      balanced.push(c);
+    }
+  }
+  return balanced.trim().to_string();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::markov_chain::MarkovChain;
+
+  #[test]
+  fn splits_off_punctuation_and_balances_brackets() {
+    let tokens = Punctuation.tokenize("“Suddenly, (the walls) breathed.” Then) nothing :)\nmg. [sic").unwrap();
+    assert_eq!(tokens, ["\"", "Suddenly", ",", "(", "the", "walls", ")", "breathed.", "\"", "Then", "nothing", ":)", "\n", "mg.", "sic"]);
+
+    assert_eq!(balance("Then (it hit) and (nothing) happened) :)"), "Then (it hit) and (nothing) happened :)");
+    assert_eq!(balance("He said \"hodl and (then"), "He said hodl and then");
+
+    let mut mchain = MarkovChain::new().with_tokenizer(Punctuation).with_seed(1);
+    mchain.train_documents(["Suddenly, it hit.", "“Suddenly it was over.”"]);
+    assert_eq!(mchain.ngrams(1).iter().find( |(word, _)| word == "Suddenly" ).map( |(_, count)| *count ), Some(2));
+  }
+}
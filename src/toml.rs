@@ -0,0 +1,381 @@
+use std::fmt;
+use std::collections::BTreeMap;
+
+// the subset of toml we actually use for manifests and config: tables, arrays of tables, dotted headers,
+// strings, integers, floats, booleans, and arrays. no dates, no inline tables, no multi-line strings
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+  String(String),
+  Integer(i64),
+  Float(f64),
+  Boolean(bool),
+  Array(Vec<Value>),
+  Table(BTreeMap<String, Value>),
+}
+
+impl Value {
+  pub fn type_name(&self) -> &'static str {
+    return match self {
+      Value::String(_) => "string",
+      Value::Integer(_) => "integer",
+      Value::Float(_) => "float",
+      Value::Boolean(_) => "boolean",
+      Value::Array(_) => "array",
+      Value::Table(_) => "table",
+    };
+  }
+
+  pub fn as_str(&self) -> Option<&str> {
+    return match self {
+      Value::String(s) => Some(s),
+      _ => None,
+    };
+  }
+
+  pub fn as_integer(&self) -> Option<i64> {
+    return match self {
+      Value::Integer(i) => Some(*i),
+      _ => None,
+    };
+  }
+
+  // integers are fine wherever a float is expected, nobody wants to write `weight = 2.0`
+  pub fn as_float(&self) -> Option<f64> {
+    return match self {
+      Value::Float(f) => Some(*f),
+      Value::Integer(i) => Some(*i as f64),
+      _ => None,
+    };
+  }
+
+  pub fn as_bool(&self) -> Option<bool> {
+    return match self {
+      Value::Boolean(b) => Some(*b),
+      _ => None,
+    };
+  }
+
+  pub fn as_array(&self) -> Option<&Vec<Value>> {
+    return match self {
+      Value::Array(a) => Some(a),
+      _ => None,
+    };
+  }
+
+  pub fn as_table(&self) -> Option<&BTreeMap<String, Value>> {
+    return match self {
+      Value::Table(t) => Some(t),
+      _ => None,
+    };
+  }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+  pub line: usize,
+  pub message: String,
+}
+
+impl fmt::Display for ParseError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    return write!(f, "line {}: {}", self.line, self.message);
+  }
+}
+
+impl std::error::Error for ParseError {}
+
+pub fn parse(text: &str) -> Result<BTreeMap<String, Value>, ParseError> {
+  let mut root: BTreeMap<String, Value> = BTreeMap::new();
+  // the table keys are currently being written into, from the root
+  let mut current: Vec<String> = Vec::new();
+
+  let mut lines = text.lines().enumerate();
+  while let Some((number, line)) = lines.next() {
+    let line_number = number + 1;
+    let error = |message: String| ParseError { line: line_number, message };
+    let mut line = strip_comment(line).trim().to_string();
+    if line.is_empty() {
+      continue;
+    }
+
+    if let Some(header) = line.strip_prefix("[[") {
+      let header = header.strip_suffix("]]").ok_or_else( || error("unclosed [[table]] header".to_string()) )?;
+      let path = parse_key(header).map_err(error)?;
+      push_table_array(&mut root, &path).map_err(error)?;
+      current = path;
+      continue;
+    }
+
+    if let Some(header) = line.strip_prefix('[') {
+      let header = header.strip_suffix(']').ok_or_else( || error("unclosed [table] header".to_string()) )?;
+      let path = parse_key(header).map_err(error)?;
+      table_at(&mut root, &path).map_err(error)?;
+      current = path;
+      continue;
+    }
+
+    // arrays are allowed to span lines, keep reading until the brackets balance
+    while bracket_depth(&line) > 0 {
+      match lines.next() {
+        Some((_, next)) => {
+          line.push(' ');
+          line.push_str(strip_comment(next).trim());
+        },
+        None => return Err(error("unclosed array".to_string())),
+      }
+    }
+
+    let (key, value) = line.split_once('=').ok_or_else( || error(format!("expected `key = value`, got `{}`", line)) )?;
+    let key = parse_key(key).map_err(error)?;
+    let (value, rest) = parse_value(value.trim()).map_err(error)?;
+    if !rest.trim().is_empty() {
+      return Err(error(format!("unexpected `{}` after value", rest.trim())));
+    }
+
+    let (last, parents) = key.split_last().unwrap();
+    let mut path = current.clone();
+    path.extend(parents.iter().cloned());
+    let table = table_at(&mut root, &path).map_err(error)?;
+    if table.contains_key(last) {
+      return Err(error(format!("duplicate key `{}`", last)));
+    }
+    table.insert(last.clone(), value);
+  }
+
+  return Ok(root);
+}
+
+// a # outside of a string starts a comment
+fn strip_comment(line: &str) -> &str {
+  let mut quote: Option<char> = None;
+  let mut escaped = false;
+
+  for (i, c) in line.char_indices() {
+    match quote {
+      Some(q) => {
+        if escaped {
+          escaped = false;
+        } else if c == '\\' && q == '"' {
+          escaped = true;
+        } else if c == q {
+          quote = None;
+        }
+      },
+      None => match c {
+        '"' | '\'' => quote = Some(c),
+        '#' => return &line[..i],
+        _ => {},
+      },
+    }
+  }
+  return line;
+}
+
+fn bracket_depth(line: &str) -> i32 {
+  let value = match line.split_once('=') {
+    Some((_, value)) => value,
+    None => return 0,
+  };
+
+  let mut depth = 0;
+  let mut quote: Option<char> = None;
+  let mut escaped = false;
+  for c in value.chars() {
+    match quote {
+      Some(q) => {
+        if escaped {
+          escaped = false;
+        } else if c == '\\' && q == '"' {
+          escaped = true;
+        } else if c == q {
+          quote = None;
+        }
+      },
+      None => match c {
+        '"' | '\'' => quote = Some(c),
+        '[' => depth += 1,
+        ']' => depth -= 1,
+        _ => {},
+      },
+    }
+  }
+  return depth;
+}
+
+// dotted keys, each part bare or quoted
+fn parse_key(key: &str) -> Result<Vec<String>, String> {
+  let mut parts = Vec::new();
+  let mut rest = key.trim();
+
+  loop {
+    let (part, remaining) = if rest.starts_with('"') || rest.starts_with('\'') {
+      match parse_value(rest)? {
+        (Value::String(s), remaining) => (s, remaining),
+        _ => unreachable!(),
+      }
+    } else {
+      let end = rest.find( |c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-') ).unwrap_or(rest.len());
+      (rest[..end].to_string(), &rest[end..])
+    };
+    if part.is_empty() {
+      return Err(format!("invalid key `{}`", key.trim()));
+    }
+    parts.push(part);
+
+    rest = remaining.trim_start();
+    if rest.is_empty() {
+      return Ok(parts);
+    }
+    rest = rest.strip_prefix('.').ok_or_else( || format!("invalid key `{}`", key.trim()) )?.trim_start();
+  }
+}
+
+// parses one value off the front of the input and hands back whatever's left
+fn parse_value(input: &str) -> Result<(Value, &str), String> {
+  if let Some(rest) = input.strip_prefix('"') {
+    let mut value = String::new();
+    let mut chars = rest.char_indices();
+    while let Some((i, c)) = chars.next() {
+      match c {
+        '"' => return Ok((Value::String(value), &rest[i + 1..])),
+        '\\' => match chars.next() {
+          Some((_, 'n')) => value.push('\n'),
+          Some((_, 't')) => value.push('\t'),
+          Some((_, 'r')) => value.push('\r'),
+          Some((_, '"')) => value.push('"'),
+          Some((_, '\\')) => value.push('\\'),
+          Some((_, 'u')) => {
+            let hex: String = (0..4).filter_map( |_| chars.next().map( |(_, c)| c ) ).collect();
+            let code = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32).ok_or_else( || format!("bad unicode escape \\u{}", hex) )?;
+            value.push(code);
+          },
+          other => return Err(format!("unknown escape \\{}", other.map( |(_, c)| c ).unwrap_or(' '))),
+        },
+        c => value.push(c),
+      }
+    }
+    return Err("unclosed string".to_string());
+  }
+
+  if let Some(rest) = input.strip_prefix('\'') {
+    let end = rest.find('\'').ok_or_else( || "unclosed string".to_string() )?;
+    return Ok((Value::String(rest[..end].to_string()), &rest[end + 1..]));
+  }
+
+  if let Some(mut rest) = input.strip_prefix('[') {
+    let mut values = Vec::new();
+    loop {
+      rest = rest.trim_start();
+      if let Some(after) = rest.strip_prefix(']') {
+        return Ok((Value::Array(values), after));
+      }
+      let (value, after) = parse_value(rest)?;
+      values.push(value);
+      rest = after.trim_start();
+      if let Some(after) = rest.strip_prefix(',') {
+        rest = after;
+      } else if !rest.starts_with(']') {
+        return Err("expected `,` or `]` in array".to_string());
+      }
+    }
+  }
+
+  let end = input.find( |c: char| c == ',' || c == ']' || c.is_whitespace() ).unwrap_or(input.len());
+  let (token, rest) = input.split_at(end);
+  let value = match token {
+    "true" => Value::Boolean(true),
+    "false" => Value::Boolean(false),
+    "" => return Err("missing value".to_string()),
+    _ => {
+      let digits = token.replace('_', "");
+      if let Ok(i) = digits.parse::<i64>() {
+        Value::Integer(i)
+      } else if let Ok(f) = digits.parse::<f64>() {
+        Value::Float(f)
+      } else {
+        return Err(format!("can't make sense of `{}` (strings need quotes)", token));
+      }
+    },
+  };
+  return Ok((value, rest));
+}
+
+// walks (and creates) tables along the path. for arrays of tables we go into the last one
+fn table_at<'a>(root: &'a mut BTreeMap<String, Value>, path: &[String]) -> Result<&'a mut BTreeMap<String, Value>, String> {
+  let mut table = root;
+  for key in path {
+    let entry = table.entry(key.clone()).or_insert_with( || Value::Table(BTreeMap::new()) );
+    table = match entry {
+      Value::Table(t) => t,
+      Value::Array(tables) => match tables.last_mut() {
+        Some(Value::Table(t)) => t,
+        _ => return Err(format!("`{}` is not a table", key)),
+      },
+      other => return Err(format!("`{}` is a {}, not a table", key, other.type_name())),
+    };
+  }
+  return Ok(table);
+}
+
+fn push_table_array(root: &mut BTreeMap<String, Value>, path: &[String]) -> Result<(), String> {
+  let (last, parents) = path.split_last().unwrap();
+  let table = table_at(root, parents)?;
+
+  let entry = table.entry(last.clone()).or_insert_with( || Value::Array(Vec::new()) );
+  return match entry {
+    Value::Array(tables) if tables.iter().all( |t| matches!(t, Value::Table(_)) ) => {
+      tables.push(Value::Table(BTreeMap::new()));
+      Ok(())
+    },
+    other => Err(format!("`{}` is a {}, not an array of tables", last, other.type_name())),
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_the_bits_we_use() {
+    let text = r#"
+      # top level
+      name = "erowid coin"
+      order = 2
+      weight = 0.5
+      dry_run = true
+
+      [twitter]
+      handle = 'erowidcoin' # trailing comment
+      "api key" = "abc\"def"
+
+      [[file]]
+      path = "dmt.txt"
+      tags = ["erowid",
+              "dmt"]
+
+      [[file]]
+      path = "bitcoin.txt"
+    "#;
+    let root = parse(text).unwrap();
+
+    assert_eq!(root["name"].as_str(), Some("erowid coin"));
+    assert_eq!(root["order"].as_integer(), Some(2));
+    assert_eq!(root["weight"].as_float(), Some(0.5));
+    assert_eq!(root["dry_run"].as_bool(), Some(true));
+
+    let twitter = root["twitter"].as_table().unwrap();
+    assert_eq!(twitter["handle"].as_str(), Some("erowidcoin"));
+    assert_eq!(twitter["api key"].as_str(), Some("abc\"def"));
+
+    let files = root["file"].as_array().unwrap();
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0].as_table().unwrap()["tags"], Value::Array(vec!(Value::String("erowid".to_string()), Value::String("dmt".to_string()))));
+    assert_eq!(files[1].as_table().unwrap()["path"].as_str(), Some("bitcoin.txt"));
+  }
+
+  #[test]
+  fn reports_the_line() {
+    assert_eq!(parse("a = 1\nb = nope").unwrap_err(), ParseError { line: 2, message: "can't make sense of `nope` (strings need quotes)".to_string() });
+    assert_eq!(parse("a = 1\na = 2").unwrap_err().message, "duplicate key `a`");
+  }
+}
@@ -0,0 +1,102 @@
+// cutting text down to a length limit. every cut lands between grapheme clusters (what a reader sees as one
+// character), never inside one: half a flag or a family emoji without its kids renders as something else entirely,
+// and half a code point isn't a str at all. the clusters are worked out by hand, close enough to unicode's rules for
+// what ends up in a tweet: combining marks, variation selectors, skin tones, emoji tags, zero width joiner sequences,
+// flag pairs and \r\n
+
+// carries on the cluster before it
+fn extends(c: char) -> bool {
+  return matches!(c as u32,
+    0x0300..=0x036F | 0x0483..=0x0489 | 0x0591..=0x05BD | 0x0610..=0x061A | 0x064B..=0x065F | 0x0900..=0x0903
+    | 0x093A..=0x094F | 0x1AB0..=0x1AFF | 0x1DC0..=0x1DFF | 0x200C | 0x20D0..=0x20FF | 0x302A..=0x302F
+    | 0x3099..=0x309A | 0xFE00..=0xFE0F | 0xFE20..=0xFE2F | 0x1F3FB..=0x1F3FF | 0xE0020..=0xE007F | 0xE0100..=0xE01EF
+  );
+}
+
+fn is_regional_indicator(c: char) -> bool {
+  return ('\u{1F1E6}'..='\u{1F1FF}').contains(&c);
+}
+
+// the text a cluster at a time
+pub fn graphemes(text: &str) -> impl Iterator<Item = &str> + '_ {
+  let mut rest = text;
+  return std::iter::from_fn(move || {
+    let mut chars = rest.char_indices().peekable();
+    let (_, first) = chars.next()?;
+    let mut previous = first;
+    // regional indicators pair up, a third one starts the next flag
+    let mut indicators = is_regional_indicator(first) as usize;
+    let mut end = rest.len();
+    while let Some(&(i, c)) = chars.peek() {
+      let joins = extends(c) || c == '\u{200D}' || previous == '\u{200D}' || (previous == '\r' && c == '\n')
+        || (is_regional_indicator(c) && indicators % 2 == 1);
+      if !joins {
+        end = i;
+        break;
+      }
+      indicators += is_regional_indicator(c) as usize;
+      previous = c;
+      chars.next();
+    }
+    let (cluster, after) = rest.split_at(end);
+    rest = after;
+    return Some(cluster);
+  });
+}
+
+// the longest start of text that comes to at most max, as `length` counts it. length has to add up over pieces of
+// text, the way counting chars (or twitter's weights) does
+pub fn prefix(text: &str, max: usize, length: impl Fn(&str) -> usize) -> &str {
+  let mut total = 0;
+  let mut end = 0;
+  for cluster in graphemes(text) {
+    total += length(cluster);
+    if total > max {
+      break;
+    }
+    end += cluster.len();
+  }
+  return &text[..end];
+}
+
+// text cut back to fit in max: to the last sentence that fits, or the last word with an ellipsis if not even the
+// first sentence does, or wherever it stops fitting (with the ellipsis) if the first word is too long on its own
+pub fn fit(text: &str, max: usize, length: impl Fn(&str) -> usize) -> String {
+  if length(text) <= max {
+    return text.to_string();
+  }
+
+  let words: Vec<&str> = text.split(' ').collect();
+  let fits = |n: usize, suffix: &str| length(&words[..n].join(" ")) + length(suffix) <= max;
+  if let Some(n) = (1..words.len()).rev().find( |&n| words[n - 1].ends_with(['!', '.', '?']) && fits(n, "") ) {
+    return words[..n].join(" ");
+  }
+  return match (1..words.len()).rev().find( |&n| fits(n, "…") ) {
+    Some(n) => format!("{}…", words[..n].join(" ")),
+    None => format!("{}…", prefix(text, max.saturating_sub(length("…")), length)),
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn chars(text: &str) -> usize {
+    return text.chars().count();
+  }
+
+  #[test]
+  fn cuts_land_between_clusters() {
+    let family = "👨\u{200D}👩\u{200D}👧";
+    let text = format!("{}🇺🇸🇬🇧e\u{0301}\r\n👍🏽", family);
+    assert_eq!(graphemes(&text).collect::<Vec<&str>>(), vec!(family, "🇺🇸", "🇬🇧", "e\u{0301}", "\r\n", "👍🏽"));
+
+    assert_eq!(prefix(&text, 4, chars), "");
+    assert_eq!(prefix(&text, 6, chars), family);
+    assert_eq!(prefix(&text, 8, chars), format!("{}🇺🇸", family));
+    assert_eq!(prefix("héllo", 3, chars), "hél");
+    assert_eq!(fit("Buy the dip. Then more", 15, chars), "Buy the dip.");
+    assert_eq!(fit("hodl hodl hodl", 11, chars), "hodl hodl…");
+    assert_eq!(fit(&format!("🚀🚀{}", family), 6, chars), "🚀🚀…");
+  }
+}
@@ -0,0 +1,199 @@
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::BTreeMap;
+use rand::Rng;
+use rand::distributions::Alphanumeric;
+use crate::{http, json};
+use crate::publish::{self, Format, Publisher};
+use crate::sha1::hmac_sha1;
+use crate::toml::Value;
+
+const TWEETS_URL: &str = "https://api.x.com/2/tweets";
+
+// the four keys from the developer portal, for the account that's tweeting. OAuth 1.0a user context, so there's no
+// token to refresh: they stay good until somebody regenerates them
+#[derive(Clone, PartialEq)]
+pub struct Credentials {
+  pub consumer_key: String,
+  pub consumer_secret: String,
+  pub access_token: String,
+  pub access_secret: String,
+}
+
+// config key -> environment variable
+const KEYS: [(&str, &str); 4] = [
+  ("consumer_key", "EROWIDCOIN_TWITTER_CONSUMER_KEY"),
+  ("consumer_secret", "EROWIDCOIN_TWITTER_CONSUMER_SECRET"),
+  ("access_token", "EROWIDCOIN_TWITTER_ACCESS_TOKEN"),
+  ("access_secret", "EROWIDCOIN_TWITTER_ACCESS_SECRET"),
+];
+
+impl Credentials {
+  // the environment, or the table for whatever isn't set there, see publish::setting
+  pub fn from_table(table: &BTreeMap<String, Value>) -> Result<Credentials, String> {
+    let values = KEYS.iter().map( |(key, variable)| publish::setting("twitter", table, key, variable) ).collect::<Result<Vec<String>, String>>()?;
+    let [consumer_key, consumer_secret, access_token, access_secret] = values.try_into().unwrap();
+    return Ok(Credentials { consumer_key, consumer_secret, access_token, access_secret });
+  }
+
+  pub fn from_env() -> Result<Credentials, String> {
+    return Credentials::from_table(&BTreeMap::new());
+  }
+
+  // the Authorization header for one request. params are the query string and form fields, a json body isn't
+  // signed. the nonce and timestamp are passed in so the signature can be checked against twitter's example
+  pub fn authorization(&self, method: &str, url: &str, params: &[(&str, &str)], nonce: &str, timestamp: u64) -> String {
+    let timestamp = timestamp.to_string();
+    let mut oauth = vec!(
+      ("oauth_consumer_key", self.consumer_key.as_str()),
+      ("oauth_nonce", nonce),
+      ("oauth_signature_method", "HMAC-SHA1"),
+      ("oauth_timestamp", timestamp.as_str()),
+      ("oauth_token", self.access_token.as_str()),
+      ("oauth_version", "1.0"),
+    );
+
+    let mut signed: Vec<(String, String)> = oauth.iter().chain(params).map( |(k, v)| (encode(k), encode(v)) ).collect();
+    signed.sort();
+    let parameters: Vec<String> = signed.iter().map( |(k, v)| format!("{}={}", k, v) ).collect();
+    let base = format!("{}&{}&{}", method, encode(url), encode(&parameters.join("&")));
+    let key = format!("{}&{}", encode(&self.consumer_secret), encode(&self.access_secret));
+    let signature = base64(&hmac_sha1(key.as_bytes(), base.as_bytes()));
+
+    oauth.push(("oauth_signature", signature.as_str()));
+    oauth.sort();
+    let fields: Vec<String> = oauth.iter().map( |(k, v)| format!("{}=\"{}\"", encode(k), encode(v)) ).collect();
+    return format!("OAuth {}", fields.join(", "));
+  }
+}
+
+// so the keys don't end up in a log by accident
+impl std::fmt::Debug for Credentials {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    return write!(f, "Credentials({})", self.consumer_key);
+  }
+}
+
+// posts through the v2 api, see http::post_json
+pub struct TwitterPublisher {
+  credentials: Credentials,
+}
+
+impl TwitterPublisher {
+  pub fn new(credentials: Credentials) -> TwitterPublisher {
+    return TwitterPublisher { credentials };
+  }
+}
+
+impl Publisher for TwitterPublisher {
+  fn name(&self) -> String {
+    return "twitter".to_string();
+  }
+
+  // the link is the tweet's
+  fn publish(&self, text: &str) -> io::Result<Option<String>> {
+    return self.send(text, false);
+  }
+
+  // there's no idempotency key to pass along
+  fn publish_flagged(&self, text: &str, _key: Option<&str>, sensitive: bool) -> io::Result<Option<String>> {
+    return self.send(text, sensitive);
+  }
+
+  fn format(&self) -> Format {
+    return Format::platform("twitter").unwrap();
+  }
+}
+
+impl TwitterPublisher {
+  fn send(&self, text: &str, sensitive: bool) -> io::Result<Option<String>> {
+    let nonce: String = rand::thread_rng().sample_iter(&Alphanumeric).take(32).map(char::from).collect();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map( |d| d.as_secs() ).unwrap_or(0);
+    let authorization = self.credentials.authorization("POST", TWEETS_URL, &[], &nonce, now);
+    let body = tweet_body(text, sensitive);
+
+    let (status, body) = http::post_json(TWEETS_URL, &[format!("Authorization: {}", authorization)], &body)?;
+    return match status.as_str() {
+      "201" | "200" => match tweet_id(&body) {
+        Some(id) => Ok(Some(format!("https://x.com/i/status/{}", id))),
+        None => Err(io::Error::other(format!("twitter said {} but didn't send back a tweet id: {}", status, body))),
+      },
+      status => Err(http::failed("twitter", status, &error_message(&body))),
+    };
+  }
+}
+
+// possibly_sensitive puts the tweet behind twitter's sensitive content warning
+fn tweet_body(text: &str, sensitive: bool) -> String {
+  return match sensitive {
+    true => format!("{{\"text\":{},\"possibly_sensitive\":true}}", json::string(text)),
+    false => format!("{{\"text\":{}}}", json::string(text)),
+  };
+}
+
+// {"data":{"id":"1445880548472328192","text":"..."}}
+fn tweet_id(body: &str) -> Option<String> {
+  return json::parse(body).ok()?.get("data")?.get("id")?.as_str().map(String::from);
+}
+
+// the v2 api says what went wrong in detail (or title), the older errors in errors[0].message
+fn error_message(body: &str) -> String {
+  let Ok(value) = json::parse(body) else {
+    return body.trim().to_string();
+  };
+  let message = value.get("detail").or(value.get("title"))
+    .or_else( || value.get("errors")?.as_array()?.first()?.get("message") )
+    .and_then( |message| message.as_str() );
+  return message.unwrap_or(body.trim()).to_string();
+}
+
+// RFC 3986, which is what OAuth wants: everything but the unreserved characters gets escaped
+fn encode(value: &str) -> String {
+  let mut encoded = String::with_capacity(value.len());
+  for byte in value.bytes() {
+    match byte {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+      _ => encoded.push_str(&format!("%{:02X}", byte)),
+    }
+  }
+  return encoded;
+}
+
+fn base64(bytes: &[u8]) -> String {
+  const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+  let mut encoded = String::new();
+  for chunk in bytes.chunks(3) {
+    let n = chunk.iter().enumerate().fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+    for i in 0..4 {
+      match i <= chunk.len() {
+        true => encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char),
+        false => encoded.push('='),
+      }
+    }
+  }
+  return encoded;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn signs_like_twitters_example() {
+    // https://developer.x.com/en/docs/authentication/oauth-1-0a/creating-a-signature
+    let credentials = Credentials {
+      consumer_key: "xvz1evFS4wEEPTGEFPHBog".to_string(),
+      consumer_secret: "kAcSOqF21Fu85e7zjz7ZN2U4ZRhfV3WpwPAoE3Z7kBw".to_string(),
+      access_token: "370773112-GmHxMAgYyLbNEtIKZeRNFsMKPR9EyMZeS9weJAEb".to_string(),
+      access_secret: "LswwdoUaIvS8ltyTt5jkRh4J50vUPVVHtR2YPi5kE".to_string(),
+    };
+    let params = [("status", "Hello Ladies + Gentlemen, a signed OAuth request!"), ("include_entities", "true")];
+    let header = credentials.authorization("POST", "https://api.twitter.com/1.1/statuses/update.json", &params, "kYjzVBB8Y0ZFabxSWbWovY3uYSQ2pTgmZeNu2VS4cg", 1318622958);
+
+    assert!(header.starts_with("OAuth oauth_consumer_key=\"xvz1evFS4wEEPTGEFPHBog\", oauth_nonce="), "{}", header);
+    assert!(header.contains("oauth_signature=\"hCtSmYh%2BiHYCEqBWrE7C7hYmtUk%3D\""), "{}", header);
+    assert_eq!(base64(b"ab"), "YWI=");
+    assert_eq!(tweet_body("gm", true), "{\"text\":\"gm\",\"possibly_sensitive\":true}");
+    assert_eq!(error_message("{\"title\":\"Forbidden\",\"detail\":\"You are not allowed to create a Tweet with duplicate content.\"}"), "You are not allowed to create a Tweet with duplicate content.");
+  }
+}